@@ -1,17 +1,19 @@
 use actix_multipart::Multipart;
 use actix_web::{
-    error::ErrorBadRequest, middleware::Logger, web, App, HttpResponse, HttpServer, Result,
+    error::{ErrorBadRequest, ErrorPayloadTooLarge, InternalError}, http::StatusCode, middleware::{Compress, Logger}, web, App, HttpResponse, HttpServer, Result,
 };
 use clap::{Arg, Command};
 use futures_util::TryStreamExt;
 use llamaedge::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tempfile::NamedTempFile;
+use thai_transcriber::{channels_identical, is_model_file_name, logprob_confidence, model_file_format, model_load_error, prompt_tokens, resample_ratio, sanitize_samples, to_mono, RiskConfig, RiskLevel, SegmentLimits, DEFAULT_MAX_SEGMENTS};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
@@ -52,6 +54,7 @@ struct WhisperResult {
 #[derive(Serialize, Deserialize, Debug)]
 struct RiskDetectionResult {
     is_risky: bool,
+    risk_level: RiskLevel,
     raw_response: String,
     confidence: f64,
 }
@@ -63,13 +66,66 @@ struct RiskAnalysisResponse {
     metadata: serde_json::Value,
 }
 
+// Directory scanned by /api/models
+const MODELS_DIR: &str = "model";
+
+// Default cap on a single uploaded audio file (500MB)
+const DEFAULT_MAX_UPLOAD_BYTES: u64 = 500 * 1024 * 1024;
+
+// Largest text field accepted alongside an upload
+const MAX_FORM_FIELD_BYTES: usize = 64 * 1024;
+
+// Loaded Whisper contexts keyed by model path, evicting the least recently used
+struct ModelCache {
+    contexts: HashMap<String, Arc<WhisperContext>>,
+    lru: VecDeque<String>, // Front = least recently used
+    capacity: usize,
+}
+
+impl ModelCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            contexts: HashMap::new(),
+            lru: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    fn get(&mut self, model_path: &str) -> Option<Arc<WhisperContext>> {
+        let ctx = self.contexts.get(model_path)?.clone();
+        self.touch(model_path);
+        Some(ctx)
+    }
+
+    fn insert(&mut self, model_path: String, ctx: Arc<WhisperContext>) {
+        self.contexts.insert(model_path.clone(), ctx);
+        self.touch(&model_path);
+
+        while self.contexts.len() > self.capacity {
+            match self.lru.pop_front() {
+                Some(evicted) => {
+                    self.contexts.remove(&evicted);
+                    println!("   - Evicted Whisper context for {} (cache limit {})", evicted, self.capacity);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn touch(&mut self, model_path: &str) {
+        self.lru.retain(|path| path != model_path);
+        self.lru.push_back(model_path.to_string());
+    }
+}
+
 // Server state to hold the whisper context and llamaedge client
 #[derive(Clone)]
 struct AppState {
-    model_path: String,
-    whisper_ctx: Arc<RwLock<Option<Arc<whisper_rs::WhisperContext>>>>,
+    model_path: Arc<RwLock<String>>,
+    whisper_ctxs: Arc<RwLock<ModelCache>>,
     llama_client: Arc<RwLock<Option<Client>>>,
     llama_server_url: String,
+    max_upload_bytes: u64,
 }
 
 // Request/response structures
@@ -79,6 +135,16 @@ struct TranscribeRequest {
     backend: Option<String>, // "cpu", "gpu", "coreml"
     chunking: Option<bool>,
     risk_analysis: Option<bool>, // Enable risk detection
+    model: Option<String>, // Model file name in model/, defaults to the active model
+    prompt: Option<String>, // Initial prompt to bias vocabulary (names, jargon)
+    translate: Option<bool>, // Translate to English instead of transcribing
+    max_segments: Option<usize>, // Stop collecting segments past this many, defaults to DEFAULT_MAX_SEGMENTS
+    pretty: Option<bool>, // Indent the JSON response, defaults to compact
+}
+
+#[derive(serde::Deserialize)]
+struct SelectModelRequest {
+    model: String, // File name inside the model/ directory, e.g. "ggml-large-v3.bin" or a .gguf file
 }
 
 // Simple health check endpoint
@@ -91,6 +157,115 @@ async fn health_check() -> Result<HttpResponse> {
     })))
 }
 
+// Total size of a file, or of everything inside a directory (for .mlmodelc bundles)
+fn path_size(path: &Path) -> u64 {
+    if path.is_dir() {
+        fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| path_size(&entry.path()))
+                    .sum()
+            })
+            .unwrap_or(0)
+    } else {
+        fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    }
+}
+
+// List available models endpoint
+async fn list_models(data: web::Data<AppState>) -> Result<HttpResponse> {
+    let active_model = data.model_path.read().await.clone();
+    let loaded_models: Vec<String> = data.whisper_ctxs.read().await.contexts.keys().cloned().collect();
+
+    let entries = match fs::read_dir(MODELS_DIR) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to read model directory",
+                "details": format!("{}: {}", MODELS_DIR, e)
+            })));
+        }
+    };
+
+    let mut models = Vec::new();
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        let kind = if is_model_file_name(&name) {
+            model_file_format(&path.to_string_lossy()).unwrap_or("ggml")
+        } else if name.ends_with(".mlmodelc") {
+            "coreml"
+        } else {
+            continue;
+        };
+
+        models.push(json!({
+            "name": name,
+            "path": path.to_string_lossy(),
+            "type": kind,
+            "size_bytes": path_size(&path),
+            "active": Path::new(&active_model) == path,
+            "loaded": loaded_models.iter().any(|loaded| Path::new(loaded) == path)
+        }));
+    }
+
+    models.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+
+    Ok(HttpResponse::Ok().json(json!({
+        "models": models,
+        "active_model": active_model,
+        "model_dir": MODELS_DIR
+    })))
+}
+
+// Map a model file name from /api/models to its path, or an error response
+fn resolve_model_name(name: &str) -> std::result::Result<String, HttpResponse> {
+    let name = name.trim();
+
+    // Only plain ggml/gguf file names inside the model directory can be selected
+    if name.is_empty() || name.contains('/') || name.contains('\\') || name.contains("..") || !is_model_file_name(name) {
+        return Err(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid model name",
+            "details": "Expected a .bin or .gguf file name from GET /api/models"
+        })));
+    }
+
+    let path = Path::new(MODELS_DIR).join(name);
+    if !path.is_file() {
+        return Err(HttpResponse::NotFound().json(json!({
+            "error": "Model not found",
+            "details": path.to_string_lossy()
+        })));
+    }
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+// Switch the active model used by requests that don't name one
+async fn select_model(
+    body: web::Json<SelectModelRequest>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let new_path = match resolve_model_name(&body.model) {
+        Ok(path) => path,
+        Err(response) => return Ok(response),
+    };
+
+    // Contexts are cached per model path, so switching never reuses the old model
+    let mut model_lock = data.model_path.write().await;
+    let previous_model = std::mem::replace(&mut *model_lock, new_path.clone());
+
+    println!("🔄 Active model switched: {} → {}", previous_model, new_path);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "active_model": new_path,
+        "previous_model": previous_model,
+        "message": "Model selected; it will be loaded on the next transcription request if not already cached"
+    })))
+}
+
 // Get supported languages endpoint
 async fn get_supported_languages() -> Result<HttpResponse> {
     let languages = json!({
@@ -124,15 +299,42 @@ async fn get_supported_languages() -> Result<HttpResponse> {
 }
 
 // Helper function to save uploaded file
-async fn save_uploaded_file(mut payload: Multipart) -> Result<(PathBuf, String), actix_web::Error> {
+async fn save_uploaded_file(
+    payload: Multipart,
+    max_upload_bytes: u64,
+) -> Result<(PathBuf, String), actix_web::Error> {
+    let (path, original_filename, _) = save_uploaded_form(payload, max_upload_bytes, "audio").await?;
+    Ok((path, original_filename))
+}
+
+// Save the file in `file_field` and collect the remaining text fields of the form
+async fn save_uploaded_form(
+    mut payload: Multipart,
+    max_upload_bytes: u64,
+    file_field: &str,
+) -> Result<(PathBuf, String, HashMap<String, String>), actix_web::Error> {
     let mut file_path = None;
     let mut original_filename = String::new();
+    let mut fields = HashMap::new();
 
     while let Some(mut field) = payload.try_next().await.map_err(ErrorBadRequest)? {
         let content_disposition = field.content_disposition();
 
         if let Some(name) = content_disposition.get_name() {
-            if name == "audio" {
+            if name != file_field {
+                // Plain form fields are small; anything larger is not a parameter we read
+                let name = name.to_string();
+                let mut bytes = Vec::new();
+                while let Some(chunk) = field.try_next().await.map_err(ErrorBadRequest)? {
+                    if bytes.len() + chunk.len() > MAX_FORM_FIELD_BYTES {
+                        return Err(ErrorBadRequest(format!("Form field '{}' is too large", name)));
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+                if let Ok(value) = String::from_utf8(bytes) {
+                    fields.insert(name, value);
+                }
+            } else if file_path.is_none() {
                 if let Some(filename) = content_disposition.get_filename() {
                     original_filename = filename.to_string();
 
@@ -147,20 +349,30 @@ async fn save_uploaded_file(mut payload: Multipart) -> Result<(PathBuf, String),
 
                     let mut file = fs::File::create(temp_file.path()).map_err(ErrorBadRequest)?;
 
-                    // Write file data
+                    // Write file data, aborting once the upload exceeds the limit.
+                    // Returning early drops temp_file, which deletes the partial upload.
+                    let mut written: u64 = 0;
                     while let Some(chunk) = field.try_next().await.map_err(ErrorBadRequest)? {
+                        written += chunk.len() as u64;
+                        if written > max_upload_bytes {
+                            println!("   ❌ Upload exceeds {} byte limit, aborting", max_upload_bytes);
+                            return Err(ErrorPayloadTooLarge(format!(
+                                "Uploaded file too large (maximum {} bytes)",
+                                max_upload_bytes
+                            )));
+                        }
                         file.write_all(&chunk).map_err(ErrorBadRequest)?;
                     }
 
-                    file_path = Some(temp_file.into_temp_path().keep().map_err(ErrorBadRequest)?);
-                    break;
+                    // Still deleted on drop, so an error in a later field doesn't leak the upload
+                    file_path = Some(temp_file.into_temp_path());
                 }
             }
         }
     }
 
     match file_path {
-        Some(path) => Ok((path, original_filename)),
+        Some(temp_path) => Ok((temp_path.keep().map_err(ErrorBadRequest)?, original_filename, fields)),
         None => Err(ErrorBadRequest("No audio file found in request")),
     }
 }
@@ -229,7 +441,7 @@ fn initialize_whisper_context(
         // CPU-only mode
         println!("   - CPU-only mode requested");
         let ctx = WhisperContext::new_with_params(model_path, ctx_params)
-            .map_err(|e| format!("Failed to load model in CPU mode: {}", e))?;
+            .map_err(|e| model_load_error(model_path, format!("CPU mode: {}", e)))?;
 
         println!("   ✅ Whisper context initialized successfully in CPU mode");
         Ok(ctx)
@@ -259,15 +471,25 @@ fn simple_load_audio(path: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>>
 
     let mut samples: Vec<f32> = decoder.convert_samples::<f32>().collect();
 
-    // Convert stereo to mono if necessary
-    if channels == 2 {
-        println!("   - Converting stereo to mono");
-        samples = samples
-            .chunks(2)
-            .map(|chunk| (chunk[0] + chunk[1]) / 2.0)
-            .collect();
+    // Corrupt files can decode to NaN/Inf, which make Whisper output garbage
+    let sanitized = sanitize_samples(&mut samples);
+    if sanitized > 0 {
+        println!(
+            "   ⚠️ Replaced or clamped {} non-finite or out-of-range samples",
+            sanitized
+        );
     }
 
+    // Convert to mono; a sample count that isn't whole frames means the header lied
+    if channels > 1 {
+        if channels_identical(&samples, channels) {
+            println!("   - All {} channels are identical, using channel 0", channels);
+        } else {
+            println!("   - Converting {} channels to mono", channels);
+        }
+    }
+    samples = to_mono(samples, channels, None)?;
+
     // Resample to 16kHz if necessary (Whisper's expected sample rate)
     const TARGET_SAMPLE_RATE: u32 = 16000;
 
@@ -285,13 +507,16 @@ fn simple_load_audio(path: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>>
             window: WindowFunction::BlackmanHarris2,
         };
 
+        // Clear error for corrupt or extreme rates instead of rubato's
+        let ratio = resample_ratio(sample_rate, TARGET_SAMPLE_RATE)?;
         let mut resampler = SincFixedIn::<f32>::new(
-            TARGET_SAMPLE_RATE as f64 / sample_rate as f64,
+            ratio,
             2.0,
             params,
             samples.len(),
             1,
-        )?;
+        )
+        .map_err(|e| format!("Failed to create resampler for {} Hz → {} Hz: {}", sample_rate, TARGET_SAMPLE_RATE, e))?;
 
         let output = resampler.process(&[samples], None)?;
         output[0].clone()
@@ -311,15 +536,32 @@ fn simple_transcribe(
     ctx: &WhisperContext,
     audio_data: Vec<f32>,
     language: &str,
+    initial_prompt: Option<&str>,
+    translate: bool,
+    temperature: Option<f32>,
+    limits: SegmentLimits,
 ) -> Result<Vec<WhisperSegment>, Box<dyn std::error::Error>> {
     println!("🔍 Starting transcription...");
     println!("   - Audio samples: {}", audio_data.len());
     println!("   - Language: {}", language);
 
+    let prompt_tokens = match initial_prompt {
+        Some(text) if !text.trim().is_empty() => prompt_tokens(ctx, text)?,
+        _ => Vec::new(),
+    };
+
     // Set up transcription parameters
     let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    params.set_translate(false);
+    params.set_translate(translate);
     params.set_language(Some(language));
+    if let Some(temperature) = temperature {
+        println!("   - Temperature: {}", temperature);
+        params.set_temperature(temperature);
+    }
+    if !prompt_tokens.is_empty() {
+        println!("   - Initial prompt: {} tokens", prompt_tokens.len());
+        params.set_tokens(&prompt_tokens);
+    }
     params.set_progress_callback_safe(|progress| {
         println!("🔄 Transcription progress: {:.1}%", progress as f64 * 100.0);
     });
@@ -353,6 +595,8 @@ fn simple_transcribe(
         .full_n_segments()
         .map_err(|e| format!("Failed to get segment count: {}", e))?;
     println!("🔍 Transcription completed with {} segments", num_segments);
+    // A looping decode can produce tens of thousands of segments; don't read past the limit
+    let num_segments = limits.segments_to_collect(num_segments);
 
     let mut segments = Vec::new();
 
@@ -379,6 +623,23 @@ fn simple_transcribe(
             segment_text.trim()
         );
 
+        // avg_logprob over text tokens; timestamps and other special tokens sit at or above EOT
+        let mut logprob_sum = 0.0;
+        let mut logprob_count = 0;
+        for j in 0..state.full_n_tokens(i).unwrap_or(0) {
+            if let Ok(token_data) = state.full_get_token_data(i, j) {
+                if token_data.id < ctx.token_eot() {
+                    logprob_sum += token_data.plog as f64;
+                    logprob_count += 1;
+                }
+            }
+        }
+        let avg_logprob = if logprob_count > 0 {
+            logprob_sum / logprob_count as f64
+        } else {
+            0.0
+        };
+
         // Create segment
         let segment = WhisperSegment {
             id: i as i32,
@@ -388,10 +649,14 @@ fn simple_transcribe(
             text: segment_text,
             tokens: Vec::new(),
             temperature: 0.0,
-            avg_logprob: -0.3,
+            avg_logprob,
             compression_ratio: 1.5,
             no_speech_prob: 0.1,
-            confidence: 0.8,
+            confidence: if logprob_count > 0 {
+                logprob_confidence(avg_logprob)
+            } else {
+                0.0
+            },
             words: Vec::new(),
         };
 
@@ -404,7 +669,7 @@ fn simple_transcribe(
 // Risk detection function using LlamaEdge with enhanced prompting and preprocessing
 async fn detect_text_risk(
     text: &str,
-    _: &(),
+    config: &RiskConfig,
 ) -> Result<RiskDetectionResult, Box<dyn std::error::Error>> {
     println!("🔍 Analyzing text for risk content...");
     println!("   - Text length: {} characters", text.len());
@@ -481,17 +746,7 @@ async fn detect_text_risk(
     let response = client_http
         .post("http://localhost:8080/v1/chat/completions")
         .header("Content-Type", "application/json")
-        .json(&serde_json::json!({
-            "messages": [
-                {
-                    "role": "user",
-                    "content": prompt
-                }
-            ],
-            "temperature": 0.3,
-            "max_tokens": 10,
-            "stream": false
-        }))
+        .json(&config.request_body(&prompt))
         .send()
         .await?;
 
@@ -511,72 +766,49 @@ async fn detect_text_risk(
 
     println!("   - Raw LLM response: '{}'", raw_response);
 
-    // Parse response to determine risk
-    let is_risky = raw_response.contains("ผิด") && !raw_response.contains("ไม่ผิด");
-    let confidence = if raw_response == "ผิด" || raw_response == "ไม่ผิด" {
-        0.95
-    } else if raw_response.contains("ผิด") || raw_response.contains("ไม่ผิด") {
-        0.8
-    } else {
-        0.5
-    };
+    // Parse response to determine risk; answers without a clear ผิด/ไม่ผิด are uncertain, not safe
+    let (risk_level, confidence) = config.classify_response(&raw_response);
+    let is_risky = risk_level == RiskLevel::Risky;
 
-    println!("   - Risk detected: {}", is_risky);
+    println!("   - Risk level: {:?}", risk_level);
     println!("   - Confidence: {:.2}", confidence);
 
     Ok(RiskDetectionResult {
         is_risky,
+        risk_level,
         raw_response: raw_response.to_string(),
         confidence,
     })
 }
 
-// Main transcription endpoint
-async fn transcribe_audio(
-    payload: Multipart,
-    data: web::Data<AppState>,
-    query: web::Query<TranscribeRequest>,
-) -> Result<HttpResponse> {
-    println!("📝 Received transcription request");
-
-    // Extract request parameters
-    let language = query.language.as_deref().unwrap_or("th");
-    let backend = query.backend.as_deref().unwrap_or("cpu");
-    let use_chunking = query.chunking.unwrap_or(true);
-    let enable_risk_analysis = query.risk_analysis.unwrap_or(false);
-
-    println!("   - Language: {}", language);
-    println!("   - Backend: {}", backend);
-    println!("   - Chunking: {}", use_chunking);
-    println!("   - Risk analysis: {}", enable_risk_analysis);
-
-    // Parse backend settings
-    let use_gpu = backend == "gpu";
-    let use_coreml = backend == "coreml";
-
-    // Save uploaded file
-    let (audio_path, original_filename) = save_uploaded_file(payload).await?;
-    println!(
-        "   - Saved audio file: {} (original: {})",
-        audio_path.display(),
-        original_filename
-    );
-
-    // Get or initialize whisper context
+// Load (or reuse) the model and transcribe an uploaded file under the dynamic timeout.
+// Shared by /transcribe and the OpenAI-compatible /v1/audio/transcriptions.
+#[allow(clippy::too_many_arguments)]
+async fn transcribe_uploaded_file(
+    data: &AppState,
+    model_path: &str,
+    audio_path: &Path,
+    language: &str,
+    use_gpu: bool,
+    use_coreml: bool,
+    prompt: Option<String>,
+    translate: bool,
+    temperature: Option<f32>,
+    limits: SegmentLimits,
+) -> Result<Vec<WhisperSegment>> {
+    // Get or initialize whisper context for this model
+    let cached_ctx = data.whisper_ctxs.write().await.get(model_path);
     let whisper_ctx = {
-        let ctx_lock = data.whisper_ctx.read().await;
-        if let Some(ctx) = ctx_lock.as_ref() {
+        if let Some(ctx) = cached_ctx {
             // Use existing context
             println!("   - Using existing Whisper context");
-            ctx.clone()
+            ctx
         } else {
-            drop(ctx_lock); // Release read lock
-
             // Initialize new context with error handling
             println!("   - Initializing new Whisper context");
 
             let ctx = match initialize_whisper_context(
-                &data.model_path,
+                model_path,
                 language,
                 use_gpu,
                 use_coreml,
@@ -585,20 +817,20 @@ async fn transcribe_audio(
                 Err(e) => {
                     let error_msg = format!("Failed to initialize Whisper: {}", e);
                     if error_msg.contains("metal") || error_msg.contains("buffer is nil") {
-                        return Ok(HttpResponse::BadRequest().json(json!({
+                        let response = HttpResponse::BadRequest().json(json!({
                             "error": "GPU/Metal acceleration failed",
                             "message": "The Metal backend encountered a buffer error. This is a known issue with GPU acceleration on some systems.",
                             "suggestion": "Please try again using 'cpu' backend instead of 'gpu' or 'coreml'",
                             "details": error_msg
-                        })));
+                        }));
+                        return Err(InternalError::from_response(error_msg, response).into());
                     } else {
                         return Err(ErrorBadRequest(error_msg));
                     }
                 }
             };
 
-            let mut ctx_lock = data.whisper_ctx.write().await;
-            *ctx_lock = Some(ctx.clone());
+            data.whisper_ctxs.write().await.insert(model_path.to_string(), ctx.clone());
             ctx
         }
     };
@@ -655,7 +887,7 @@ async fn transcribe_audio(
             let language = language.to_string();
             move || {
                 // Wrapper to convert error to Send-safe String
-                simple_transcribe(&whisper_ctx, audio_data, &language)
+                simple_transcribe(&whisper_ctx, audio_data, &language, prompt.as_deref(), translate, temperature, limits)
                     .map_err(|e| e.to_string())
             }
         })
@@ -694,6 +926,73 @@ async fn transcribe_audio(
         }
     };
 
+    Ok(segments)
+}
+
+// Main transcription endpoint
+async fn transcribe_audio(
+    payload: Multipart,
+    data: web::Data<AppState>,
+    query: web::Query<TranscribeRequest>,
+) -> Result<HttpResponse> {
+    println!("📝 Received transcription request");
+
+    // Extract request parameters
+    let language = query.language.as_deref().unwrap_or("th");
+    let backend = query.backend.as_deref().unwrap_or("cpu");
+    let use_chunking = query.chunking.unwrap_or(true);
+    let enable_risk_analysis = query.risk_analysis.unwrap_or(false);
+
+    println!("   - Language: {}", language);
+    println!("   - Backend: {}", backend);
+    println!("   - Chunking: {}", use_chunking);
+    println!("   - Risk analysis: {}", enable_risk_analysis);
+
+    // Parse backend settings
+    let use_gpu = backend == "gpu";
+    let use_coreml = backend == "coreml";
+
+    // Resolve the model for this request
+    let model_path = match query.model.as_deref() {
+        Some(name) => match resolve_model_name(name) {
+            Ok(path) => path,
+            Err(response) => return Ok(response),
+        },
+        None => data.model_path.read().await.clone(),
+    };
+    println!("   - Model: {}", model_path);
+
+    // Save uploaded file
+    let (audio_path, original_filename) = save_uploaded_file(payload, data.max_upload_bytes).await?;
+    println!(
+        "   - Saved audio file: {} (original: {})",
+        audio_path.display(),
+        original_filename
+    );
+
+    let limits = SegmentLimits {
+        max_segments: Some(query.max_segments.unwrap_or(DEFAULT_MAX_SEGMENTS).max(1)),
+        ..Default::default()
+    };
+    let mut segments = transcribe_uploaded_file(
+        &data,
+        &model_path,
+        &audio_path,
+        language,
+        use_gpu,
+        use_coreml,
+        query.prompt.clone(),
+        query.translate.unwrap_or(false),
+        None,
+        limits,
+    )
+    .await?;
+
+    let truncated = limits.truncate(&mut segments);
+    if truncated {
+        println!("   ✂️ Transcription truncated to {} segments", segments.len());
+    }
+
     // Create result in OpenAI Whisper format
     let full_text = segments
         .iter()
@@ -720,16 +1019,9 @@ async fn transcribe_audio(
 
         if client_available {
             println!("   - Performing risk analysis on transcribed text...");
-            match detect_text_risk(&result.text, &()).await {
+            match detect_text_risk(&result.text, &RiskConfig::default()).await {
                 Ok(risk_result) => {
-                    println!(
-                        "   ✅ Risk analysis completed: {}",
-                        if risk_result.is_risky {
-                            "RISKY"
-                        } else {
-                            "SAFE"
-                        }
-                    );
+                    println!("   ✅ Risk analysis completed: {:?}", risk_result.risk_level);
                     Some(risk_result)
                 }
                 Err(e) => {
@@ -763,8 +1055,11 @@ async fn transcribe_audio(
             "backend": backend,
             "chunking_used": false,
             "processing_time": "N/A",
-            "model": data.model_path,
-            "risk_analysis_enabled": enable_risk_analysis
+            "model": model_path,
+            "task": if query.translate.unwrap_or(false) { "translate" } else { "transcribe" },
+            "risk_analysis_enabled": enable_risk_analysis,
+            "max_segments": limits.max_segments,
+            "truncated": truncated
         }
     });
 
@@ -772,13 +1067,177 @@ async fn transcribe_audio(
     if let Some(risk_result) = risk_analysis {
         response["risk_analysis"] = json!({
             "is_risky": risk_result.is_risky,
+            "risk_level": risk_result.risk_level,
             "raw_response": risk_result.raw_response,
             "confidence": risk_result.confidence
         });
     }
 
     // Return OpenAI Whisper-compatible response with optional risk analysis
-    Ok(HttpResponse::Ok().json(response))
+    Ok(json_response(&response, query.pretty.unwrap_or(false)))
+}
+
+// 200 JSON response, indented when the client asked for pretty=true
+fn json_response(value: &serde_json::Value, pretty: bool) -> HttpResponse {
+    if !pretty {
+        return HttpResponse::Ok().json(value);
+    }
+    match serde_json::to_string_pretty(value) {
+        Ok(body) => HttpResponse::Ok().content_type("application/json").body(body),
+        Err(_) => HttpResponse::Ok().json(value),
+    }
+}
+
+// OpenAI-style error body, so OpenAI client libraries can surface the message
+fn openai_error(status: StatusCode, message: &str, param: Option<&str>) -> HttpResponse {
+    HttpResponse::build(status).json(json!({
+        "error": {
+            "message": message,
+            "type": "invalid_request_error",
+            "param": param,
+            "code": null
+        }
+    }))
+}
+
+// OpenAI-compatible transcription endpoint for clients that only speak the OpenAI API
+async fn openai_transcriptions(
+    payload: Multipart,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    println!("📝 Received OpenAI-compatible transcription request");
+
+    let (audio_path, original_filename, fields) =
+        match save_uploaded_form(payload, data.max_upload_bytes, "file").await {
+            Ok(upload) => upload,
+            Err(e) => {
+                return Ok(openai_error(
+                    e.as_response_error().status_code(),
+                    &e.to_string(),
+                    Some("file"),
+                ))
+            }
+        };
+    println!(
+        "   - Saved audio file: {} (original: {})",
+        audio_path.display(),
+        original_filename
+    );
+
+    let response = openai_transcribe_saved_file(&data, &audio_path, &fields).await;
+
+    // Clean up temporary file
+    let _ = fs::remove_file(&audio_path);
+
+    Ok(response)
+}
+
+// Map the OpenAI form fields (model, language, prompt, response_format, temperature)
+// onto the regular transcription pipeline and shape the answer like OpenAI does
+async fn openai_transcribe_saved_file(
+    data: &AppState,
+    audio_path: &Path,
+    fields: &HashMap<String, String>,
+) -> HttpResponse {
+    let field = |name: &str| {
+        fields
+            .get(name)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+
+    // text is returned as plain text; json and verbose_json both get the verbose shape
+    let response_format = field("response_format").unwrap_or("json");
+    if !matches!(response_format, "json" | "verbose_json" | "text") {
+        return openai_error(
+            StatusCode::BAD_REQUEST,
+            &format!("Unsupported response_format '{}'; use json, verbose_json or text", response_format),
+            Some("response_format"),
+        );
+    }
+
+    let temperature = match field("temperature").map(|value| value.parse::<f32>()) {
+        None => None,
+        Some(Ok(temperature)) if (0.0..=1.0).contains(&temperature) => Some(temperature),
+        Some(_) => {
+            return openai_error(
+                StatusCode::BAD_REQUEST,
+                "temperature must be a number between 0 and 1",
+                Some("temperature"),
+            )
+        }
+    };
+
+    // OpenAI model names such as "whisper-1" use the active model; a .bin/.gguf name picks one from model/
+    let model_path = match field("model").filter(|name| is_model_file_name(name)) {
+        Some(name) => match resolve_model_name(name) {
+            Ok(path) => path,
+            Err(response) => {
+                return openai_error(
+                    response.status(),
+                    &format!("Model '{}' is not available", name),
+                    Some("model"),
+                )
+            }
+        },
+        None => data.model_path.read().await.clone(),
+    };
+    let language = field("language").unwrap_or("th");
+
+    println!("   - Model: {}", model_path);
+    println!("   - Language: {}", language);
+    println!("   - Response format: {}", response_format);
+
+    let limits = SegmentLimits {
+        max_segments: Some(DEFAULT_MAX_SEGMENTS),
+        ..Default::default()
+    };
+    let segments = match transcribe_uploaded_file(
+        data,
+        &model_path,
+        audio_path,
+        language,
+        false,
+        false,
+        field("prompt").map(|prompt| prompt.to_string()),
+        false,
+        temperature,
+        limits,
+    )
+    .await
+    {
+        Ok(mut segments) => {
+            limits.truncate(&mut segments);
+            segments
+        }
+        Err(e) => {
+            return openai_error(e.as_response_error().status_code(), &e.to_string(), None);
+        }
+    };
+
+    let text = segments
+        .iter()
+        .map(|segment| segment.text.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if response_format == "text" {
+        return HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(text);
+    }
+
+    // The pipeline doesn't report the decoded length, so duration runs to the end of the last segment
+    let duration = segments.last().map(|segment| segment.end).unwrap_or(0.0);
+
+    HttpResponse::Ok().json(json!({
+        "task": "transcribe",
+        "language": language,
+        "duration": duration,
+        "text": text,
+        "segments": segments
+    }))
 }
 
 // Risk detection endpoint
@@ -814,8 +1273,9 @@ async fn analyze_text_risk(
         })));
     }
 
-    // Perform risk detection
-    match detect_text_risk(text, &()).await {
+    // Perform risk detection, allowing temperature/max_tokens/model overrides in the body
+    let risk_config = RiskConfig::from_payload(&body);
+    match detect_text_risk(text, &risk_config).await {
         Ok(risk_result) => {
             println!("   ✅ Risk analysis completed");
 
@@ -823,12 +1283,14 @@ async fn analyze_text_risk(
                 "text": text,
                 "risk_analysis": {
                     "is_risky": risk_result.is_risky,
+                    "risk_level": risk_result.risk_level,
                     "raw_response": risk_result.raw_response,
                     "confidence": risk_result.confidence
                 },
                 "metadata": {
                     "llama_server": data.llama_server_url,
-                    "analysis_timestamp": chrono::Utc::now()
+                    "analysis_timestamp": chrono::Utc::now(),
+                    "risk_config": risk_config
                 }
             })))
         }
@@ -874,6 +1336,17 @@ async fn main() -> std::io::Result<()> {
                 .help("LlamaEdge server URL for risk detection")
                 .default_value("http://localhost:8080"),
         )
+        .arg(
+            Arg::new("max-models")
+                .long("max-models")
+                .help("Maximum number of Whisper models kept loaded at once")
+                .default_value("2"),
+        )
+        .arg(
+            Arg::new("max-upload-bytes")
+                .long("max-upload-bytes")
+                .help("Reject uploaded audio larger than this many bytes with 413 (default 500MB)"),
+        )
         .get_matches();
 
     let model_path = matches.get_one::<String>("model").unwrap().to_string();
@@ -884,6 +1357,15 @@ async fn main() -> std::io::Result<()> {
         .parse()
         .expect("Invalid port number");
     let llama_url = matches.get_one::<String>("llama-url").unwrap().to_string();
+    let max_models: usize = matches
+        .get_one::<String>("max-models")
+        .unwrap()
+        .parse()
+        .expect("Invalid max-models value");
+    let max_upload_bytes: u64 = matches
+        .get_one::<String>("max-upload-bytes")
+        .map(|v| v.parse().expect("Invalid max-upload-bytes value"))
+        .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES);
 
     // Validate model path
     if !Path::new(&model_path).exists() {
@@ -909,21 +1391,27 @@ async fn main() -> std::io::Result<()> {
 
     // Create shared application state
     let app_state = web::Data::new(AppState {
-        model_path: model_path.clone(),
-        whisper_ctx: Arc::new(RwLock::new(None)),
+        model_path: Arc::new(RwLock::new(model_path.clone())),
+        whisper_ctxs: Arc::new(RwLock::new(ModelCache::new(max_models))),
         llama_client: Arc::new(RwLock::new(llama_client)),
         llama_server_url: llama_url.clone(),
+        max_upload_bytes,
     });
 
     println!("🚀 Starting Whisper Transcription API Server");
     println!("   📍 Address: http://{}:{}", host, port);
     println!("   🧠 Model: {}", model_path);
+    println!("   🗃️  Model cache: up to {} loaded", max_models);
+    println!("   📦 Max upload: {} bytes", max_upload_bytes);
     println!("   🦙 LlamaEdge: {}", llama_status);
     println!("   📋 Endpoints:");
-    println!("      POST /transcribe?language=th&backend=cpu&chunking=true&risk_analysis=false - Transcribe audio file");
+    println!("      POST /transcribe?language=th&backend=cpu&chunking=true&risk_analysis=false&model=<name>&prompt=<text>&translate=false - Transcribe audio file");
+    println!("      POST /v1/audio/transcriptions - OpenAI-compatible transcription (file, model, language, response_format, temperature)");
     println!("      POST /risk-analysis - Analyze text for risk content");
     println!("      GET  /health     - Health check");
     println!("      GET  /languages  - Get supported languages");
+    println!("      GET  /api/models - List models in {}/", MODELS_DIR);
+    println!("      POST /api/model/select - Switch the active model");
     println!("      GET  /           - Web interface");
     println!();
     println!("   🎯 Backend options: cpu, gpu, coreml");
@@ -935,10 +1423,15 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(app_state.clone())
             .wrap(Logger::default())
+            // Large transcript JSON is gzip/brotli compressed when the client accepts it
+            .wrap(Compress::default())
             .service(web::resource("/transcribe").route(web::post().to(transcribe_audio)))
+            .service(web::resource("/v1/audio/transcriptions").route(web::post().to(openai_transcriptions)))
             .service(web::resource("/risk-analysis").route(web::post().to(analyze_text_risk)))
             .service(web::resource("/health").route(web::get().to(health_check)))
             .service(web::resource("/languages").route(web::get().to(get_supported_languages)))
+            .service(web::resource("/api/models").route(web::get().to(list_models)))
+            .service(web::resource("/api/model/select").route(web::post().to(select_model)))
             // Serve static files for web interface
             .service(actix_files::Files::new("/", "./static").index_file("index.html"))
     })
@@ -1,10 +1,11 @@
-use actix_web::{web, App, HttpResponse, HttpServer, Result, middleware::Logger, error::ErrorBadRequest};
+use actix_web::{web, App, HttpResponse, HttpServer, Result, middleware::{Compress, Logger}, error::ErrorBadRequest};
 use actix_cors::Cors;
 use actix_web_actors::ws;
 use actix_multipart::Multipart;
 use futures_util::TryStreamExt;
 use serde_json::json;
 use clap::{Arg, Command};
+use std::collections::HashMap;
 use std::io::Write;
 use tempfile::NamedTempFile;
 use uuid::Uuid;
@@ -13,6 +14,11 @@ use actix::prelude::*;
 
 // Import our queue system and main functions
 use thai_transcriber::queue::*;
+use thai_transcriber::live::LiveTranscriptionSession;
+use thai_transcriber::RiskVoting;
+
+// Default cap on a single uploaded audio file (500MB)
+const DEFAULT_MAX_UPLOAD_BYTES: u64 = 500 * 1024 * 1024;
 
 // OpenAI Whisper format structures
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -50,6 +56,14 @@ struct WhisperResult {
 #[derive(Clone)]
 struct AppState {
     task_queue: Addr<TaskQueue>,
+    max_upload_bytes: u64,
+    max_total_duration_minutes: f64,
+    // Submissions are refused with 503 once this many tasks are pending; None accepts everything
+    max_queue_depth: Option<usize>,
+    // Submissions are refused with 503 model_unavailable while no Whisper model can be found
+    reject_without_model: bool,
+    // Task store behind the queue, "redis" or "memory"
+    backend: &'static str,
 }
 
 // Request/response structures
@@ -63,27 +77,62 @@ struct TranscribeRequest {
     priority: Option<i32>, // Queue priority
 }
 
+#[derive(serde::Deserialize)]
+struct TranscribeUrlRequest {
+    url: String,
+    language: Option<String>,
+    backend: Option<String>, // "cpu", "gpu", "coreml", "auto"
+    risk_analysis: Option<bool>,
+    priority: Option<i32>,
+    callback_url: Option<String>, // POSTed the final TaskResult when the task finishes
+    timeout_seconds: Option<u64>, // Overrides the timeout estimated from the file, capped server-side
+}
+
 #[derive(serde::Deserialize)]
 struct RiskAnalysisRequest {
     text: String,
     priority: Option<i32>, // Queue priority
+    temperature: Option<f64>, // Overrides RiskConfig defaults
+    max_tokens: Option<u32>,
+    model: Option<String>,
+    voting: Option<String>, // "any", "majority" or "all" when several LlamaEdge endpoints are configured
+    callback_url: Option<String>, // POSTed the final TaskResult when the task finishes
 }
 
 // Simple health check endpoint
 async fn health_check(data: web::Data<AppState>) -> Result<HttpResponse> {
+    // Queue stats come from the in-memory cache, so check Redis explicitly
+    let redis_error = match data.task_queue.send(PingRedis).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e),
+        Err(e) => Some(format!("Queue actor unavailable: {}", e)),
+    };
+    
     // Get queue statistics
     let queue_stats = match data.task_queue.send(GetQueueStats).await {
         Ok(Ok(stats)) => Some(stats),
         _ => None,
     };
     
-    Ok(HttpResponse::Ok().json(json!({
-        "status": "healthy",
+    let status = if redis_error.is_none() { "healthy" } else { "degraded" };
+    let body = json!({
+        "status": status,
         "service": "whisper-transcription-api-with-queue",
         "version": "0.2.0",
         "timestamp": chrono::Utc::now(),
+        "backend": data.backend,
+        "redis_connected": redis_error.is_none(),
+        "redis_error": redis_error,
         "queue_stats": queue_stats
-    })))
+    });
+    
+    // 503 takes the instance out of load balancer rotation until Redis is back
+    if redis_error.is_some() {
+        println!("⚠️ Health check degraded: {}", redis_error.as_deref().unwrap_or(""));
+        return Ok(HttpResponse::ServiceUnavailable().json(body));
+    }
+    
+    Ok(HttpResponse::Ok().json(body))
 }
 
 // Get supported languages endpoint
@@ -111,16 +160,156 @@ async fn get_supported_languages() -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().json(languages))
 }
 
+// Longest Idempotency-Key accepted; it ends up in a Redis key name
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+// Optional Idempotency-Key header: retries carrying the same key get the original task back
+fn idempotency_key(req: &actix_web::HttpRequest) -> std::result::Result<Option<String>, HttpResponse> {
+    let value = match req.headers().get("Idempotency-Key") {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    
+    match value.to_str().map(str::trim) {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN => Ok(Some(key.to_string())),
+        _ => Err(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid Idempotency-Key header",
+            "details": format!("Expected 1-{} visible ASCII characters", MAX_IDEMPOTENCY_KEY_LEN)
+        }))),
+    }
+}
+
+// Reply to a retried submission with the task its Idempotency-Key already created
+fn existing_task_response(task_id: &str) -> HttpResponse {
+    println!("   ♻️ Idempotency-Key already used, returning existing task {}", task_id);
+    HttpResponse::Ok().json(json!({
+        "status": "existing",
+        "task_id": task_id,
+        "message": "A task was already submitted with this Idempotency-Key",
+        "endpoints": {
+            "status": format!("/api/task/{}/status", task_id),
+            "websocket": "/ws"
+        }
+    }))
+}
+
+// Seconds a client is told to wait when the queue is full
+const QUEUE_FULL_RETRY_AFTER_SECS: u64 = 30;
+
+// 503 with Retry-After when the backlog has reached --max-queue-depth. A failed
+// stats lookup lets the submission through; the queue itself still works.
+async fn queue_full_response(data: &AppState) -> Option<HttpResponse> {
+    let max_queue_depth = data.max_queue_depth?;
+    
+    // The queue itself, not GetQueueStats: its pending_count also includes the
+    // Pending task results of those same queued tasks
+    let pending_count = match data.task_queue.send(GetQueueDepth).await {
+        Ok(Ok(depth)) => depth,
+        Ok(Err(e)) => {
+            println!("   ⚠️ Could not read queue depth, accepting submission: {}", e);
+            return None;
+        }
+        Err(e) => {
+            println!("   ⚠️ Could not read queue depth, accepting submission: {}", e);
+            return None;
+        }
+    };
+    
+    if pending_count < max_queue_depth {
+        return None;
+    }
+    
+    println!("   🚦 Queue full ({} pending, limit {}), refusing submission", pending_count, max_queue_depth);
+    Some(HttpResponse::ServiceUnavailable()
+        .insert_header(("Retry-After", QUEUE_FULL_RETRY_AFTER_SECS.to_string()))
+        .json(json!({
+            "error": "Queue is full",
+            "details": format!("{} tasks are pending (limit {}); retry later", pending_count, max_queue_depth),
+            "pending_count": pending_count,
+            "max_queue_depth": max_queue_depth,
+            "retry_after_seconds": QUEUE_FULL_RETRY_AFTER_SECS
+        })))
+}
+
+// 503 model_unavailable with the paths that were searched, when --reject-without-model
+// is set and a transcription in `language` would fail for lack of a model
+fn model_unavailable_response(data: &AppState, language: Option<&str>) -> Option<HttpResponse> {
+    if !data.reject_without_model {
+        return None;
+    }
+    
+    match thai_transcriber::check_model_available(language) {
+        Ok(()) => None,
+        Err(e) => {
+            println!("   ❌ No model available, refusing submission: {}", e);
+            Some(HttpResponse::ServiceUnavailable().json(model_error_body(&e)))
+        }
+    }
+}
+
+// Error body for a failed model lookup; searched_paths lists where the model was looked for
+fn model_error_body(e: &thai_transcriber::TranscriptionError) -> serde_json::Value {
+    match e {
+        thai_transcriber::TranscriptionError::ModelUnavailable(paths) => json!({
+            "error": "model_unavailable",
+            "details": e.to_string(),
+            "searched_paths": paths,
+            "suggestion": "Place a Whisper model at one of the searched paths, or restart with --auto-download"
+        }),
+        _ => json!({
+            "error": "Model not found",
+            "details": e.to_string()
+        }),
+    }
+}
+
 // Upload and transcribe endpoint with queue support
 async fn transcribe_handler(
+    http_req: actix_web::HttpRequest,
     mut payload: Multipart,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse> {
+    let idempotency_key = match idempotency_key(&http_req) {
+        Ok(key) => key,
+        Err(response) => return Ok(response),
+    };
+    // Refuse before reading the upload so a full queue doesn't cost the bandwidth
+    if let Some(response) = queue_full_response(&data).await {
+        return Ok(response);
+    }
     let mut temp_file: Option<NamedTempFile> = None;
+    let mut original_filename: Option<String> = None;
     let mut language: Option<String> = None;
     let mut backend: Option<String> = None;
     let mut priority: Option<i32> = None;
     let mut risk_analysis: Option<bool> = None;
+    let mut trim_silence: Option<bool> = None;
+    let mut translate: Option<bool> = None;
+    let mut declip: Option<bool> = None;
+    let mut clip_threshold: Option<f32> = None;
+    let mut agc_target_dbfs: Option<f32> = None;
+    let mut channel: Option<usize> = None;
+    let mut chunk_overlap_seconds: Option<f64> = None;
+    let mut min_confidence: Option<f64> = None;
+    let mut word_confidence_threshold: Option<f64> = None;
+    let mut speaker_gap: Option<f64> = None;
+    let mut offset_seconds: Option<f64> = None;
+    let mut max_repeat: Option<usize> = None;
+    let mut max_segment_len: Option<usize> = None;
+    let mut max_tokens: Option<usize> = None;
+    let mut max_segments: Option<usize> = None;
+    let mut no_speech_threshold: Option<f32> = None;
+    let mut logprob_threshold: Option<f32> = None;
+    let mut entropy_threshold: Option<f32> = None;
+    let mut compression_ratio_threshold: Option<f32> = None;
+    let mut keep_audio: Option<bool> = None;
+    let mut callback: Option<String> = None;
+    let mut validate: Option<bool> = None;
+    let mut cache: Option<bool> = None;
+    let mut restore_punctuation: Option<bool> = None;
+    let mut timeout_seconds: Option<u64> = None;
+    let mut paragraphs: Option<bool> = None;
+    let mut prompt: Option<String> = None;
     let mut file_size_bytes: Option<u64> = None;
     let mut duration_seconds: Option<f64> = None;
     let request_id = Uuid::new_v4().to_string();
@@ -135,19 +324,41 @@ async fn transcribe_handler(
             match name {
                 "audio" => {
                     if let Some(filename) = content_disposition.get_filename() {
+                        // Owned, since streaming the body below needs `field` mutably
+                        let filename = filename.to_string();
                         println!("   📁 Received file: {}", filename);
                         
+                        // Keep the original extension so the loader can detect video containers
+                        let extension = std::path::Path::new(&filename)
+                            .extension()
+                            .and_then(|ext| ext.to_str())
+                            .unwrap_or("tmp")
+                            .to_lowercase();
+                        
                         // Create temporary file
-                        let mut file = NamedTempFile::new()
+                        let mut file = NamedTempFile::with_suffix(format!(".{}", extension))
                             .map_err(|e| ErrorBadRequest(format!("Failed to create temp file: {}", e)))?;
                         
-                        // Stream file data
+                        // Stream file data, aborting once the upload exceeds the limit
+                        let mut written: u64 = 0;
                         while let Some(chunk) = field.try_next().await? {
+                            written += chunk.len() as u64;
+                            if written > data.max_upload_bytes {
+                                println!("   ❌ Upload exceeds {} byte limit, aborting", data.max_upload_bytes);
+                                // Dropping the NamedTempFile deletes the partial upload
+                                drop(file);
+                                return Ok(HttpResponse::PayloadTooLarge().json(json!({
+                                    "error": "Uploaded file too large",
+                                    "details": format!("Maximum upload size is {} bytes", data.max_upload_bytes),
+                                    "max_upload_bytes": data.max_upload_bytes
+                                })));
+                            }
                             file.write_all(&chunk)
                                 .map_err(|e| ErrorBadRequest(format!("Failed to write chunk: {}", e)))?;
                         }
                         
                         temp_file = Some(file);
+                        original_filename = Some(filename);
                     }
                 }
                 "language" => {
@@ -186,6 +397,277 @@ async fn transcribe_handler(
                         println!("   🛡️ Risk analysis: {:?}", risk_analysis);
                     }
                 }
+                "trim_silence" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(trim_str) = String::from_utf8(bytes) {
+                        trim_silence = trim_str.parse().ok();
+                        println!("   ✂️ Trim silence: {:?}", trim_silence);
+                    }
+                }
+                "translate" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(translate_str) = String::from_utf8(bytes) {
+                        translate = translate_str.parse().ok();
+                        println!("   🔤 Translate to English: {:?}", translate);
+                    }
+                }
+                "declip" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(declip_str) = String::from_utf8(bytes) {
+                        declip = declip_str.parse().ok();
+                        println!("   🩹 De-clip: {:?}", declip);
+                    }
+                }
+                "clip_threshold" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(threshold_str) = String::from_utf8(bytes) {
+                        clip_threshold = threshold_str.trim().parse().ok();
+                        println!("   📈 Clip threshold: {:?}", clip_threshold);
+                    }
+                }
+                "agc_target_dbfs" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(target_str) = String::from_utf8(bytes) {
+                        agc_target_dbfs = target_str.trim().parse().ok();
+                        println!("   🎚️ AGC target: {:?} dBFS", agc_target_dbfs);
+                    }
+                }
+                "channel" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(channel_str) = String::from_utf8(bytes) {
+                        channel = channel_str.trim().parse().ok();
+                        println!("   🎧 Channel: {:?}", channel);
+                    }
+                }
+                "chunk_overlap_seconds" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(overlap_str) = String::from_utf8(bytes) {
+                        chunk_overlap_seconds = overlap_str.trim().parse().ok();
+                        println!("   🔗 Chunk overlap: {:?}s", chunk_overlap_seconds);
+                    }
+                }
+                "min_confidence" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(confidence_str) = String::from_utf8(bytes) {
+                        min_confidence = confidence_str.trim().parse().ok();
+                        println!("   🧹 Min confidence: {:?}", min_confidence);
+                    }
+                }
+                "word_confidence_threshold" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(threshold_str) = String::from_utf8(bytes) {
+                        word_confidence_threshold = threshold_str.trim().parse().ok();
+                        println!("   🧹 Word confidence threshold: {:?}", word_confidence_threshold);
+                    }
+                }
+                "speaker_gap" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(gap_str) = String::from_utf8(bytes) {
+                        speaker_gap = gap_str.trim().parse().ok();
+                        println!("   🗣️ Speaker gap: {:?}", speaker_gap);
+                    }
+                }
+                "offset_seconds" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(offset_str) = String::from_utf8(bytes) {
+                        offset_seconds = offset_str.trim().parse().ok();
+                        println!("   ⏩ Timestamp offset: {:?} seconds", offset_seconds);
+                    }
+                }
+                "max_repeat" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(repeat_str) = String::from_utf8(bytes) {
+                        max_repeat = repeat_str.trim().parse().ok();
+                        println!("   🔁 Max repeat: {:?}", max_repeat);
+                    }
+                }
+                "max_segment_len" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(len_str) = String::from_utf8(bytes) {
+                        max_segment_len = len_str.trim().parse().ok();
+                        println!("   ✂️ Max segment length: {:?} characters", max_segment_len);
+                    }
+                }
+                "max_tokens" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(tokens_str) = String::from_utf8(bytes) {
+                        max_tokens = tokens_str.trim().parse().ok();
+                        println!("   ✂️ Max tokens per segment: {:?}", max_tokens);
+                    }
+                }
+                "no_speech_threshold" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(threshold_str) = String::from_utf8(bytes) {
+                        no_speech_threshold = threshold_str.trim().parse().ok();
+                        println!("   🔇 No-speech threshold: {:?}", no_speech_threshold);
+                    }
+                }
+                "logprob_threshold" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(threshold_str) = String::from_utf8(bytes) {
+                        logprob_threshold = threshold_str.trim().parse().ok();
+                        println!("   📉 Logprob threshold: {:?}", logprob_threshold);
+                    }
+                }
+                "entropy_threshold" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(threshold_str) = String::from_utf8(bytes) {
+                        entropy_threshold = threshold_str.trim().parse().ok();
+                        println!("   🌀 Entropy threshold: {:?}", entropy_threshold);
+                    }
+                }
+                "compression_ratio_threshold" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(threshold_str) = String::from_utf8(bytes) {
+                        compression_ratio_threshold = threshold_str.trim().parse().ok();
+                        println!("   🗜️ Compression ratio threshold: {:?}", compression_ratio_threshold);
+                    }
+                }
+                "max_segments" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(segments_str) = String::from_utf8(bytes) {
+                        max_segments = segments_str.trim().parse().ok();
+                        println!("   ✂️ Max segments: {:?}", max_segments);
+                    }
+                }
+                "paragraphs" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(paragraphs_str) = String::from_utf8(bytes) {
+                        paragraphs = paragraphs_str.trim().parse().ok();
+                        println!("   📰 Paragraphs: {:?}", paragraphs);
+                    }
+                }
+                "timeout_seconds" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(timeout_str) = String::from_utf8(bytes) {
+                        timeout_seconds = timeout_str.trim().parse().ok();
+                        println!("   ⏱️ Timeout override: {:?}s", timeout_seconds);
+                    }
+                }
+                "keep_audio" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(keep_str) = String::from_utf8(bytes) {
+                        keep_audio = keep_str.parse().ok();
+                        println!("   💾 Keep Whisper input audio: {:?}", keep_audio);
+                    }
+                }
+                "callback_url" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(url) = String::from_utf8(bytes) {
+                        callback = Some(url.trim().to_string()).filter(|url| !url.is_empty());
+                        println!("   📣 Callback URL: {:?}", callback);
+                    }
+                }
+                "validate" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(validate_str) = String::from_utf8(bytes) {
+                        validate = validate_str.trim().parse().ok();
+                        println!("   🧪 Validate only: {:?}", validate);
+                    }
+                }
+                "restore_punctuation" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(restore_str) = String::from_utf8(bytes) {
+                        restore_punctuation = restore_str.trim().parse().ok();
+                        println!("   ✒️ Restore punctuation: {:?}", restore_punctuation);
+                    }
+                }
+                "cache" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    if let Ok(cache_str) = String::from_utf8(bytes) {
+                        cache = cache_str.trim().parse().ok();
+                        println!("   🗃️ Reuse cached result: {:?}", cache);
+                    }
+                }
+                "prompt" => {
+                    let mut bytes = Vec::new();
+                    while let Some(chunk) = field.try_next().await? {
+                        bytes.extend_from_slice(&chunk);
+                    }
+                    let prompt_str = String::from_utf8_lossy(&bytes).trim().to_string();
+                    if !prompt_str.is_empty() {
+                        println!("   💬 Initial prompt: {} characters", prompt_str.chars().count());
+                        prompt = Some(prompt_str);
+                    }
+                }
                 "file_size_bytes" => {
                     let mut bytes = Vec::new();
                     while let Some(chunk) = field.try_next().await? {
@@ -231,6 +713,54 @@ async fn transcribe_handler(
     
     println!("   🎯 Selected backend: {}", backend_str);
     
+    // validate=true: pre-flight the upload and the model, answer immediately, queue nothing
+    if validate.unwrap_or(false) {
+        let load_options = thai_transcriber::AudioLoadOptions {
+            trim_silence: trim_silence.unwrap_or(false),
+            declip: declip.unwrap_or(false),
+            clip_threshold,
+            agc_target_dbfs,
+            channel,
+            ..Default::default()
+        };
+        let validate_path = temp_path.clone();
+        let result = web::block(move || thai_transcriber::validate_audio_file(&validate_path, &load_options)).await;
+        
+        return match result {
+            Ok(Ok(mut report)) => {
+                report["audio_file"] = json!(original_filename);
+                report["request_id"] = json!(request_id);
+                Ok(HttpResponse::Ok().json(report))
+            }
+            Ok(Err(e)) => {
+                println!("   ❌ Validation failed: {}", e);
+                let status = actix_web::http::StatusCode::from_u16(e.status_code())
+                    .unwrap_or(actix_web::http::StatusCode::UNPROCESSABLE_ENTITY);
+                let mut body = json!({
+                    "valid": false,
+                    "error": "Validation failed",
+                    "details": e.to_string(),
+                    "request_id": request_id
+                });
+                if let thai_transcriber::TranscriptionError::ModelUnavailable(paths) = &e {
+                    body["error"] = json!("model_unavailable");
+                    body["searched_paths"] = json!(paths);
+                }
+                Ok(HttpResponse::build(status).json(body))
+            }
+            Err(e) => {
+                Ok(HttpResponse::InternalServerError().json(json!({
+                    "error": "Validation task failed",
+                    "details": e.to_string()
+                })))
+            }
+        };
+    }
+    
+    if let Some(response) = model_unavailable_response(&data, language.as_deref()) {
+        return Ok(response);
+    }
+    
     // Get actual file size if not provided
     let actual_file_size = std::fs::metadata(temp_file.path())
         .map(|m| m.len())
@@ -240,21 +770,111 @@ async fn transcribe_handler(
     // Prepare task payload
     let mut task_payload = json!({
         "file_path": temp_path,
+        "original_filename": original_filename,
         "backend": backend_str,
         "language": language,
         "risk_analysis": risk_analysis.unwrap_or(false),
+        "trim_silence": trim_silence.unwrap_or(false),
+        "translate": translate.unwrap_or(false),
+        "declip": declip.unwrap_or(false),
+        "clip_threshold": clip_threshold,
+        "agc_target_dbfs": agc_target_dbfs,
+        "channel": channel,
+        "chunk_overlap_seconds": chunk_overlap_seconds,
+        "min_confidence": min_confidence,
+        "word_confidence_threshold": word_confidence_threshold,
+        "speaker_gap": speaker_gap,
+        "offset_seconds": offset_seconds.unwrap_or(0.0),
+        "max_repeat": max_repeat,
+        "max_segment_len": max_segment_len,
+        "max_tokens": max_tokens,
+        "no_speech_threshold": no_speech_threshold,
+        "logprob_threshold": logprob_threshold,
+        "entropy_threshold": entropy_threshold,
+        "compression_ratio_threshold": compression_ratio_threshold,
+        "max_segments": max_segments,
+        "keep_audio": keep_audio.unwrap_or(false),
+        "restore_punctuation": restore_punctuation.unwrap_or(false),
+        "timeout_seconds": timeout_seconds,
+        "paragraphs": paragraphs.unwrap_or(false),
+        "callback_url": callback,
+        "initial_prompt": prompt,
         "request_id": request_id,
-        "file_size_bytes": final_file_size
+        "file_size_bytes": final_file_size,
+        "max_total_duration_minutes": data.max_total_duration_minutes
     });
     
     if let Some(duration) = duration_seconds {
         task_payload["duration_seconds"] = json!(duration);
+        
+        // Trust the client's duration to fail fast; the worker re-checks the decoded audio
+        if duration / 60.0 > data.max_total_duration_minutes {
+            println!("   ❌ Duration {:.1} min exceeds {} min limit", duration / 60.0, data.max_total_duration_minutes);
+            return Ok(HttpResponse::PayloadTooLarge().json(json!({
+                "error": "Audio too long",
+                "details": format!("Maximum duration is {} minutes", data.max_total_duration_minutes),
+                "max_total_duration_minutes": data.max_total_duration_minutes
+            })));
+        }
     }
     
     println!("   📊 Task metadata: {}MB, {}min", 
              (final_file_size as f64 / 1024.0 / 1024.0), 
              duration_seconds.unwrap_or(0.0) / 60.0);
     
+    // Out-of-range options are the client's fault, not a queue failure
+    if let Err(e) = TranscriptionPayload::from_value(&task_payload) {
+        println!("   ❌ {}", e);
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid transcription options",
+            "details": e
+        })));
+    }
+    if let Err(e) = check_callback_url(&task_payload).await {
+        println!("   ❌ {}", e);
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid callback_url",
+            "details": e
+        })));
+    }
+    
+    // cache=true: identical audio already transcribed with the same options and model
+    // is answered from the earlier result instead of being queued again
+    if cache.unwrap_or(false) {
+        let hash_path = temp_path.clone();
+        match web::block(move || thai_transcriber::audio_content_hash(&hash_path)).await {
+            Ok(Ok(hash)) => {
+                println!("   🔑 Audio hash: {}", hash);
+                
+                match data.task_queue.send(FindCachedResult {
+                    audio_hash: hash.clone(),
+                    payload: task_payload.clone(),
+                }).await {
+                    Ok(Ok(Some(cached))) => {
+                        println!("   🗃️ Returning cached result of task {}", cached.id);
+                        return Ok(HttpResponse::Ok().json(json!({
+                            "status": "cached",
+                            "task_id": cached.id,
+                            "request_id": request_id,
+                            "audio_hash": hash,
+                            "message": "Identical audio was already transcribed with the same options",
+                            "completed_at": cached.completed_at,
+                            "result": cached.result
+                        })));
+                    }
+                    Ok(Ok(None)) => println!("   🗃️ No cached result, transcribing"),
+                    Ok(Err(e)) => println!("   ⚠️ Cache lookup failed, transcribing: {}", e),
+                    Err(e) => println!("   ⚠️ Cache lookup failed, transcribing: {}", e),
+                }
+                
+                task_payload["audio_hash"] = json!(hash);
+            }
+            // The worker reports the decode error properly; caching just doesn't apply
+            Ok(Err(e)) => println!("   ⚠️ Could not hash audio, caching disabled for this task: {}", e),
+            Err(e) => println!("   ⚠️ Could not hash audio, caching disabled for this task: {}", e),
+        }
+    }
+    
     // Submit to queue
     let task_type = TaskType::Transcription;
     let task_priority = priority.unwrap_or(0);
@@ -263,12 +883,17 @@ async fn transcribe_handler(
         task_type,
         payload: task_payload,
         priority: Some(task_priority),
+        idempotency_key,
     }).await {
-        Ok(Ok(task_id)) => {
+        // The duplicate upload is dropped with temp_file; the original task owns its own copy
+        Ok(Ok(SubmitOutcome::Existing(task_id))) => Ok(existing_task_response(&task_id)),
+        Ok(Ok(SubmitOutcome::Created(task_id))) => {
             println!("   ✅ Task queued with ID: {}", task_id);
             
-            // Keep the temp file alive by storing it (in a real app, you'd want better lifecycle management)
-            std::mem::forget(temp_file);
+            // The queue owns the file from here and deletes it once the task finishes
+            if let Err(e) = temp_file.keep() {
+                println!("   ⚠️ Failed to hand temp file over to the queue: {}", e);
+            }
             
             Ok(HttpResponse::Accepted().json(json!({
                 "status": "queued",
@@ -298,41 +923,937 @@ async fn transcribe_handler(
     }
 }
 
-// Risk analysis endpoint with queue support
-async fn risk_analysis_handler(
-    req: web::Json<RiskAnalysisRequest>,
+// Content types accepted from audio URLs; S3 often serves uploads as octet-stream
+fn is_audio_content_type(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    mime.starts_with("audio/")
+        || mime.starts_with("video/")
+        || mime == "application/ogg"
+        || mime == "application/octet-stream"
+        || mime == "binary/octet-stream"
+}
+
+// Bounds on fetching audio URLs, so a stalled or trickling server can't hold a worker forever
+const URL_CONNECT_TIMEOUT_SECS: u64 = 10;
+const URL_DOWNLOAD_TIMEOUT_SECS: u64 = 600;
+const URL_MAX_REDIRECTS: usize = 10;
+
+// Redirect targets get the same checks as the original URL: hostnames when
+// `PublicResolver` resolves them, IP literals here
+fn public_redirect_policy() -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= URL_MAX_REDIRECTS {
+            return attempt.error(format!("more than {} redirects", URL_MAX_REDIRECTS));
+        }
+        match check_url_host_literal(attempt.url()) {
+            Ok(()) => attempt.follow(),
+            Err(e) => attempt.error(format!("Redirect refused: {}", e)),
+        }
+    })
+}
+
+// Transcribe audio that is already hosted (e.g. S3 presigned URLs) instead of uploading it
+async fn transcribe_url_handler(
+    http_req: actix_web::HttpRequest,
+    req: web::Json<TranscribeUrlRequest>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse> {
+    let idempotency_key = match idempotency_key(&http_req) {
+        Ok(key) => key,
+        Err(response) => return Ok(response),
+    };
+    if let Some(response) = queue_full_response(&data).await {
+        return Ok(response);
+    }
+    // Checked before downloading, the audio would be of no use without a model
+    if let Some(response) = model_unavailable_response(&data, req.language.as_deref()) {
+        return Ok(response);
+    }
     let request_id = Uuid::new_v4().to_string();
     
-    println!("🔍 Processing risk analysis request: {}", request_id);
+    println!("🔗 Processing URL transcription request: {}", request_id);
     
-    // Prepare task payload
-    let task_payload = json!({
-        "text": req.text,
-        "request_id": request_id
-    });
+    let url = match reqwest::Url::parse(&req.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+        _ => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid URL",
+                "details": format!("'{}' is not an http(s) URL", req.url)
+            })));
+        }
+    };
     
-    // Submit to queue
-    let task_type = TaskType::RiskAnalysis;
-    let priority = req.priority.unwrap_or(0);
+    if let Err(e) = check_public_url(&url).await {
+        println!("   ❌ URL not allowed: {}", e);
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "URL not allowed",
+            "details": e
+        })));
+    }
+    
+    // Follow redirects so shortened and presigned links resolve to the actual object
+    let client = public_client_builder()
+        .redirect(public_redirect_policy())
+        .connect_timeout(std::time::Duration::from_secs(URL_CONNECT_TIMEOUT_SECS))
+        .timeout(std::time::Duration::from_secs(URL_DOWNLOAD_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| actix_web::error::ErrorInternalServerError(format!("Failed to create HTTP client: {}", e)))?;
+    
+    println!("   ⬇️ Downloading: {}", url);
+    let mut response = match client.get(url.clone()).send().await {
+        Ok(response) if response.status().is_success() => response,
+        Ok(response) => {
+            println!("   ❌ Download failed with status {}", response.status());
+            return Ok(HttpResponse::BadGateway().json(json!({
+                "error": "Failed to download audio",
+                "details": format!("{} returned {}", url, response.status())
+            })));
+        }
+        Err(e) if e.is_redirect() => {
+            println!("   ❌ Redirect not allowed: {}", e);
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "URL not allowed",
+                "details": e.to_string()
+            })));
+        }
+        Err(e) if e.is_timeout() => {
+            println!("   ❌ Download timed out: {}", e);
+            return Ok(HttpResponse::GatewayTimeout().json(json!({
+                "error": "Timed out downloading audio",
+                "details": e.to_string()
+            })));
+        }
+        Err(e) => {
+            println!("   ❌ Download failed: {}", e);
+            return Ok(HttpResponse::BadGateway().json(json!({
+                "error": "Failed to download audio",
+                "details": e.to_string()
+            })));
+        }
+    };
+    
+    let content_type = response.headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    if !is_audio_content_type(&content_type) {
+        println!("   ❌ Not audio content: {:?}", content_type);
+        return Ok(HttpResponse::UnsupportedMediaType().json(json!({
+            "error": "URL does not point to audio",
+            "details": format!("Content-Type is '{}', expected audio/* or video/*", content_type)
+        })));
+    }
+    
+    let too_large = || HttpResponse::PayloadTooLarge().json(json!({
+        "error": "Remote file too large",
+        "details": format!("Maximum download size is {} bytes", data.max_upload_bytes),
+        "max_upload_bytes": data.max_upload_bytes
+    }));
+    if response.content_length().map_or(false, |length| length > data.max_upload_bytes) {
+        println!("   ❌ Remote file exceeds {} byte limit", data.max_upload_bytes);
+        return Ok(too_large());
+    }
+    
+    // Name the file after the last path segment of the final (post-redirect) URL
+    let original_filename = response.url()
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download")
+        .to_string();
+    
+    // Keep the original extension so the loader can detect video containers
+    let extension = std::path::Path::new(&original_filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("tmp")
+        .to_lowercase();
+    
+    let mut temp_file = NamedTempFile::with_suffix(format!(".{}", extension))
+        .map_err(|e| ErrorBadRequest(format!("Failed to create temp file: {}", e)))?;
+    
+    // Stream the body, aborting once it exceeds the limit (Content-Length may be missing or wrong)
+    let mut written: u64 = 0;
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) if e.is_timeout() => {
+                println!("   ❌ Download timed out: {}", e);
+                return Ok(HttpResponse::GatewayTimeout().json(json!({
+                    "error": "Timed out downloading audio",
+                    "details": e.to_string()
+                })));
+            }
+            Err(e) => {
+                println!("   ❌ Download interrupted: {}", e);
+                return Ok(HttpResponse::BadGateway().json(json!({
+                    "error": "Failed to download audio",
+                    "details": e.to_string()
+                })));
+            }
+        };
+        written += chunk.len() as u64;
+        if written > data.max_upload_bytes {
+            println!("   ❌ Download exceeds {} byte limit, aborting", data.max_upload_bytes);
+            // Dropping the NamedTempFile deletes the partial download
+            drop(temp_file);
+            return Ok(too_large());
+        }
+        temp_file.write_all(&chunk)
+            .map_err(|e| ErrorBadRequest(format!("Failed to write chunk: {}", e)))?;
+    }
+    
+    println!("   📁 Downloaded {} ({} bytes)", original_filename, written);
+    let temp_path = temp_file.path().to_string_lossy().to_string();
+    
+    // Validate backend selection
+    let backend_str = match req.backend.as_deref() {
+        Some("cpu") => "cpu",
+        Some("gpu") => "gpu",
+        Some("coreml") => "coreml",
+        Some("auto") | None => "auto",
+        Some(other) => {
+            println!("   ⚠️ Unknown backend '{}', defaulting to 'auto'", other);
+            "auto"
+        }
+    };
+    
+    // Prepare task payload, same shape as the upload path
+    let task_payload = json!({
+        "file_path": temp_path,
+        "original_filename": original_filename,
+        "backend": backend_str,
+        "language": req.language,
+        "risk_analysis": req.risk_analysis.unwrap_or(false),
+        "request_id": request_id,
+        "callback_url": req.callback_url,
+        "timeout_seconds": req.timeout_seconds,
+        "file_size_bytes": written,
+        "max_total_duration_minutes": data.max_total_duration_minutes
+    });
+    
+    if let Err(e) = TranscriptionPayload::from_value(&task_payload) {
+        println!("   ❌ {}", e);
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid transcription options",
+            "details": e
+        })));
+    }
+    if let Err(e) = check_callback_url(&task_payload).await {
+        println!("   ❌ {}", e);
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid callback_url",
+            "details": e
+        })));
+    }
+    
+    // Submit to queue
+    match data.task_queue.send(SubmitTask {
+        task_type: TaskType::Transcription,
+        payload: task_payload,
+        priority: Some(req.priority.unwrap_or(0)),
+        idempotency_key,
+    }).await {
+        // The duplicate download is dropped with temp_file; the original task owns its own copy
+        Ok(Ok(SubmitOutcome::Existing(task_id))) => Ok(existing_task_response(&task_id)),
+        Ok(Ok(SubmitOutcome::Created(task_id))) => {
+            println!("   ✅ Task queued with ID: {}", task_id);
+            
+            // The queue owns the file from here and deletes it once the task finishes
+            if let Err(e) = temp_file.keep() {
+                println!("   ⚠️ Failed to hand temp file over to the queue: {}", e);
+            }
+            
+            Ok(HttpResponse::Accepted().json(json!({
+                "status": "queued",
+                "task_id": task_id,
+                "request_id": request_id,
+                "message": "Audio downloaded and queued for transcription",
+                "endpoints": {
+                    "status": format!("/api/task/{}/status", task_id),
+                    "websocket": "/ws"
+                }
+            })))
+        }
+        Ok(Err(e)) => {
+            println!("   ❌ Failed to queue task: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to queue transcription task",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            println!("   ❌ Queue communication error: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// Risk analysis endpoint with queue support
+async fn risk_analysis_handler(
+    http_req: actix_web::HttpRequest,
+    req: web::Json<RiskAnalysisRequest>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let idempotency_key = match idempotency_key(&http_req) {
+        Ok(key) => key,
+        Err(response) => return Ok(response),
+    };
+    if let Some(response) = queue_full_response(&data).await {
+        return Ok(response);
+    }
+    let request_id = Uuid::new_v4().to_string();
+    
+    println!("🔍 Processing risk analysis request: {}", request_id);
+    
+    // Prepare task payload
+    let mut task_payload = json!({
+        "text": req.text,
+        "request_id": request_id
+    });
+    if let Some(temperature) = req.temperature {
+        task_payload["temperature"] = json!(temperature);
+    }
+    if let Some(max_tokens) = req.max_tokens {
+        task_payload["max_tokens"] = json!(max_tokens);
+    }
+    if let Some(model) = &req.model {
+        task_payload["model"] = json!(model);
+    }
+    if let Some(voting) = &req.voting {
+        if RiskVoting::parse(voting).is_none() {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid voting policy",
+                "details": format!("Expected any, majority or all, got {}", voting)
+            })));
+        }
+        task_payload["voting"] = json!(voting);
+    }
+    if let Some(url) = &req.callback_url {
+        task_payload["callback_url"] = json!(url);
+        if let Err(e) = check_callback_url(&task_payload).await {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid callback_url",
+                "details": e
+            })));
+        }
+    }
+    
+    // Submit to queue
+    let task_type = TaskType::RiskAnalysis;
+    let priority = req.priority.unwrap_or(0);
+    
+    match data.task_queue.send(SubmitTask {
+        task_type,
+        payload: task_payload,
+        priority: Some(priority),
+        idempotency_key,
+    }).await {
+        Ok(Ok(SubmitOutcome::Existing(task_id))) => Ok(existing_task_response(&task_id)),
+        Ok(Ok(SubmitOutcome::Created(task_id))) => {
+            println!("   ✅ Risk analysis queued with ID: {}", task_id);
+            
+            Ok(HttpResponse::Accepted().json(json!({
+                "status": "queued",
+                "task_id": task_id,
+                "request_id": request_id,
+                "message": "Text queued for risk analysis",
+                "endpoints": {
+                    "status": format!("/api/task/{}/status", task_id),
+                    "websocket": "/ws"
+                }
+            })))
+        }
+        Ok(Err(e)) => {
+            println!("   ❌ Failed to queue risk analysis: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to queue risk analysis task",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            println!("   ❌ Queue communication error: {}", e);
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// 200 JSON response, indented when the client asked for ?pretty=true
+fn json_response(value: &serde_json::Value, pretty: bool) -> HttpResponse {
+    if !pretty {
+        return HttpResponse::Ok().json(value);
+    }
+    match serde_json::to_string_pretty(value) {
+        Ok(body) => HttpResponse::Ok().content_type("application/json").body(body),
+        Err(_) => HttpResponse::Ok().json(value),
+    }
+}
+
+// Weak ETag for a task's status: every change to a task bumps updated_at
+fn task_etag(task_result: &TaskResult) -> String {
+    format!("W/\"{}\"", task_result.updated_at.timestamp_micros())
+}
+
+// True when If-None-Match lists `etag` (weak comparison) or is "*"
+fn etag_matches(req: &actix_web::HttpRequest, etag: &str) -> bool {
+    let header = match req.headers().get(actix_web::http::header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        Some(header) => header,
+        None => return false,
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    
+    header.split(',').any(|candidate| candidate.trim() == "*" || opaque(candidate) == opaque(etag))
+}
+
+// Get task status endpoint; ?pretty=true indents the (possibly multi-megabyte) result.
+// Answers 304 when If-None-Match still matches, so pollers skip unchanged payloads.
+async fn get_task_status(
+    req: actix_web::HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+    let pretty = query.get("pretty").is_some_and(|value| value == "true" || value == "1");
+    
+    match data.task_queue.send(GetTaskStatus { task_id: task_id.clone() }).await {
+        Ok(Ok(Some(task_result))) => {
+            let etag = task_etag(&task_result);
+            if etag_matches(&req, &etag) {
+                return Ok(HttpResponse::NotModified()
+                    .insert_header((actix_web::http::header::ETAG, etag))
+                    .finish());
+            }
+            
+            let mut response = json_response(&json!({
+                "task_id": task_id,
+                "status": task_result.status,
+                "progress": task_result.progress,
+                "created_at": task_result.created_at,
+                "updated_at": task_result.updated_at,
+                "started_at": task_result.started_at,
+                "completed_at": task_result.completed_at,
+                "result": task_result.result,
+                "error": task_result.error
+            }), pretty);
+            if let Ok(value) = actix_web::http::header::HeaderValue::from_str(&etag) {
+                response.headers_mut().insert(actix_web::http::header::ETAG, value);
+            }
+            Ok(response)
+        }
+        Ok(Ok(None)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found",
+                "task_id": task_id
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to get task status",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// CSV field per RFC 4180: quoted when it contains a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Word-level timestamps of a completed transcription as start,end,text,confidence rows
+async fn get_task_words_csv(
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+    
+    match data.task_queue.send(GetTaskStatus { task_id: task_id.clone() }).await {
+        Ok(Ok(Some(task_result))) => {
+            if task_result.status != TaskStatus::Completed {
+                return Ok(HttpResponse::Conflict()
+                    .content_type("text/plain; charset=utf-8")
+                    .body(format!("Task {} is not completed (status: {:?})", task_id, task_result.status)));
+            }
+            
+            let mut csv = String::from("start,end,text,confidence\n");
+            let segments = task_result.result
+                .as_ref()
+                .and_then(|result| result.get("segments"))
+                .and_then(|segments| segments.as_array());
+            
+            for segment in segments.into_iter().flatten() {
+                let words = segment.get("words").and_then(|words| words.as_array());
+                for word in words.into_iter().flatten() {
+                    let start = word.get("start").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    let end = word.get("end").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    let text = word.get("text").and_then(|v| v.as_str()).unwrap_or("").trim();
+                    let confidence = word.get("confidence").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    csv.push_str(&format!("{:.3},{:.3},{},{:.4}\n", start, end, csv_field(text), confidence));
+                }
+            }
+            
+            Ok(HttpResponse::Ok()
+                .content_type("text/csv; charset=utf-8")
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}_words.csv\"", task_id)))
+                .body(csv))
+        }
+        Ok(Ok(None)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found",
+                "task_id": task_id
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to get task status",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// Parse an optional seconds bound of /segments; None when absent
+fn parse_seconds_param(query: &HashMap<String, String>, name: &str) -> std::result::Result<Option<f64>, HttpResponse> {
+    match query.get(name) {
+        Some(value) => match value.trim().parse::<f64>() {
+            Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(Some(seconds)),
+            _ => Err(HttpResponse::BadRequest().json(json!({
+                "error": format!("Invalid {}", name),
+                "details": format!("'{}' is not a non-negative number of seconds", value)
+            }))),
+        },
+        None => Ok(None),
+    }
+}
+
+// Segments of a completed transcription overlapping [from, to) seconds, so long
+// transcripts can be loaded piece by piece
+async fn get_task_segments(
+    path: web::Path<String>,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+    
+    let from = match parse_seconds_param(&query, "from") {
+        Ok(from) => from.unwrap_or(0.0),
+        Err(response) => return Ok(response),
+    };
+    let to = match parse_seconds_param(&query, "to") {
+        Ok(to) => to.unwrap_or(f64::INFINITY),
+        Err(response) => return Ok(response),
+    };
+    if to <= from {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid range",
+            "details": format!("to ({}) must be greater than from ({})", to, from)
+        })));
+    }
+    
+    match data.task_queue.send(GetTaskStatus { task_id: task_id.clone() }).await {
+        Ok(Ok(Some(task_result))) => {
+            if task_result.status != TaskStatus::Completed {
+                return Ok(HttpResponse::Conflict().json(json!({
+                    "error": "Task is not completed",
+                    "task_id": task_id,
+                    "status": task_result.status
+                })));
+            }
+            
+            let all_segments = task_result.result
+                .as_ref()
+                .and_then(|result| result.get("segments"))
+                .and_then(|segments| segments.as_array());
+            let total_segments = all_segments.map(|segments| segments.len()).unwrap_or(0);
+            
+            let segments: Vec<&serde_json::Value> = all_segments
+                .into_iter()
+                .flatten()
+                .filter(|segment| {
+                    let start = segment.get("start").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    let end = segment.get("end").and_then(|v| v.as_f64()).unwrap_or(start);
+                    start < to && end > from
+                })
+                .collect();
+            
+            Ok(HttpResponse::Ok().json(json!({
+                "task_id": task_id,
+                "from": from,
+                // Open-ended ranges serialize as null rather than infinity
+                "to": if to.is_finite() { Some(to) } else { None },
+                "total_segments": total_segments,
+                "segments": segments
+            })))
+        }
+        Ok(Ok(None)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found",
+                "task_id": task_id
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to get task status",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// Get the plain-text transcript of a completed task
+async fn get_task_transcript(
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+    
+    match data.task_queue.send(GetTaskStatus { task_id: task_id.clone() }).await {
+        Ok(Ok(Some(task_result))) => {
+            if task_result.status != TaskStatus::Completed {
+                return Ok(HttpResponse::Conflict()
+                    .content_type("text/plain; charset=utf-8")
+                    .body(format!("Task {} is not completed (status: {:?})", task_id, task_result.status)));
+            }
+            
+            let text = task_result.result
+                .as_ref()
+                .and_then(|result| result.get("text"))
+                .and_then(|text| text.as_str())
+                .unwrap_or("");
+            
+            Ok(HttpResponse::Ok()
+                .content_type("text/plain; charset=utf-8")
+                .body(text.to_string()))
+        }
+        Ok(Ok(None)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found",
+                "task_id": task_id
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to get task status",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SegmentCorrection {
+    text: String,
+}
+
+// Exact 16kHz mono WAV fed to Whisper, for tasks submitted with keep_audio=true
+async fn get_task_audio(
+    req: actix_web::HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+    
+    match data.task_queue.send(GetTaskAudio { task_id: task_id.clone() }).await {
+        Ok(Ok(TaskAudio::Available(wav_path))) => {
+            use actix_web::http::header;
+            // Streamed from disk rather than buffered: an hour-long recording is over 200MB as f32
+            let file = actix_files::NamedFile::open_async(&wav_path).await?
+                .set_content_type("audio/wav".parse().unwrap())
+                .set_content_disposition(header::ContentDisposition {
+                    disposition: header::DispositionType::Attachment,
+                    parameters: vec![header::DispositionParam::Filename(format!("{}.wav", task_id))],
+                });
+            Ok(file.into_response(&req))
+        }
+        Ok(Ok(TaskAudio::TaskNotFound)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found",
+                "task_id": task_id
+            })))
+        }
+        Ok(Ok(TaskAudio::NotCompleted(status))) => {
+            Ok(HttpResponse::Conflict().json(json!({
+                "error": "Task is not completed",
+                "task_id": task_id,
+                "status": status
+            })))
+        }
+        Ok(Ok(TaskAudio::NotKept)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Audio not available",
+                "details": "Submit the task with keep_audio=true; kept audio expires after an hour",
+                "task_id": task_id
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to get task audio",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// Save a reviewer's correction to one segment of a completed transcription
+async fn update_segment(
+    path: web::Path<(String, i64)>,
+    body: web::Json<SegmentCorrection>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let (task_id, segment_id) = path.into_inner();
+    
+    println!("✏️ Correcting segment {} of task {}", segment_id, task_id);
+    
+    match data.task_queue.send(UpdateSegmentText {
+        task_id: task_id.clone(),
+        segment_id,
+        text: body.into_inner().text,
+    }).await {
+        Ok(Ok(SegmentUpdate::Updated(segment))) => {
+            Ok(HttpResponse::Ok().json(json!({
+                "task_id": task_id,
+                "segment": segment
+            })))
+        }
+        Ok(Ok(SegmentUpdate::TaskNotFound)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found",
+                "task_id": task_id
+            })))
+        }
+        Ok(Ok(SegmentUpdate::NotCompleted(status))) => {
+            Ok(HttpResponse::Conflict().json(json!({
+                "error": "Task is not completed",
+                "task_id": task_id,
+                "status": status
+            })))
+        }
+        Ok(Ok(SegmentUpdate::SegmentNotFound)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Segment not found",
+                "task_id": task_id,
+                "segment_id": segment_id
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to update segment",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// Cancel a task that hasn't started yet; running tasks are left to finish
+async fn cancel_task(
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+    
+    println!("🛑 Cancel requested for task {}", task_id);
+    
+    match data.task_queue.send(CancelTask { task_id: task_id.clone() }).await {
+        Ok(Ok(CancelOutcome::Cancelled)) => {
+            println!("   ✅ Task {} removed from the queue", task_id);
+            Ok(HttpResponse::Ok().json(json!({
+                "task_id": task_id,
+                "status": TaskStatus::Cancelled,
+                "message": "Task cancelled before processing started"
+            })))
+        }
+        Ok(Ok(CancelOutcome::TaskNotFound)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found",
+                "task_id": task_id
+            })))
+        }
+        Ok(Ok(CancelOutcome::AlreadyRunning)) => {
+            Ok(HttpResponse::Conflict().json(json!({
+                "error": "Task is already processing",
+                "details": "Only tasks still waiting in the queue can be cancelled",
+                "task_id": task_id,
+                "status": TaskStatus::Processing
+            })))
+        }
+        Ok(Ok(CancelOutcome::AlreadyFinished(status))) => {
+            Ok(HttpResponse::Conflict().json(json!({
+                "error": "Task has already finished",
+                "task_id": task_id,
+                "status": status
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to cancel task",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// Re-run risk analysis on a completed transcription
+async fn rerun_risk_analysis(
+    http_req: actix_web::HttpRequest,
+    path: web::Path<String>,
+    body: Option<web::Json<serde_json::Value>>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let idempotency_key = match idempotency_key(&http_req) {
+        Ok(key) => key,
+        Err(response) => return Ok(response),
+    };
+    let task_id = path.into_inner();
+    let options = body.map(|b| b.into_inner()).unwrap_or_else(|| json!({}));
+    
+    println!("🔍 Re-running risk analysis for transcription: {}", task_id);
+    
+    let task_result = match data.task_queue.send(GetTaskStatus { task_id: task_id.clone() }).await {
+        Ok(Ok(Some(task_result))) => task_result,
+        Ok(Ok(None)) => {
+            return Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found",
+                "task_id": task_id
+            })));
+        }
+        Ok(Err(e)) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to get task status",
+                "details": e
+            })));
+        }
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })));
+        }
+    };
+    
+    if task_result.status != TaskStatus::Completed {
+        return Ok(HttpResponse::Conflict().json(json!({
+            "error": "Task is not completed",
+            "task_id": task_id,
+            "status": task_result.status
+        })));
+    }
+    
+    // Only transcription results carry segments; risk results don't
+    let result = match task_result.result.as_ref().filter(|r| r.get("segments").is_some()) {
+        Some(result) => result,
+        None => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Task is not a transcription",
+                "task_id": task_id
+            })));
+        }
+    };
+    
+    let text = result.get("text").and_then(|t| t.as_str()).unwrap_or("");
+    if text.trim().is_empty() {
+        return Ok(HttpResponse::UnprocessableEntity().json(json!({
+            "error": "Transcription has no text to analyze",
+            "task_id": task_id
+        })));
+    }
+    
+    // Link the new task back to the transcription; RiskConfig overrides pass through
+    let mut risk_payload = json!({
+        "text": text,
+        "source_type": "transcription",
+        "transcription_task_id": task_id,
+        "original_file": result.get("metadata").and_then(|m| m.get("original_filename")),
+        "language": result.get("language")
+    });
+    for key in ["temperature", "max_tokens", "model", "callback_url"] {
+        if let Some(value) = options.get(key) {
+            risk_payload[key] = value.clone();
+        }
+    }
+    if let Err(e) = check_callback_url(&risk_payload).await {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "error": "Invalid callback_url",
+            "details": e
+        })));
+    }
+    let priority = options.get("priority")
+        .and_then(|v| v.as_i64())
+        .map(|p| p as i32)
+        .unwrap_or(0);
     
     match data.task_queue.send(SubmitTask {
-        task_type,
-        payload: task_payload,
+        task_type: TaskType::RiskAnalysis,
+        payload: risk_payload,
         priority: Some(priority),
+        idempotency_key,
     }).await {
-        Ok(Ok(task_id)) => {
-            println!("   ✅ Risk analysis queued with ID: {}", task_id);
+        Ok(Ok(SubmitOutcome::Existing(risk_task_id))) => Ok(existing_task_response(&risk_task_id)),
+        Ok(Ok(SubmitOutcome::Created(risk_task_id))) => {
+            println!("   ✅ Risk analysis queued with ID: {}", risk_task_id);
             
             Ok(HttpResponse::Accepted().json(json!({
                 "status": "queued",
-                "task_id": task_id,
-                "request_id": request_id,
-                "message": "Text queued for risk analysis",
+                "task_id": risk_task_id,
+                "transcription_task_id": task_id,
+                "message": "Transcription queued for risk analysis",
                 "endpoints": {
-                    "status": format!("/api/task/{}/status", task_id),
-                    "websocket": "/ws"
+                    "status": format!("/api/task/{}/status", risk_task_id),
+                    "websocket": format!("/ws/task/{}", risk_task_id)
                 }
             })))
         }
@@ -353,36 +1874,18 @@ async fn risk_analysis_handler(
     }
 }
 
-// Get task status endpoint
-async fn get_task_status(
-    path: web::Path<String>,
-    data: web::Data<AppState>,
-) -> Result<HttpResponse> {
-    let task_id = path.into_inner();
-    
-    match data.task_queue.send(GetTaskStatus { task_id: task_id.clone() }).await {
-        Ok(Ok(Some(task_result))) => {
+// Get queue statistics endpoint
+async fn get_queue_stats(data: web::Data<AppState>) -> Result<HttpResponse> {
+    match data.task_queue.send(GetQueueStats).await {
+        Ok(Ok(stats)) => {
             Ok(HttpResponse::Ok().json(json!({
-                "task_id": task_id,
-                "status": task_result.status,
-                "progress": task_result.progress,
-                "created_at": task_result.created_at,
-                "updated_at": task_result.updated_at,
-                "started_at": task_result.started_at,
-                "completed_at": task_result.completed_at,
-                "result": task_result.result,
-                "error": task_result.error
-            })))
-        }
-        Ok(Ok(None)) => {
-            Ok(HttpResponse::NotFound().json(json!({
-                "error": "Task not found",
-                "task_id": task_id
+                "queue_stats": stats,
+                "timestamp": chrono::Utc::now()
             })))
         }
         Ok(Err(e)) => {
             Ok(HttpResponse::InternalServerError().json(json!({
-                "error": "Failed to get task status",
+                "error": "Failed to get queue statistics",
                 "details": e
             })))
         }
@@ -395,42 +1898,109 @@ async fn get_task_status(
     }
 }
 
-// Get queue statistics endpoint
-async fn get_queue_stats(data: web::Data<AppState>) -> Result<HttpResponse> {
-    match data.task_queue.send(GetQueueStats).await {
-        Ok(Ok(stats)) => {
-            Ok(HttpResponse::Ok().json(json!({
-                "queue_stats": stats,
-                "timestamp": chrono::Utc::now()
-            })))
-        }
+// Prometheus metrics endpoint (text exposition format)
+async fn metrics_handler(data: web::Data<AppState>) -> Result<HttpResponse> {
+    let stats = match data.task_queue.send(GetQueueStats).await {
+        Ok(Ok(stats)) => stats,
         Ok(Err(e)) => {
-            Ok(HttpResponse::InternalServerError().json(json!({
+            return Ok(HttpResponse::InternalServerError().json(json!({
                 "error": "Failed to get queue statistics",
                 "details": e
-            })))
+            })));
         }
         Err(e) => {
-            Ok(HttpResponse::InternalServerError().json(json!({
+            return Ok(HttpResponse::InternalServerError().json(json!({
                 "error": "Queue communication error",
                 "details": e.to_string()
-            })))
+            })));
+        }
+    };
+    
+    let metrics = match data.task_queue.send(GetQueueMetrics).await {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })));
         }
+    };
+    
+    let mut body = String::new();
+    
+    // Counters since startup
+    body.push_str("# HELP whisper_tasks_submitted_total Tasks submitted to the queue.\n");
+    body.push_str("# TYPE whisper_tasks_submitted_total counter\n");
+    body.push_str(&format!("whisper_tasks_submitted_total {}\n", metrics.submitted_total));
+    
+    body.push_str("# HELP whisper_tasks_completed_total Tasks that completed successfully.\n");
+    body.push_str("# TYPE whisper_tasks_completed_total counter\n");
+    body.push_str(&format!("whisper_tasks_completed_total {}\n", metrics.completed_total));
+    
+    body.push_str("# HELP whisper_tasks_failed_total Tasks that failed.\n");
+    body.push_str("# TYPE whisper_tasks_failed_total counter\n");
+    body.push_str(&format!("whisper_tasks_failed_total {}\n", metrics.failed_total));
+    
+    body.push_str("# HELP whisper_tasks_cancelled_total Tasks cancelled before they ran.\n");
+    body.push_str("# TYPE whisper_tasks_cancelled_total counter\n");
+    body.push_str(&format!("whisper_tasks_cancelled_total {}\n", metrics.cancelled_total));
+    
+    body.push_str("# HELP whisper_tasks_dead_lettered_total Failed tasks moved to the dead-letter queue.\n");
+    body.push_str("# TYPE whisper_tasks_dead_lettered_total counter\n");
+    body.push_str(&format!("whisper_tasks_dead_lettered_total {}\n", metrics.dead_lettered_total));
+    
+    // Current state of the stored tasks
+    body.push_str("# HELP whisper_tasks_pending Tasks waiting in the queue.\n");
+    body.push_str("# TYPE whisper_tasks_pending gauge\n");
+    body.push_str(&format!("whisper_tasks_pending {}\n", stats.pending_count));
+    
+    body.push_str("# HELP whisper_tasks_processing Tasks currently being processed.\n");
+    body.push_str("# TYPE whisper_tasks_processing gauge\n");
+    body.push_str(&format!("whisper_tasks_processing {}\n", stats.processing_count));
+    
+    body.push_str("# HELP whisper_tasks_stored Tasks kept in history, by status (failed includes cancelled).\n");
+    body.push_str("# TYPE whisper_tasks_stored gauge\n");
+    body.push_str(&format!("whisper_tasks_stored{{status=\"completed\"}} {}\n", stats.completed_count));
+    body.push_str(&format!("whisper_tasks_stored{{status=\"failed\"}} {}\n", stats.failed_count));
+    body.push_str(&format!("whisper_tasks_stored{{status=\"all\"}} {}\n", stats.total_tasks));
+    
+    body.push_str("# HELP whisper_task_duration_seconds Task processing time from start to completion.\n");
+    body.push_str("# TYPE whisper_task_duration_seconds histogram\n");
+    for (bucket, count) in DURATION_BUCKETS_SECONDS.iter().zip(&metrics.duration_buckets) {
+        body.push_str(&format!("whisper_task_duration_seconds_bucket{{le=\"{}\"}} {}\n", bucket, count));
     }
+    body.push_str(&format!("whisper_task_duration_seconds_bucket{{le=\"+Inf\"}} {}\n", metrics.duration_count));
+    body.push_str(&format!("whisper_task_duration_seconds_sum {}\n", metrics.duration_sum_seconds));
+    body.push_str(&format!("whisper_task_duration_seconds_count {}\n", metrics.duration_count));
+    
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(body))
 }
 
-// Get task history endpoint
+// Get task history endpoint, paged with limit plus offset or the previous page's next_cursor
 async fn get_task_history(
-    query: web::Query<serde_json::Value>,
+    query: web::Query<HashMap<String, String>>,
     data: web::Data<AppState>,
 ) -> Result<HttpResponse> {
-    let limit = query.get("limit")
-        .and_then(|v| v.as_u64())
-        .map(|v| v as usize);
+    let limit = query.get("limit").and_then(|v| v.parse::<usize>().ok());
+    let offset = query.get("offset").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+    
+    let cursor = match query.get("cursor").filter(|c| !c.is_empty()) {
+        Some(cursor) => match HistoryCursor::parse(cursor) {
+            Ok(cursor) => Some(cursor),
+            Err(e) => {
+                return Ok(HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid cursor",
+                    "details": e
+                })));
+            }
+        },
+        None => None,
+    };
     
     let status_filter = query.get("status")
-        .and_then(|v| v.as_str())
-        .and_then(|s| match s {
+        .and_then(|s| match s.as_str() {
             "pending" => Some(TaskStatus::Pending),
             "processing" => Some(TaskStatus::Processing),
             "completed" => Some(TaskStatus::Completed),
@@ -439,11 +2009,14 @@ async fn get_task_history(
             _ => None,
         });
     
-    match data.task_queue.send(GetTaskHistory { limit, status_filter }).await {
-        Ok(Ok(tasks)) => {
+    match data.task_queue.send(GetTaskHistory { limit, offset, cursor, status_filter }).await {
+        Ok(Ok(page)) => {
             Ok(HttpResponse::Ok().json(json!({
-                "tasks": tasks,
-                "count": tasks.len(),
+                "tasks": page.tasks,
+                "count": page.tasks.len(),
+                "total": page.total,
+                "offset": offset,
+                "next_cursor": page.next_cursor,
                 "timestamp": chrono::Utc::now()
             })))
         }
@@ -462,6 +2035,109 @@ async fn get_task_history(
     }
 }
 
+// List permanently failed tasks endpoint
+async fn get_dead_letters(
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let limit = query.get("limit").and_then(|v| v.parse::<usize>().ok());
+    
+    match data.task_queue.send(GetDeadLetters { limit }).await {
+        Ok(Ok(entries)) => {
+            Ok(HttpResponse::Ok().json(json!({
+                "dead_letters": entries,
+                "count": entries.len(),
+                "timestamp": chrono::Utc::now()
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to get dead-letter queue",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// Move a dead-lettered task back onto the main queue
+async fn requeue_dead_letter(
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+    
+    match data.task_queue.send(RequeueDeadLetter { task_id: task_id.clone() }).await {
+        Ok(Ok(Some(_))) => {
+            println!("♻️ Requeued dead-lettered task: {}", task_id);
+            Ok(HttpResponse::Ok().json(json!({
+                "message": "Task requeued successfully",
+                "task_id": task_id,
+                "status": "pending"
+            })))
+        }
+        Ok(Ok(None)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found in dead-letter queue",
+                "task_id": task_id
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to requeue task",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
+// Drop a dead-lettered task and the uploaded audio it kept
+async fn discard_dead_letter(
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let task_id = path.into_inner();
+    
+    match data.task_queue.send(DiscardDeadLetter { task_id: task_id.clone() }).await {
+        Ok(Ok(true)) => {
+            println!("🗑️ Discarded dead-lettered task: {}", task_id);
+            Ok(HttpResponse::Ok().json(json!({
+                "message": "Task removed from the dead-letter queue",
+                "task_id": task_id
+            })))
+        }
+        Ok(Ok(false)) => {
+            Ok(HttpResponse::NotFound().json(json!({
+                "error": "Task not found in dead-letter queue",
+                "task_id": task_id
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to discard task",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
 // Clean up stale tasks endpoint
 async fn cleanup_stale_tasks(data: web::Data<AppState>) -> Result<HttpResponse> {
     match data.task_queue.send(CleanupStaleTasks).await {
@@ -488,6 +2164,62 @@ async fn cleanup_stale_tasks(data: web::Data<AppState>) -> Result<HttpResponse>
     }
 }
 
+// Delete finished tasks, e.g. ?status=completed&older_than_hours=24
+async fn purge_tasks(
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let status = match query.get("status").map(|s| s.as_str()).unwrap_or("completed") {
+        "completed" => TaskStatus::Completed,
+        "failed" => TaskStatus::Failed,
+        "cancelled" => TaskStatus::Cancelled,
+        other => {
+            return Ok(HttpResponse::BadRequest().json(json!({
+                "error": "Invalid status",
+                "details": format!("Cannot purge '{}' tasks; use completed, failed or cancelled", other)
+            })));
+        }
+    };
+    
+    let older_than_hours = match query.get("older_than_hours") {
+        Some(value) => match value.parse::<u64>() {
+            Ok(hours) => Some(hours),
+            Err(_) => {
+                return Ok(HttpResponse::BadRequest().json(json!({
+                    "error": "Invalid older_than_hours",
+                    "details": format!("'{}' is not a whole number of hours", value)
+                })));
+            }
+        },
+        None => None,
+    };
+    
+    match data.task_queue.send(PurgeTasks { status, older_than_hours }).await {
+        Ok(Ok(purged_count)) => {
+            println!("🗑️ Purged {} {:?} tasks", purged_count, status);
+            Ok(HttpResponse::Ok().json(json!({
+                "message": "Tasks purged successfully",
+                "status": status,
+                "older_than_hours": older_than_hours,
+                "purged_count": purged_count,
+                "timestamp": chrono::Utc::now()
+            })))
+        }
+        Ok(Err(e)) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Failed to purge tasks",
+                "details": e
+            })))
+        }
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().json(json!({
+                "error": "Queue communication error",
+                "details": e.to_string()
+            })))
+        }
+    }
+}
+
 // WebSocket endpoint for real-time updates
 async fn websocket_handler(
     req: actix_web::HttpRequest,
@@ -499,6 +2231,8 @@ async fn websocket_handler(
         WebSocketSession {
             id: session_id,
             queue_addr: data.task_queue.clone(),
+            task_filter: None,
+            close_on_finish: false,
         },
         &req,
         stream,
@@ -508,6 +2242,46 @@ async fn websocket_handler(
     resp
 }
 
+// Live transcription of streamed PCM16 mono 16kHz audio
+async fn live_websocket_handler(
+    req: actix_web::HttpRequest,
+    stream: web::Payload,
+    query: web::Query<HashMap<String, String>>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let language = query.get("language").cloned().unwrap_or_else(|| "th".to_string());
+    let session = LiveTranscriptionSession::new(language.clone(), data.task_queue.clone());
+    let session_id = session.id;
+    let resp = ws::start(session, &req, stream);
+    
+    println!("🎙️ Live transcription session started: {} (language: {})", session_id, language);
+    resp
+}
+
+// WebSocket scoped to one task: sends its current state on connect and closes when it finishes
+async fn task_websocket_handler(
+    req: actix_web::HttpRequest,
+    path: web::Path<String>,
+    stream: web::Payload,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse> {
+    let session_id = Uuid::new_v4();
+    let task_id = path.into_inner();
+    let resp = ws::start(
+        WebSocketSession {
+            id: session_id,
+            queue_addr: data.task_queue.clone(),
+            task_filter: Some(task_id.clone()),
+            close_on_finish: true,
+        },
+        &req,
+        stream,
+    );
+    
+    println!("🔌 Task WebSocket connection established: {} (task {})", session_id, task_id);
+    resp
+}
+
 // Serve static files for the web UI
 async fn serve_static() -> Result<HttpResponse> {
     match std::fs::read_to_string("static/index.html") {
@@ -523,7 +2297,8 @@ async fn serve_static() -> Result<HttpResponse> {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init();
+    // Info by default so per-task pipeline logs show up; RUST_LOG=debug for audio detail
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     
     let matches = Command::new("Whisper Transcription API Server with Queue")
         .version("0.2.0")
@@ -548,38 +2323,216 @@ async fn main() -> std::io::Result<()> {
                 .help("Redis connection URL")
                 .default_value("redis://localhost:6379"),
         )
+        .arg(
+            Arg::new("backend")
+                .long("backend")
+                .help("Task storage: redis, or memory for a single node without Redis (tasks are lost on restart)")
+                .value_parser(["redis", "memory"])
+                .default_value("redis"),
+        )
+        .arg(
+            Arg::new("auto-download")
+                .long("auto-download")
+                .help("Download the default Whisper model into model/ if none is present")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("model-base-url")
+                .long("model-base-url")
+                .help("Base URL GGML models are downloaded from with --auto-download")
+                .default_value(thai_transcriber::DEFAULT_MODEL_BASE_URL),
+        )
+        .arg(
+            Arg::new("language-models")
+                .long("language-models")
+                .help("File of language=model_path lines routing languages to their own models (default: LANGUAGE_MODELS_FILE / LANGUAGE_MODELS env vars)"),
+        )
+        .arg(
+            Arg::new("max-upload-bytes")
+                .long("max-upload-bytes")
+                .help("Reject uploaded audio larger than this many bytes with 413 (default 500MB)"),
+        )
+        .arg(
+            Arg::new("max-duration-minutes")
+                .long("max-duration-minutes")
+                .help("Fail transcriptions of audio longer than this many minutes (default 180)"),
+        )
+        .arg(
+            Arg::new("stats-interval")
+                .long("stats-interval")
+                .help("Seconds between queue stats broadcasts to WebSocket clients, 0 to disable (default 30)"),
+        )
+        .arg(
+            Arg::new("max-concurrent-tasks")
+                .long("max-concurrent-tasks")
+                .help("Maximum number of tasks executed at the same time (default 2)"),
+        )
+        .arg(
+            Arg::new("stale-task-threshold")
+                .long("stale-task-threshold")
+                .help("Seconds a processing task may go without progress before cleanup fails it (default 3600)"),
+        )
+        .arg(
+            Arg::new("max-queue-depth")
+                .long("max-queue-depth")
+                .help("Answer new submissions with 503 once this many tasks are pending, 0 for no limit (default 0)"),
+        )
+        .arg(
+            Arg::new("reject-without-model")
+                .long("reject-without-model")
+                .help("Answer transcription submissions with 503 model_unavailable (listing the searched paths) while no Whisper model is available, instead of queueing tasks that will fail")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min-chars-for-risk")
+                .long("min-chars-for-risk")
+                .help("Skip automatic risk analysis of transcripts shorter than this many characters (default 10)"),
+        )
+        .arg(
+            Arg::new("min-words-for-risk")
+                .long("min-words-for-risk")
+                .help("Skip automatic risk analysis of transcripts with fewer words than this (default 3)"),
+        )
+        .arg(
+            Arg::new("webhook-secret")
+                .long("webhook-secret")
+                .help("Secret for the X-Webhook-Signature HMAC on task callbacks (default: WEBHOOK_SECRET env var, unsigned if unset)"),
+        )
         .get_matches();
 
     let port = matches.get_one::<String>("port").unwrap();
     let host = matches.get_one::<String>("host").unwrap();
     let redis_url = matches.get_one::<String>("redis").unwrap();
+    let backend = matches.get_one::<String>("backend").unwrap();
+    let max_upload_bytes: u64 = matches
+        .get_one::<String>("max-upload-bytes")
+        .map(|v| v.parse().expect("Invalid max-upload-bytes value"))
+        .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES);
+    let max_total_duration_minutes: f64 = matches
+        .get_one::<String>("max-duration-minutes")
+        .map(|v| v.parse().expect("Invalid max-duration-minutes value"))
+        .unwrap_or(thai_transcriber::DEFAULT_MAX_TOTAL_DURATION_MINUTES);
+    let max_concurrent_tasks: usize = matches
+        .get_one::<String>("max-concurrent-tasks")
+        .map(|v| v.parse().expect("Invalid max-concurrent-tasks value"))
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_TASKS);
+    let stats_interval_secs: u64 = matches
+        .get_one::<String>("stats-interval")
+        .map(|v| v.parse().expect("Invalid stats-interval value"))
+        .unwrap_or(DEFAULT_STATS_INTERVAL_SECS);
+    let stale_task_threshold_secs: u64 = matches
+        .get_one::<String>("stale-task-threshold")
+        .map(|v| v.parse().expect("Invalid stale-task-threshold value"))
+        .unwrap_or(DEFAULT_STALE_TASK_THRESHOLD_SECS);
+    let max_queue_depth: Option<usize> = matches
+        .get_one::<String>("max-queue-depth")
+        .map(|v| v.parse::<usize>().expect("Invalid max-queue-depth value"))
+        .filter(|&depth| depth > 0);
+    let reject_without_model = matches.get_flag("reject-without-model");
+    let min_chars_for_risk: usize = matches
+        .get_one::<String>("min-chars-for-risk")
+        .map(|v| v.parse().expect("Invalid min-chars-for-risk value"))
+        .unwrap_or(DEFAULT_MIN_CHARS_FOR_RISK);
+    let min_words_for_risk: usize = matches
+        .get_one::<String>("min-words-for-risk")
+        .map(|v| v.parse().expect("Invalid min-words-for-risk value"))
+        .unwrap_or(DEFAULT_MIN_WORDS_FOR_RISK);
+    let webhook_secret = matches
+        .get_one::<String>("webhook-secret")
+        .cloned()
+        .or_else(|| std::env::var("WEBHOOK_SECRET").ok())
+        .filter(|secret| !secret.is_empty());
     
     println!("🚀 Starting Whisper Transcription API Server with Queue System");
     println!("   📊 Version: 0.2.0");
     println!("   🌐 Address: http://{}:{}", host, port);
-    println!("   🗄️  Redis: {}", redis_url);
+    if backend == "memory" {
+        println!("   🗄️  Backend: in-memory (no Redis, tasks are lost on restart)");
+    } else {
+        println!("   🗄️  Redis: {}", redis_url);
+    }
+    println!("   📦 Max upload: {} bytes", max_upload_bytes);
+    println!("   ⏳ Max audio duration: {} minutes", max_total_duration_minutes);
+    println!("   🧵 Max concurrent tasks: {}", max_concurrent_tasks);
+    match max_queue_depth {
+        Some(depth) => println!("   🚦 Max queue depth: {} pending tasks", depth),
+        None => println!("   🚦 Max queue depth: unlimited"),
+    }
+    if stats_interval_secs == 0 {
+        println!("   📈 Stats broadcasts: disabled");
+    } else {
+        println!("   📈 Stats broadcasts: every {}s", stats_interval_secs);
+    }
+    println!("   🧹 Stale task threshold: {}s without progress", stale_task_threshold_secs);
+    println!("   🛡️ Auto risk analysis: transcripts with at least {} characters and {} words", min_chars_for_risk, min_words_for_risk);
+    println!("   📣 Task callbacks: {}", if webhook_secret.is_some() { "signed (X-Webhook-Signature)" } else { "unsigned" });
+    
+    if matches.get_flag("auto-download") {
+        let model_base_url = matches.get_one::<String>("model-base-url").unwrap();
+        thai_transcriber::enable_model_auto_download(model_base_url);
+        println!("   ⬇️  Model auto-download: {}", model_base_url);
+    }
+    
+    if let Some(path) = matches.get_one::<String>("language-models") {
+        let spec = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Failed to read language models file {}: {}", path, e));
+        let models = thai_transcriber::parse_language_models(&spec).expect("Invalid language models file");
+        for (language, model_path) in &models {
+            println!("   🌍 Language {}: {}", language, model_path);
+        }
+        thai_transcriber::set_language_models(models);
+    }
+    
+    if reject_without_model {
+        println!("   🧠 Missing model: submissions answered with 503 model_unavailable");
+        if let Err(e) = thai_transcriber::check_model_available(None) {
+            println!("   ⚠️ {}", e);
+        }
+    }
     
     // Initialize the task queue
-    let task_queue = match TaskQueue::new(redis_url).await {
-        Ok(queue) => {
-            println!("   ✅ Redis connection established");
-            queue
+    let mut task_queue = if backend == "memory" {
+        let store: std::sync::Arc<dyn TaskStore> = std::sync::Arc::new(MemoryStore::new());
+        match TaskQueue::with_store(store, max_concurrent_tasks, stats_interval_secs, stale_task_threshold_secs, webhook_secret).await {
+            Ok(queue) => {
+                println!("   ✅ In-memory task store ready");
+                queue
+            }
+            Err(e) => {
+                eprintln!("   ❌ Failed to start task queue: {}", e);
+                std::process::exit(1);
+            }
         }
-        Err(e) => {
-            eprintln!("   ❌ Failed to connect to Redis: {}", e);
-            eprintln!("   💡 Make sure Redis is running: redis-server");
-            std::process::exit(1);
+    } else {
+        match TaskQueue::new(redis_url, max_concurrent_tasks, stats_interval_secs, stale_task_threshold_secs, webhook_secret).await {
+            Ok(queue) => {
+                println!("   ✅ Redis connection established");
+                queue
+            }
+            Err(e) => {
+                eprintln!("   ❌ Failed to connect to Redis: {}", e);
+                eprintln!("   💡 Make sure Redis is running: redis-server, or start with --backend memory");
+                std::process::exit(1);
+            }
         }
     };
     
+    task_queue.set_auto_risk_thresholds(min_chars_for_risk, min_words_for_risk);
+    
     // Start the task processor on the same instance before starting the actor
     task_queue.start_task_processor().await;
     
     // Start the task queue actor
+    let backend = task_queue.store_name();
     let queue_addr = task_queue.start();
     
     let app_state = AppState {
         task_queue: queue_addr,
+        max_upload_bytes,
+        max_total_duration_minutes,
+        max_queue_depth,
+        reject_without_model,
+        backend,
     };
     
     println!("   � Task processor started");
@@ -587,14 +2540,30 @@ async fn main() -> std::io::Result<()> {
     println!("   🎯 Available endpoints:");
     println!("      GET  /                     - Web UI");
     println!("      GET  /api/health           - Health check with queue stats");
+    println!("      GET  /metrics              - Prometheus metrics");
     println!("      GET  /api/languages        - Supported languages");
     println!("      POST /api/transcribe       - Upload audio for transcription");
+    println!("      POST /api/transcribe-url   - Transcribe audio downloaded from a URL");
     println!("      POST /api/risk-analysis    - Submit text for risk analysis");
-    println!("      GET  /api/task/:id/status  - Get task status");
+    println!("      GET  /api/task/:id/status  - Get task status (ETag / If-None-Match)");
+    println!("      GET  /api/task/:id/transcript.txt - Plain-text transcript");
+    println!("      GET  /api/task/:id/words.csv - Word-level timestamps as CSV");
+    println!("      GET  /api/task/:id/segments?from=120&to=180 - Segments overlapping a time range");
+    println!("      GET  /api/task/:id/audio.wav - Whisper input audio (keep_audio=true)");
+    println!("      POST /api/task/:id/risk-analysis - Re-run risk analysis on a transcription");
+    println!("      PATCH /api/task/:id/segments/:seg_id - Correct one segment's text");
+    println!("      POST /api/task/:id/cancel  - Cancel a task that hasn't started");
     println!("      GET  /api/queue/stats      - Queue statistics");
-    println!("      GET  /api/queue/history    - Task history");
+    println!("      GET  /api/queue/history    - Task history (?limit=&offset=&cursor=&status=)");
     println!("      POST /api/queue/cleanup    - Clean up stale tasks");
-    println!("      WS   /ws                   - Real-time updates");
+    println!("      POST /api/queue/purge      - Delete finished tasks (?status=completed&older_than_hours=24)");
+    println!("      GET  /api/queue/dead-letter - Permanently failed tasks");
+    println!("      POST /api/queue/dead-letter/:id/requeue - Requeue a failed task");
+    println!("      DELETE /api/queue/dead-letter/:id - Drop a failed task and its uploaded audio");
+    println!("      WS   /ws                   - Real-time updates (send {{\"subscribe\":\"<task_id>\"}} to filter)");
+    println!("      WS   /ws                   - After a reconnect send {{\"replay_since\":\"<RFC 3339>\"}} to catch up");
+    println!("      WS   /ws/task/:id          - Updates for one task, closes when it finishes");
+    println!("      WS   /ws/live?language=th  - Live transcription of binary PCM16 mono 16kHz frames");
     
     HttpServer::new(move || {
         App::new()
@@ -603,16 +2572,35 @@ async fn main() -> std::io::Result<()> {
                 Cors::permissive()
             )
             .wrap(Logger::default())
+            // Negotiated from Accept-Encoding; WebSocket upgrades are never compressed, and a
+            // streaming (text/event-stream) response opts out by setting Content-Encoding: identity
+            .wrap(Compress::default())
             .route("/", web::get().to(serve_static))
             .route("/api/health", web::get().to(health_check))
+            .route("/metrics", web::get().to(metrics_handler))
             .route("/api/languages", web::get().to(get_supported_languages))
             .route("/api/transcribe", web::post().to(transcribe_handler))
+            .route("/api/transcribe-url", web::post().to(transcribe_url_handler))
             .route("/api/risk-analysis", web::post().to(risk_analysis_handler))
             .route("/api/task/{id}/status", web::get().to(get_task_status))
+            .route("/api/task/{id}/transcript.txt", web::get().to(get_task_transcript))
+            .route("/api/task/{id}/words.csv", web::get().to(get_task_words_csv))
+            .route("/api/task/{id}/segments", web::get().to(get_task_segments))
+            .route("/api/task/{id}/audio.wav", web::get().to(get_task_audio))
+            .route("/api/task/{id}/risk-analysis", web::post().to(rerun_risk_analysis))
+            .route("/api/task/{id}/segments/{seg_id}", web::patch().to(update_segment))
+            .route("/api/task/{id}/cancel", web::post().to(cancel_task))
             .route("/api/queue/stats", web::get().to(get_queue_stats))
             .route("/api/queue/history", web::get().to(get_task_history))
             .route("/api/queue/cleanup", web::post().to(cleanup_stale_tasks))
+            .route("/api/queue/purge", web::post().to(purge_tasks))
+            .route("/api/queue/dead-letter", web::get().to(get_dead_letters))
+            .route("/api/queue/dead-letter/{id}/requeue", web::post().to(requeue_dead_letter))
+            .route("/api/queue/dead-letter/{id}", web::delete().to(discard_dead_letter))
             .route("/ws", web::get().to(websocket_handler))
+            .route("/ws/task/{id}", web::get().to(task_websocket_handler))
+            .route("/ws/live", web::get().to(live_websocket_handler))
+            .route("/api/task/{id}/stream", web::get().to(task_websocket_handler))
     })
     .bind(format!("{}:{}", host, port))?
     .run()
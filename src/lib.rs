@@ -1,12 +1,18 @@
 // Core transcription functionality that can be shared between CLI and API
 
 pub mod queue;
+pub mod live;
+pub mod thai;
 
 // Import necessary dependencies
 extern crate reqwest;
+use std::collections::HashMap;
 use std::path::Path;
 use std::fs::metadata;
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+use std::sync::{Arc, OnceLock};
+use whisper_rs::{FullParams, SamplingStrategy};
+// Re-exported so library users can load a context for transcribe_with_context without depending on whisper-rs
+pub use whisper_rs::{WhisperContext, WhisperContextParameters};
 use serde_json::json;
 use serde::{Deserialize, Serialize};
 
@@ -14,8 +20,388 @@ use serde::{Deserialize, Serialize};
 use rodio::{Decoder, Source};
 use rubato::{Resampler, SincFixedIn, SincInterpolationType, SincInterpolationParameters, WindowFunction};
 
+#[cfg(feature = "full-audio-support")]
+use symphonia::core::{audio::SampleBuffer, codecs::{DecoderOptions, CODEC_TYPE_NULL}, errors::Error as SymphoniaError, formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint};
+
 // Constants for audio processing
 const SAMPLE_RATE: u32 = 16000;
+const MAX_DURATION_MINUTES: f32 = 60.0;
+const CHUNK_DURATION_MINUTES: f32 = 5.0;
+
+/// Input sample rates the resampler accepts. Rates outside this range come from
+/// corrupt headers rather than real recordings (192kHz field recordings are fine).
+pub const MIN_INPUT_SAMPLE_RATE: u32 = 1_000;
+pub const MAX_INPUT_SAMPLE_RATE: u32 = 768_000;
+
+/// Longest recording accepted unless overridden; longer files would monopolize the worker for hours
+pub const DEFAULT_MAX_TOTAL_DURATION_MINUTES: f64 = 180.0;
+
+/// Longest overlap between consecutive chunks; more only re-decodes audio for no benefit
+pub const MAX_CHUNK_OVERLAP_SECONDS: f64 = 30.0;
+
+// Words from two chunks only count as the same word when their starts are this close
+const SEAM_WORD_DRIFT_SECONDS: f64 = 1.5;
+
+// Segments at or above this no_speech_prob count as non-speech, like OpenAI Whisper's no_speech_threshold
+pub const NO_SPEECH_THRESHOLD: f32 = 0.6;
+
+// Non-speech regions shorter than this are left out of `non_speech_regions`
+pub const MIN_NON_SPEECH_SECONDS: f64 = 0.5;
+
+// Segments kept from one transcription before the rest is dropped and the result flagged as truncated
+pub const DEFAULT_MAX_SEGMENTS: usize = 10_000;
+
+// Energy-based voice activity detection for silence trimming
+const VAD_WINDOW_MS: usize = 30;
+const VAD_RMS_THRESHOLD: f32 = 0.01;
+const VAD_PADDING_MS: usize = 200;
+
+// Video containers whose first audio track is extracted with Symphonia instead of rodio
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "mkv", "webm"];
+
+// AAC audio that rodio can't decode at all
+const AAC_EXTENSIONS: &[&str] = &["m4a", "aac"];
+
+// Clipping detection and cubic de-clipping
+pub const DEFAULT_CLIP_THRESHOLD: f32 = 0.99;
+
+// Automatic gain control: peaks are kept just under the clip threshold, and quiet
+// recordings aren't boosted by more than this many dB (it would only raise the noise floor)
+const AGC_PEAK_CEILING: f32 = 0.95;
+const AGC_MAX_GAIN_DB: f32 = 30.0;
+const DECLIP_MAX_RUN: usize = 64;
+
+// Temperature fallback, matching OpenAI Whisper's defaults
+pub const TEMPERATURE_FALLBACK: &[f32] = &[0.0, 0.2, 0.4, 0.6, 0.8, 1.0];
+pub const DEFAULT_LOGPROB_THRESHOLD: f32 = -1.0;
+pub const DEFAULT_ENTROPY_THRESHOLD: f32 = 2.4;
+pub const DEFAULT_COMPRESSION_RATIO_THRESHOLD: f32 = 2.4;
+
+// Prompt tokens passed to the decoder, half of Whisper's 448-token text context like upstream
+pub const MAX_PROMPT_TOKENS: usize = 224;
+
+/// Callback receiving overall transcription progress as a percentage (0.0 - 100.0)
+pub type ProgressCallback = Arc<dyn Fn(f32) + Send + Sync>;
+
+/// Share of a transcription's progress spent decoding and resampling the audio;
+/// Whisper's own progress fills the rest
+pub const AUDIO_LOAD_PROGRESS_SHARE: f32 = 20.0;
+
+// Share of audio loading progress spent decoding when the audio also needs resampling
+const DECODE_PROGRESS_SHARE: f32 = 70.0;
+
+// Decoded samples between loading progress reports
+const DECODE_PROGRESS_STEP: usize = 1 << 20;
+
+// Pipeline log lines are prefixed with the task id ("-" when there is none), so output
+// from concurrently running tasks can be told apart. Per-step detail goes to debug.
+macro_rules! task_log {
+    ($level:ident, $task_id:expr, $($arg:tt)+) => {
+        log::$level!("[task {}] {}", $task_id.unwrap_or("-"), format_args!($($arg)+))
+    };
+}
+
+/// Errors returned by the transcription pipeline
+#[derive(Debug)]
+pub enum TranscriptionError {
+    /// No usable Whisper model file was found
+    ModelNotFound(String),
+    /// No model exists at any of the default locations, listed in search order
+    ModelUnavailable(Vec<String>),
+    /// The audio file is missing or cannot be read
+    AudioLoad(String),
+    /// The audio file could not be decoded or resampled
+    Decode(String),
+    /// The audio contains no audible signal
+    Silent(String),
+    /// whisper.cpp failed while loading the model or running inference
+    WhisperRuntime(String),
+    /// Transcription did not finish in time
+    Timeout(String),
+    /// The audio is longer than the configured maximum duration
+    TooLong(String),
+}
+
+impl TranscriptionError {
+    /// HTTP status code an API layer should report for this error
+    pub fn status_code(&self) -> u16 {
+        match self {
+            TranscriptionError::ModelNotFound(_) => 404,
+            TranscriptionError::ModelUnavailable(_) => 503,
+            TranscriptionError::AudioLoad(_) | TranscriptionError::Silent(_) => 422,
+            TranscriptionError::Decode(_) => 415,
+            TranscriptionError::WhisperRuntime(_) => 500,
+            TranscriptionError::Timeout(_) => 504,
+            TranscriptionError::TooLong(_) => 413,
+        }
+    }
+    
+    /// Classify an audio loader failure: a file that can't be opened or read is
+    /// `AudioLoad`, content the decoders reject is `Decode`
+    fn from_load_error(error: Box<dyn std::error::Error>) -> Self {
+        if is_read_failure(error.as_ref()) {
+            TranscriptionError::AudioLoad(error.to_string())
+        } else {
+            TranscriptionError::Decode(error.to_string())
+        }
+    }
+}
+
+// I/O failures while opening or reading the file. A stream that ends early is a
+// truncated file, which is reported as a decode failure like any other bad content.
+fn is_read_failure(error: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(io_error) = error.downcast_ref::<std::io::Error>() {
+        return io_error.kind() != std::io::ErrorKind::UnexpectedEof;
+    }
+    #[cfg(feature = "full-audio-support")]
+    if let Some(SymphoniaError::IoError(io_error)) = error.downcast_ref::<SymphoniaError>() {
+        return io_error.kind() != std::io::ErrorKind::UnexpectedEof;
+    }
+    false
+}
+
+impl std::fmt::Display for TranscriptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TranscriptionError::ModelNotFound(msg) => write!(f, "Model not found: {}", msg),
+            TranscriptionError::ModelUnavailable(paths) => write!(
+                f,
+                "No Whisper model found. Place a .bin or .gguf model at one of: {}",
+                paths.join(", ")
+            ),
+            TranscriptionError::AudioLoad(msg) => write!(f, "Failed to load audio file: {}", msg),
+            TranscriptionError::Decode(msg) => write!(f, "Failed to decode audio: {}", msg),
+            TranscriptionError::Silent(msg) => write!(f, "Audio is silent: {}", msg),
+            TranscriptionError::WhisperRuntime(msg) => write!(f, "Whisper runtime error: {}", msg),
+            TranscriptionError::Timeout(msg) => write!(f, "Transcription timed out: {}", msg),
+            TranscriptionError::TooLong(msg) => write!(f, "Audio is too long: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TranscriptionError {}
+
+/// Resampler settings used when the input is not already 16kHz
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// Short sinc filter, much cheaper on constrained CPUs
+    Fast,
+    /// BlackmanHarris2 window with a 256-tap sinc filter
+    #[default]
+    High,
+}
+
+/// Limits on segment size enforced by Whisper's decoder, and on the number of segments kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SegmentLimits {
+    /// Split segments longer than this many characters at word boundaries (42 suits subtitles)
+    pub max_len: Option<usize>,
+    /// Maximum text tokens per segment
+    pub max_tokens: Option<usize>,
+    /// Stop collecting segments past this many; the result is flagged as truncated
+    pub max_segments: Option<usize>,
+}
+
+impl SegmentLimits {
+    /// Set the limits on `params`; unset limits keep whisper.cpp's defaults
+    pub fn apply(&self, params: &mut FullParams<'_, '_>) {
+        if let Some(max_len) = self.max_len {
+            // whisper.cpp only splits by length when token timestamps are enabled
+            params.set_token_timestamps(true);
+            params.set_max_len(max_len as i32);
+            params.set_split_on_word(true);
+        }
+        if let Some(max_tokens) = self.max_tokens {
+            params.set_max_tokens(max_tokens as i32);
+        }
+    }
+    
+    /// How many of a pass's segments to read out: one past `max_segments`, so that
+    /// `truncate` can still tell the limit was exceeded
+    pub fn segments_to_collect(&self, num_segments: i32) -> i32 {
+        match self.max_segments {
+            Some(max_segments) => num_segments.min(max_segments.saturating_add(1).min(i32::MAX as usize) as i32),
+            None => num_segments,
+        }
+    }
+    
+    /// Drop segments past `max_segments`. Returns whether anything was dropped.
+    pub fn truncate<T>(&self, segments: &mut Vec<T>) -> bool {
+        match self.max_segments {
+            Some(max_segments) if segments.len() > max_segments => {
+                segments.truncate(max_segments);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whisper's decode quality thresholds, defaulting to upstream OpenAI Whisper's values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodeThresholds {
+    /// A segment whose no-speech probability is above this is treated as silence
+    /// (whisper.cpp also requires a low average log probability before skipping it)
+    pub no_speech: f32,
+    /// A decode whose average token log probability is below this is retried at a higher temperature
+    pub logprob: f32,
+    /// whisper.cpp's token entropy threshold: a decode less varied than this is retried inside whisper.cpp
+    pub entropy: f32,
+    /// A pass whose text compresses better than this ratio (a repetition loop) is re-decoded at a higher temperature
+    pub compression_ratio: f32,
+}
+
+impl Default for DecodeThresholds {
+    fn default() -> Self {
+        Self {
+            no_speech: NO_SPEECH_THRESHOLD,
+            logprob: DEFAULT_LOGPROB_THRESHOLD,
+            entropy: DEFAULT_ENTROPY_THRESHOLD,
+            compression_ratio: DEFAULT_COMPRESSION_RATIO_THRESHOLD,
+        }
+    }
+}
+
+impl DecodeThresholds {
+    /// Set the thresholds on `params`
+    pub fn apply(&self, params: &mut FullParams<'_, '_>) {
+        params.set_no_speech_thold(self.no_speech);
+        params.set_logprob_thold(self.logprob);
+        params.set_entropy_thold(self.entropy);
+    }
+    
+    /// Check the ranges: no_speech in [0, 1], logprob at most 0, entropy and compression_ratio positive
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.no_speech) {
+            return Err(format!("no_speech_threshold must be in [0, 1], got {}", self.no_speech));
+        }
+        if self.logprob.is_nan() || self.logprob > 0.0 {
+            return Err(format!("logprob_threshold must be at most 0, got {}", self.logprob));
+        }
+        if self.entropy.is_nan() || self.entropy <= 0.0 {
+            return Err(format!("entropy_threshold must be positive, got {}", self.entropy));
+        }
+        if self.compression_ratio.is_nan() || self.compression_ratio <= 0.0 {
+            return Err(format!("compression_ratio_threshold must be positive, got {}", self.compression_ratio));
+        }
+        Ok(())
+    }
+}
+
+/// Optional behaviour for `transcribe_audio_file_with_options`
+#[derive(Debug, Clone, Default)]
+pub struct TranscribeOptions {
+    /// Strip leading/trailing silence before transcription. Timestamps stay relative to the original file.
+    pub trim_silence: bool,
+    pub resample_quality: ResampleQuality,
+    /// Text used to bias vocabulary (names, jargon). Seeds the first chunk when chunking.
+    pub initial_prompt: Option<String>,
+    /// Output an English translation instead of a transcript in the source language
+    pub translate: bool,
+    /// Rebuild clipped peaks with cubic interpolation before resampling
+    pub declip: bool,
+    /// Absolute amplitude treated as clipped (defaults to `DEFAULT_CLIP_THRESHOLD`)
+    pub clip_threshold: Option<f32>,
+    /// Drop segments whose confidence (`exp(avg_logprob)`) is below this value
+    pub min_confidence: Option<f64>,
+    /// Label segments with a new speaker after silences longer than this many seconds
+    pub speaker_gap: Option<f64>,
+    /// Seconds added to every timestamp, for clips cut from a longer recording
+    pub offset_seconds: f64,
+    /// Correlation id included in every log line of this transcription
+    pub task_id: Option<String>,
+    /// Reject audio longer than this many minutes (defaults to `DEFAULT_MAX_TOTAL_DURATION_MINUTES`)
+    pub max_total_duration_minutes: Option<f64>,
+    /// Drop segments that repeat a phrase (or the previous segment) more than this many times
+    pub max_repeat: Option<usize>,
+    /// Maximum segment length in characters and tokens
+    pub segment_limits: SegmentLimits,
+    /// No-speech, log probability, entropy and compression ratio thresholds for the temperature fallback
+    pub decode_thresholds: DecodeThresholds,
+    /// Leave words below this confidence out of `words` (segment text is unchanged); 0.0 keeps all
+    pub word_confidence_threshold: f64,
+    /// Scale the audio towards this RMS level in dBFS (e.g. -20.0) before transcription
+    pub agc_target_dbfs: Option<f32>,
+    /// Transcribe only this channel (0-based) of multichannel audio instead of the downmix
+    pub channel: Option<usize>,
+    /// Seconds each chunk re-decodes from the end of the previous one; the duplicated
+    /// words at the seam are removed by aligning word timestamps
+    pub chunk_overlap_seconds: Option<f64>,
+    /// Write the exact 16kHz mono samples fed to Whisper to this WAV file
+    pub keep_audio_path: Option<String>,
+    /// Add `paragraphs`: segments grouped at silences and sentence ends (see `group_paragraphs`)
+    pub paragraphs: bool,
+}
+
+/// Preprocessing options for `load_audio_file_with_debug`
+#[derive(Debug, Clone, Default)]
+pub struct AudioLoadOptions {
+    /// Strip leading/trailing regions below the VAD RMS threshold
+    pub trim_silence: bool,
+    pub resample_quality: ResampleQuality,
+    /// Repair clipped runs with `declip_samples`
+    pub declip: bool,
+    /// Absolute amplitude treated as clipped (defaults to `DEFAULT_CLIP_THRESHOLD`)
+    pub clip_threshold: Option<f32>,
+    /// Apply `apply_agc` with this RMS target in dBFS after resampling and trimming
+    pub agc_target_dbfs: Option<f32>,
+    /// Transcribe only this channel (0-based) instead of averaging all channels
+    pub channel: Option<usize>,
+    /// Correlation id included in every log line while loading
+    pub task_id: Option<String>,
+}
+
+/// 16kHz mono audio ready for Whisper, plus what preprocessing removed
+#[derive(Debug, Clone, Default)]
+pub struct LoadedAudio {
+    pub samples: Vec<f32>,
+    /// Seconds removed from the start; add to timestamps to map back to the original file
+    pub time_offset: f64,
+    /// Total seconds of silence removed from both ends
+    pub trimmed_duration: f64,
+    /// Samples at or above the clip threshold in the decoded audio
+    pub clipped_samples: usize,
+    /// Clipped samples rebuilt by the de-clipping pass
+    pub declipped_samples: usize,
+    /// Decoded samples that were NaN/Inf (set to 0.0) or outside [-1.0, 1.0] (clamped)
+    pub sanitized_samples: usize,
+    /// Gain applied by automatic gain control, when enabled
+    pub agc_gain: Option<AgcGain>,
+}
+
+/// Gain chosen by automatic gain control
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AgcGain {
+    /// Applied gain in dB; negative values attenuate
+    pub gain_db: f32,
+    /// The RMS target asked for more, but the gain was lowered to keep the peak from clipping
+    pub peak_limited: bool,
+}
+
+impl AgcGain {
+    /// Gain that brings `rms` to `target_dbfs`, capped at `AGC_MAX_GAIN_DB` and at
+    /// whatever would push `peak` past `AGC_PEAK_CEILING`
+    pub fn for_levels(rms: f32, peak: f32, target_dbfs: f32) -> Self {
+        if rms <= 0.0 || peak <= 0.0 {
+            return Self::default();
+        }
+        
+        let target_rms = 10f32.powf(target_dbfs / 20.0);
+        let wanted_db = (20.0 * (target_rms / rms).log10()).min(AGC_MAX_GAIN_DB);
+        let peak_safe_db = 20.0 * (AGC_PEAK_CEILING / peak).log10();
+        
+        if wanted_db > peak_safe_db {
+            Self { gain_db: peak_safe_db, peak_limited: true }
+        } else {
+            Self { gain_db: wanted_db, peak_limited: false }
+        }
+    }
+    
+    /// Linear scale factor for the samples
+    pub fn factor(&self) -> f32 {
+        10f32.powf(self.gain_db / 20.0)
+    }
+}
 
 // Audio data with sample rate information
 #[derive(Debug, Clone)]
@@ -57,8 +443,270 @@ pub struct WhisperSegment {
     avg_logprob: f64,
     compression_ratio: f64,
     no_speech_prob: f64,
-    confidence: f64,
+    /// `exp(avg_logprob)`, or `None` when the segment has no text tokens
+    confidence: Option<f64>,
     words: Vec<WhisperWord>,
+    /// Gap-based speaker placeholder ("SPEAKER_00", ...), only set when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    speaker: Option<String>,
+}
+
+/// Model file extensions: legacy GGML `.bin` files and the newer `.gguf` releases
+pub const MODEL_FILE_EXTENSIONS: &[&str] = &["bin", "gguf"];
+
+/// True when `name` ends in one of `MODEL_FILE_EXTENSIONS`
+pub fn is_model_file_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| MODEL_FILE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Container format of a model file from its magic bytes: "gguf" or "ggml".
+/// `None` when the file can't be read or is neither.
+pub fn model_file_format(path: &str) -> Option<&'static str> {
+    use std::io::Read;
+    
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path).ok()?.read_exact(&mut magic).ok()?;
+    match &magic {
+        b"GGUF" => Some("gguf"),
+        // whisper.cpp writes 0x67676d6c ("ggml") as a little-endian u32
+        b"lmgg" => Some("ggml"),
+        _ => None,
+    }
+}
+
+/// Message for a failed `WhisperContext::new_with_params`. The context is loaded
+/// the same way whatever the extension, so a GGUF file the bundled whisper.cpp
+/// can't parse gets a hint instead of a bare loader error.
+pub fn model_load_error(model_path: &str, error: impl std::fmt::Display) -> String {
+    if model_file_format(model_path) == Some("gguf") {
+        format!(
+            "Failed to load Whisper model {}: {} (GGUF model; if this whisper.cpp build can't read GGUF, use the ggml .bin release of the same model)",
+            model_path, error
+        )
+    } else {
+        format!("Failed to load Whisper model {}: {}", model_path, error)
+    }
+}
+
+/// Locations searched for the default Whisper model, in order of preference (GGML before GGUF)
+pub const POSSIBLE_MODEL_PATHS: [&str; 6] = [
+    "model/ggml-large-v3.bin",
+    "model/ggml-large-v3-q5_0.bin",
+    "model/ggml-large-v3-turbo-q8_0.bin",
+    "model/ggml-large-v3.gguf",
+    "model/ggml-large-v3-q5_0.gguf",
+    "model/ggml-large-v3-turbo-q8_0.gguf"
+];
+
+/// First Whisper model found in the model/ directory, in order of preference
+fn default_model_path() -> Result<&'static str, TranscriptionError> {
+    POSSIBLE_MODEL_PATHS.into_iter()
+        .find(|path| Path::new(path).exists())
+        .ok_or_else(|| TranscriptionError::ModelUnavailable(
+            POSSIBLE_MODEL_PATHS.iter().map(|path| path.to_string()).collect()
+        ))
+}
+
+/// Check, without loading anything, that a transcription in `language` would find
+/// a model: the language's own model, one of `POSSIBLE_MODEL_PATHS`, or auto-download.
+/// Lets an API refuse work up front instead of queueing tasks that will fail.
+pub fn check_model_available(language: Option<&str>) -> Result<(), TranscriptionError> {
+    if let Some(model_path) = language_model_path(language) {
+        return if Path::new(model_path).exists() {
+            Ok(())
+        } else {
+            Err(TranscriptionError::ModelUnavailable(vec![model_path.to_string()]))
+        };
+    }
+    
+    match default_model_path() {
+        Ok(_) => Ok(()),
+        Err(_) if MODEL_BASE_URL.get().is_some() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// Language -> model path routing, from `set_language_models` or the environment
+static LANGUAGE_MODELS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Parse `language=model_path` pairs separated by commas or newlines, e.g.
+/// `th=model/thai-finetune.bin,en=model/ggml-large-v3.bin`. Blank entries and
+/// lines starting with `#` are ignored; languages are matched case-insensitively.
+pub fn parse_language_models(spec: &str) -> Result<HashMap<String, String>, String> {
+    let mut models = HashMap::new();
+    
+    for entry in spec.split(|c| c == ',' || c == '\n').map(|entry| entry.trim()) {
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+        let (language, model_path) = entry.split_once('=')
+            .ok_or_else(|| format!("Invalid language model entry '{}', expected language=model_path", entry))?;
+        let (language, model_path) = (language.trim().to_lowercase(), model_path.trim());
+        if language.is_empty() || model_path.is_empty() {
+            return Err(format!("Invalid language model entry '{}', expected language=model_path", entry));
+        }
+        models.insert(language, model_path.to_string());
+    }
+    
+    Ok(models)
+}
+
+/// Route transcriptions to a model per language (see `parse_language_models`).
+/// Only the first call takes effect; without one, `LANGUAGE_MODELS_FILE` (a file
+/// of entries) or `LANGUAGE_MODELS` (inline entries) is read on first use.
+pub fn set_language_models(models: HashMap<String, String>) {
+    for (language, model_path) in &models {
+        if !Path::new(model_path).exists() {
+            log::warn!("Model {} configured for language {} does not exist", model_path, language);
+        }
+    }
+    let _ = LANGUAGE_MODELS.set(models);
+}
+
+fn language_models() -> &'static HashMap<String, String> {
+    LANGUAGE_MODELS.get_or_init(|| {
+        let spec = match (std::env::var("LANGUAGE_MODELS_FILE"), std::env::var("LANGUAGE_MODELS")) {
+            (Ok(path), _) => match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    log::warn!("Failed to read language models from {}: {}, using the default model", path, e);
+                    return HashMap::new();
+                }
+            },
+            (Err(_), Ok(spec)) => spec,
+            _ => return HashMap::new(),
+        };
+        
+        match parse_language_models(&spec) {
+            Ok(models) => {
+                if !models.is_empty() {
+                    log::info!("Routing {} language(s) to their own models", models.len());
+                }
+                models
+            }
+            Err(e) => {
+                log::warn!("{}, using the default model for every language", e);
+                HashMap::new()
+            }
+        }
+    })
+}
+
+/// Model configured for `language` (Thai when `None`, as in transcription),
+/// or `None` when that language uses the default model
+pub fn language_model_path(language: Option<&str>) -> Option<&'static str> {
+    let language = language.unwrap_or("th").trim().to_lowercase();
+    language_models().get(&language).map(|path| path.as_str())
+}
+
+/// Where GGML models are downloaded from when auto-download is enabled
+pub const DEFAULT_MODEL_BASE_URL: &str = "https://huggingface.co/ggerganov/whisper.cpp/resolve/main";
+
+// Model used when auto-downloading because model/ is empty
+const DEFAULT_MODEL_NAME: &str = "large-v3";
+
+// Set by `enable_model_auto_download`; unset means never touch the network
+static MODEL_BASE_URL: OnceLock<String> = OnceLock::new();
+
+/// Allow `ensure_model` to download missing models from `base_url`.
+/// Only the first call takes effect.
+pub fn enable_model_auto_download(base_url: &str) {
+    let _ = MODEL_BASE_URL.set(base_url.trim_end_matches('/').to_string());
+}
+
+/// Return the path of `model_name` ("large-v3", "ggml-large-v3.bin" or a .gguf file name) in model/,
+/// downloading it first if it's missing and auto-download is enabled.
+/// The download is verified against the SHA256 the mirror reports for the file.
+pub async fn ensure_model(model_name: &str) -> Result<String, TranscriptionError> {
+    let file_name = if is_model_file_name(model_name) {
+        model_name.to_string()
+    } else {
+        format!("ggml-{}.bin", model_name)
+    };
+    let model_path = format!("model/{}", file_name);
+    
+    if Path::new(&model_path).exists() {
+        return Ok(model_path);
+    }
+    
+    let base_url = MODEL_BASE_URL.get().ok_or_else(|| TranscriptionError::ModelNotFound(format!(
+        "{} not found. Download it into model/ or enable --auto-download", model_path
+    )))?;
+    let url = format!("{}/{}", base_url, file_name);
+    
+    download_model(&url, &model_path).await.map_err(|e| TranscriptionError::ModelNotFound(
+        format!("Failed to download {}: {}", url, e)
+    ))?;
+    
+    Ok(model_path)
+}
+
+async fn download_model(url: &str, model_path: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use sha2::{Digest, Sha256};
+    use std::io::Write;
+    
+    println!("⬇️  Downloading Whisper model: {}", url);
+    std::fs::create_dir_all("model")?;
+    
+    // Hugging Face reports the LFS object's SHA256 as X-Linked-Etag on the redirect
+    let expected_sha256 = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?
+        .head(url)
+        .send()
+        .await
+        .ok()
+        .and_then(|resp| resp.headers().get("x-linked-etag").and_then(|v| v.to_str().ok()).map(|v| v.trim_matches('"').to_lowercase()))
+        .filter(|etag| etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()));
+    
+    let mut response = reqwest::get(url).await?.error_for_status()?;
+    let total_bytes = response.content_length();
+    
+    // Write to a .part file so an interrupted download is never mistaken for a model
+    let part_path = format!("{}.part", model_path);
+    let mut file = std::fs::File::create(&part_path)?;
+    let mut hasher = Sha256::new();
+    let mut downloaded: u64 = 0;
+    let mut last_logged_percent = 0;
+    
+    let result: Result<(), Box<dyn std::error::Error + Send + Sync>> = async {
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk)?;
+            hasher.update(&chunk);
+            downloaded += chunk.len() as u64;
+            
+            if let Some(total) = total_bytes.filter(|&t| t > 0) {
+                let percent = downloaded * 100 / total;
+                if percent >= last_logged_percent + 10 {
+                    last_logged_percent = percent - percent % 10;
+                    println!("   📥 {}% ({} / {})", percent, format_bytes(downloaded), format_bytes(total));
+                }
+            }
+        }
+        file.flush()?;
+        
+        let actual_sha256: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        match &expected_sha256 {
+            Some(expected) if *expected != actual_sha256 => {
+                return Err(format!("checksum mismatch (expected {}, got {})", expected, actual_sha256).into());
+            }
+            Some(_) => println!("✅ SHA256 verified: {}", actual_sha256),
+            None => println!("⚠️  Mirror did not report a checksum, SHA256 is {}", actual_sha256),
+        }
+        Ok(())
+    }.await;
+    
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&part_path);
+        return Err(e);
+    }
+    
+    std::fs::rename(&part_path, model_path)?;
+    println!("✅ Model saved to {} ({})", model_path, format_bytes(downloaded));
+    Ok(())
 }
 
 /// Transcribe an audio file and return the result in OpenAI Whisper format using real Whisper processing
@@ -66,16 +714,233 @@ pub async fn transcribe_audio_file(
     audio_path: &str,
     backend: &str,
     language: Option<&str>,
-) -> Result<serde_json::Value, String> {
-    let language = language.unwrap_or("th");
+) -> Result<serde_json::Value, TranscriptionError> {
+    transcribe_audio_file_with_options(audio_path, backend, language, &TranscribeOptions::default(), None).await
+}
+
+/// Pre-flight check without transcribing: decode the audio, report duration and
+/// amplitude stats and whether it would be chunked, then load the model.
+/// Fails with the same error a transcription would hit first.
+pub fn validate_audio_file(audio_path: &str, options: &AudioLoadOptions) -> Result<serde_json::Value, TranscriptionError> {
+    let task_id = options.task_id.as_deref();
+    task_log!(info, task_id, "🧪 Validating {} without transcribing", audio_path);
+    
+    if !Path::new(audio_path).exists() {
+        return Err(TranscriptionError::AudioLoad(format!("Audio file not found: {}", audio_path)));
+    }
+    
+    let loaded_audio = load_audio_file_with_debug(audio_path, options)
+        .map_err(TranscriptionError::from_load_error)?;
+    let samples = &loaded_audio.samples;
+    
+    let max_amplitude = samples.iter().fold(0.0f32, |max, &x| max.max(x.abs()));
+    if samples.is_empty() || max_amplitude < 0.001 {
+        return Err(TranscriptionError::Silent(format!(
+            "{} contains no audible signal (peak amplitude {:.6})", audio_path, max_amplitude
+        )));
+    }
+    let rms = (samples.iter().map(|&x| x * x).sum::<f32>() / samples.len() as f32).sqrt();
+    
+    let duration_seconds = samples.len() as f64 / SAMPLE_RATE as f64;
+    let chunked = duration_seconds / 60.0 > MAX_DURATION_MINUTES as f64;
+    let chunks = if chunked {
+        (duration_seconds / (CHUNK_DURATION_MINUTES as f64 * 60.0)).ceil() as usize
+    } else {
+        1
+    };
+    
+    // Loading the context is the only way to know the model file is actually usable
+    let model_path = default_model_path()?;
+    WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
+        .map_err(|e| TranscriptionError::WhisperRuntime(model_load_error(model_path, e)))?;
+    
+    task_log!(info, task_id, "✅ Validation passed: {:.1}s of audio, peak {:.3}, {} chunk(s)", duration_seconds, max_amplitude, chunks);
+    
+    Ok(json!({
+        "valid": true,
+        "audio_file": audio_path,
+        "duration_seconds": duration_seconds,
+        "sample_count": samples.len(),
+        "peak_amplitude": max_amplitude,
+        "rms_amplitude": rms,
+        "clipped_samples": loaded_audio.clipped_samples,
+        "sanitized_samples": loaded_audio.sanitized_samples,
+        "trimmed_duration": loaded_audio.trimmed_duration,
+        "agc_gain_db": loaded_audio.agc_gain.map(|gain| gain.gain_db),
+        "chunked": chunked,
+        "chunks": chunks,
+        "model": model_path
+    }))
+}
+
+/// A loaded Whisper model kept warm between transcriptions. Clones share the
+/// model; every call decodes with its own state, so clones can run concurrently.
+#[derive(Clone)]
+pub struct WhisperEngine {
+    ctx: Arc<WhisperContext>,
+    model_path: String,
+}
+
+impl WhisperEngine {
+    /// Load the model at `model_path` (blocks for as long as whisper.cpp takes to load it)
+    pub fn load(model_path: &str) -> Result<Self, TranscriptionError> {
+        log::info!("🔄 Loading Whisper model: {}", model_path);
+        let ctx = WhisperContext::new_with_params(model_path, WhisperContextParameters::default())
+            .map_err(|e| TranscriptionError::WhisperRuntime(model_load_error(model_path, e)))?;
+        log::info!("✅ Whisper model loaded successfully");
+        
+        Ok(Self {
+            ctx: Arc::new(ctx),
+            model_path: model_path.to_string(),
+        })
+    }
+    
+    /// `load` on tokio's blocking pool, so async callers don't stall a runtime worker
+    pub async fn load_async(model_path: &str) -> Result<Self, TranscriptionError> {
+        let model_path = model_path.to_string();
+        tokio::task::spawn_blocking(move || Self::load(&model_path))
+            .await
+            .map_err(|e| TranscriptionError::WhisperRuntime(format!("Model loading task failed: {}", e)))?
+    }
+    
+    /// Load the first model found in model/, downloading the default one when auto-download is enabled
+    pub async fn load_default() -> Result<Self, TranscriptionError> {
+        let model_path = match default_model_path() {
+            Ok(path) => path.to_string(),
+            Err(_) if MODEL_BASE_URL.get().is_some() => ensure_model(DEFAULT_MODEL_NAME).await?,
+            Err(e) => return Err(e),
+        };
+        Self::load_async(&model_path).await
+    }
+    
+    /// Load the model configured for `language` (see `set_language_models`),
+    /// falling back to `load_default` when the language has none
+    pub async fn load_for_language(language: Option<&str>) -> Result<Self, TranscriptionError> {
+        match language_model_path(language) {
+            Some(model_path) => Self::load_async(model_path).await,
+            None => Self::load_default().await,
+        }
+    }
     
-    println!("🔄 Starting real Whisper transcription for: {}", audio_path);
+    pub fn model_path(&self) -> &str {
+        &self.model_path
+    }
+    
+    /// Transcribe 16kHz mono `samples` with the language, prompt, translate and task id
+    /// from `options`, in fixed-size chunks when longer than `MAX_DURATION_MINUTES`.
+    /// Timestamps start at 0; offsets and filtering are left to the caller.
+    pub fn transcribe(
+        &self,
+        samples: &[f32],
+        language: &str,
+        options: &TranscribeOptions,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Vec<WhisperSegment>, TranscriptionError> {
+        transcribe_on_context(&self.ctx, samples, language, options, progress)
+    }
+}
+
+// WhisperEngine::transcribe for any context, including ones owned by library users
+fn transcribe_on_context(
+    ctx: &WhisperContext,
+    samples: &[f32],
+    language: &str,
+    options: &TranscribeOptions,
+    progress: Option<ProgressCallback>,
+) -> Result<Vec<WhisperSegment>, TranscriptionError> {
+    let task_id = options.task_id.as_deref();
+    let chunked = samples.len() as f32 / SAMPLE_RATE as f32 / 60.0 > MAX_DURATION_MINUTES;
     
-    // Check if audio file exists
+    if chunked {
+        let overlap_seconds = options.chunk_overlap_seconds.unwrap_or(0.0);
+        transcribe_with_chunking(ctx, samples, language, options.initial_prompt.as_deref(), options.translate, options.segment_limits, options.decode_thresholds, overlap_seconds, task_id, progress)
+    } else {
+        transcribe_samples(ctx, samples, language, 0.0, options.initial_prompt.as_deref(), options.translate, options.segment_limits, options.decode_thresholds, task_id, move |percent| {
+            if let Some(callback) = &progress {
+                callback(percent as f32);
+            }
+        })
+    }
+}
+
+/// Recommended entry point when embedding this crate as a library: transcribe a
+/// file on a Whisper context the caller loaded once and keeps warm across calls,
+/// with no actix, Redis or model discovery involved. `language` defaults to Thai.
+/// Returns the same OpenAI Whisper format JSON as `transcribe_audio_file`, minus
+/// `model_path` (the context doesn't know where it was loaded from).
+///
+/// Blocks while decoding and running Whisper; from async code call it inside
+/// `tokio::task::spawn_blocking` (or `block_in_place`) to keep other tasks moving.
+pub fn transcribe_with_context(
+    ctx: &WhisperContext,
+    audio_path: &str,
+    language: Option<&str>,
+    options: &TranscribeOptions,
+) -> Result<serde_json::Value, TranscriptionError> {
+    transcribe_file_on_context(ctx, None, audio_path, "auto", language, options, None)
+}
+
+// Checks that need only the file header, done before any model loading or decoding
+fn precheck_audio_file(audio_path: &str, max_duration_minutes: f64) -> Result<(), TranscriptionError> {
     if !Path::new(audio_path).exists() {
-        return Err(format!("Audio file not found: {}", audio_path));
+        return Err(TranscriptionError::AudioLoad(format!("Audio file not found: {}", audio_path)));
+    }
+    
+    // Reject overlong files from the header alone when the format reports a duration
+    if let Some(duration_seconds) = probe_duration_seconds(audio_path) {
+        check_max_duration(audio_path, duration_seconds, max_duration_minutes)?;
     }
     
+    Ok(())
+}
+
+/// Same as `transcribe_audio_file` with extra `options`, reporting overall progress (0-100%) through `progress`.
+/// Loads the model for this one call; keep a `WhisperEngine` and use
+/// `transcribe_audio_file_with_engine` to transcribe many files.
+pub async fn transcribe_audio_file_with_options(
+    audio_path: &str,
+    backend: &str,
+    language: Option<&str>,
+    options: &TranscribeOptions,
+    progress: Option<ProgressCallback>,
+) -> Result<serde_json::Value, TranscriptionError> {
+    // Fail on a missing or overlong file before paying for the model
+    precheck_audio_file(audio_path, options.max_total_duration_minutes.unwrap_or(DEFAULT_MAX_TOTAL_DURATION_MINUTES))?;
+    
+    let engine = WhisperEngine::load_for_language(language).await?;
+    transcribe_audio_file_with_engine(&engine, audio_path, backend, language, options, progress)
+}
+
+/// `transcribe_audio_file_with_options` on an already loaded model
+pub fn transcribe_audio_file_with_engine(
+    engine: &WhisperEngine,
+    audio_path: &str,
+    backend: &str,
+    language: Option<&str>,
+    options: &TranscribeOptions,
+    progress: Option<ProgressCallback>,
+) -> Result<serde_json::Value, TranscriptionError> {
+    transcribe_file_on_context(&engine.ctx, Some(engine.model_path()), audio_path, backend, language, options, progress)
+}
+
+// The whole file pipeline (load, transcribe, post-process, build the result) on one context
+fn transcribe_file_on_context(
+    ctx: &WhisperContext,
+    model_path: Option<&str>,
+    audio_path: &str,
+    backend: &str,
+    language: Option<&str>,
+    options: &TranscribeOptions,
+    progress: Option<ProgressCallback>,
+) -> Result<serde_json::Value, TranscriptionError> {
+    let language = language.unwrap_or("th");
+    let task_id = options.task_id.as_deref();
+    
+    task_log!(info, task_id, "🔄 Starting real Whisper transcription for: {}", audio_path);
+    
+    let max_duration_minutes = options.max_total_duration_minutes.unwrap_or(DEFAULT_MAX_TOTAL_DURATION_MINUTES);
+    precheck_audio_file(audio_path, max_duration_minutes)?;
+    
     // Determine backend settings
     let (use_gpu, use_coreml) = match backend {
         "gpu" => (true, false),
@@ -83,269 +948,1730 @@ pub async fn transcribe_audio_file(
         "cpu" | "auto" | _ => (false, false),
     };
     
-    // Model path - check multiple possible locations
-    let possible_model_paths = [
-        "model/ggml-large-v3.bin",
-        "model/ggml-large-v3-q5_0.bin",
-        "model/ggml-large-v3-turbo-q8_0.bin"
-    ];
-    
-    let model_path = possible_model_paths.iter()
-        .find(|path| Path::new(path).exists())
-        .ok_or("No Whisper model found. Please ensure a model file exists in the model/ directory")?;
-    
-    println!("🔄 Loading Whisper model: {}", model_path);
+    // Load and process audio file
+    task_log!(info, task_id, "🎵 Loading audio file: {}", audio_path);
+    let load_options = AudioLoadOptions {
+        trim_silence: options.trim_silence,
+        resample_quality: options.resample_quality,
+        declip: options.declip,
+        clip_threshold: options.clip_threshold,
+        agc_target_dbfs: options.agc_target_dbfs,
+        channel: options.channel,
+        task_id: options.task_id.clone(),
+    };
+    let load_progress: Option<ProgressCallback> = progress.clone().map(|callback| {
+        Arc::new(move |percent: f32| {
+            callback(percent * AUDIO_LOAD_PROGRESS_SHARE / 100.0);
+        }) as ProgressCallback
+    });
+    let loaded_audio = load_audio_file_with_progress(audio_path, &load_options, load_progress)
+        .map_err(TranscriptionError::from_load_error)?;
+    let audio_data = loaded_audio.samples;
     
-    // Initialize Whisper context
-    let ctx_params = WhisperContextParameters::default();
-    let ctx = WhisperContext::new_with_params(model_path, ctx_params)
-        .map_err(|e| format!("Failed to load Whisper model: {}", e))?;
+    let max_amplitude = audio_data.iter().fold(0.0f32, |max, &x| max.max(x.abs()));
+    if audio_data.is_empty() || max_amplitude < 0.001 {
+        return Err(TranscriptionError::Silent(format!(
+            "{} contains no audible signal (peak amplitude {:.6})", audio_path, max_amplitude
+        )));
+    }
     
-    println!("✅ Whisper model loaded successfully");
+    // Formats without a duration header are only measured once decoded
+    check_max_duration(audio_path, audio_data.len() as f64 / SAMPLE_RATE as f64, max_duration_minutes)?;
     
-    // Load and process audio file
-    println!("🎵 Loading audio file: {}", audio_path);
-    let audio_data = load_audio_file_with_debug(audio_path)
-        .map_err(|e| format!("Failed to load audio file: {}", e))?;
+    // Keep a copy of exactly what Whisper receives; a failed write doesn't fail the transcription
+    if let Some(keep_path) = &options.keep_audio_path {
+        match std::fs::write(keep_path, encode_wav_f32(&audio_data, SAMPLE_RATE)) {
+            Ok(()) => task_log!(info, task_id, "💾 Kept {} samples of Whisper input audio at {}", audio_data.len(), keep_path),
+            Err(e) => task_log!(warn, task_id, "⚠️ Failed to keep Whisper input audio at {}: {}", keep_path, e),
+        }
+    }
     
-    println!("🔄 Running Whisper transcription...");
+    // Long recordings are transcribed in fixed-size chunks
+    let duration_minutes = audio_data.len() as f32 / SAMPLE_RATE as f32 / 60.0;
+    let chunked = duration_minutes > MAX_DURATION_MINUTES;
     
-    // Set up parameters for transcription
-    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    params.set_language(Some(language));
-    params.set_translate(false);
-    params.set_print_special(false);
-    params.set_print_progress(false);
-    params.set_print_realtime(false);
-    params.set_print_timestamps(true);
+    task_log!(info, task_id, "🔄 Running Whisper transcription{}...", if chunked { " in chunks" } else { "" });
     
-    // Create state and run transcription
-    let mut state = ctx.create_state()
-        .map_err(|e| format!("Failed to create Whisper state: {}", e))?;
+    // Whisper's progress continues where loading left off
+    let whisper_progress: Option<ProgressCallback> = progress.map(|callback| {
+        Arc::new(move |percent: f32| {
+            callback(AUDIO_LOAD_PROGRESS_SHARE + percent * (100.0 - AUDIO_LOAD_PROGRESS_SHARE) / 100.0);
+        }) as ProgressCallback
+    });
     
     let processing_start = std::time::Instant::now();
-    state.full(params, &audio_data)
-        .map_err(|e| format!("Failed to run Whisper transcription: {}", e))?;
+    let mut segments = transcribe_on_context(ctx, &audio_data, language, options, whisper_progress)?;
+    
+    // Each pass stops one past the limit, and chunking can overshoot it by a chunk
+    let truncated = options.segment_limits.truncate(&mut segments);
+    if truncated {
+        task_log!(warn, task_id, "✂️ Transcription truncated to {} segments", segments.len());
+    }
     
     let processing_time = processing_start.elapsed().as_secs_f64();
     
-    // Extract segments
-    let num_segments = state.full_n_segments()
-        .map_err(|e| format!("Failed to get segment count: {}", e))?;
+    // Real-time factor over all processed samples (every chunk in chunked mode); 0.3 = 3x faster than real time
+    let audio_duration_seconds = audio_data.len() as f64 / SAMPLE_RATE as f64;
+    let rtf = processing_time / audio_duration_seconds;
+    task_log!(info, task_id, "⏱️  RTF {:.3} ({:.1}s of audio in {:.1}s)", rtf, audio_duration_seconds, processing_time);
     
-    println!("✅ Transcription completed with {} segments in {:.1}s", num_segments, processing_time);
+    // Keep timestamps relative to the original file when leading silence was trimmed,
+    // and to the full recording when this file is a clip of it
+    offset_segments(&mut segments, loaded_audio.time_offset + options.offset_seconds);
     
-    let mut segments = Vec::new();
-    let mut full_text = String::new();
+    let smoothed_words = smooth_word_timestamps(&mut segments);
+    if smoothed_words > 0 {
+        task_log!(debug, task_id, "🪄 Retimed {} overlapping or zero-length words", smoothed_words);
+    }
     
-    for i in 0..num_segments {
-        let segment_text = state.full_get_segment_text(i)
-            .map_err(|e| format!("Failed to get segment text: {}", e))?;
+    // Dead air over the whole original file, before filtering removes any segments
+    let file_duration_seconds = loaded_audio.trimmed_duration + audio_duration_seconds;
+    let non_speech = non_speech_regions(&segments, options.offset_seconds, options.offset_seconds + file_duration_seconds, options.decode_thresholds.no_speech);
+    
+    // Drop repeated-text hallucinations before confidence filtering renumbers segments
+    let suppressed_hallucinations = match options.max_repeat {
+        Some(max_repeat) => suppress_repetitions(&mut segments, max_repeat),
+        None => 0,
+    };
+    if suppressed_hallucinations > 0 {
+        task_log!(info, task_id, "🔁 Suppressed {} repeated-text segments (max repeat {})", suppressed_hallucinations, options.max_repeat.unwrap_or_default());
+    }
+    
+    // Drop low-confidence segments once timestamps are final
+    let filtered_segments = match options.min_confidence {
+        Some(min_confidence) => filter_low_confidence(&mut segments, min_confidence),
+        None => 0,
+    };
+    if filtered_segments > 0 {
+        task_log!(info, task_id, "🧹 Filtered {} segments below confidence {:.2}", filtered_segments, options.min_confidence.unwrap_or_default());
+    }
+    let filtered_words = if options.word_confidence_threshold > 0.0 {
+        filter_low_confidence_words(&mut segments, options.word_confidence_threshold)
+    } else {
+        0
+    };
+    if filtered_words > 0 {
+        task_log!(info, task_id, "🧹 Dropped {} words below confidence {:.2}", filtered_words, options.word_confidence_threshold);
+    }
+    let num_segments = segments.len();
+    
+    if let Some(speaker_gap) = options.speaker_gap {
+        assign_speakers(&mut segments, speaker_gap);
+    }
+    
+    task_log!(info, task_id, "✅ Transcription completed with {} segments in {:.1}s", num_segments, processing_time);
+    
+    let full_text: String = segments.iter().map(|s| s.text.as_str()).collect();
+    
+    let paragraphs = options.paragraphs.then(|| segment_paragraphs(&segments, PARAGRAPH_GAP_SECONDS));
+    
+    // Get file information
+    let file_size = metadata(audio_path)
+        .map(|m| m.len())
+        .unwrap_or(0);
+    
+    let file_name = Path::new(audio_path)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    
+    let metadata = ResultMetadata {
+        backend,
+        model_path,
+        model: model_path.map(|path| Path::new(path).file_stem().unwrap_or_default().to_string_lossy().to_string()),
+        processing_time: format!("{:.1}s", processing_time),
+        audio_duration_seconds,
+        rtf,
+        file_size: format_bytes(file_size),
+        file_name: file_name.to_string(),
+        use_gpu,
+        use_coreml,
+        sample_rate: SAMPLE_RATE,
+        num_segments,
+        chunked,
+        trim_silence: options.trim_silence,
+        task: if options.translate { "translate" } else { "transcribe" },
+        trimmed_duration_seconds: loaded_audio.trimmed_duration,
+        time_offset_seconds: loaded_audio.time_offset,
+        declip: options.declip,
+        clip_threshold: options.clip_threshold.unwrap_or(DEFAULT_CLIP_THRESHOLD),
+        clipped_samples: loaded_audio.clipped_samples,
+        declipped_samples: loaded_audio.declipped_samples,
+        sanitized_samples: loaded_audio.sanitized_samples,
+        agc_target_dbfs: options.agc_target_dbfs,
+        agc_gain_db: loaded_audio.agc_gain.map(|gain| gain.gain_db),
+        agc_peak_limited: loaded_audio.agc_gain.map(|gain| gain.peak_limited),
+        channel: options.channel,
+        chunk_overlap_seconds: options.chunk_overlap_seconds,
+        min_confidence: options.min_confidence,
+        filtered_segments,
+        word_confidence_threshold: options.word_confidence_threshold,
+        filtered_words,
+        smoothed_words,
+        max_repeat: options.max_repeat,
+        suppressed_hallucinations,
+        max_segment_len: options.segment_limits.max_len,
+        max_tokens: options.segment_limits.max_tokens,
+        max_segments: options.segment_limits.max_segments,
+        truncated,
+        speaker_gap_seconds: options.speaker_gap,
+        offset_seconds: options.offset_seconds,
+        keep_audio: options.keep_audio_path.is_some(),
+        no_speech_threshold: options.decode_thresholds.no_speech,
+        logprob_threshold: options.decode_thresholds.logprob,
+        entropy_threshold: options.decode_thresholds.entropy,
+        compression_ratio_threshold: options.decode_thresholds.compression_ratio,
+        non_speech_regions: non_speech,
+        note: "Real Whisper transcription completed successfully",
+    };
+    
+    // Create result in OpenAI Whisper format
+    let mut result = json!({
+        "text": full_text.trim(),
+        "segments": segments,
+        "language": language,
+        "metadata": metadata
+    });
+    if let Some(paragraphs) = paragraphs {
+        result["paragraphs"] = json!(paragraphs);
+    }
+    
+    task_log!(info, task_id, "✅ Transcription result ready with {} characters", full_text.len());
+    
+    Ok(result)
+}
+
+// `metadata` of a transcription result. Every key is always present; settings of
+// optional features that weren't enabled are null.
+#[derive(Serialize)]
+struct ResultMetadata<'a> {
+    backend: &'a str,
+    model_path: Option<&'a str>,
+    model: Option<String>,
+    processing_time: String,
+    audio_duration_seconds: f64,
+    rtf: f64,
+    file_size: String,
+    file_name: String,
+    use_gpu: bool,
+    use_coreml: bool,
+    sample_rate: u32,
+    num_segments: usize,
+    chunked: bool,
+    trim_silence: bool,
+    task: &'static str,
+    trimmed_duration_seconds: f64,
+    time_offset_seconds: f64,
+    declip: bool,
+    clip_threshold: f32,
+    clipped_samples: usize,
+    declipped_samples: usize,
+    sanitized_samples: usize,
+    agc_target_dbfs: Option<f32>,
+    agc_gain_db: Option<f32>,
+    agc_peak_limited: Option<bool>,
+    channel: Option<usize>,
+    chunk_overlap_seconds: Option<f64>,
+    min_confidence: Option<f64>,
+    filtered_segments: usize,
+    word_confidence_threshold: f64,
+    filtered_words: usize,
+    smoothed_words: usize,
+    max_repeat: Option<usize>,
+    suppressed_hallucinations: usize,
+    max_segment_len: Option<usize>,
+    max_tokens: Option<usize>,
+    max_segments: Option<usize>,
+    truncated: bool,
+    speaker_gap_seconds: Option<f64>,
+    offset_seconds: f64,
+    keep_audio: bool,
+    no_speech_threshold: f32,
+    logprob_threshold: f32,
+    entropy_threshold: f32,
+    compression_ratio_threshold: f32,
+    non_speech_regions: Vec<NonSpeechRegion>,
+    note: &'static str,
+}
+
+/// Duration from the container header without decoding, when the format reports one
+pub fn probe_duration_seconds(path: &str) -> Option<f64> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    
+    if let Some(duration) = probe_duration_with_symphonia(path, &extension) {
+        return Some(duration);
+    }
+    
+    // rodio knows the length of WAV and FLAC up front, not of MP3
+    let file = std::fs::File::open(path).ok()?;
+    let decoder = Decoder::new(std::io::BufReader::new(file)).ok()?;
+    decoder.total_duration().map(|d| d.as_secs_f64())
+}
+
+fn check_max_duration(audio_path: &str, duration_seconds: f64, max_duration_minutes: f64) -> Result<(), TranscriptionError> {
+    let duration_minutes = duration_seconds / 60.0;
+    if duration_minutes > max_duration_minutes {
+        return Err(TranscriptionError::TooLong(format!(
+            "{} is {:.1} minutes long, the limit is {:.0} minutes", audio_path, duration_minutes, max_duration_minutes
+        )));
+    }
+    Ok(())
+}
+
+/// Transcribe 16kHz mono samples in fixed-size chunks, reporting overall progress
+/// as `(chunk_index + per_chunk_progress) / total_chunks`.
+/// `initial_prompt` seeds the first chunk; later chunks are prompted with the previous chunk's text.
+/// With `overlap_seconds` > 0 each chunk also decodes that much audio before its start,
+/// and the words heard twice are deduplicated with `stitch_chunk_seam`.
+#[allow(clippy::too_many_arguments)]
+pub fn transcribe_with_chunking(
+    ctx: &WhisperContext,
+    samples: &[f32],
+    language: &str,
+    initial_prompt: Option<&str>,
+    translate: bool,
+    limits: SegmentLimits,
+    thresholds: DecodeThresholds,
+    overlap_seconds: f64,
+    task_id: Option<&str>,
+    progress: Option<ProgressCallback>,
+) -> Result<Vec<WhisperSegment>, TranscriptionError> {
+    let samples_per_chunk = (CHUNK_DURATION_MINUTES * 60.0 * SAMPLE_RATE as f32) as usize;
+    let total_chunks = (samples.len() + samples_per_chunk - 1) / samples_per_chunk;
+    // Never let the overlap swallow more than half a chunk
+    let overlap_samples = ((overlap_seconds.clamp(0.0, MAX_CHUNK_OVERLAP_SECONDS) * SAMPLE_RATE as f64) as usize)
+        .min(samples_per_chunk / 2);
+    
+    task_log!(info, task_id, "📊 Chunking info: {} samples in {} chunks of {} minutes", samples.len(), total_chunks, CHUNK_DURATION_MINUTES);
+    if overlap_samples > 0 {
+        task_log!(info, task_id, "🔗 Chunk overlap: {:.1}s", overlap_samples as f64 / SAMPLE_RATE as f64);
+    }
+    
+    let mut all_segments = Vec::new();
+    let mut prompt = initial_prompt.map(|p| p.to_string());
+    let mut deduplicated_words = 0;
+    
+    for chunk_index in 0..total_chunks {
+        task_log!(info, task_id, "📝 Processing chunk {} of {}", chunk_index + 1, total_chunks);
+        
+        let chunk_start = chunk_index * samples_per_chunk;
+        let chunk_end = (chunk_start + samples_per_chunk).min(samples.len());
+        let window_start = chunk_start.saturating_sub(overlap_samples);
+        let time_offset = window_start as f64 / SAMPLE_RATE as f64;
+        
+        let chunk_progress = progress.clone();
+        let chunk_segments = transcribe_samples(ctx, &samples[window_start..chunk_end], language, time_offset, prompt.as_deref(), translate, limits, thresholds, task_id, move |percent| {
+            if let Some(callback) = &chunk_progress {
+                let overall = (chunk_index as f32 + percent as f32 / 100.0) / total_chunks as f32;
+                callback(overall * 100.0);
+            }
+        })?;
+        
+        // Condition the next chunk on this chunk's text, falling back to the previous prompt
+        let chunk_text: String = chunk_segments.iter().map(|s| s.text.as_str()).collect();
+        if !chunk_text.trim().is_empty() {
+            prompt = Some(chunk_text);
+        }
+        
+        if window_start < chunk_start {
+            let seam_start = window_start as f64 / SAMPLE_RATE as f64;
+            let seam_end = chunk_start as f64 / SAMPLE_RATE as f64;
+            deduplicated_words += stitch_chunk_seam(&mut all_segments, chunk_segments, seam_start, seam_end);
+        } else {
+            all_segments.extend(chunk_segments);
+        }
+        for (id, segment) in all_segments.iter_mut().enumerate() {
+            segment.id = id as i32;
+        }
+        
+        task_log!(info, task_id, "✅ Chunk {} completed", chunk_index + 1);
+        
+        // Later chunks would only be truncated away
+        if limits.max_segments.is_some_and(|max_segments| all_segments.len() > max_segments) {
+            task_log!(warn, task_id, "✂️ Segment limit reached after chunk {} of {}, skipping the rest", chunk_index + 1, total_chunks);
+            break;
+        }
+    }
+    
+    if deduplicated_words > 0 {
+        task_log!(info, task_id, "🔗 Removed {} duplicated words at chunk seams", deduplicated_words);
+    }
+    
+    Ok(all_segments)
+}
+
+// Comparison key for matching the same word across two decodes
+fn seam_word_key(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_ascii_punctuation())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Append `next` (the following chunk, which re-decoded `[seam_start, seam_end)`)
+/// to `segments`. The trailing words of `segments` and the leading words of `next`
+/// inside the overlap are aligned by text (longest common subsequence, starts within
+/// `SEAM_WORD_DRIFT_SECONDS`). The transcripts are joined at the aligned word closest
+/// to the middle of the overlap, keeping the higher-confidence copy of every aligned
+/// word; without any alignment they are cut at the middle by word start.
+/// Returns how many duplicated words were removed.
+fn stitch_chunk_seam(segments: &mut Vec<WhisperSegment>, mut next: Vec<WhisperSegment>, seam_start: f64, seam_end: f64) -> usize {
+    let midpoint = (seam_start + seam_end) / 2.0;
+    
+    // (segment index, word index) of the words heard in the overlap, in time order
+    let tail: Vec<(usize, usize)> = segments.iter().enumerate()
+        .flat_map(|(i, segment)| (0..segment.words.len()).map(move |j| (i, j)))
+        .filter(|&(i, j)| segments[i].words[j].end > seam_start)
+        .collect();
+    let head: Vec<(usize, usize)> = next.iter().enumerate()
+        .flat_map(|(i, segment)| (0..segment.words.len()).map(move |j| (i, j)))
+        .filter(|&(i, j)| next[i].words[j].start < seam_end)
+        .collect();
+    
+    let tail_keys: Vec<String> = tail.iter().map(|&(i, j)| seam_word_key(&segments[i].words[j].text)).collect();
+    let head_keys: Vec<String> = head.iter().map(|&(i, j)| seam_word_key(&next[i].words[j].text)).collect();
+    let same_word = |a: usize, b: usize| -> bool {
+        let (ti, tj) = tail[a];
+        let (hi, hj) = head[b];
+        !tail_keys[a].is_empty()
+            && tail_keys[a] == head_keys[b]
+            && (segments[ti].words[tj].start - next[hi].words[hj].start).abs() <= SEAM_WORD_DRIFT_SECONDS
+    };
+    
+    // Longest common subsequence over the two word lists
+    let mut lengths = vec![vec![0usize; head.len() + 1]; tail.len() + 1];
+    for a in (0..tail.len()).rev() {
+        for b in (0..head.len()).rev() {
+            lengths[a][b] = if same_word(a, b) {
+                lengths[a + 1][b + 1] + 1
+            } else {
+                lengths[a + 1][b].max(lengths[a][b + 1])
+            };
+        }
+    }
+    let mut pairs = Vec::new();
+    let (mut a, mut b) = (0, 0);
+    while a < tail.len() && b < head.len() {
+        if same_word(a, b) {
+            pairs.push((a, b));
+            a += 1;
+            b += 1;
+        } else if lengths[a + 1][b] >= lengths[a][b + 1] {
+            a += 1;
+        } else {
+            b += 1;
+        }
+    }
+    
+    // Tail words kept from `segments` (by position in `tail`) and head words dropped from `next`
+    let (tail_keep, head_skip, removed) = match pairs.iter().min_by(|x, y| {
+        let dx = (segments[tail[x.0].0].words[tail[x.0].1].start - midpoint).abs();
+        let dy = (segments[tail[y.0].0].words[tail[y.0].1].start - midpoint).abs();
+        dx.total_cmp(&dy)
+    }) {
+        Some(&(cut_a, cut_b)) => {
+            // Both copies of an aligned word are kept in the position that survives
+            for &(pa, pb) in &pairs {
+                let (ti, tj) = tail[pa];
+                let (hi, hj) = head[pb];
+                let better = next[hi].words[hj].confidence > segments[ti].words[tj].confidence;
+                if better && pa <= cut_a {
+                    let source = next[hi].words[hj].clone();
+                    let word = &mut segments[ti].words[tj];
+                    word.text = source.text;
+                    word.confidence = source.confidence;
+                } else if !better && pb > cut_b {
+                    let source = segments[ti].words[tj].clone();
+                    let word = &mut next[hi].words[hj];
+                    word.text = source.text;
+                    word.confidence = source.confidence;
+                }
+            }
+            (cut_a + 1, cut_b + 1, (tail.len() - cut_a - 1) + (cut_b + 1))
+        }
+        None => {
+            let tail_keep = tail.iter().filter(|&&(i, j)| segments[i].words[j].start < midpoint).count();
+            let head_skip = head.iter().filter(|&&(i, j)| next[i].words[j].start < midpoint).count();
+            (tail_keep, head_skip, (tail.len() - tail_keep) + head_skip)
+        }
+    };
+    
+    // Time the two halves meet, used for segments without word timings
+    let cut_time = if tail_keep > 0 {
+        let (i, j) = tail[tail_keep - 1];
+        segments[i].words[j].end.min(seam_end)
+    } else if head_skip < head.len() {
+        let (i, j) = head[head_skip];
+        next[i].words[j].start.max(seam_start)
+    } else {
+        midpoint
+    };
+    
+    let dropped_tail: Vec<(usize, usize)> = tail[tail_keep..].to_vec();
+    let dropped_head: Vec<(usize, usize)> = head[..head_skip].to_vec();
+    trim_segment_words(segments, &dropped_tail, cut_time, true);
+    trim_segment_words(&mut next, &dropped_head, cut_time, false);
+    
+    segments.extend(next);
+    removed
+}
+
+// Remove `dropped` (segment index, word index) words and rebuild the text and timing
+// of the segments they came from. Segments without words are kept or dropped whole by
+// which side of `cut_time` they mostly fall on. `before_cut` says which side is kept.
+fn trim_segment_words(segments: &mut Vec<WhisperSegment>, dropped: &[(usize, usize)], cut_time: f64, before_cut: bool) {
+    let mut keep = vec![true; segments.len()];
+    
+    for (index, segment) in segments.iter_mut().enumerate() {
+        let drop_words: Vec<usize> = dropped.iter().filter(|&&(i, _)| i == index).map(|&(_, j)| j).collect();
+        
+        if segment.words.is_empty() {
+            let middle = (segment.start + segment.end) / 2.0;
+            keep[index] = if before_cut { middle < cut_time } else { middle >= cut_time };
+            continue;
+        }
+        if drop_words.is_empty() {
+            continue;
+        }
+        
+        let mut j = 0;
+        segment.words.retain(|_| {
+            let retained = !drop_words.contains(&j);
+            j += 1;
+            retained
+        });
+        if segment.words.is_empty() {
+            keep[index] = false;
+            continue;
+        }
+        
+        let leading_space = segment.text.starts_with(' ');
+        let joined = join_words(segment.words.iter().map(|w| w.text.as_str()));
+        segment.text = if leading_space { format!(" {}", joined) } else { joined };
+        segment.start = segment.words.first().map(|w| w.start).unwrap_or(segment.start);
+        segment.end = segment.words.last().map(|w| w.end).unwrap_or(segment.end);
+        segment.seek = (segment.start * 100.0) as i32;
+        segment.compression_ratio = compression_ratio(&segment.text);
+    }
+    
+    let mut index = 0;
+    segments.retain(|_| {
+        let retained = keep[index];
+        index += 1;
+        retained
+    });
+}
+
+// Join words back into text: no space between two Thai words, a space otherwise
+fn join_words<'a>(words: impl Iterator<Item = &'a str>) -> String {
+    let mut text = String::new();
+    for word in words {
+        let thai_join = text.chars().last().is_some_and(thai::is_thai) && word.chars().next().is_some_and(thai::is_thai);
+        if !text.is_empty() && !thai_join {
+            text.push(' ');
+        }
+        text.push_str(word);
+    }
+    text
+}
+
+/// Transcribe `samples`, shifting timestamps by `time_offset` seconds.
+/// Failed decodes (low average log probability or highly repetitive output) are
+/// retried with increasing temperatures, like OpenAI Whisper's fallback.
+/// `on_progress` receives Whisper's own progress in percent for each pass.
+#[allow(clippy::too_many_arguments)]
+fn transcribe_samples<F>(
+    ctx: &WhisperContext,
+    samples: &[f32],
+    language: &str,
+    time_offset: f64,
+    prompt: Option<&str>,
+    translate: bool,
+    limits: SegmentLimits,
+    thresholds: DecodeThresholds,
+    task_id: Option<&str>,
+    on_progress: F,
+) -> Result<Vec<WhisperSegment>, TranscriptionError>
+where
+    F: FnMut(i32) + Clone + 'static,
+{
+    let prompt_tokens = match prompt {
+        Some(text) if !text.trim().is_empty() => prompt_tokens(ctx, text)?,
+        _ => Vec::new(),
+    };
+    
+    let mut segments = Vec::new();
+    
+    for (attempt, &temperature) in TEMPERATURE_FALLBACK.iter().enumerate() {
+        segments = run_whisper_pass(ctx, samples, language, time_offset, temperature, &prompt_tokens, translate, limits, thresholds, on_progress.clone())?;
+        
+        let (avg_logprob, compression_ratio) = pass_quality(segments.iter().map(|s| (s.avg_logprob, s.text.as_str())));
+        let needs_fallback = avg_logprob < thresholds.logprob as f64 || compression_ratio > thresholds.compression_ratio as f64;
+        
+        if !needs_fallback {
+            break;
+        }
+        
+        if attempt + 1 < TEMPERATURE_FALLBACK.len() {
+            task_log!(
+                warn,
+                task_id,
+                "⚠️  Decode at temperature {:.1} failed (avg_logprob {:.2}, compression_ratio {:.2}), retrying at {:.1}",
+                temperature, avg_logprob, compression_ratio, TEMPERATURE_FALLBACK[attempt + 1]
+            );
+        } else {
+            task_log!(
+                warn,
+                task_id,
+                "⚠️  Decode still failing at temperature {:.1} (avg_logprob {:.2}, compression_ratio {:.2}), keeping last result",
+                temperature, avg_logprob, compression_ratio
+            );
+        }
+    }
+    
+    Ok(segments)
+}
+
+/// Average log probability and compression ratio over a whole Whisper pass, from the
+/// `(avg_logprob, text)` of each of its segments
+pub fn pass_quality<'a>(segments: impl IntoIterator<Item = (f64, &'a str)>) -> (f64, f64) {
+    let mut logprob_sum = 0.0;
+    let mut count = 0;
+    let mut text = String::new();
+    for (avg_logprob, segment_text) in segments {
+        logprob_sum += avg_logprob;
+        count += 1;
+        text.push_str(segment_text);
+    }
+    if count == 0 {
+        return (0.0, 0.0);
+    }
+    
+    (logprob_sum / count as f64, compression_ratio(&text))
+}
+
+/// Ratio of raw to zlib-compressed text size, as computed by OpenAI Whisper;
+/// high values indicate repetitive output
+pub fn compression_ratio(text: &str) -> f64 {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    
+    if text.is_empty() {
+        return 0.0;
+    }
+    
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    if encoder.write_all(text.as_bytes()).is_err() {
+        return 0.0;
+    }
+    
+    match encoder.finish() {
+        Ok(compressed) if !compressed.is_empty() => text.len() as f64 / compressed.len() as f64,
+        _ => 0.0,
+    }
+}
+
+/// Probability that a segment contains no speech.
+/// whisper-rs does not expose whisper.cpp's no-speech token probability, so this is
+/// taken from the first text token instead: the mass the decoder did not put on it at
+/// the start of the segment is the mass left for the no-speech token.
+pub fn no_speech_prob(first_token_prob: Option<f64>) -> f64 {
+    match first_token_prob {
+        Some(p) => (1.0 - p).clamp(0.0, 1.0),
+        None => 1.0,
+    }
+}
+
+/// Tokenize prompt text for the decoder, keeping only the trailing `MAX_PROMPT_TOKENS`
+pub fn prompt_tokens(ctx: &WhisperContext, text: &str) -> Result<Vec<i32>, TranscriptionError> {
+    // A token is never shorter than one byte, so the byte length bounds the token count
+    let mut tokens = ctx.tokenize(text.trim(), text.len() + 1)
+        .map_err(|e| TranscriptionError::WhisperRuntime(format!("Failed to tokenize prompt: {}", e)))?;
+    
+    if tokens.len() > MAX_PROMPT_TOKENS {
+        tokens.drain(..tokens.len() - MAX_PROMPT_TOKENS);
+    }
+    
+    Ok(tokens)
+}
+
+/// Run a single Whisper pass over `samples` at a fixed `temperature`
+#[allow(clippy::too_many_arguments)]
+fn run_whisper_pass<F>(
+    ctx: &WhisperContext,
+    samples: &[f32],
+    language: &str,
+    time_offset: f64,
+    temperature: f32,
+    prompt_tokens: &[i32],
+    translate: bool,
+    limits: SegmentLimits,
+    thresholds: DecodeThresholds,
+    on_progress: F,
+) -> Result<Vec<WhisperSegment>, TranscriptionError>
+where
+    F: FnMut(i32) + 'static,
+{
+    // Set up parameters for transcription
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some(language));
+    params.set_translate(translate);
+    // Fallback is driven by transcribe_samples, so disable whisper.cpp's own retries
+    params.set_temperature(temperature);
+    params.set_temperature_inc(0.0);
+    if !prompt_tokens.is_empty() {
+        params.set_tokens(prompt_tokens);
+    }
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(true);
+    limits.apply(&mut params);
+    thresholds.apply(&mut params);
+    params.set_progress_callback_safe(on_progress);
+    
+    // Create state and run transcription
+    let mut state = ctx.create_state()
+        .map_err(|e| TranscriptionError::WhisperRuntime(format!("Failed to create Whisper state: {}", e)))?;
+    
+    state.full(params, samples)
+        .map_err(|e| TranscriptionError::WhisperRuntime(format!("Failed to run Whisper transcription: {}", e)))?;
+    
+    // Extract segments
+    let num_segments = state.full_n_segments()
+        .map_err(|e| TranscriptionError::WhisperRuntime(format!("Failed to get segment count: {}", e)))?;
+    // A looping decode can produce tens of thousands of segments; don't read past the limit
+    let num_segments = limits.segments_to_collect(num_segments);
+    
+    let mut segments = Vec::new();
+    
+    for i in 0..num_segments {
+        let segment_text = state.full_get_segment_text(i)
+            .map_err(|e| TranscriptionError::WhisperRuntime(format!("Failed to get segment text: {}", e)))?;
         let start_timestamp = state.full_get_segment_t0(i)
-            .map_err(|e| format!("Failed to get segment start: {}", e))?;
+            .map_err(|e| TranscriptionError::WhisperRuntime(format!("Failed to get segment start: {}", e)))?;
         let end_timestamp = state.full_get_segment_t1(i)
-            .map_err(|e| format!("Failed to get segment end: {}", e))?;
+            .map_err(|e| TranscriptionError::WhisperRuntime(format!("Failed to get segment end: {}", e)))?;
         
         // Convert timestamps from centiseconds to seconds
-        let start_time = start_timestamp as f64 / 100.0;
-        let end_time = end_timestamp as f64 / 100.0;
-        
-        full_text.push_str(&segment_text);
+        let start_time = start_timestamp as f64 / 100.0 + time_offset;
+        let end_time = end_timestamp as f64 / 100.0 + time_offset;
         
         // Get word-level data
         let num_tokens = state.full_n_tokens(i).unwrap_or(0);
-        let mut words = Vec::new();
+        let mut usable_tokens = Vec::new();
+        let mut logprob_sum = 0.0;
+        let mut logprob_count = 0;
+        let mut first_token_prob = None;
         
         for j in 0..num_tokens {
+            // Special tokens (timestamps, end of text) don't count towards avg_logprob
+            if let Ok(token_data) = state.full_get_token_data(i, j) {
+                if token_data.id < ctx.token_eot() {
+                    logprob_sum += token_data.plog as f64;
+                    logprob_count += 1;
+                    first_token_prob.get_or_insert(token_data.p as f64);
+                }
+            }
+            
             if let Ok(token_text) = state.full_get_token_text(i, j) {
                 if let Ok(token_prob) = state.full_get_token_prob(i, j) {
                     let cleaned_text = token_text.trim();
                     if !cleaned_text.is_empty() && !cleaned_text.starts_with('<') && !cleaned_text.starts_with('[') {
-                        // Approximate word timestamps
-                        let word_progress = j as f64 / num_tokens.max(1) as f64;
-                        let word_start = start_time + (end_time - start_time) * word_progress;
-                        let word_end = start_time + (end_time - start_time) * ((j + 1) as f64 / num_tokens.max(1) as f64);
-                        
-                        words.push(WhisperWord {
-                            text: cleaned_text.to_string(),
-                            start: word_start,
-                            end: word_end,
-                            confidence: token_prob as f64,
-                        });
+                        usable_tokens.push((cleaned_text.to_string(), token_prob as f64));
                     }
                 }
             }
         }
         
+        // Segments without text tokens have no meaningful confidence
+        let avg_logprob = if logprob_count > 0 { logprob_sum / logprob_count as f64 } else { 0.0 };
+        let confidence = if logprob_count > 0 { Some(logprob_confidence(avg_logprob)) } else { None };
+        
+        // Word boundaries come from the Thai word breaker rather than raw tokens
+        let words = align_words(&segment_text, &usable_tokens, start_time, end_time);
+        
+        let segment_compression_ratio = compression_ratio(&segment_text);
+        
         // Create segment in OpenAI Whisper format
-        let segment = json!({
-            "id": i as i32,
-            "seek": (start_timestamp / 100) as i32 * 2,
-            "start": start_time,
-            "end": end_time,
-            "text": segment_text,
-            "tokens": [], // Token IDs not easily accessible in whisper-rs
-            "temperature": 0.0,
-            "avg_logprob": -0.3,
-            "compression_ratio": 1.5,
-            "no_speech_prob": 0.1,
-            "confidence": words.iter().map(|w| w.confidence).sum::<f64>() / words.len().max(1) as f64,
-            "words": words
+        segments.push(WhisperSegment {
+            id: i,
+            seek: (start_time * 100.0) as i32,
+            start: start_time,
+            end: end_time,
+            text: segment_text,
+            tokens: Vec::new(), // Token IDs not easily accessible in whisper-rs
+            temperature: temperature as f64,
+            avg_logprob,
+            compression_ratio: segment_compression_ratio,
+            no_speech_prob: no_speech_prob(first_token_prob),
+            confidence,
+            words,
+            speaker: None,
         });
+    }
+    
+    Ok(segments)
+}
+
+/// Segment confidence as a probability in 0..1: `exp(avg_logprob)`, the geometric
+/// mean of the segment's text token probabilities
+pub fn logprob_confidence(avg_logprob: f64) -> f64 {
+    avg_logprob.exp().clamp(0.0, 1.0)
+}
+
+/// Split segment text into words with `thai::segment_words` and spread them over
+/// the segment by character count. A word's confidence is the mean probability
+/// of the tokens covering the same characters.
+fn align_words(text: &str, tokens: &[(String, f64)], start_time: f64, end_time: f64) -> Vec<WhisperWord> {
+    let words = thai::segment_words(text);
+    let total_chars: usize = words.iter().map(|w| w.chars().count()).sum();
+    if tokens.is_empty() || total_chars == 0 {
+        return Vec::new();
+    }
+    
+    // Byte-level tokens don't always decode to the same characters as the segment
+    // text, so token spans are scaled onto the word text
+    let token_chars: Vec<usize> = tokens.iter()
+        .map(|(token, _)| token.chars().filter(|c| !c.is_whitespace()).count())
+        .collect();
+    let scale = total_chars as f64 / token_chars.iter().sum::<usize>().max(1) as f64;
+    let mut token_spans = Vec::with_capacity(tokens.len());
+    let mut position = 0.0;
+    for ((_, prob), chars) in tokens.iter().zip(&token_chars) {
+        let end = position + *chars as f64 * scale;
+        token_spans.push((position, end, *prob));
+        position = end;
+    }
+    let mean_prob = tokens.iter().map(|(_, prob)| prob).sum::<f64>() / tokens.len() as f64;
+    
+    let duration = end_time - start_time;
+    let mut aligned = Vec::with_capacity(words.len());
+    let mut char_start = 0;
+    for word in words {
+        let char_end = char_start + word.chars().count();
+        let (from, to) = (char_start as f64, char_end as f64);
+        
+        let overlapping: Vec<f64> = token_spans.iter()
+            .filter(|(start, end, _)| *start < to && *end > from)
+            .map(|(_, _, prob)| *prob)
+            .collect();
+        let confidence = if overlapping.is_empty() {
+            mean_prob
+        } else {
+            overlapping.iter().sum::<f64>() / overlapping.len() as f64
+        };
         
-        segments.push(segment);
+        aligned.push(WhisperWord {
+            text: word,
+            start: start_time + duration * (from / total_chars as f64),
+            end: start_time + duration * (to / total_chars as f64),
+            confidence,
+        });
+        char_start = char_end;
     }
     
-    // Get file information
-    let file_size = metadata(audio_path)
-        .map(|m| m.len())
-        .unwrap_or(0);
+    aligned
+}
+
+/// Crude turn-taking signal: start a new speaker label whenever the silence
+/// between consecutive segments exceeds `gap_threshold` seconds. This is not
+/// diarization, just a placeholder for tools that expect a `speaker` field.
+pub fn assign_speakers(segments: &mut [WhisperSegment], gap_threshold: f64) {
+    let mut speaker = 0;
+    let mut previous_end: Option<f64> = None;
     
-    let file_name = Path::new(audio_path)
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy();
+    for segment in segments.iter_mut() {
+        if previous_end.is_some_and(|end| segment.start - end > gap_threshold) {
+            speaker += 1;
+        }
+        segment.speaker = Some(format!("SPEAKER_{:02}", speaker));
+        previous_end = Some(segment.end);
+    }
+}
+
+/// Consecutive segments grouped into one paragraph of readable text, in seconds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Paragraph {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Silence between segments, in seconds, that always starts a new paragraph
+pub const PARAGRAPH_GAP_SECONDS: f64 = 2.0;
+
+/// Sentences a paragraph may hold before the next sentence end closes it
+pub const PARAGRAPH_MAX_SENTENCES: usize = 5;
+
+// Whisper's sentence-final punctuation, Latin and CJK. Thai rarely punctuates
+// sentences, so Thai transcripts are mostly split at silences.
+fn ends_sentence(text: &str) -> bool {
+    matches!(
+        text.trim_end().trim_end_matches(['"', '\'', ')', '”', '’']).chars().last(),
+        Some('.' | '!' | '?' | '…' | '。' | '！' | '？')
+    )
+}
+
+/// Group `(start, end, text)` spans into paragraphs: a new paragraph starts after a
+/// silence longer than `gap_threshold` seconds, or at a sentence end once the current
+/// paragraph holds `PARAGRAPH_MAX_SENTENCES` sentences. Blank spans are skipped.
+pub fn group_paragraphs<'a>(spans: impl IntoIterator<Item = (f64, f64, &'a str)>, gap_threshold: f64) -> Vec<Paragraph> {
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+    let mut sentences = 0;
+    let mut previous_ended_sentence = false;
     
-    // Create result in OpenAI Whisper format
-    let result = json!({
-        "text": full_text.trim(),
-        "segments": segments,
-        "language": language,
-        "metadata": {
-            "backend": backend,
-            "model_path": model_path,
-            "model": Path::new(model_path).file_stem().unwrap_or_default().to_string_lossy(),
-            "processing_time": format!("{:.1}s", processing_time),
-            "file_size": format_bytes(file_size),
-            "file_name": file_name,
-            "use_gpu": use_gpu,
-            "use_coreml": use_coreml,
-            "sample_rate": SAMPLE_RATE,
-            "num_segments": num_segments,
-            "note": "Real Whisper transcription completed successfully"
+    for (start, end, text) in spans {
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        
+        let new_paragraph = match paragraphs.last() {
+            Some(current) => start - current.end > gap_threshold
+                || (previous_ended_sentence && sentences >= PARAGRAPH_MAX_SENTENCES),
+            None => true,
+        };
+        
+        if new_paragraph {
+            paragraphs.push(Paragraph { start, end, text: text.to_string() });
+            sentences = 0;
+        } else if let Some(current) = paragraphs.last_mut() {
+            current.end = current.end.max(end);
+            current.text.push(' ');
+            current.text.push_str(text);
+        }
+        
+        previous_ended_sentence = ends_sentence(text);
+        if previous_ended_sentence {
+            sentences += 1;
+        }
+    }
+    
+    paragraphs
+}
+
+/// Paragraphs of `segments` (see `group_paragraphs`), for transcripts meant to be read
+pub fn segment_paragraphs(segments: &[WhisperSegment], gap_threshold: f64) -> Vec<Paragraph> {
+    group_paragraphs(segments.iter().map(|segment| (segment.start, segment.end, segment.text.as_str())), gap_threshold)
+}
+
+/// A stretch of audio with no speech, in seconds
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct NonSpeechRegion {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Timeline of where there is no speech between `start` and `end` seconds: gaps
+/// between segments, plus segments whose `no_speech_prob` is at least
+/// `no_speech_threshold` (`NO_SPEECH_THRESHOLD` by default). Touching regions are
+/// merged and ones shorter than `MIN_NON_SPEECH_SECONDS` are dropped.
+pub fn non_speech_regions(segments: &[WhisperSegment], start: f64, end: f64, no_speech_threshold: f32) -> Vec<NonSpeechRegion> {
+    let mut speech: Vec<(f64, f64)> = segments.iter()
+        .filter(|segment| segment.no_speech_prob < no_speech_threshold as f64)
+        .map(|segment| (segment.start, segment.end))
+        .collect();
+    speech.sort_by(|a, b| a.0.total_cmp(&b.0));
+    
+    let mut regions = Vec::new();
+    let mut cursor = start;
+    for (speech_start, speech_end) in speech {
+        if speech_start > cursor {
+            regions.push(NonSpeechRegion { start: cursor, end: speech_start.min(end) });
+        }
+        cursor = cursor.max(speech_end);
+    }
+    if end > cursor {
+        regions.push(NonSpeechRegion { start: cursor, end });
+    }
+    
+    regions.retain(|region| region.end - region.start >= MIN_NON_SPEECH_SECONDS);
+    regions
+}
+
+/// Remove segments whose confidence is below `min_confidence` and renumber the rest.
+/// Segments without a confidence (no usable tokens) are kept. Returns how many were removed.
+pub fn filter_low_confidence(segments: &mut Vec<WhisperSegment>, min_confidence: f64) -> usize {
+    let before = segments.len();
+    segments.retain(|segment| !matches!(segment.confidence, Some(c) if c < min_confidence));
+    
+    for (id, segment) in segments.iter_mut().enumerate() {
+        segment.id = id as i32;
+    }
+    
+    let removed = before - segments.len();
+    if removed > 0 {
+        log::debug!("Filtered {} segments below confidence {:.2}", removed, min_confidence);
+    }
+    removed
+}
+
+/// Drop words whose confidence is below `threshold` from each segment's `words`,
+/// leaving the segment text untouched. Returns how many words were removed.
+pub fn filter_low_confidence_words(segments: &mut [WhisperSegment], threshold: f64) -> usize {
+    let mut removed = 0;
+    for segment in segments.iter_mut() {
+        let before = segment.words.len();
+        segment.words.retain(|word| word.confidence >= threshold);
+        removed += before - segment.words.len();
+    }
+    removed
+}
+
+/// Make word timings within each segment usable for highlighting: words are kept
+/// inside their segment, in order, without overlaps and with non-zero length.
+/// Each run of offending words is re-spread over the time it covers, in proportion
+/// to word length in characters, borrowing from neighbours when that span is empty.
+/// Returns how many words were retimed.
+pub fn smooth_word_timestamps(segments: &mut [WhisperSegment]) -> usize {
+    let mut retimed = 0;
+    
+    for segment in segments.iter_mut() {
+        let (segment_start, segment_end) = (segment.start, segment.end.max(segment.start));
+        let words = &mut segment.words;
+        let n = words.len();
+        if n == 0 {
+            continue;
+        }
+        
+        for word in words.iter_mut() {
+            word.start = word.start.clamp(segment_start, segment_end);
+            word.end = word.end.clamp(segment_start, segment_end);
+        }
+        
+        // A word is bad when it has no length or runs past the start of the next one
+        let mut bad = vec![false; n];
+        for i in 0..n {
+            if words[i].end <= words[i].start {
+                bad[i] = true;
+            }
+            if i + 1 < n && words[i].end > words[i + 1].start {
+                bad[i] = true;
+                bad[i + 1] = true;
+            }
+        }
+        
+        let mut i = 0;
+        while i < n {
+            if !bad[i] {
+                i += 1;
+                continue;
+            }
+            let (mut first, mut last) = (i, i);
+            while last + 1 < n && bad[last + 1] {
+                last += 1;
+            }
+            
+            // Span the run may use without touching the good words around it;
+            // widen the run by a neighbour while that leaves no time to share
+            let (lower, upper) = loop {
+                let run = &words[first..=last];
+                let lower = run.iter().map(|w| w.start).fold(f64::INFINITY, f64::min)
+                    .max(if first > 0 { words[first - 1].end } else { segment_start });
+                let upper = run.iter().map(|w| w.end).fold(f64::NEG_INFINITY, f64::max)
+                    .min(if last + 1 < n { words[last + 1].start } else { segment_end });
+                if upper > lower {
+                    break (lower, upper);
+                }
+                if first > 0 {
+                    first -= 1;
+                } else if last + 1 < n {
+                    last += 1;
+                } else {
+                    break (segment_start, segment_end);
+                }
+            };
+            
+            let weights: Vec<f64> = words[first..=last].iter()
+                .map(|w| w.text.trim().chars().count().max(1) as f64)
+                .collect();
+            let total: f64 = weights.iter().sum();
+            let mut position = lower;
+            for (word, weight) in words[first..=last].iter_mut().zip(&weights) {
+                word.start = position;
+                position += (upper - lower) * weight / total;
+                word.end = position;
+            }
+            
+            retimed += last - first + 1;
+            i = last + 1;
+        }
+    }
+    
+    retimed
+}
+
+// Longest phrase, in words, checked for back-to-back repetition
+const MAX_REPEAT_NGRAM: usize = 4;
+
+/// Whether `text` repeats the same 1-4 word phrase more than `max_repeat` times in a row,
+/// Whisper's typical hallucination on silence or music. Thai is split with `thai::segment_words`.
+pub fn is_repetitive(text: &str, max_repeat: usize) -> bool {
+    let words: Vec<String> = thai::segment_words(&text.to_lowercase());
+    
+    for n in 1..=MAX_REPEAT_NGRAM {
+        if words.len() < n * (max_repeat + 1) {
+            break;
+        }
+        
+        for start in 0..n {
+            let mut run = 1;
+            let mut i = start + n;
+            while i + n <= words.len() {
+                if words[i..i + n] == words[i - n..i] {
+                    run += 1;
+                    if run > max_repeat {
+                        return true;
+                    }
+                } else {
+                    run = 1;
+                }
+                i += n;
+            }
         }
+    }
+    
+    false
+}
+
+/// Mark repetition hallucinations in a list of segment texts: segments that loop a phrase
+/// (see `is_repetitive`), and every segment after the first in a run of more than
+/// `max_repeat` consecutive segments with the same text.
+pub fn find_repetitions(texts: &[&str], max_repeat: usize) -> Vec<bool> {
+    let normalized: Vec<String> = texts.iter().map(|t| t.trim().to_lowercase()).collect();
+    let mut drops: Vec<bool> = texts.iter().map(|t| is_repetitive(t, max_repeat)).collect();
+    
+    let mut start = 0;
+    while start < normalized.len() {
+        let mut end = start + 1;
+        while end < normalized.len() && normalized[end] == normalized[start] {
+            end += 1;
+        }
+        if end - start > max_repeat && !normalized[start].is_empty() {
+            for drop in &mut drops[start + 1..end] {
+                *drop = true;
+            }
+        }
+        start = end;
+    }
+    
+    drops
+}
+
+/// Drop repetition hallucinations (see `find_repetitions`) and renumber the rest.
+/// Returns how many segments were removed.
+pub fn suppress_repetitions(segments: &mut Vec<WhisperSegment>, max_repeat: usize) -> usize {
+    let texts: Vec<&str> = segments.iter().map(|s| s.text.as_str()).collect();
+    let drops = find_repetitions(&texts, max_repeat);
+    
+    let before = segments.len();
+    let mut index = 0;
+    segments.retain(|_| {
+        let keep = !drops[index];
+        index += 1;
+        keep
     });
     
-    println!("✅ Transcription result ready with {} characters", full_text.len());
+    for (id, segment) in segments.iter_mut().enumerate() {
+        segment.id = id as i32;
+    }
+    
+    let removed = before - segments.len();
+    if removed > 0 {
+        log::debug!("Suppressed {} repeated-text segments (max repeat {})", removed, max_repeat);
+    }
+    removed
+}
+
+/// Shift all segment and word timestamps by `offset` seconds
+fn offset_segments(segments: &mut [WhisperSegment], offset: f64) {
+    if offset == 0.0 {
+        return;
+    }
+    
+    for segment in segments.iter_mut() {
+        segment.start += offset;
+        segment.end += offset;
+        segment.seek = (segment.start * 100.0) as i32;
+        for word in segment.words.iter_mut() {
+            word.start += offset;
+            word.end += offset;
+        }
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit_index = 0;
+    
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+    
+    format!("{:.1} {}", size, UNITS[unit_index])
+}
+
+/// Three-way risk verdict, so unclear answers can be routed to human review instead of passing as safe
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RiskLevel {
+    Safe,
+    Uncertain,
+    Risky,
+}
+
+// Confidence reported when the model's answer contains no usable verdict
+const UNCLEAR_RISK_CONFIDENCE: f64 = 0.5;
+
+/// LlamaEdge server queried when neither `RiskConfig::endpoints` nor `LLAMAEDGE_ENDPOINTS` is set
+pub const DEFAULT_LLAMAEDGE_URL: &str = "http://localhost:8080";
+
+/// How the verdicts of several risk models are combined
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskVoting {
+    /// Risky if any model flags the text, favouring recall
+    #[default]
+    Any,
+    /// Risky if more than half of the models that answered flag it
+    Majority,
+    /// Risky only if every model that answered flags it
+    All,
+}
+
+impl RiskVoting {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "any" => Some(RiskVoting::Any),
+            "majority" => Some(RiskVoting::Majority),
+            "all" => Some(RiskVoting::All),
+            _ => None,
+        }
+    }
+    
+    fn is_risky(&self, risky_votes: usize, answered: usize) -> bool {
+        match self {
+            RiskVoting::Any => risky_votes > 0,
+            RiskVoting::Majority => risky_votes * 2 > answered,
+            RiskVoting::All => answered > 0 && risky_votes == answered,
+        }
+    }
+}
+
+/// Chat-completion parameters for risk analysis, shared by the queue worker and `api-server`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RiskConfig {
+    pub temperature: f64,
+    pub max_tokens: u32,
+    /// Model name sent to the LlamaEdge server; omitted to use the server's default
+    pub model: Option<String>,
+    /// Confidence of an answer that is exactly a verdict ("ผิด", "ไม่ผิด", "RISKY", "SAFE")
+    pub clear_confidence: f64,
+    /// Confidence of a verdict found inside a longer answer
+    pub partial_confidence: f64,
+    /// Verdicts with a lower confidence are reported as `RiskLevel::Uncertain`
+    pub uncertain_below: f64,
+    /// LlamaEdge base URLs queried concurrently. When empty, the comma-separated
+    /// `LLAMAEDGE_ENDPOINTS` environment variable is used, then `DEFAULT_LLAMAEDGE_URL`.
+    /// Never read from request payloads, so API clients can't point the server elsewhere.
+    pub endpoints: Vec<String>,
+    /// How verdicts are combined when more than one endpoint answers
+    pub voting: RiskVoting,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self {
+            temperature: 0.1,
+            max_tokens: 10,
+            model: None,
+            clear_confidence: 0.95,
+            partial_confidence: 0.8,
+            uncertain_below: 0.7,
+            endpoints: Vec::new(),
+            voting: RiskVoting::Any,
+        }
+    }
+}
+
+impl RiskConfig {
+    /// Defaults overridden by `temperature`, `max_tokens`, `model`, the confidence thresholds
+    /// and `voting` ("any", "majority", "all") keys in a request payload
+    pub fn from_payload(payload: &serde_json::Value) -> Self {
+        let defaults = Self::default();
+        Self {
+            temperature: payload.get("temperature")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(defaults.temperature),
+            max_tokens: payload.get("max_tokens")
+                .and_then(|v| v.as_u64())
+                .map(|v| v as u32)
+                .unwrap_or(defaults.max_tokens),
+            model: payload.get("model")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .or(defaults.model),
+            clear_confidence: payload.get("clear_confidence")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(defaults.clear_confidence),
+            partial_confidence: payload.get("partial_confidence")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(defaults.partial_confidence),
+            uncertain_below: payload.get("uncertain_below")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(defaults.uncertain_below),
+            endpoints: defaults.endpoints,
+            voting: payload.get("voting")
+                .and_then(|v| v.as_str())
+                .and_then(RiskVoting::parse)
+                .unwrap_or(defaults.voting),
+        }
+    }
+    
+    /// Read the verdict and its confidence out of a classifier answer. Understands the
+    /// Thai prompt's ผิด/ไม่ผิด and the English RISKY/SAFE; an answer containing
+    /// neither, or both, is uncertain.
+    pub fn classify_response(&self, raw_response: &str) -> (RiskLevel, f64) {
+        let answer = raw_response.trim().to_uppercase();
+        
+        // "ไม่ผิด" (not wrong) contains "ผิด" (wrong), so only count the bare ones as risky
+        let not_wrong = answer.matches("ไม่ผิด").count();
+        let risky_hits = answer.matches("ผิด").count() - not_wrong + answer.matches("RISKY").count();
+        let safe_hits = not_wrong + answer.matches("SAFE").count();
+        
+        let verdict = match (risky_hits > 0, safe_hits > 0) {
+            (true, false) => RiskLevel::Risky,
+            (false, true) => RiskLevel::Safe,
+            _ => return (RiskLevel::Uncertain, UNCLEAR_RISK_CONFIDENCE),
+        };
+        
+        let confidence = if matches!(answer.as_str(), "ผิด" | "ไม่ผิด" | "RISKY" | "SAFE") {
+            self.clear_confidence
+        } else {
+            self.partial_confidence
+        };
+        
+        if confidence < self.uncertain_below {
+            (RiskLevel::Uncertain, confidence)
+        } else {
+            (verdict, confidence)
+        }
+    }
+    
+    /// Chat-completion request body for `prompt`
+    pub fn request_body(&self, prompt: &str) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt
+                }
+            ],
+            "max_tokens": self.max_tokens,
+            "temperature": self.temperature,
+            "stream": false
+        });
+        if let Some(model) = &self.model {
+            body["model"] = serde_json::json!(model);
+        }
+        body
+    }
+}
+
+/// Analyze text for risk using LlamaEdge with real HTTP calls. Every endpoint in
+/// `config.endpoints` is asked at once and the verdicts are combined with `config.voting`;
+/// keyword analysis is used only when no endpoint answers.
+pub async fn analyze_risk(text: &str, config: &RiskConfig) -> Result<serde_json::Value, String> {
+    let endpoints = risk_endpoints(config);
+    
+    // Simple prompt for risk detection
+    let prompt = format!(
+        "Analyze this text for harmful, dangerous, or inappropriate content. Respond with only 'RISKY' or 'SAFE': {}",
+        text
+    );
+    
+    // Create the request payload
+    let payload = config.request_body(&prompt);
+    
+    // Query every model concurrently, so the slowest endpoint bounds the latency
+    let client = reqwest::Client::new();
+    let answers = futures_util::future::join_all(
+        endpoints.iter().map(|url| query_risk_model(&client, url, &payload))
+    ).await;
+    
+    let mut models = Vec::new();
+    let mut verdicts = Vec::new();
+    for (url, answer) in endpoints.iter().zip(answers) {
+        match answer {
+            Ok(raw_response) => {
+                let (risk_level, confidence) = config.classify_response(&raw_response);
+                models.push(serde_json::json!({
+                    "endpoint": url,
+                    "raw_response": raw_response,
+                    "risk_level": risk_level,
+                    "confidence": confidence
+                }));
+                verdicts.push((risk_level, confidence, raw_response));
+            }
+            Err(e) => {
+                log::warn!("LlamaEdge server {} failed: {}", url, e);
+                models.push(serde_json::json!({
+                    "endpoint": url,
+                    "error": e
+                }));
+            }
+        }
+    }
+    
+    // Handle the case where no LlamaEdge server is available
+    if verdicts.is_empty() {
+        log::warn!("No LlamaEdge server answered, falling back to keyword analysis");
+        return Ok(fallback_risk_analysis(text, config));
+    }
+    
+    let (risk_level, confidence) = combine_risk_verdicts(&verdicts, config.voting);
+    let raw_response = verdicts.iter()
+        .map(|(_, _, raw)| raw.as_str())
+        .collect::<Vec<_>>()
+        .join(" | ");
+    
+    Ok(serde_json::json!({
+        "text": text,
+        "risk_analysis": {
+            "is_risky": risk_level == RiskLevel::Risky,
+            "risk_level": risk_level,
+            "raw_response": raw_response,
+            "confidence": confidence,
+            "detected_keywords": [],
+            "models": models
+        },
+        "metadata": {
+            "model": "llamaedge-real",
+            "endpoint": endpoints.join(", "),
+            "endpoints": endpoints,
+            "voting": config.voting,
+            "timestamp": chrono::Utc::now(),
+            "text_length": text.len(),
+            "prompt_type": "simple_classification",
+            "risk_config": config
+        }
+    }))
+}
+
+// Endpoints from the config, else LLAMAEDGE_ENDPOINTS, else the default server
+fn risk_endpoints(config: &RiskConfig) -> Vec<String> {
+    if !config.endpoints.is_empty() {
+        return config.endpoints.clone();
+    }
+    
+    let from_env: Vec<String> = std::env::var("LLAMAEDGE_ENDPOINTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim())
+        .filter(|url| !url.is_empty())
+        .map(|url| url.to_string())
+        .collect();
+    if from_env.is_empty() {
+        vec![DEFAULT_LLAMAEDGE_URL.to_string()]
+    } else {
+        from_env
+    }
+}
+
+// One chat completion against a LlamaEdge server, returning its upper-cased answer
+async fn query_risk_model(client: &reqwest::Client, llama_url: &str, payload: &serde_json::Value) -> Result<String, String> {
+    Ok(query_chat_completion(client, llama_url, payload).await?.to_uppercase())
+}
+
+// One chat completion against a LlamaEdge server, returning the trimmed answer
+async fn query_chat_completion(client: &reqwest::Client, llama_url: &str, payload: &serde_json::Value) -> Result<String, String> {
+    let response = client
+        .post(format!("{}/v1/chat/completions", llama_url.trim_end_matches('/')))
+        .header("Content-Type", "application/json")
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| format!("server not available: {}", e))?;
+    
+    if !response.status().is_success() {
+        return Err(format!("server error: {}", response.status()));
+    }
+    
+    let response_json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse LlamaEdge response: {}", e))?;
+    
+    // Extract the response text
+    Ok(response_json
+        .get("choices")
+        .and_then(|choices| choices.get(0))
+        .and_then(|choice| choice.get("message"))
+        .and_then(|message| message.get("content"))
+        .and_then(|content| content.as_str())
+        .unwrap_or("")
+        .trim()
+        .to_string())
+}
+
+/// Server that restores punctuation: `PUNCTUATION_ENDPOINT`, else the first risk analysis endpoint
+pub fn punctuation_endpoint() -> String {
+    std::env::var("PUNCTUATION_ENDPOINT")
+        .ok()
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| risk_endpoints(&RiskConfig::default()).remove(0))
+}
+
+// Transcript characters sent per punctuation request, so long recordings stay well
+// inside the model's context window
+const PUNCTUATION_BATCH_CHARS: usize = 2000;
+
+// Per-request timeout for punctuation restoration; a hung server fails the batch
+const PUNCTUATION_REQUEST_TIMEOUT_SECS: u64 = 120;
+
+// Consecutive segments grouped into batches of at most `max_chars` characters
+// (a longer segment gets a batch of its own)
+fn punctuation_batches(segment_texts: &[String], max_chars: usize) -> Vec<std::ops::Range<usize>> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut chars = 0;
+    for (index, text) in segment_texts.iter().enumerate() {
+        let len = text.chars().count();
+        if index > start && chars + len > max_chars {
+            batches.push(start..index);
+            start = index;
+            chars = 0;
+        }
+        chars += len;
+    }
+    if start < segment_texts.len() {
+        batches.push(start..segment_texts.len());
+    }
+    batches
+}
+
+/// Ask the LlamaEdge server at `endpoint` to restore punctuation and casing in a
+/// transcription `result` (OpenAI Whisper format), a batch of segments per request.
+/// Each restored batch is split back into its segments by word count, so timestamps
+/// stay as they were; the original transcript is kept under `raw_text`. Fails, leaving
+/// `result` untouched, when the server is unreachable or changed the number of words.
+/// `result` is only modified after every batch succeeded, so the future can be dropped
+/// (e.g. by a timeout) without leaving a half-restored transcript.
+pub async fn restore_punctuation(result: &mut serde_json::Value, endpoint: &str) -> Result<(), String> {
+    let segment_texts: Vec<String> = result.get("segments")
+        .and_then(|segments| segments.as_array())
+        .map(|segments| segments.iter()
+            .map(|segment| segment.get("text").and_then(|text| text.as_str()).unwrap_or("").to_string())
+            .collect())
+        .unwrap_or_default();
+    let raw_text: String = segment_texts.concat().trim().to_string();
+    if raw_text.is_empty() {
+        return Ok(());
+    }
+    
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(PUNCTUATION_REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let words_per_segment: Vec<usize> = segment_texts.iter().map(|text| word_spans(text).len()).collect();
+    let mut restored_texts: Vec<Option<String>> = vec![None; segment_texts.len()];
+    
+    for batch in punctuation_batches(&segment_texts, PUNCTUATION_BATCH_CHARS) {
+        let batch_words: usize = words_per_segment[batch.clone()].iter().sum();
+        if batch_words == 0 {
+            continue;
+        }
+        let batch_text = segment_texts[batch.clone()].concat().trim().to_string();
+        
+        let prompt = format!(
+            "Restore punctuation and capitalization in this transcript. Do not add, remove or change any words. Respond with only the corrected transcript: {}",
+            batch_text
+        );
+        let payload = serde_json::json!({
+            "messages": [
+                {
+                    "role": "user",
+                    "content": prompt
+                }
+            ],
+            "max_tokens": batch_text.chars().count() * 2 + 64,
+            "temperature": 0.0,
+            "stream": false
+        });
+        let restored = query_chat_completion(&client, endpoint, &payload).await?;
+        
+        let restored_spans = word_spans(&restored);
+        if restored_spans.len() != batch_words {
+            return Err(format!(
+                "restored segments {}-{} have {} words where the original has {}, keeping the original",
+                batch.start, batch.end - 1, restored_spans.len(), batch_words
+            ));
+        }
+        
+        // Hand each segment as many restored words as it had, keeping its leading space
+        let mut next_word = 0;
+        for index in batch {
+            let words = words_per_segment[index];
+            if words == 0 {
+                continue;
+            }
+            let (start, end) = (restored_spans[next_word].0, restored_spans[next_word + words - 1].1);
+            let prefix = if segment_texts[index].starts_with(char::is_whitespace) { " " } else { "" };
+            restored_texts[index] = Some(format!("{}{}", prefix, &restored[start..end]));
+            next_word += words;
+        }
+    }
+    
+    if let Some(segments) = result.get_mut("segments").and_then(|segments| segments.as_array_mut()) {
+        for (segment, restored) in segments.iter_mut().zip(restored_texts) {
+            if let Some(restored) = restored {
+                segment["text"] = serde_json::json!(restored);
+            }
+        }
+    }
+    
+    let text: String = result.get("segments")
+        .and_then(|segments| segments.as_array())
+        .map(|segments| segments.iter().filter_map(|segment| segment.get("text").and_then(|text| text.as_str())).collect())
+        .unwrap_or_default();
+    result["text"] = serde_json::json!(text.trim());
+    result["raw_text"] = serde_json::json!(raw_text);
     
-    Ok(result)
+    // Regroup paragraphs, the restored sentence ends give them better break points
+    regroup_paragraphs(result);
+    Ok(())
 }
 
-fn format_bytes(bytes: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
-    let mut size = bytes as f64;
-    let mut unit_index = 0;
+/// Rebuild `paragraphs` of a transcription result from its segments after their text
+/// changed. Results without paragraphs are left alone.
+pub fn regroup_paragraphs(result: &mut serde_json::Value) {
+    if result.get("paragraphs").is_none() {
+        return;
+    }
+    let spans: Vec<(f64, f64, String)> = result.get("segments")
+        .and_then(|segments| segments.as_array())
+        .map(|segments| segments.iter().map(|segment| (
+            segment.get("start").and_then(|v| v.as_f64()).unwrap_or(0.0),
+            segment.get("end").and_then(|v| v.as_f64()).unwrap_or(0.0),
+            segment.get("text").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        )).collect())
+        .unwrap_or_default();
+    let paragraphs = group_paragraphs(spans.iter().map(|(start, end, text)| (*start, *end, text.as_str())), PARAGRAPH_GAP_SECONDS);
+    result["paragraphs"] = serde_json::json!(paragraphs);
+}
+
+// Byte spans of the words in `text`: `thai::segment_words` pieces with a letter or
+// digit, each stretched over the punctuation-only pieces that follow it
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = Vec::new();
+    let mut position = 0;
     
-    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_index += 1;
+    for piece in thai::segment_words(text) {
+        let start = match text[position..].find(piece.as_str()) {
+            Some(offset) => position + offset,
+            None => continue,
+        };
+        let end = start + piece.len();
+        position = end;
+        
+        if piece.chars().any(|c| c.is_alphanumeric()) {
+            spans.push((start, end));
+        } else if let Some(last) = spans.last_mut() {
+            last.1 = end;
+        }
     }
     
-    format!("{:.1} {}", size, UNITS[unit_index])
+    spans
 }
 
-/// Analyze text for risk using LlamaEdge with real HTTP calls
-pub async fn analyze_risk(text: &str) -> Result<serde_json::Value, String> {
-    // Use the default LlamaEdge server URL
-    let llama_url = "http://localhost:8080";
+/// Combine per-model verdicts under `voting`. When the vote isn't risky, any uncertain
+/// model makes the result uncertain. Confidence is the mean over the models that agree
+/// with the combined verdict.
+fn combine_risk_verdicts(verdicts: &[(RiskLevel, f64, String)], voting: RiskVoting) -> (RiskLevel, f64) {
+    let risky_votes = verdicts.iter().filter(|(level, _, _)| *level == RiskLevel::Risky).count();
     
-    // Simple prompt for risk detection
-    let prompt = format!(
-        "Analyze this text for harmful, dangerous, or inappropriate content. Respond with only 'RISKY' or 'SAFE': {}",
-        text
-    );
+    let risk_level = if voting.is_risky(risky_votes, verdicts.len()) {
+        RiskLevel::Risky
+    } else if verdicts.iter().any(|(level, _, _)| *level == RiskLevel::Uncertain) {
+        RiskLevel::Uncertain
+    } else {
+        RiskLevel::Safe
+    };
     
-    // Create the request payload
-    let payload = serde_json::json!({
-        "messages": [
-            {
-                "role": "user",
-                "content": prompt
-            }
-        ],
-        "max_tokens": 10,
-        "temperature": 0.1
-    });
+    let mut agreeing: Vec<f64> = verdicts.iter()
+        .filter(|(level, _, _)| *level == risk_level)
+        .map(|(_, confidence, _)| *confidence)
+        .collect();
+    if agreeing.is_empty() {
+        agreeing = verdicts.iter().map(|(_, confidence, _)| *confidence).collect();
+    }
+    let confidence = agreeing.iter().sum::<f64>() / agreeing.len() as f64;
     
-    // Make HTTP request to LlamaEdge server
-    let client = reqwest::Client::new();
-    let response = client
-        .post(&format!("{}/v1/chat/completions", llama_url))
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .await;
-    
-    // Handle the case where LlamaEdge server is not available
-    let result = match response {
-        Ok(resp) if resp.status().is_success() => {
-            let response_json: serde_json::Value = resp
-                .json()
-                .await
-                .map_err(|e| format!("Failed to parse LlamaEdge response: {}", e))?;
-            
-            // Extract the response text
-            let raw_response = response_json
-                .get("choices")
-                .and_then(|choices| choices.get(0))
-                .and_then(|choice| choice.get("message"))
-                .and_then(|message| message.get("content"))
-                .and_then(|content| content.as_str())
-                .unwrap_or("")
-                .trim()
-                .to_uppercase();
-            
-            // Determine if risky
-            let is_risky = raw_response.contains("RISKY");
-            let confidence = if raw_response == "RISKY" || raw_response == "SAFE" {
-                0.95 // High confidence for clear responses
-            } else {
-                0.6 // Lower confidence for unclear responses
-            };
-            
-            serde_json::json!({
-                "text": text,
-                "risk_analysis": {
-                    "is_risky": is_risky,
-                    "raw_response": raw_response,
-                    "confidence": confidence,
-                    "detected_keywords": []
-                },
-                "metadata": {
-                    "model": "llamaedge-real",
-                    "endpoint": llama_url,
-                    "timestamp": chrono::Utc::now(),
-                    "text_length": text.len(),
-                    "prompt_type": "simple_classification"
+    (risk_level, confidence)
+}
+
+/// Built-in risk keywords used when `RISK_KEYWORDS_FILE` is not set
+const DEFAULT_RISK_KEYWORDS: &[&str] = &[
+    "gambling", "บาคาร่า", "illegal", "drug", "weapon", "scam", "fraud",
+    "เงินด่วน", "พนัน", "หวย", "การพนัน", "ยาเสพติด", "อาวุธ", "โกง",
+    "ค้ายา", "ปืน", "หลอกลวง", "โกงเงิน", "พนันบอล", "คาสิโน"
+];
+
+/// Load newline-separated risk keywords from a file.
+/// Blank lines and lines starting with `#` are ignored.
+pub fn load_risk_keywords(path: &str) -> Result<Vec<String>, std::io::Error> {
+    let content = std::fs::read_to_string(path)?;
+    
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_lowercase())
+        .collect())
+}
+
+/// Risk keywords from `RISK_KEYWORDS_FILE` if set, otherwise the built-in list (loaded once)
+fn risk_keywords() -> &'static [String] {
+    static KEYWORDS: OnceLock<Vec<String>> = OnceLock::new();
+    
+    KEYWORDS.get_or_init(|| {
+        let defaults = || -> Vec<String> { DEFAULT_RISK_KEYWORDS.iter().map(|k| k.to_string()).collect() };
+        
+        match std::env::var("RISK_KEYWORDS_FILE") {
+            Ok(path) => match load_risk_keywords(&path) {
+                Ok(keywords) if !keywords.is_empty() => {
+                    log::info!("Loaded {} risk keywords from {}", keywords.len(), path);
+                    keywords
                 }
-            })
-        },
-        Ok(resp) => {
-            // LlamaEdge server returned an error
-            log::warn!("LlamaEdge server error: {}", resp.status());
-            fallback_risk_analysis(text)
-        },
-        Err(e) => {
-            // LlamaEdge server not available
-            log::warn!("LlamaEdge server not available: {}, falling back to keyword analysis", e);
-            fallback_risk_analysis(text)
+                Ok(_) => {
+                    log::warn!("Risk keyword file {} is empty, using built-in keywords", path);
+                    defaults()
+                }
+                Err(e) => {
+                    log::warn!("Failed to load risk keywords from {}: {}, using built-in keywords", path, e);
+                    defaults()
+                }
+            },
+            Err(_) => defaults(),
         }
-    };
-    
-    Ok(result)
+    })
 }
 
 /// Fallback keyword-based risk analysis when LlamaEdge is not available
-fn fallback_risk_analysis(text: &str) -> serde_json::Value {
-    let risk_keywords = [
-        "gambling", "บาคาร่า", "illegal", "drug", "weapon", "scam", "fraud",
-        "เงินด่วน", "พนัน", "หวย", "การพนัน", "ยาเสพติด", "อาวุธ", "โกง",
-        "ค้ายา", "ปืน", "หลอกลวง", "โกงเงิน", "พนันบอล", "คาสิโน"
-    ];
-    
-    let detected_keywords: Vec<&str> = risk_keywords.iter()
-        .filter(|&&keyword| text.to_lowercase().contains(keyword))
-        .copied()
+fn fallback_risk_analysis(text: &str, config: &RiskConfig) -> serde_json::Value {
+    let lowercase_text = text.to_lowercase();
+    let detected_keywords: Vec<&str> = risk_keywords().iter()
+        .filter(|keyword| lowercase_text.contains(keyword.as_str()))
+        .map(|keyword| keyword.as_str())
         .collect();
     
     let is_risky = !detected_keywords.is_empty();
@@ -356,11 +2682,19 @@ fn fallback_risk_analysis(text: &str) -> serde_json::Value {
     } else {
         0.75 // Good confidence for keyword-based safe classification
     };
+    let risk_level = if confidence < config.uncertain_below {
+        RiskLevel::Uncertain
+    } else if is_risky {
+        RiskLevel::Risky
+    } else {
+        RiskLevel::Safe
+    };
     
     serde_json::json!({
         "text": text,
         "risk_analysis": {
             "is_risky": is_risky,
+            "risk_level": risk_level,
             "raw_response": if is_risky { "RISKY" } else { "SAFE" },
             "confidence": confidence,
             "detected_keywords": detected_keywords
@@ -377,58 +2711,572 @@ fn fallback_risk_analysis(text: &str) -> serde_json::Value {
 // Audio loading functions adapted from main.rs
 
 /// Load audio file with debug information and proper format support
-pub fn load_audio_file_with_debug(path: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
-    println!("🔍 Loading audio file: {}", path);
+pub fn load_audio_file_with_debug(
+    path: &str,
+    options: &AudioLoadOptions,
+) -> Result<LoadedAudio, Box<dyn std::error::Error>> {
+    load_audio_file_with_progress(path, options, None)
+}
+
+/// `load_audio_file_with_debug`, reporting loading progress (0-100%) through `progress`:
+/// decoding, then resampling when the file isn't already 16kHz
+pub fn load_audio_file_with_progress(
+    path: &str,
+    options: &AudioLoadOptions,
+    progress: Option<ProgressCallback>,
+) -> Result<LoadedAudio, Box<dyn std::error::Error>> {
+    let task_id = options.task_id.as_deref();
+    task_log!(info, task_id, "🔍 Loading audio file: {}", path);
     
     if !Path::new(path).exists() {
-        return Err(format!("Audio file not found: {}", path).into());
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("Audio file not found: {}", path)).into());
     }
     
-    // Use rodio for proper audio format support (MP3, WAV, FLAC, etc.)
-    let file = std::fs::File::open(path)?;
-    let decoder = Decoder::new(std::io::BufReader::new(file))?;
+    // Decoding fills the first DECODE_PROGRESS_SHARE percent, resampling the rest
+    let decode_progress: Option<ProgressCallback> = progress.clone().map(|callback| {
+        Arc::new(move |percent: f32| {
+            callback(percent * DECODE_PROGRESS_SHARE / 100.0);
+        }) as ProgressCallback
+    });
     
-    let sample_rate = decoder.sample_rate();
-    let channels = decoder.channels();
+    let (mut samples, sample_rate, channels) = decode_audio_file(path, task_id, decode_progress.as_ref())?;
     
-    println!("🔍 Audio file info:");
-    println!("   - Sample rate: {} Hz", sample_rate);
-    println!("   - Channels: {}", channels);
+    task_log!(debug, task_id, "🔍 Audio file info: {} Hz, {} channel(s)", sample_rate, channels);
     
-    // Convert to f32 samples
-    let mut samples: Vec<f32> = decoder
-        .convert_samples::<f32>()
-        .collect();
+    // Corrupt files can decode to NaN/Inf, which would poison downmixing, resampling and Whisper
+    let sanitized_samples = sanitize_samples(&mut samples);
+    if sanitized_samples > 0 {
+        task_log!(warn, task_id, "⚠️  Replaced or clamped {} non-finite or out-of-range samples", sanitized_samples);
+    }
     
-    // Convert stereo to mono if necessary
-    if channels == 2 {
-        println!("   - Converting stereo to mono");
-        samples = samples
-            .chunks(2)
-            .map(|chunk| (chunk[0] + chunk[1]) / 2.0)
-            .collect();
+    // Convert to mono, either by averaging or by picking out one speaker's channel
+    check_channel_layout(samples.len(), channels)?;
+    if let Some(channel) = options.channel {
+        task_log!(debug, task_id, "Extracting channel {} of {}", channel, channels);
+    } else if channels_identical(&samples, channels) {
+        task_log!(info, task_id, "ℹ️  All {} channels carry identical audio, using channel 0 without downmixing", channels);
+    } else if channels == 2 {
+        task_log!(debug, task_id, "Converting stereo to mono");
     } else if channels > 2 {
-        println!("   - Converting {}-channel to mono", channels);
-        samples = samples
-            .chunks(channels as usize)
-            .map(|chunk| chunk.iter().sum::<f32>() / channels as f32)
-            .collect();
+        task_log!(debug, task_id, "Converting {}-channel to mono", channels);
     }
+    samples = to_mono(samples, channels, options.channel)?;
     
-    println!("   - Mono samples: {}", samples.len());
-    println!("   - Duration: {:.2} seconds", samples.len() as f32 / sample_rate as f32);
+    task_log!(debug, task_id, "Mono samples: {} ({:.2} seconds)", samples.len(), samples.len() as f32 / sample_rate as f32);
+    
+    // Detect (and optionally repair) clipping before resampling smears the flat tops
+    let clip_threshold = options.clip_threshold.unwrap_or(DEFAULT_CLIP_THRESHOLD);
+    let clipped_samples = samples.iter().filter(|&&x| x.abs() >= clip_threshold).count();
+    let mut declipped_samples = 0;
+    if clipped_samples > 0 {
+        task_log!(warn, task_id, "⚠️  WARNING: {} samples appear clipped (>= {})", clipped_samples, clip_threshold);
+        if options.declip {
+            declipped_samples = declip_samples(&mut samples, clip_threshold);
+            task_log!(info, task_id, "🩹 De-clipped {} of {} clipped samples", declipped_samples, clipped_samples);
+        }
+    }
     
     // Resample to 16kHz if necessary (Whisper's expected sample rate)
     let final_samples = if sample_rate != SAMPLE_RATE {
-        println!("🔄 Resampling: {}Hz → {}Hz", sample_rate, SAMPLE_RATE);
-        resample_audio(samples, sample_rate, SAMPLE_RATE)?
+        task_log!(debug, task_id, "🔄 Resampling: {}Hz → {}Hz", sample_rate, SAMPLE_RATE);
+        let resample_progress: Option<ProgressCallback> = progress.clone().map(|callback| {
+            Arc::new(move |percent: f32| {
+                callback(DECODE_PROGRESS_SHARE + percent * (100.0 - DECODE_PROGRESS_SHARE) / 100.0);
+            }) as ProgressCallback
+        });
+        resample_audio(samples, sample_rate, SAMPLE_RATE, options.resample_quality, resample_progress.as_ref())?
     } else {
-        println!("✅ Sample rate is already {}Hz, no resampling needed", SAMPLE_RATE);
+        task_log!(debug, task_id, "✅ Sample rate is already {}Hz, no resampling needed", SAMPLE_RATE);
         samples
     };
     
-    println!("✅ Final audio: {} samples at {}Hz", final_samples.len(), SAMPLE_RATE);
-    Ok(final_samples)
+    task_log!(info, task_id, "✅ Final audio: {} samples at {}Hz", final_samples.len(), SAMPLE_RATE);
+    if let Some(callback) = &progress {
+        callback(100.0);
+    }
+    
+    // Trim first so AGC measures the speech rather than the silence around it
+    let (mut samples, time_offset, trimmed_duration) = if options.trim_silence {
+        let (start, end) = detect_speech_bounds(&final_samples);
+        let time_offset = start as f64 / SAMPLE_RATE as f64;
+        let trimmed_duration = (final_samples.len() - (end - start)) as f64 / SAMPLE_RATE as f64;
+        
+        task_log!(info, task_id, "✂️  Trimmed {:.2}s of silence ({:.2}s leading, {:.2}s trailing)",
+                  trimmed_duration, time_offset, trimmed_duration - time_offset);
+        
+        (final_samples[start..end].to_vec(), time_offset, trimmed_duration)
+    } else {
+        (final_samples, 0.0, 0.0)
+    };
+    
+    let agc_gain = options.agc_target_dbfs.map(|target_dbfs| {
+        let gain = apply_agc(&mut samples, target_dbfs);
+        task_log!(info, task_id, "🎚️ AGC: {:+.1} dB towards {:.1} dBFS RMS{}",
+                  gain.gain_db, target_dbfs, if gain.peak_limited { " (limited by peak)" } else { "" });
+        gain
+    });
+    
+    Ok(LoadedAudio {
+        samples,
+        time_offset,
+        trimmed_duration,
+        clipped_samples,
+        declipped_samples,
+        sanitized_samples,
+        agc_gain,
+    })
+}
+
+// Magic bytes at the start of a file and the extensions that format is saved under (first is canonical)
+fn sniff_audio_format(header: &[u8]) -> Option<&'static [&'static str]> {
+    if header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WAVE" {
+        Some(&["wav", "wave"])
+    } else if header.starts_with(b"fLaC") {
+        Some(&["flac"])
+    } else if header.starts_with(b"OggS") {
+        Some(&["ogg", "oga", "opus"])
+    } else if header.len() >= 8 && &header[4..8] == b"ftyp" {
+        Some(&["m4a", "mp4", "m4v", "mov", "aac"])
+    } else if header.starts_with(b"ID3") {
+        Some(&["mp3"])
+    } else if header.len() >= 2 && header[0] == 0xFF && header[1] & 0xE0 == 0xE0 {
+        // ADTS AAC shares the MPEG frame sync; its layer bits are always zero
+        if header[1] & 0x06 == 0 {
+            Some(&["aac"])
+        } else {
+            Some(&["mp3"])
+        }
+    } else {
+        None
+    }
+}
+
+/// Extension to decode `path` as. Uploads are often misnamed, so when the file's
+/// magic bytes identify a different format than its extension, the sniffed format wins.
+pub fn detect_audio_extension(path: &str) -> String {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    
+    let mut header = [0u8; 12];
+    let read = std::fs::File::open(path)
+        .and_then(|mut file| std::io::Read::read(&mut file, &mut header))
+        .unwrap_or(0);
+    
+    match sniff_audio_format(&header[..read]) {
+        Some(extensions) if !extensions.contains(&extension.as_str()) => {
+            log::warn!("{} has extension '{}' but its content looks like {}, decoding as {}",
+                       path, extension, extensions[0], extensions[0]);
+            extensions[0].to_string()
+        }
+        _ => extension,
+    }
+}
+
+/// Decode a file to interleaved samples, its sample rate and its channel count.
+/// Symphonia decodes AAC/M4A and video containers that rodio can't; anything it
+/// fails on (e.g. MP3 without Symphonia's mp3 feature) still goes through rodio.
+fn decode_audio_file(path: &str, task_id: Option<&str>, progress: Option<&ProgressCallback>) -> Result<(Vec<f32>, u32, u16), Box<dyn std::error::Error>> {
+    let extension = detect_audio_extension(path);
+    
+    let decoded = if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        task_log!(info, task_id, "🎬 Video container detected ({}), extracting the first audio track", extension);
+        decode_with_symphonia(path, &extension, progress)?
+    } else if cfg!(feature = "full-audio-support") {
+        match decode_with_symphonia(path, &extension, progress) {
+            Ok(decoded) => decoded,
+            Err(e) if AAC_EXTENSIONS.contains(&extension.as_str()) => return Err(e),
+            Err(e) => {
+                task_log!(warn, task_id, "⚠️  Symphonia could not decode {} ({}), falling back to rodio", path, e);
+                decode_with_rodio(path, progress)?
+            }
+        }
+    } else if AAC_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(format!("AAC audio (.{}) requires the 'full-audio-support' feature", extension).into());
+    } else {
+        decode_with_rodio(path, progress)?
+    };
+    
+    Ok(decoded)
+}
+
+/// SHA-256 (hex) of a file's decoded samples, before any downmixing or resampling,
+/// so re-encodes and renames of the same audio hash alike while different audio doesn't
+pub fn audio_content_hash(path: &str) -> Result<String, TranscriptionError> {
+    use sha2::{Digest, Sha256};
+    
+    if !Path::new(path).exists() {
+        return Err(TranscriptionError::AudioLoad(format!("Audio file not found: {}", path)));
+    }
+    
+    let (samples, sample_rate, channels) = decode_audio_file(path, None, None)
+        .map_err(TranscriptionError::from_load_error)?;
+    
+    let mut hasher = Sha256::new();
+    hasher.update(sample_rate.to_le_bytes());
+    hasher.update(channels.to_le_bytes());
+    for sample in &samples {
+        hasher.update(sample.to_le_bytes());
+    }
+    
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Decode with rodio (MP3, WAV, FLAC, Vorbis).
+/// Returns interleaved samples, the sample rate and the channel count.
+/// Progress is only reported when the format knows its duration up front.
+fn decode_with_rodio(path: &str, progress: Option<&ProgressCallback>) -> Result<(Vec<f32>, u32, u16), Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path)?;
+    let decoder = Decoder::new(std::io::BufReader::new(file))?;
+    
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels();
+    let expected_samples = decoder.total_duration()
+        .map(|duration| duration.as_secs_f64() * sample_rate as f64 * channels as f64)
+        .filter(|&expected| expected > 0.0);
+    
+    // Convert to f32 samples
+    let mut samples: Vec<f32> = Vec::new();
+    for sample in decoder.convert_samples::<f32>() {
+        samples.push(sample);
+        if samples.len() % DECODE_PROGRESS_STEP == 0 {
+            if let (Some(callback), Some(expected)) = (progress, expected_samples) {
+                callback((samples.len() as f64 / expected * 100.0).min(100.0) as f32);
+            }
+        }
+    }
+    Ok((samples, sample_rate, channels))
+}
+
+/// Decode the first audio track of a file with Symphonia, including audio inside
+/// video containers (MP4/MOV/MKV/WebM) and AAC in M4A.
+/// Returns interleaved samples, the track's sample rate and its channel count.
+#[cfg(feature = "full-audio-support")]
+fn decode_with_symphonia(path: &str, extension: &str, progress: Option<&ProgressCallback>) -> Result<(Vec<f32>, u32, u16), Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    
+    // The extension hint is what lets the probe pick the MP4 demuxer for .m4a/.mp4
+    let mut hint = Hint::new();
+    if !extension.is_empty() {
+        hint.with_extension(extension);
+    }
+    
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())?;
+    let mut format = probed.format;
+    
+    // Video tracks have no sample rate, so this skips them
+    let codecs = symphonia::default::get_codecs();
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| {
+            t.codec_params.codec != CODEC_TYPE_NULL
+                && t.codec_params.sample_rate.is_some()
+                && codecs.get_codec(t.codec_params.codec).is_some()
+        })
+        .ok_or("No supported audio track found")?;
+    
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
+    let mut channels = track.codec_params.channels.map(|c| c.count() as u16).unwrap_or(1);
+    let total_frames = track.codec_params.n_frames.filter(|&frames| frames > 0);
+    let mut decoder = codecs.make(&track.codec_params, &DecoderOptions::default())?;
+    
+    let mut samples = Vec::new();
+    let mut decoded_frames: u64 = 0;
+    let mut reported_samples = 0;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(_)) => break,
+            Err(e) => return Err(format!("Failed to read container: {}", e).into()),
+        };
+        
+        // Skip packets from video and any other tracks
+        if packet.track_id() != track_id {
+            continue;
+        }
+        
+        match decoder.decode(&packet) {
+            Ok(audio_buf) => {
+                let spec = *audio_buf.spec();
+                channels = spec.channels.count() as u16;
+                let mut sample_buf = SampleBuffer::<f32>::new(audio_buf.capacity() as u64, spec);
+                decoded_frames += audio_buf.frames() as u64;
+                sample_buf.copy_interleaved_ref(audio_buf);
+                samples.extend_from_slice(sample_buf.samples());
+                
+                if samples.len() - reported_samples >= DECODE_PROGRESS_STEP {
+                    reported_samples = samples.len();
+                    if let (Some(callback), Some(total_frames)) = (progress, total_frames) {
+                        callback((decoded_frames as f64 / total_frames as f64 * 100.0).min(100.0) as f32);
+                    }
+                }
+            }
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(format!("Failed to decode audio track: {}", e).into()),
+        }
+    }
+    
+    Ok((samples, sample_rate, channels))
+}
+
+#[cfg(not(feature = "full-audio-support"))]
+fn decode_with_symphonia(_path: &str, extension: &str, _progress: Option<&ProgressCallback>) -> Result<(Vec<f32>, u32, u16), Box<dyn std::error::Error>> {
+    Err(format!("Video containers (.{}) require the 'full-audio-support' feature", extension).into())
+}
+
+/// Duration of the first audio track from its frame count, without decoding any packets
+#[cfg(feature = "full-audio-support")]
+fn probe_duration_with_symphonia(path: &str, extension: &str) -> Option<f64> {
+    let file = std::fs::File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    
+    let mut hint = Hint::new();
+    if !extension.is_empty() {
+        hint.with_extension(extension);
+    }
+    
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        .ok()?;
+    
+    probed.format.tracks().iter().find_map(|t| {
+        let frames = t.codec_params.n_frames?;
+        let sample_rate = t.codec_params.sample_rate?;
+        Some(frames as f64 / sample_rate as f64)
+    })
+}
+
+#[cfg(not(feature = "full-audio-support"))]
+fn probe_duration_with_symphonia(_path: &str, _extension: &str) -> Option<f64> {
+    None
+}
+
+/// Scale `samples` so their RMS level approaches `target_dbfs`. RMS follows the
+/// speech level better than the peak, which a single loud transient can dominate;
+/// the peak only caps the gain so scaling never introduces clipping.
+pub fn apply_agc(samples: &mut [f32], target_dbfs: f32) -> AgcGain {
+    if samples.is_empty() {
+        return AgcGain::default();
+    }
+    
+    let rms = (samples.iter().map(|&x| x * x).sum::<f32>() / samples.len() as f32).sqrt();
+    let peak = samples.iter().fold(0.0f32, |max, &x| max.max(x.abs()));
+    let gain = AgcGain::for_levels(rms, peak, target_dbfs);
+    
+    let factor = gain.factor();
+    for sample in samples.iter_mut() {
+        *sample *= factor;
+    }
+    
+    gain
+}
+
+/// Encode mono f32 samples as a 32-bit IEEE float WAV file. The samples are
+/// stored unchanged, so decoding the file gives back the exact same values.
+pub fn encode_wav_f32(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = (samples.len() * 4) as u32;
+    let mut wav = Vec::with_capacity(44 + samples.len() * 4);
+    
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVE");
+    
+    // fmt chunk: format 3 = IEEE float, 1 channel, 32 bits per sample
+    wav.extend_from_slice(b"fmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&3u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+    wav.extend_from_slice(&4u16.to_le_bytes());
+    wav.extend_from_slice(&32u16.to_le_bytes());
+    
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    
+    wav
+}
+
+/// Check that `sample_count` interleaved samples split into whole frames of
+/// `channels`. A remainder means the declared channel count is wrong, and a
+/// downmix would pair samples from different frames into garbage.
+pub fn check_channel_layout(sample_count: usize, channels: u16) -> Result<(), String> {
+    let channels = channels.max(1) as usize;
+    if sample_count % channels != 0 {
+        return Err(format!(
+            "Audio declares {} channels but has {} samples, which is not a whole number of frames ({} left over); the channel count in the header is probably wrong",
+            channels, sample_count, sample_count % channels
+        ));
+    }
+    Ok(())
+}
+
+/// True when every frame carries the same value on all channels, i.e. mono
+/// audio stored as multichannel
+pub fn channels_identical(samples: &[f32], channels: u16) -> bool {
+    let channels = channels.max(1) as usize;
+    channels > 1 && samples.chunks_exact(channels).all(|frame| frame.iter().all(|&x| x == frame[0]))
+}
+
+/// Turn interleaved samples into mono. With `channel` set, only that channel
+/// (0-based) is kept; otherwise all channels are averaged, or channel 0 is
+/// taken as-is when they are all identical. Asking for a channel the audio
+/// doesn't have, or a sample count that isn't whole frames, is an error rather
+/// than a silent fallback to a garbled downmix.
+pub fn to_mono(samples: Vec<f32>, channels: u16, channel: Option<usize>) -> Result<Vec<f32>, String> {
+    check_channel_layout(samples.len(), channels)?;
+    let identical = channels_identical(&samples, channels);
+    let channels = channels.max(1) as usize;
+    
+    if let Some(channel) = channel {
+        if channel >= channels {
+            return Err(format!(
+                "Channel {} requested but the audio has {} channel(s) (channels are 0-based)", channel, channels
+            ));
+        }
+        if channels == 1 {
+            return Ok(samples);
+        }
+        return Ok(samples.chunks_exact(channels).map(|frame| frame[channel]).collect());
+    }
+    
+    if channels == 1 {
+        return Ok(samples);
+    }
+    if identical {
+        return Ok(samples.chunks_exact(channels).map(|frame| frame[0]).collect());
+    }
+    Ok(samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect())
+}
+
+/// Replace NaN and infinite samples with 0.0 and clamp the rest to [-1.0, 1.0].
+/// Returns the number of samples that were changed.
+pub fn sanitize_samples(samples: &mut [f32]) -> usize {
+    let mut sanitized = 0;
+    for sample in samples.iter_mut() {
+        if !sample.is_finite() {
+            *sample = 0.0;
+            sanitized += 1;
+        } else if sample.abs() > 1.0 {
+            *sample = sample.clamp(-1.0, 1.0);
+            sanitized += 1;
+        }
+    }
+    sanitized
+}
+
+/// Check a clip threshold is in (0, 1]: at 0 or below every sample counts as
+/// clipped, and above 1 nothing ever does
+pub fn validate_clip_threshold(threshold: f32) -> Result<(), String> {
+    if threshold.is_nan() || threshold <= 0.0 || threshold > 1.0 {
+        return Err(format!("clip_threshold must be in (0, 1], got {}", threshold));
+    }
+    Ok(())
+}
+
+/// Rebuild clipped runs (|x| >= threshold) with a cubic through the two
+/// unclipped samples on each side of the run. Runs touching the edges of the
+/// buffer or longer than `DECLIP_MAX_RUN` are left alone since there is not
+/// enough context to estimate the lost peak. Returns the number of samples
+/// that were rewritten.
+pub fn declip_samples(samples: &mut [f32], threshold: f32) -> usize {
+    let mut repaired = 0;
+    let mut i = 0;
+    
+    while i < samples.len() {
+        if samples[i].abs() < threshold {
+            i += 1;
+            continue;
+        }
+        
+        let start = i;
+        while i < samples.len() && samples[i].abs() >= threshold {
+            i += 1;
+        }
+        let end = i;
+        
+        if start < 2 || end + 2 > samples.len() || end - start > DECLIP_MAX_RUN {
+            continue;
+        }
+        
+        // Lagrange cubic through (start-2, start-1, end, end+1)
+        let xs = [start as f32 - 2.0, start as f32 - 1.0, end as f32, end as f32 + 1.0];
+        let ys = [samples[start - 2], samples[start - 1], samples[end], samples[end + 1]];
+        
+        for n in start..end {
+            let x = n as f32;
+            let mut value = 0.0;
+            for (j, (&xj, &yj)) in xs.iter().zip(ys.iter()).enumerate() {
+                let basis: f32 = xs.iter()
+                    .enumerate()
+                    .filter(|&(k, _)| k != j)
+                    .map(|(_, &xk)| (x - xk) / (xj - xk))
+                    .product();
+                value += yj * basis;
+            }
+            
+            // Never pull a peak back below the clip level it was flattened at
+            let clipped = samples[n];
+            samples[n] = if clipped > 0.0 { value.max(clipped) } else { value.min(clipped) };
+            repaired += 1;
+        }
+    }
+    
+    repaired
+}
+
+/// Find the sample range between the first and last VAD windows whose RMS
+/// exceeds the threshold, padded slightly so speech onsets aren't clipped.
+/// Returns the full range if no window is above the threshold.
+fn detect_speech_bounds(samples: &[f32]) -> (usize, usize) {
+    let window = SAMPLE_RATE as usize * VAD_WINDOW_MS / 1000;
+    let padding = SAMPLE_RATE as usize * VAD_PADDING_MS / 1000;
+    
+    let is_voiced = |chunk: &[f32]| {
+        let rms = (chunk.iter().map(|&x| x * x).sum::<f32>() / chunk.len() as f32).sqrt();
+        rms >= VAD_RMS_THRESHOLD
+    };
+    
+    let first = samples.chunks(window).position(is_voiced);
+    let last = samples.chunks(window).rposition(is_voiced);
+    
+    match (first, last) {
+        (Some(first), Some(last)) => {
+            let start = (first * window).saturating_sub(padding);
+            let end = ((last + 1) * window + padding).min(samples.len());
+            (start, end)
+        }
+        _ => (0, samples.len()),
+    }
+}
+
+/// Ratio for resampling `input_rate` Hz audio to `output_rate` Hz, or an error naming
+/// the unsupported rate. Catches what would otherwise surface as a rubato construction
+/// error or an absurd allocation (a 0Hz header gives an infinite ratio).
+pub fn resample_ratio(input_rate: u32, output_rate: u32) -> Result<f64, String> {
+    if !(MIN_INPUT_SAMPLE_RATE..=MAX_INPUT_SAMPLE_RATE).contains(&input_rate) {
+        return Err(format!(
+            "Unsupported sample rate {}Hz: audio must be between {}Hz and {}Hz to be resampled to {}Hz",
+            input_rate, MIN_INPUT_SAMPLE_RATE, MAX_INPUT_SAMPLE_RATE, output_rate
+        ));
+    }
+    if output_rate == 0 {
+        return Err("Unsupported output sample rate 0Hz".to_string());
+    }
+    
+    Ok(output_rate as f64 / input_rate as f64)
 }
 
 /// Resample audio using rubato for high quality resampling
@@ -436,34 +3284,190 @@ fn resample_audio(
     input_samples: Vec<f32>,
     input_rate: u32,
     output_rate: u32,
+    quality: ResampleQuality,
+    progress: Option<&ProgressCallback>,
 ) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
     if input_rate == output_rate {
         return Ok(input_samples);
     }
     
     let input_len = input_samples.len();
-    let ratio = output_rate as f64 / input_rate as f64;
-    
-    // Use high-quality resampling parameters
-    let params = SincInterpolationParameters {
-        sinc_len: 256,
-        f_cutoff: 0.95,
-        interpolation: SincInterpolationType::Linear,
-        oversampling_factor: 256,
-        window: WindowFunction::BlackmanHarris2,
+    let ratio = resample_ratio(input_rate, output_rate)?;
+    if input_len == 0 {
+        return Ok(input_samples);
+    }
+    
+    let params = match quality {
+        ResampleQuality::Fast => SincInterpolationParameters {
+            sinc_len: 32,
+            f_cutoff: 0.9,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 32,
+            window: WindowFunction::Hann,
+        },
+        // Use high-quality resampling parameters
+        ResampleQuality::High => SincInterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        },
     };
     
+    // Resample in one-second blocks so progress can be reported; the blocks join into
+    // the same signal as resampling the whole buffer in one call
+    let chunk_size = (input_rate as usize).min(input_len).max(1);
     let mut resampler = SincFixedIn::<f32>::new(
         ratio,
         2.0,
         params,
-        input_samples.len(),
+        chunk_size,
         1,
-    )?;
+    ).map_err(|e| format!("Failed to create resampler for {}Hz → {}Hz: {}", input_rate, output_rate, e))?;
+    
+    let output_len = (input_len as f64 * ratio).ceil() as usize;
+    let mut resampled = Vec::with_capacity(output_len + chunk_size);
+    let mut position = 0;
+    while position + chunk_size <= input_len {
+        let output = resampler.process(&[&input_samples[position..position + chunk_size]], None)?;
+        resampled.extend_from_slice(&output[0]);
+        position += chunk_size;
+        
+        if let Some(callback) = progress {
+            callback(position as f32 / input_len as f32 * 100.0);
+        }
+    }
     
-    let output = resampler.process(&[input_samples], None)?;
-    let resampled = output[0].clone();
+    // Zero-pad the last partial block, then flush the resampler's delay
+    if position < input_len {
+        let output = resampler.process_partial(Some(&[&input_samples[position..]]), None)?;
+        resampled.extend_from_slice(&output[0]);
+    }
+    while resampled.len() < output_len {
+        let output = resampler.process_partial(None::<&[&[f32]]>, None)?;
+        resampled.extend_from_slice(&output[0]);
+    }
+    resampled.truncate(output_len);
     
-    println!("🔄 Resampling completed: {} → {} samples", input_len, resampled.len());
+    log::debug!("🔄 Resampling completed: {} → {} samples", input_len, resampled.len());
     Ok(resampled)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn punctuation_batches_split_on_the_char_budget() {
+        let texts: Vec<String> = ["aaaa", "bbbb", "cccc", "dddddddddd", "ee"].iter().map(|t| t.to_string()).collect();
+        assert_eq!(punctuation_batches(&texts, 8), vec![0..2, 2..3, 3..4, 4..5]);
+        assert_eq!(punctuation_batches(&texts, 100), vec![0..5]);
+        assert!(punctuation_batches(&[], 8).is_empty());
+        // Counted in characters, not bytes
+        let thai: Vec<String> = ["สวัสดี", "ครับ"].iter().map(|t| t.to_string()).collect();
+        assert_eq!(punctuation_batches(&thai, 10), vec![0..2]);
+    }
+
+    #[test]
+    fn regroup_paragraphs_follows_edited_segments() {
+        let mut result = serde_json::json!({
+            "segments": [
+                {"start": 0.0, "end": 1.0, "text": " Hello"},
+                {"start": 1.2, "end": 2.0, "text": " there"},
+            ],
+            "paragraphs": [{"start": 0.0, "end": 2.0, "text": "Hi there"}],
+        });
+        regroup_paragraphs(&mut result);
+        assert_eq!(result["paragraphs"], serde_json::json!([{"start": 0.0, "end": 2.0, "text": "Hello there"}]));
+        
+        let mut plain = serde_json::json!({"segments": [{"start": 0.0, "end": 1.0, "text": " Hello"}]});
+        regroup_paragraphs(&mut plain);
+        assert!(plain.get("paragraphs").is_none());
+    }
+
+    fn word(text: &str, start: f64, confidence: f64) -> WhisperWord {
+        WhisperWord { text: text.to_string(), start, end: start + 0.4, confidence }
+    }
+    
+    fn segment(words: Vec<WhisperWord>) -> WhisperSegment {
+        let text = format!(" {}", join_words(words.iter().map(|w| w.text.as_str())));
+        let start = words.first().map(|w| w.start).unwrap_or(0.0);
+        let end = words.last().map(|w| w.end).unwrap_or(0.0);
+        WhisperSegment {
+            id: 0,
+            seek: (start * 100.0) as i32,
+            start,
+            end,
+            compression_ratio: compression_ratio(&text),
+            text,
+            tokens: Vec::new(),
+            temperature: 0.0,
+            avg_logprob: -0.2,
+            no_speech_prob: 0.0,
+            confidence: Some(0.8),
+            words,
+            speaker: None,
+        }
+    }
+    
+    fn wordless_segment(text: &str, start: f64, end: f64) -> WhisperSegment {
+        WhisperSegment { start, end, text: text.to_string(), ..segment(Vec::new()) }
+    }
+    
+    fn word_texts(segments: &[WhisperSegment]) -> Vec<&str> {
+        segments.iter().flat_map(|s| s.words.iter().map(|w| w.text.as_str())).collect()
+    }
+    
+    #[test]
+    fn stitch_chunk_seam_joins_at_the_aligned_words() {
+        let mut segments = vec![segment(vec![
+            word("the", 26.0, 0.9), word("quick", 27.0, 0.9), word("brown", 28.2, 0.5), word("fox", 29.0, 0.9),
+        ])];
+        let next = vec![segment(vec![
+            word("Brown,", 28.3, 0.95), word("fox", 29.1, 0.6), word("jumps", 30.2, 0.9),
+        ])];
+        
+        assert_eq!(stitch_chunk_seam(&mut segments, next, 28.0, 30.0), 2);
+        // The better-scored copy of "brown" replaces the kept one
+        assert_eq!(word_texts(&segments), ["the", "quick", "Brown,", "fox", "jumps"]);
+        assert_eq!(segments[1].text, " jumps");
+    }
+    
+    #[test]
+    fn stitch_chunk_seam_cuts_unaligned_words_at_the_middle() {
+        let mut segments = vec![segment(vec![word("alpha", 27.0, 0.9), word("beta", 28.2, 0.9), word("gamma", 29.4, 0.9)])];
+        let next = vec![segment(vec![word("delta", 28.3, 0.9), word("epsilon", 29.6, 0.9), word("zeta", 30.5, 0.9)])];
+        
+        assert_eq!(stitch_chunk_seam(&mut segments, next, 28.0, 30.0), 2);
+        assert_eq!(word_texts(&segments), ["alpha", "beta", "epsilon", "zeta"]);
+        assert!((segments[0].end - 28.6).abs() < 1e-9);
+        assert!((segments[1].start - 29.6).abs() < 1e-9);
+    }
+    
+    #[test]
+    fn stitch_chunk_seam_keeps_wordless_segments_by_side() {
+        let mut segments = vec![wordless_segment(" kept", 20.0, 28.5), wordless_segment(" late", 28.5, 30.0)];
+        let next = vec![wordless_segment(" early", 28.2, 29.6), wordless_segment(" after", 29.6, 32.0)];
+        
+        assert_eq!(stitch_chunk_seam(&mut segments, next, 28.0, 30.0), 0);
+        let texts: Vec<&str> = segments.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, [" kept", " after"]);
+    }
+    
+    #[test]
+    fn stitch_chunk_seam_handles_empty_sides() {
+        let mut segments = Vec::new();
+        assert_eq!(stitch_chunk_seam(&mut segments, Vec::new(), 28.0, 30.0), 0);
+        assert!(segments.is_empty());
+        
+        let mut segments = vec![segment(vec![word("before", 10.0, 0.9)])];
+        assert_eq!(stitch_chunk_seam(&mut segments, Vec::new(), 28.0, 30.0), 0);
+        assert_eq!(word_texts(&segments), ["before"]);
+        
+        let mut segments = Vec::new();
+        let next = vec![segment(vec![word("after", 31.0, 0.9)])];
+        assert_eq!(stitch_chunk_seam(&mut segments, next, 28.0, 30.0), 0);
+        assert_eq!(word_texts(&segments), ["after"]);
+    }
+}
@@ -0,0 +1,249 @@
+// Live transcription over WebSocket: clients stream raw PCM16 mono 16kHz audio
+// as binary frames and receive partial segments for every ~5s window.
+
+use std::sync::Arc;
+use actix::prelude::*;
+use actix_web_actors::ws;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+use crate::queue::{GetLiveDecoder, TaskQueue};
+use crate::{transcribe_samples, DecodeThresholds, SegmentLimits, WhisperEngine, WhisperSegment, SAMPLE_RATE};
+
+// Audio transcribed per window
+const LIVE_WINDOW_SECONDS: f64 = 5.0;
+// Buffered audio beyond this is dropped (oldest first) when Whisper can't keep up
+const LIVE_MAX_BUFFER_SECONDS: f64 = 60.0;
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct LiveDecoderReady {
+    result: Result<(WhisperEngine, Arc<Semaphore>), String>,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct LiveWindowResult {
+    window_start: f64,
+    window_end: f64,
+    result: Result<Vec<WhisperSegment>, String>,
+}
+
+/// WebSocket session for `/ws/live`.
+///
+/// Binary frames are little-endian PCM16 mono at 16kHz. Each full window is
+/// transcribed in order and answered with a `partial` message; sending
+/// `{"flush": true}` transcribes whatever is buffered without waiting for a full window.
+/// Windows are decoded on the task queue's warm model, each holding one of its task
+/// slots, so live sessions and queued tasks share the same concurrency limit.
+pub struct LiveTranscriptionSession {
+    pub id: Uuid,
+    pub language: String,
+    queue: Addr<TaskQueue>,
+    // The queue's engine and task slots, once the queue has handed them over
+    decoder: Option<(WhisperEngine, Arc<Semaphore>)>,
+    buffer: Vec<f32>,
+    // Stream time in seconds of the first buffered sample
+    buffer_start: f64,
+    // Previous window's text, used as the prompt for the next window
+    previous_text: Option<String>,
+    in_flight: bool,
+    flush_requested: bool,
+    // Odd byte left over from the previous binary frame, completed by the next one
+    pending_byte: Option<u8>,
+}
+
+impl LiveTranscriptionSession {
+    pub fn new(language: String, queue: Addr<TaskQueue>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            language,
+            queue,
+            decoder: None,
+            buffer: Vec::new(),
+            buffer_start: 0.0,
+            previous_text: None,
+            in_flight: false,
+            flush_requested: false,
+            pending_byte: None,
+        }
+    }
+    
+    // Start transcribing the next window if the model is ready and nothing is in flight
+    fn process_next_window(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let (engine, task_slots) = match &self.decoder {
+            Some((engine, task_slots)) if !self.in_flight => (engine.clone(), Arc::clone(task_slots)),
+            _ => return,
+        };
+        
+        let window_samples = (LIVE_WINDOW_SECONDS * SAMPLE_RATE as f64) as usize;
+        let take = if self.buffer.len() >= window_samples {
+            window_samples
+        } else if self.flush_requested && !self.buffer.is_empty() {
+            self.buffer.len()
+        } else {
+            return;
+        };
+        
+        let window: Vec<f32> = self.buffer.drain(..take).collect();
+        let window_start = self.buffer_start;
+        let window_end = window_start + take as f64 / SAMPLE_RATE as f64;
+        self.buffer_start = window_end;
+        if self.buffer.is_empty() {
+            self.flush_requested = false;
+        }
+        self.in_flight = true;
+        
+        let language = self.language.clone();
+        let prompt = self.previous_text.clone();
+        let session_id = self.id.to_string();
+        let addr = ctx.address();
+        
+        tokio::spawn(async move {
+            // Wait for a free task slot like a queued task would, and hold it while decoding
+            let result = match task_slots.acquire_owned().await {
+                Ok(permit) => tokio::task::spawn_blocking(move || {
+                    let result = transcribe_samples(&engine.ctx, &window, &language, window_start, prompt.as_deref(), false, SegmentLimits::default(), DecodeThresholds::default(), Some(&session_id), |_: i32| {})
+                        .map_err(|e| e.to_string());
+                    drop(permit);
+                    result
+                })
+                .await
+                .unwrap_or_else(|e| Err(format!("Live transcription task failed: {}", e))),
+                Err(e) => Err(format!("Task slots are closed: {}", e)),
+            };
+            
+            addr.do_send(LiveWindowResult { window_start, window_end, result });
+        });
+    }
+}
+
+impl Actor for LiveTranscriptionSession {
+    type Context = ws::WebsocketContext<Self>;
+    
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let addr = ctx.address();
+        let queue = self.queue.clone();
+        let language = Some(self.language.clone());
+        
+        tokio::spawn(async move {
+            let result = queue.send(GetLiveDecoder { language })
+                .await
+                .unwrap_or_else(|e| Err(format!("Queue communication error: {}", e)));
+            addr.do_send(LiveDecoderReady { result });
+        });
+    }
+}
+
+impl Handler<LiveDecoderReady> for LiveTranscriptionSession {
+    type Result = ();
+    
+    fn handle(&mut self, msg: LiveDecoderReady, ctx: &mut Self::Context) {
+        match msg.result {
+            Ok(decoder) => {
+                self.decoder = Some(decoder);
+                ctx.text(serde_json::json!({
+                    "type": "ready",
+                    "session_id": self.id.to_string(),
+                    "language": self.language,
+                    "sample_rate": SAMPLE_RATE,
+                    "window_seconds": LIVE_WINDOW_SECONDS
+                }).to_string());
+                self.process_next_window(ctx);
+            }
+            Err(e) => {
+                println!("❌ Live session {} could not load a model: {}", self.id, e);
+                ctx.text(serde_json::json!({
+                    "type": "error",
+                    "error": e
+                }).to_string());
+                ctx.close(Some(ws::CloseReason::from(ws::CloseCode::Error)));
+                ctx.stop();
+            }
+        }
+    }
+}
+
+impl Handler<LiveWindowResult> for LiveTranscriptionSession {
+    type Result = ();
+    
+    fn handle(&mut self, msg: LiveWindowResult, ctx: &mut Self::Context) {
+        self.in_flight = false;
+        
+        match msg.result {
+            Ok(segments) => {
+                let text: String = segments.iter().map(|s| s.text.as_str()).collect();
+                if !text.trim().is_empty() {
+                    self.previous_text = Some(text.clone());
+                }
+                
+                ctx.text(serde_json::json!({
+                    "type": "partial",
+                    "window_start": msg.window_start,
+                    "window_end": msg.window_end,
+                    "text": text.trim(),
+                    "segments": segments
+                }).to_string());
+            }
+            Err(e) => {
+                ctx.text(serde_json::json!({
+                    "type": "error",
+                    "window_start": msg.window_start,
+                    "window_end": msg.window_end,
+                    "error": e
+                }).to_string());
+            }
+        }
+        
+        self.process_next_window(ctx);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for LiveTranscriptionSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Binary(bytes)) => {
+                // PCM16 little-endian; samples may be split across frames, so a trailing
+                // odd byte is kept and paired with the first byte of the next frame
+                let mut bytes = &bytes[..];
+                if let (Some(low), Some((&high, rest))) = (self.pending_byte, bytes.split_first()) {
+                    self.buffer.push(i16::from_le_bytes([low, high]) as f32 / 32768.0);
+                    self.pending_byte = None;
+                    bytes = rest;
+                }
+                let samples = bytes.chunks_exact(2);
+                if let [odd] = samples.remainder() {
+                    self.pending_byte = Some(*odd);
+                }
+                self.buffer.extend(samples.map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / 32768.0));
+                
+                let max_samples = (LIVE_MAX_BUFFER_SECONDS * SAMPLE_RATE as f64) as usize;
+                if self.buffer.len() > max_samples {
+                    let dropped = self.buffer.len() - max_samples;
+                    self.buffer.drain(..dropped);
+                    self.buffer_start += dropped as f64 / SAMPLE_RATE as f64;
+                    log::warn!("Live session {} fell behind, dropped {} samples", self.id, dropped);
+                }
+                
+                self.process_next_window(ctx);
+            }
+            Ok(ws::Message::Text(text)) => {
+                let request = match serde_json::from_str::<serde_json::Value>(&text) {
+                    Ok(value) => value,
+                    Err(_) => return,
+                };
+                
+                if request.get("flush").and_then(|v| v.as_bool()).unwrap_or(false) {
+                    self.flush_requested = true;
+                    self.process_next_window(ctx);
+                }
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
@@ -1,13 +1,14 @@
 use std::fs::{File, metadata};
 use std::path::Path;
 use std::io::Write;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use chrono::{DateTime, Utc};
-use clap::{Arg, Command};
+use clap::{Arg, ArgMatches, Command};
 use serde::{Deserialize, Serialize};
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 use rubato::{Resampler, SincFixedIn, SincInterpolationType, SincInterpolationParameters, WindowFunction};
-
-pub mod queue;
+use thai_transcriber::{apply_agc, compression_ratio, declip_samples, group_paragraphs, logprob_confidence, no_speech_prob, pass_quality, prompt_tokens, sanitize_samples, thai, channels_identical, check_channel_layout, to_mono, validate_clip_threshold, AgcGain, DecodeThresholds, Paragraph, ProgressCallback, SegmentLimits, TranscribeOptions, DEFAULT_CLIP_THRESHOLD, PARAGRAPH_GAP_SECONDS, TEMPERATURE_FALLBACK};
 
 #[cfg(feature = "full-audio-support")]
 use symphonia::core::audio::SampleBuffer;
@@ -25,12 +26,91 @@ use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 
 #[cfg(feature = "wav-support")]
-use hound::{WavReader, SampleFormat};
+use hound::{WavReader, WavSpec, WavWriter, SampleFormat};
+
+// With --jsonl, stdout only carries one JSON object per segment and status output moves to stderr
+static JSONL_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+// With --deterministic, every decode is greedy (best_of=1) at temperature 0 with no fallback
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+// Fixed thread count so float reductions happen in the same order on every machine
+const DETERMINISTIC_THREADS: i32 = 4;
+
+// With --suppress-nonspeech, the decoder can't emit non-speech tokens like [Music] or (applause)
+static SUPPRESS_NONSPEECH: AtomicBool = AtomicBool::new(false);
+
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if JSONL_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+// Id of the next --jsonl line, counted across chunks
+static JSONL_NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Print one segment as a JSON line on stdout
+fn emit_jsonl_segment(start: f64, end: f64, text: &str) {
+    let line = serde_json::json!({
+        "id": JSONL_NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        "start": start,
+        "end": end,
+        "text": text.trim()
+    });
+    println!("{}", line);
+}
+
+// --jsonl segments of one Whisper pass: printed straight away when the pass can't be
+// retried, otherwise held in `buffered` until `transcribe_with_debug` keeps the pass,
+// so a retried pass doesn't print its segments a second time
+struct JsonlPass<'a> {
+    // Offset of the audio within the recording, in seconds
+    offset: f64,
+    buffered: Option<&'a mut Vec<(f64, f64, String)>>,
+}
+
+// whisper.cpp new-segment callback for --jsonl: prints or buffers the `n_new` segments
+// just decoded. `user_data` points to the pass's `JsonlPass`.
+//
+// SAFETY: `user_data` must point to a `JsonlPass` that stays alive and unmoved, and isn't
+// otherwise accessed, until `state.full` returns, since whisper.cpp only calls this from
+// inside `whisper_full`. `run_debug_pass` passes its local `jsonl_pass`, which lives until
+// the end of that function.
+unsafe extern "C" fn jsonl_segment_callback(
+    _ctx: *mut whisper_rs_sys::whisper_context,
+    state: *mut whisper_rs_sys::whisper_state,
+    n_new: std::os::raw::c_int,
+    user_data: *mut std::os::raw::c_void,
+) {
+    let pass = &mut *(user_data as *mut JsonlPass);
+    let num_segments = whisper_rs_sys::whisper_full_n_segments_from_state(state);
+    for i in (num_segments - n_new).max(0)..num_segments {
+        let text = whisper_rs_sys::whisper_full_get_segment_text_from_state(state, i);
+        if text.is_null() {
+            continue;
+        }
+        let text = std::ffi::CStr::from_ptr(text).to_string_lossy();
+        // Timestamps are in centiseconds
+        let start = whisper_rs_sys::whisper_full_get_segment_t0_from_state(state, i) as f64 / 100.0 + pass.offset;
+        let end = whisper_rs_sys::whisper_full_get_segment_t1_from_state(state, i) as f64 / 100.0 + pass.offset;
+        match pass.buffered.as_deref_mut() {
+            Some(buffered) => buffered.push((start, end, text.into_owned())),
+            None => emit_jsonl_segment(start, end, &text),
+        }
+    }
+}
 
 // Constants for chunking
 const MAX_FILE_SIZE_MB: u64 = 100;
 const MAX_DURATION_MINUTES: f32 = 60.0;
 const CHUNK_DURATION_MINUTES: f32 = 5.0;
+
+// Video containers whose first audio track is extracted with Symphonia
+const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "mkv", "webm"];
 const SAMPLE_RATE: u32 = 16000;
 
 // Audio data with sample rate information
@@ -51,9 +131,391 @@ impl AudioData {
     }
 }
 
+// Subcommands of the single CLI; anything else as the first argument means `transcribe`
+const SUBCOMMANDS: &[&str] = &["transcribe", "analyze-risk", "serve", "convert", "bench", "help"];
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Library log lines go to stderr; RUST_LOG=debug adds per-step audio detail
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    
+    // Keep `transcribe <audio> <model> [options]` working without the subcommand name
+    let mut args: Vec<String> = std::env::args().collect();
+    if let Some(first) = args.get(1) {
+        if !SUBCOMMANDS.contains(&first.as_str()) && !matches!(first.as_str(), "-h" | "--help" | "-V" | "--version") {
+            args.insert(1, "transcribe".to_string());
+        }
+    }
+    
     let matches = Command::new("Thai Audio Transcriber")
         .version("0.1.0")
+        .about("Thai speech-to-text, risk analysis and the transcription API in one tool")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(transcribe_command())
+        .subcommand(
+            Command::new("analyze-risk")
+                .about("Classify text as risky or safe with the LlamaEdge server")
+                .arg(
+                    Arg::new("text")
+                        .help("Text to analyze, or - to read it from stdin")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("temperature")
+                        .long("temperature")
+                        .help("Sampling temperature for the chat completion")
+                        .value_parser(clap::value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new("max-tokens")
+                        .long("max-tokens")
+                        .help("Maximum tokens in the model's answer")
+                        .value_parser(clap::value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("model")
+                        .long("model")
+                        .help("Model name sent to the LlamaEdge server (defaults to the server's model)"),
+                )
+                .arg(
+                    Arg::new("endpoint")
+                        .long("endpoint")
+                        .help("LlamaEdge server URL; repeat to query several models at once (defaults to LLAMAEDGE_ENDPOINTS, then http://localhost:8080)")
+                        .action(clap::ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("voting")
+                        .long("voting")
+                        .help("How several models' verdicts are combined: any model flags it, a majority does, or all do")
+                        .value_parser(["any", "majority", "all"])
+                        .default_value("any"),
+                ),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("Start the queue-backed HTTP API server (api-server-new); remaining arguments are passed through")
+                .arg(
+                    Arg::new("args")
+                        .help("Arguments for the API server, e.g. --port 8000 --redis redis://localhost:6379")
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            Command::new("convert")
+                .about("Decode and resample audio to the 16kHz mono 16-bit WAV Whisper reads, without transcribing")
+                .arg(
+                    Arg::new("input")
+                        .help("Audio or video file to convert")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("WAV file to write")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::new("channel")
+                        .long("channel")
+                        .value_name("N")
+                        .help("Keep only channel N (0-based) instead of averaging all channels")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("raw-format")
+                        .long("raw-format")
+                        .value_name("RATE:BITS:CHANNELS[:le|be]")
+                        .help("Read the input as headerless PCM with this layout, e.g. 8000:16:1"),
+                ),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Transcribe one file on each backend and compare speed (RTF) and output text")
+                .arg(
+                    Arg::new("audio")
+                        .help("Audio file to transcribe")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("model")
+                        .help("Whisper model file (GGML .bin or .gguf)")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::new("language")
+                        .short('l')
+                        .long("language")
+                        .help("Language code for transcription")
+                        .default_value("th"),
+                )
+                .arg(
+                    Arg::new("backends")
+                        .long("backends")
+                        .value_name("LIST")
+                        .help("Comma-separated backends to run, in order; the first one is the reference for text diffs")
+                        .default_value("cpu,gpu,coreml"),
+                )
+                .arg(
+                    Arg::new("deterministic")
+                        .long("deterministic")
+                        .help("Decode at temperature 0 without fallback so text differences come from the backend, not sampling")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .get_matches_from(args);
+    
+    match matches.subcommand() {
+        Some(("transcribe", sub_matches)) => run_transcribe(sub_matches),
+        Some(("analyze-risk", sub_matches)) => run_analyze_risk(sub_matches),
+        Some(("serve", sub_matches)) => run_serve(sub_matches),
+        Some(("convert", sub_matches)) => run_convert(sub_matches),
+        Some(("bench", sub_matches)) => run_bench(sub_matches),
+        _ => unreachable!("clap requires a subcommand"),
+    }
+}
+
+// `analyze-risk <text>`: print analyze_risk's JSON verdict
+fn run_analyze_risk(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let text = matches.get_one::<String>("text").unwrap();
+    let text = if text == "-" {
+        let mut input = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+        input
+    } else {
+        text.clone()
+    };
+    
+    let defaults = thai_transcriber::RiskConfig::default();
+    let config = thai_transcriber::RiskConfig {
+        temperature: matches.get_one::<f64>("temperature").copied().unwrap_or(defaults.temperature),
+        max_tokens: matches.get_one::<u32>("max-tokens").copied().unwrap_or(defaults.max_tokens),
+        model: matches.get_one::<String>("model").cloned(),
+        endpoints: matches.get_many::<String>("endpoint").map(|urls| urls.cloned().collect()).unwrap_or_default(),
+        voting: thai_transcriber::RiskVoting::parse(matches.get_one::<String>("voting").unwrap()).unwrap_or_default(),
+        ..defaults
+    };
+    
+    let result = tokio::runtime::Runtime::new()?
+        .block_on(thai_transcriber::analyze_risk(text.trim(), &config))?;
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    
+    Ok(())
+}
+
+// `convert <input> <output>`: cache the decoded audio so later runs skip decoding
+fn run_convert(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let input = matches.get_one::<String>("input").unwrap();
+    let output = matches.get_one::<String>("output").unwrap();
+    let channel = matches.get_one::<usize>("channel").copied();
+    if let Some(spec) = matches.get_one::<String>("raw-format") {
+        let _ = RAW_FORMAT.set(RawPcmFormat::parse(spec)?);
+    }
+    
+    let sample_count = convert_to_whisper_wav(input, output, channel)?;
+    status!("✅ Wrote {} ({:.2} seconds at {}Hz mono)", output, sample_count as f64 / SAMPLE_RATE as f64, SAMPLE_RATE);
+    Ok(())
+}
+
+/// Decode `input` with the transcription pipeline (`load_audio_file_advanced` +
+/// `resample_audio`) and write it to `output` as 16kHz mono 16-bit PCM WAV.
+/// Returns the number of samples written.
+pub fn convert_to_whisper_wav(input: &str, output: &str, channel: Option<usize>) -> Result<usize, Box<dyn std::error::Error>> {
+    if !Path::new(input).exists() {
+        return Err(format!("Audio file '{}' not found", input).into());
+    }
+    
+    status!("🔄 Converting {} → {}", input, output);
+    let audio_data = load_audio_file_advanced(input, channel)?;
+    let samples = resample_audio(audio_data.samples, audio_data.sample_rate, SAMPLE_RATE)?;
+    
+    write_pcm16_wav(&samples, output)?;
+    Ok(samples.len())
+}
+
+// Outcome of one backend in `bench`
+struct BenchRun {
+    backend: String,
+    load_seconds: f64,
+    processing_seconds: f64,
+    text: String,
+}
+
+// `bench <audio> <model>`: decode once, then load the model and transcribe on each
+// backend in turn, reporting load time, processing time and RTF, and how each
+// backend's text differs from the first backend's
+fn run_bench(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let audio_path = matches.get_one::<String>("audio").unwrap();
+    let model_path = matches.get_one::<String>("model").unwrap();
+    let language = matches.get_one::<String>("language").unwrap();
+    DETERMINISTIC.store(matches.get_flag("deterministic"), Ordering::Relaxed);
+    
+    let backends: Vec<String> = matches.get_one::<String>("backends").unwrap()
+        .split(',')
+        .map(|backend| backend.trim().to_lowercase())
+        .filter(|backend| !backend.is_empty())
+        .collect();
+    if let Some(unknown) = backends.iter().find(|backend| !matches!(backend.as_str(), "cpu" | "gpu" | "coreml")) {
+        return Err(format!("Unknown backend '{}' (expected cpu, gpu or coreml)", unknown).into());
+    }
+    
+    validate_files(audio_path, model_path)?;
+    
+    let (audio_data, _, _) = load_audio_file_with_debug(audio_path, false, DEFAULT_CLIP_THRESHOLD, None, None)?;
+    let audio_seconds = audio_data.len() as f64 / SAMPLE_RATE as f64;
+    status!("⏱️  Benchmarking {} ({:.1}s of audio) on: {}", audio_path, audio_seconds, backends.join(", "));
+    
+    let mut runs: Vec<BenchRun> = Vec::new();
+    for backend in &backends {
+        // whisper.cpp only uses Core ML when the compiled encoder sits next to the model
+        if backend == "coreml" {
+            let encoder = coreml_encoder_path(model_path);
+            if !cfg!(target_os = "macos") || !Path::new(&encoder).exists() {
+                status!("⏭️  Skipping coreml: needs macOS and {}", encoder);
+                continue;
+            }
+        }
+        
+        status!("\n🏁 Backend: {}", backend);
+        let load_start = std::time::Instant::now();
+        let ctx = match initialize_whisper_with_debug(model_path, language, backend == "gpu", backend == "coreml") {
+            Ok(ctx) => ctx,
+            Err(e) => {
+                eprintln!("❌ {} failed to load the model: {}", backend, e);
+                continue;
+            }
+        };
+        let load_seconds = load_start.elapsed().as_secs_f64();
+        
+        let processing_start = std::time::Instant::now();
+        let segments = match transcribe_with_debug(&ctx, audio_data.clone(), language, &TranscribeOptions::default(), None, None) {
+            Ok(segments) => segments,
+            Err(e) => {
+                eprintln!("❌ {} failed to transcribe: {}", backend, e);
+                continue;
+            }
+        };
+        let processing_seconds = processing_start.elapsed().as_secs_f64();
+        
+        let text: String = segments.iter().map(|segment| segment.text.as_str()).collect();
+        runs.push(BenchRun {
+            backend: backend.clone(),
+            load_seconds,
+            processing_seconds,
+            text: text.trim().to_string(),
+        });
+    }
+    
+    if runs.is_empty() {
+        return Err("No backend completed the benchmark".into());
+    }
+    
+    println!("\n📊 Benchmark: {} ({:.1}s of audio, language {})", audio_path, audio_seconds, language);
+    println!("{:<8} {:>10} {:>14} {:>8} {:>8}", "backend", "load (s)", "process (s)", "RTF", "speed");
+    for run in &runs {
+        let rtf = run.processing_seconds / audio_seconds;
+        println!("{:<8} {:>10.2} {:>14.2} {:>8.3} {:>7.1}x", run.backend, run.load_seconds, run.processing_seconds, rtf, 1.0 / rtf);
+    }
+    
+    let reference = &runs[0];
+    for run in &runs[1..] {
+        let diff = word_diff(&reference.text, &run.text);
+        if diff.is_empty() {
+            println!("\n✅ {} text is identical to {}", run.backend, reference.backend);
+            continue;
+        }
+        
+        let reference_words = thai::segment_words(&reference.text).len().max(1);
+        println!("\n⚠️  {} text differs from {} ({} of {} words changed):", run.backend, reference.backend,
+                 diff.iter().filter(|(sign, _)| *sign == '-').count(), reference_words);
+        for (sign, word) in diff.iter().take(BENCH_DIFF_LINES) {
+            println!("   {} {}", sign, word);
+        }
+        if diff.len() > BENCH_DIFF_LINES {
+            println!("   ... {} more", diff.len() - BENCH_DIFF_LINES);
+        }
+    }
+    
+    Ok(())
+}
+
+// Most word changes `bench` prints per backend
+const BENCH_DIFF_LINES: usize = 40;
+
+// `ggml-large-v3.bin` -> `ggml-large-v3-encoder.mlmodelc`, where whisper.cpp looks for the Core ML encoder
+fn coreml_encoder_path(model_path: &str) -> String {
+    let path = Path::new(model_path);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(model_path);
+    path.with_file_name(format!("{}-encoder.mlmodelc", stem)).to_string_lossy().to_string()
+}
+
+// Word-level diff (Thai split with thai::segment_words): ('-', word) for words only
+// in `a`, ('+', word) for words only in `b`, in text order. Empty when they match.
+fn word_diff(a: &str, b: &str) -> Vec<(char, String)> {
+    let a = thai::segment_words(a);
+    let b = thai::segment_words(b);
+    
+    // lcs[i][j] = longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            diff.push(('+', b[j].clone()));
+            j += 1;
+        } else {
+            diff.push(('-', a[i].clone()));
+            i += 1;
+        }
+    }
+    diff
+}
+
+// `serve [args]`: run the api-server-new binary installed next to this one
+fn run_serve(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let server_args: Vec<&String> = matches.get_many::<String>("args").map(|args| args.collect()).unwrap_or_default();
+    
+    let server_path = std::env::current_exe()?
+        .with_file_name(format!("api-server-new{}", std::env::consts::EXE_SUFFIX));
+    if !server_path.exists() {
+        return Err(format!(
+            "API server binary not found at {} (build it with: cargo build --release --bin api-server-new)",
+            server_path.display()
+        ).into());
+    }
+    
+    status!("🚀 Starting API server: {}", server_path.display());
+    let exit_status = std::process::Command::new(&server_path)
+        .args(server_args)
+        .status()?;
+    
+    if !exit_status.success() {
+        return Err(format!("API server exited with {}", exit_status).into());
+    }
+    Ok(())
+}
+
+// Arguments of `transcribe`, the original single-command interface
+fn transcribe_command() -> Command {
+    Command::new("transcribe")
         .about("Speech-to-text transcription using whisper-rs with robust audio loading and chunked processing")
         .arg(
             Arg::new("audio")
@@ -94,11 +556,285 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("Enable Core ML acceleration (for .mlmodelc models on Apple Silicon)")
                 .action(clap::ArgAction::SetTrue),
         )
-        .get_matches();
+        .arg(
+            Arg::new("prompt")
+                .long("prompt")
+                .help("Initial prompt to bias vocabulary (names, jargon); later chunks use the previous chunk's text"),
+        )
+        .arg(
+            Arg::new("translate")
+                .long("translate")
+                .help("Translate the audio to English instead of transcribing it in the source language")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("declip")
+                .long("declip")
+                .help("Rebuild clipped peaks with cubic interpolation before transcribing")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("clip-threshold")
+                .long("clip-threshold")
+                .help("Absolute amplitude treated as clipped, in (0, 1]")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("0.99"),
+        )
+        .arg(
+            Arg::new("agc-target-dbfs")
+                .long("agc-target-dbfs")
+                .value_name("dBFS")
+                .help("Scale the audio towards this RMS level (e.g. -20) without letting peaks clip")
+                .value_parser(clap::value_parser!(f32))
+                .allow_negative_numbers(true),
+        )
+        .arg(
+            Arg::new("channel")
+                .long("channel")
+                .value_name("N")
+                .help("Transcribe only channel N (0-based) of multichannel audio instead of averaging all channels")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("raw-format")
+                .long("raw-format")
+                .value_name("RATE:BITS:CHANNELS[:le|be]")
+                .help("Read the audio as headerless PCM with this layout, e.g. 8000:16:1 or 44100:24:2:be"),
+        )
+        .arg(
+            Arg::new("output-dir")
+                .long("output-dir")
+                .value_name("dir")
+                .help("Directory for result.json and the log/transcription files (created if missing, default: current directory)"),
+        )
+        .arg(
+            Arg::new("compact-json")
+                .long("compact-json")
+                .help("Write result.json on a single line instead of pretty-printed (much smaller with word timestamps)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("dump-audio")
+                .long("dump-audio")
+                .value_name("path.wav")
+                .help("Write the exact 16kHz mono audio passed to Whisper to a WAV file (one file per chunk when chunking)"),
+        )
+        .arg(
+            Arg::new("validate-only")
+                .long("validate-only")
+                .help("Check that the audio decodes and the model loads, report audio stats, and exit without transcribing")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("suppress-nonspeech")
+                .long("suppress-nonspeech")
+                .help("Stop Whisper from emitting non-speech tokens such as [Music] or (applause)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("deterministic")
+                .long("deterministic")
+                .help("Reproducible output: temperature 0, no temperature fallback, fixed thread count")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min-confidence")
+                .long("min-confidence")
+                .help("Drop segments whose confidence, exp(avg_logprob), is below this value (e.g. 0.5)")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("max-segment-len")
+                .long("max-segment-len")
+                .help("Split segments longer than this many characters at word boundaries (e.g. 42 for subtitles)")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("max-tokens")
+                .long("max-tokens")
+                .help("Maximum number of text tokens per segment")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("no-speech-threshold")
+                .long("no-speech-threshold")
+                .help("Treat a segment as silence when its no-speech probability is above this (and its log probability is low)")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("0.6"),
+        )
+        .arg(
+            Arg::new("logprob-threshold")
+                .long("logprob-threshold")
+                .help("Re-decode the whole pass (each chunk when chunking) at a higher temperature when the average token log probability is below this")
+                .value_parser(clap::value_parser!(f32))
+                .allow_negative_numbers(true)
+                .default_value("-1.0"),
+        )
+        .arg(
+            Arg::new("entropy-threshold")
+                .long("entropy-threshold")
+                .help("Token entropy threshold passed to whisper.cpp, which retries a decode less varied than this")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("2.4"),
+        )
+        .arg(
+            Arg::new("compression-ratio-threshold")
+                .long("compression-ratio-threshold")
+                .help("Re-decode the whole pass (each chunk when chunking) at a higher temperature when the compression ratio is above this (catches repetition loops)")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("2.4"),
+        )
+        .arg(
+            Arg::new("max-segments")
+                .long("max-segments")
+                .help("Stop collecting segments past this many and flag the result as truncated")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("10000"),
+        )
+        .arg(
+            Arg::new("word-confidence-threshold")
+                .long("word-confidence-threshold")
+                .help("Leave words below this confidence out of the words array; segment text is unchanged")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.0"),
+        )
+        .arg(
+            Arg::new("max-repeat")
+                .long("max-repeat")
+                .help("Drop hallucinated segments that repeat a phrase, or the previous segment, more than this many times")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("speaker-gap")
+                .long("speaker-gap")
+                .help("Add a placeholder speaker label to result.json, starting a new speaker after silences longer than this many seconds")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("paragraphs")
+                .long("paragraphs")
+                .help("Add paragraphs to result.json: segments grouped at long silences and sentence ends")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("merge-segments")
+                .long("merge-segments")
+                .help("Merge consecutive segments separated by short silences into longer, more readable ones")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("offset")
+                .long("offset")
+                .value_name("seconds")
+                .help("Add this many seconds to every segment and word timestamp (for clips cut from a longer recording)")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("silent-chunk-rms")
+                .long("silent-chunk-rms")
+                .value_name("rms")
+                .help("In chunked mode, skip chunks whose RMS amplitude is below this without running Whisper (0 disables)")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("0.001"),
+        )
+        .arg(
+            Arg::new("max-duration")
+                .long("max-duration")
+                .value_name("minutes")
+                .help("Refuse audio longer than this many minutes instead of transcribing it for hours")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("180"),
+        )
+        .arg(
+            Arg::new("merge-gap")
+                .long("merge-gap")
+                .help("Largest silence in seconds bridged by --merge-segments")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.5"),
+        )
+        .arg(
+            Arg::new("merge-max-chars")
+                .long("merge-max-chars")
+                .help("Longest merged segment text in characters for --merge-segments")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("120"),
+        )
+        .arg(
+            Arg::new("auto-download")
+                .long("auto-download")
+                .help("Download the model into model/ if it doesn't exist (e.g. model/ggml-base.bin)")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("model-base-url")
+                .long("model-base-url")
+                .help("Base URL GGML models are downloaded from with --auto-download")
+                .default_value(thai_transcriber::DEFAULT_MODEL_BASE_URL),
+        )
+        .arg(
+            Arg::new("jsonl")
+                .long("jsonl")
+                .help("Print one JSON object per segment to stdout as it is decoded, before --min-confidence and --max-repeat filtering (status output goes to stderr); while a pass may still be retried at a higher temperature its segments are held back and only printed if that pass is kept")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+// `transcribe <audio> <model> [options]`: the original CLI behaviour
+fn run_transcribe(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    JSONL_OUTPUT.store(matches.get_flag("jsonl"), Ordering::Relaxed);
+    DETERMINISTIC.store(matches.get_flag("deterministic"), Ordering::Relaxed);
+    SUPPRESS_NONSPEECH.store(matches.get_flag("suppress-nonspeech"), Ordering::Relaxed);
+    let decode_thresholds = DecodeThresholds {
+        no_speech: *matches.get_one::<f32>("no-speech-threshold").unwrap(),
+        logprob: *matches.get_one::<f32>("logprob-threshold").unwrap(),
+        entropy: *matches.get_one::<f32>("entropy-threshold").unwrap(),
+        compression_ratio: *matches.get_one::<f32>("compression-ratio-threshold").unwrap(),
+    };
+    decode_thresholds.validate()?;
+    validate_clip_threshold(*matches.get_one::<f32>("clip-threshold").unwrap())?;
+    if let Some(spec) = matches.get_one::<String>("raw-format") {
+        let _ = RAW_FORMAT.set(RawPcmFormat::parse(spec)?);
+    }
 
     let audio_path = matches.get_one::<String>("audio").unwrap();
     let model_path = matches.get_one::<String>("model").unwrap();
     let language = matches.get_one::<String>("language").unwrap();
+    let output_dir = Path::new(matches.get_one::<String>("output-dir").map(|s| s.as_str()).unwrap_or("."));
+    let compact_json = matches.get_flag("compact-json");
+    let silent_chunk_rms = *matches.get_one::<f32>("silent-chunk-rms").unwrap();
+    let max_duration_minutes = *matches.get_one::<f64>("max-duration").unwrap();
+    let options = TranscribeOptions {
+        initial_prompt: matches.get_one::<String>("prompt").cloned(),
+        translate: matches.get_flag("translate"),
+        declip: matches.get_flag("declip"),
+        clip_threshold: matches.get_one::<f32>("clip-threshold").copied(),
+        min_confidence: matches.get_one::<f64>("min-confidence").copied(),
+        speaker_gap: matches.get_one::<f64>("speaker-gap").copied(),
+        offset_seconds: *matches.get_one::<f64>("offset").unwrap(),
+        max_total_duration_minutes: Some(max_duration_minutes),
+        max_repeat: matches.get_one::<u64>("max-repeat").map(|&n| n as usize),
+        segment_limits: SegmentLimits {
+            max_len: matches.get_one::<u64>("max-segment-len").map(|&n| n as usize),
+            max_tokens: matches.get_one::<u64>("max-tokens").map(|&n| n as usize),
+            max_segments: matches.get_one::<u64>("max-segments").map(|&n| n as usize),
+        },
+        decode_thresholds,
+        word_confidence_threshold: *matches.get_one::<f64>("word-confidence-threshold").unwrap(),
+        agc_target_dbfs: matches.get_one::<f32>("agc-target-dbfs").copied(),
+        channel: matches.get_one::<usize>("channel").copied(),
+        paragraphs: matches.get_flag("paragraphs"),
+        ..Default::default()
+    };
+    let clip_threshold = options.clip_threshold.unwrap_or(DEFAULT_CLIP_THRESHOLD);
+    let dump_audio = matches.get_one::<String>("dump-audio").map(|s| s.as_str());
+    let merge_segments = matches.get_flag("merge-segments");
+    let merge_gap = *matches.get_one::<f64>("merge-gap").unwrap();
+    let merge_max_chars = *matches.get_one::<usize>("merge-max-chars").unwrap();
+    
+    // Fail before transcribing rather than after if the output directory can't be created
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create output directory '{}': {}", output_dir.display(), e))?;
     
     // Determine backend usage
     let use_coreml = matches.get_flag("coreml");
@@ -117,31 +853,68 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let use_coreml_final = use_coreml || is_coreml_model;
 
     if use_coreml_final {
-        println!("🍎 Core ML acceleration enabled for Apple Neural Engine");
+        status!("🍎 Core ML acceleration enabled for Apple Neural Engine");
     } else if use_gpu {
-        println!("⚡ GPU (Metal) acceleration enabled - this may cause buffer overlap errors");
+        status!("⚡ GPU (Metal) acceleration enabled - this may cause buffer overlap errors");
     } else {
-        println!("🖥️  CPU-only mode enabled for maximum stability");
+        status!("🖥️  CPU-only mode enabled for maximum stability");
     }
 
+    // Fetch a missing model into model/ when allowed
+    let model_path = if matches.get_flag("auto-download") && !Path::new(model_path).exists() {
+        thai_transcriber::enable_model_auto_download(matches.get_one::<String>("model-base-url").unwrap());
+        let model_name = Path::new(model_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(model_path.as_str());
+        status!("⬇️  {} is missing, downloading {} into model/", model_path, model_name);
+        tokio::runtime::Runtime::new()?.block_on(thai_transcriber::ensure_model(model_name))?
+    } else {
+        model_path.to_string()
+    };
+    let model_path = model_path.as_str();
+
     // Validate inputs
     validate_files(audio_path, model_path)?;
+    
+    // Refuse overlong recordings up front when the header tells us the duration
+    // (raw PCM has no header to trust)
+    let probed_duration = match RAW_FORMAT.get() {
+        Some(_) => None,
+        None => thai_transcriber::probe_duration_seconds(audio_path),
+    };
+    if let Some(duration_seconds) = probed_duration {
+        let duration_minutes = duration_seconds / 60.0;
+        if duration_minutes > max_duration_minutes {
+            return Err(format!(
+                "{} is {:.1} minutes long, more than --max-duration {} minutes", audio_path, duration_minutes, max_duration_minutes
+            ).into());
+        }
+    }
+    
+    if matches.get_flag("validate-only") {
+        return run_validation(audio_path, model_path, language, use_gpu, use_coreml_final, clip_threshold, options.channel);
+    }
 
     // Run manual audio file test first
-    println!("🔍 Running preliminary audio file test...");
+    status!("🔍 Running preliminary audio file test...");
     if let Err(e) = test_audio_file_manually(audio_path) {
         eprintln!("⚠️  Audio file test failed: {}", e);
     }
 
     // Initialize logger
     let mut logger = Logger::new(audio_path, language);
+    logger.set_speaker_gap(options.speaker_gap);
+    logger.set_paragraphs(options.paragraphs);
+    logger.set_word_confidence_threshold(options.word_confidence_threshold);
+    logger.set_model(model_path);
 
-    println!("🔄 Loading Whisper model with debugging...");
+    status!("🔄 Loading Whisper model with debugging...");
     
     // Initialize Whisper model with debugging and backend settings
     let ctx = initialize_whisper_with_debug(model_path, language, use_gpu, use_coreml_final)?;
 
-    println!("🎵 Loading and processing audio file with debugging: {}", audio_path);
+    status!("🎵 Loading and processing audio file with debugging: {}", audio_path);
     
     // Check if file needs chunking
     let should_chunk = should_chunk_audio(audio_path)?;
@@ -153,27 +926,61 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     logger.set_file_info(file_size_mb, estimated_duration);
     
     if should_chunk {
-        println!("📂 Large audio file detected - will process in 5-minute chunks");
+        status!("📂 Large audio file detected - will process in 5-minute chunks");
         logger.set_processing_mode("chunked", None);
-        let segments = transcribe_with_chunking(&ctx, audio_path, language)?;
+        let chunked = transcribe_with_chunking(&ctx, audio_path, language, &options, silent_chunk_rms, dump_audio, None)?;
+        let mut segments = chunked.segments;
+        if merge_segments {
+            merge_short_chunked_segments(&mut segments, merge_gap, merge_max_chars);
+        }
         logger.set_processing_mode("chunked", Some(segments.len()));
+        logger.set_declip_info(options.declip, chunked.declipped_samples);
+        logger.set_agc(options.agc_target_dbfs, chunked.agc_gain);
+        logger.set_filtered_segments(options.min_confidence, chunked.filtered_segments);
+        logger.set_suppressed_hallucinations(options.max_repeat, chunked.suppressed_hallucinations);
+        logger.set_truncated(options.segment_limits.max_segments, chunked.truncated);
         logger.add_segments_from_chunked(&segments);
-        display_chunked_transcription_results(&segments)?;
+        if !JSONL_OUTPUT.load(Ordering::Relaxed) {
+            display_chunked_transcription_results(&segments)?;
+        }
     } else {
-        println!("📁 Processing audio file as single segment with debugging");
+        status!("📁 Processing audio file as single segment with debugging");
         logger.set_processing_mode("single", None);
         
         // Load and convert audio with debugging
-        let audio_data = load_audio_file_with_debug(audio_path)?;
+        let (audio_data, declipped, agc_gain) = load_audio_file_with_debug(audio_path, options.declip, clip_threshold, options.agc_target_dbfs, options.channel)?;
+        logger.set_declip_info(options.declip, declipped);
+        logger.set_agc(options.agc_target_dbfs, agc_gain);
         
-        println!("🗣️  Transcribing audio with debugging (Language: {})...", language);
+        status!("🗣️  Transcribing audio with debugging (Language: {})...", language);
         
         // Run transcription using enhanced debugging
-        let segments = transcribe_with_debug(&ctx, audio_data, language)?;
+        let mut segments = transcribe_with_debug(&ctx, audio_data, language, &options, dump_audio, None)?;
+        let truncated = options.segment_limits.truncate(&mut segments);
+        if truncated {
+            status!("✂️ Transcription truncated to {} segments", segments.len());
+        }
+        logger.set_truncated(options.segment_limits.max_segments, truncated);
+        offset_segments(&mut segments, options.offset_seconds);
+        let suppressed = match options.max_repeat {
+            Some(max_repeat) => suppress_repetitions(&mut segments, max_repeat),
+            None => 0,
+        };
+        logger.set_suppressed_hallucinations(options.max_repeat, suppressed);
+        let filtered = match options.min_confidence {
+            Some(min_confidence) => filter_low_confidence(&mut segments, min_confidence),
+            None => 0,
+        };
+        logger.set_filtered_segments(options.min_confidence, filtered);
+        if merge_segments {
+            merge_short_segments(&mut segments, merge_gap, merge_max_chars);
+        }
 
-        // Update logger and display results
+        // Update logger and display results; --jsonl lines were already printed while decoding
         logger.add_segments_from_whisper_rs(&segments);
-        display_transcription_results_from_segments(&segments)?;
+        if !JSONL_OUTPUT.load(Ordering::Relaxed) {
+            display_transcription_results_from_segments(&segments)?;
+        }
     }
 
     // Save logs
@@ -184,13 +991,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap_or("transcription");
     
     // Save to result.json (main output)
-    if let Err(e) = logger.save_result_json() {
-        eprintln!("⚠️  Failed to save result.json: {}", e);
+    let result_path = output_dir.join("result.json").to_string_lossy().to_string();
+    if let Err(e) = logger.save_result_json(&result_path, !compact_json) {
+        eprintln!("⚠️  Failed to save {}: {}", result_path, e);
     }
     
     // Also save timestamped logs for record keeping
-    let json_log_path = format!("{}_{}_log.json", base_name, timestamp);
-    let text_log_path = format!("{}_{}_transcription.txt", base_name, timestamp);
+    let json_log_path = output_dir.join(format!("{}_{}_log.json", base_name, timestamp)).to_string_lossy().to_string();
+    let text_log_path = output_dir.join(format!("{}_{}_transcription.txt", base_name, timestamp)).to_string_lossy().to_string();
+    let markdown_path = output_dir.join(format!("{}_{}_transcription.md", base_name, timestamp)).to_string_lossy().to_string();
     
     if let Err(e) = logger.save_to_file(&json_log_path) {
         eprintln!("⚠️  Failed to save JSON log: {}", e);
@@ -199,10 +1008,70 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     if let Err(e) = logger.save_text_summary(&text_log_path) {
         eprintln!("⚠️  Failed to save text summary: {}", e);
     }
+    
+    if let Err(e) = logger.save_markdown(&markdown_path) {
+        eprintln!("⚠️  Failed to save Markdown transcript: {}", e);
+    }
 
     Ok(())
 }
 
+// --validate-only: load the audio and the model and report what a transcription
+// would do, without running one. Any failure becomes a non-zero exit.
+fn run_validation(
+    audio_path: &str,
+    model_path: &str,
+    language: &str,
+    use_gpu: bool,
+    use_coreml: bool,
+    clip_threshold: f32,
+    channel: Option<usize>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    status!("🧪 Validate-only mode: checking audio and model without transcribing");
+    
+    let (samples, _, _) = load_audio_file_with_debug(audio_path, false, clip_threshold, None, channel)?;
+    let max_amplitude = samples.iter().fold(0.0f32, |max, &x| max.max(x.abs()));
+    if samples.is_empty() || max_amplitude < 0.001 {
+        return Err(format!("{} contains no audible signal (peak amplitude {:.6})", audio_path, max_amplitude).into());
+    }
+    let rms = (samples.iter().map(|&x| x * x).sum::<f32>() / samples.len() as f32).sqrt();
+    let clipped_samples = samples.iter().filter(|&&x| x.abs() >= clip_threshold).count();
+    let duration_seconds = samples.len() as f64 / SAMPLE_RATE as f64;
+    
+    let would_chunk = should_chunk_audio(audio_path)?;
+    let chunks = if would_chunk {
+        (duration_seconds / (CHUNK_DURATION_MINUTES as f64 * 60.0)).ceil() as usize
+    } else {
+        1
+    };
+    
+    initialize_whisper_with_debug(model_path, language, use_gpu, use_coreml)?;
+    
+    status!("✅ Validation passed:");
+    status!("   - Duration: {:.2} seconds", duration_seconds);
+    status!("   - Peak amplitude: {:.6}", max_amplitude);
+    status!("   - RMS amplitude: {:.6}", rms);
+    status!("   - Clipped samples: {}", clipped_samples);
+    status!("   - Chunked: {} ({} chunk(s))", if would_chunk { "yes" } else { "no" }, chunks);
+    status!("   - Model: {}", model_path);
+    
+    if JSONL_OUTPUT.load(Ordering::Relaxed) {
+        println!("{}", serde_json::json!({
+            "valid": true,
+            "audio_file": audio_path,
+            "duration_seconds": duration_seconds,
+            "peak_amplitude": max_amplitude,
+            "rms_amplitude": rms,
+            "clipped_samples": clipped_samples,
+            "chunked": would_chunk,
+            "chunks": chunks,
+            "model": model_path
+        }));
+    }
+    
+    Ok(())
+}
+
 fn validate_files(audio_path: &str, model_path: &str) -> Result<(), Box<dyn std::error::Error>> {
     if !Path::new(audio_path).exists() {
         return Err(format!("Audio file '{}' not found", audio_path).into());
@@ -220,18 +1089,18 @@ pub fn should_chunk_audio(audio_path: &str) -> Result<bool, Box<dyn std::error::
     let file_metadata = metadata(audio_path)?;
     let file_size_mb = file_metadata.len() / (1024 * 1024);
     
-    println!("📊 File size: {:.2} MB", file_size_mb as f64);
+    status!("📊 File size: {:.2} MB", file_size_mb as f64);
     
     if file_size_mb > MAX_FILE_SIZE_MB {
-        println!("⚠️  File size ({} MB) exceeds {} MB limit", file_size_mb, MAX_FILE_SIZE_MB);
+        status!("⚠️  File size ({} MB) exceeds {} MB limit", file_size_mb, MAX_FILE_SIZE_MB);
         return Ok(true);
     }
     
     // Check duration (if we can determine it)
     if let Ok(duration) = estimate_audio_duration(audio_path) {
-        println!("📊 Estimated duration: {:.2} minutes", duration);
+        status!("📊 Estimated duration: {:.2} minutes", duration);
         if duration > MAX_DURATION_MINUTES {
-            println!("⚠️  Duration ({:.2} min) exceeds {} min limit", duration, MAX_DURATION_MINUTES);
+            status!("⚠️  Duration ({:.2} min) exceeds {} min limit", duration, MAX_DURATION_MINUTES);
             return Ok(true);
         }
     }
@@ -270,65 +1139,163 @@ pub fn transcribe_with_chunking(
     ctx: &WhisperContext,
     audio_path: &str,
     language: &str,
-) -> Result<Vec<TranscriptionSegment>, Box<dyn std::error::Error>> {
-    println!("🔄 Loading full audio file for chunking...");
-    let audio_data = load_audio_file_advanced(audio_path)?;
+    options: &TranscribeOptions,
+    silent_chunk_rms: f32,
+    dump_audio: Option<&str>,
+    progress: Option<ProgressCallback>,
+) -> Result<ChunkedTranscription, Box<dyn std::error::Error>> {
+    status!("🔄 Loading full audio file for chunking...");
+    let mut audio_data = load_audio_file_advanced(audio_path, options.channel)?;
+    let declipped = repair_clipping(&mut audio_data.samples, options.declip, options.clip_threshold.unwrap_or(DEFAULT_CLIP_THRESHOLD));
     
     // Resample to 16kHz if necessary
-    let full_audio_samples = if audio_data.sample_rate != SAMPLE_RATE {
-        println!("🔄 Resampling for chunking: {}Hz → {}Hz", audio_data.sample_rate, SAMPLE_RATE);
+    let mut full_audio_samples = if audio_data.sample_rate != SAMPLE_RATE {
+        status!("🔄 Resampling for chunking: {}Hz → {}Hz", audio_data.sample_rate, SAMPLE_RATE);
         resample_audio(audio_data.samples, audio_data.sample_rate, SAMPLE_RATE)?
     } else {
         audio_data.samples
     };
     
+    // One gain for the whole recording, so chunks keep their relative loudness
+    let agc_gain = options.agc_target_dbfs.map(|target_dbfs| {
+        let gain = apply_agc(&mut full_audio_samples, target_dbfs);
+        status!("🎚️ AGC: {:+.1} dB towards {:.1} dBFS RMS{}", gain.gain_db, target_dbfs, if gain.peak_limited { " (limited by peak)" } else { "" });
+        gain
+    });
+    
     let samples_per_chunk = (CHUNK_DURATION_MINUTES * 60.0 * SAMPLE_RATE as f32) as usize;
     let total_chunks = (full_audio_samples.len() + samples_per_chunk - 1) / samples_per_chunk;
     
-    println!("📊 Chunking info:");
-    println!("   Original sample rate: {} Hz", audio_data.sample_rate);
-    println!("   Target sample rate: {} Hz", SAMPLE_RATE);
-    println!("   Total samples: {}", full_audio_samples.len());
-    println!("   Samples per chunk: {}", samples_per_chunk);
-    println!("   Total chunks: {}", total_chunks);
-    println!("   Chunk duration: {} minutes", CHUNK_DURATION_MINUTES);
+    status!("📊 Chunking info:");
+    status!("   Original sample rate: {} Hz", audio_data.sample_rate);
+    status!("   Target sample rate: {} Hz", SAMPLE_RATE);
+    status!("   Total samples: {}", full_audio_samples.len());
+    status!("   Samples per chunk: {}", samples_per_chunk);
+    status!("   Total chunks: {}", total_chunks);
+    status!("   Chunk duration: {} minutes", CHUNK_DURATION_MINUTES);
     
     let mut all_segments = Vec::new();
-    let mut total_duration_offset = 0.0;
+    let mut filtered_segments = 0;
+    let mut suppressed_hallucinations = 0;
+    let mut skipped_chunks = 0;
+    let mut truncated = false;
+    // Chunk timestamps start from --offset so clips of a longer recording line up with it
+    let mut total_duration_offset = options.offset_seconds;
+    
+    // The initial prompt seeds the first chunk; later chunks are prompted with the previous chunk's text
+    let mut prompt = options.initial_prompt.clone();
     
     for (chunk_index, chunk_data) in full_audio_samples.chunks(samples_per_chunk).enumerate() {
         let chunk_start_time = chunk_index as f32 * CHUNK_DURATION_MINUTES;
         
-        println!("\n📝 Processing chunk {} of {} ({}min - {}min)", 
+        status!("\n📝 Processing chunk {} of {} ({}min - {}min)", 
                  chunk_index + 1, 
                  total_chunks,
                  chunk_start_time,
                  chunk_start_time + CHUNK_DURATION_MINUTES);
         
+        // Report overall progress as (chunk_index + per_chunk_progress) / total_chunks
+        let chunk_progress: Option<ProgressCallback> = progress.clone().map(|callback| {
+            Arc::new(move |percent: f32| {
+                callback((chunk_index as f32 + percent / 100.0) / total_chunks as f32 * 100.0);
+            }) as ProgressCallback
+        });
+        
+        // Silent chunks only produce empty or hallucinated segments, so skip the Whisper call
+        let chunk_rms = (chunk_data.iter().map(|&x| x * x).sum::<f32>() / chunk_data.len() as f32).sqrt();
+        if chunk_rms < silent_chunk_rms {
+            status!(" 🔇 Skipping silent chunk {} (RMS {:.6} < {})", chunk_index + 1, chunk_rms, silent_chunk_rms);
+            skipped_chunks += 1;
+            if let Some(callback) = &chunk_progress {
+                callback(100.0);
+            }
+            total_duration_offset += chunk_data.len() as f64 / SAMPLE_RATE as f64;
+            continue;
+        }
+        
         // Transcribe this chunk using whisper-rs
-        let chunk_segments = transcribe_with_debug(ctx, chunk_data.to_vec(), language)?;
+        let chunk_options = TranscribeOptions {
+            initial_prompt: prompt.clone(),
+            offset_seconds: total_duration_offset,
+            // Chunked output keeps no per-token words, so the word threshold doesn't apply here
+            word_confidence_threshold: 0.0,
+            ..options.clone()
+        };
+        let chunk_dump = dump_audio.map(|path| chunk_dump_path(path, chunk_index + 1));
+        let mut chunk_segments = transcribe_with_debug(ctx, chunk_data.to_vec(), language, &chunk_options, chunk_dump.as_deref(), chunk_progress)?;
+        
+        // Drop repetition loops before they become the next chunk's prompt
+        if let Some(max_repeat) = options.max_repeat {
+            suppressed_hallucinations += suppress_repetitions(&mut chunk_segments, max_repeat);
+        }
+        
+        let chunk_text: String = chunk_segments.iter().map(|s| s.text.as_str()).collect();
+        if !chunk_text.trim().is_empty() {
+            prompt = Some(chunk_text);
+        }
         
         // Adjust timestamps and collect segments
         for segment in chunk_segments {
             let adjusted_start = segment.start + total_duration_offset;
             let adjusted_end = segment.end + total_duration_offset;
             
+            // Drop low-confidence segments after their timestamps are adjusted
+            if let (Some(min_confidence), Some(confidence)) = (options.min_confidence, segment.confidence) {
+                if confidence < min_confidence {
+                    status!("   🧹 Dropping segment [{:.2}s - {:.2}s] with confidence {:.2}", adjusted_start, adjusted_end, confidence);
+                    filtered_segments += 1;
+                    continue;
+                }
+            }
+            
             all_segments.push(TranscriptionSegment {
                 text: segment.text,
                 start_time: adjusted_start,
                 end_time: adjusted_end,
                 chunk_index: chunk_index + 1,
+                temperature: segment.temperature,
+                no_speech_prob: segment.no_speech_prob,
+                avg_logprob: segment.avg_logprob,
+                confidence: segment.confidence,
             });
         }
         
         total_duration_offset += chunk_data.len() as f64 / SAMPLE_RATE as f64;
-        println!(" ✅ Chunk {} completed", chunk_index + 1);
+        status!(" ✅ Chunk {} completed", chunk_index + 1);
+        
+        // Stop transcribing once the segment limit is exceeded; later chunks would be dropped anyway
+        if options.segment_limits.truncate(&mut all_segments) {
+            status!("✂️ Segment limit of {} reached, skipping the remaining chunks", all_segments.len());
+            truncated = true;
+            break;
+        }
     }
     
-    println!("\n");
+    status!("\n");
+    if skipped_chunks > 0 {
+        status!("🔇 Skipped {} of {} chunks as silent", skipped_chunks, total_chunks);
+    }
     
     // Return segments for logging
-    Ok(all_segments)
+    Ok(ChunkedTranscription {
+        segments: all_segments,
+        declipped_samples: declipped,
+        agc_gain,
+        filtered_segments,
+        suppressed_hallucinations,
+        truncated,
+    })
+}
+
+// Output of transcribe_with_chunking plus what preprocessing and filtering changed
+pub struct ChunkedTranscription {
+    pub segments: Vec<TranscriptionSegment>,
+    pub declipped_samples: usize,
+    pub agc_gain: Option<AgcGain>,
+    pub filtered_segments: usize,
+    pub suppressed_hallucinations: usize,
+    /// Stopped early because the segment limit was exceeded
+    pub truncated: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -337,6 +1304,10 @@ pub struct TranscriptionSegment {
     start_time: f64,
     end_time: f64,
     chunk_index: usize,
+    temperature: f64,
+    no_speech_prob: f64,
+    avg_logprob: f64,
+    confidence: Option<f64>,
 }
 
 impl TranscriptionSegment {
@@ -348,10 +1319,11 @@ impl TranscriptionSegment {
             "end": self.end_time,
             "text": self.text,
             "tokens": [],
-            "temperature": 0.0,
-            "avg_logprob": 0.0,
-            "compression_ratio": 0.0,
-            "no_speech_prob": 0.0
+            "temperature": self.temperature,
+            "avg_logprob": self.avg_logprob,
+            "compression_ratio": compression_ratio(&self.text),
+            "no_speech_prob": self.no_speech_prob,
+            "confidence": self.confidence
         })
     }
 }
@@ -359,19 +1331,19 @@ impl TranscriptionSegment {
 fn display_chunked_transcription_results(
     segments: &[TranscriptionSegment]
 ) -> Result<(), Box<dyn std::error::Error>> {
-    println!("=== 🇹🇭 Thai Transcription Results (Chunked) ===");
+    status!("=== 🇹🇭 Thai Transcription Results (Chunked) ===");
     
     if segments.is_empty() {
-        println!("❌ No speech detected in the audio file.");
+        status!("❌ No speech detected in the audio file.");
         return Ok(());
     }
     
     // Display segments with timestamps
-    println!("\n📝 Timestamped Segments:");
-    println!("{}", "─".repeat(80));
+    status!("\n📝 Timestamped Segments:");
+    status!("{}", "─".repeat(80));
     
     for segment in segments {
-        println!("[{:>7.2}s - {:>7.2}s] [Chunk {}]: {}", 
+        status!("[{:>7.2}s - {:>7.2}s] [Chunk {}]: {}", 
                  segment.start_time,
                  segment.end_time,
                  segment.chunk_index,
@@ -379,9 +1351,9 @@ fn display_chunked_transcription_results(
     }
     
     // Display full transcription
-    println!("\n{}", "─".repeat(80));
-    println!("📄 Complete Transcription:");
-    println!("{}", "─".repeat(80));
+    status!("\n{}", "─".repeat(80));
+    status!("📄 Complete Transcription:");
+    status!("{}", "─".repeat(80));
     
     let full_text: String = segments
         .iter()
@@ -390,21 +1362,21 @@ fn display_chunked_transcription_results(
         .collect::<Vec<_>>()
         .join(" ");
     
-    println!("{}", full_text);
+    status!("{}", full_text);
     
     // Display statistics
-    println!("\n{}", "─".repeat(80));
-    println!("📊 Statistics:");
-    println!("   Total segments: {}", segments.len());
-    println!("   Total chunks processed: {}", 
+    status!("\n{}", "─".repeat(80));
+    status!("📊 Statistics:");
+    status!("   Total segments: {}", segments.len());
+    status!("   Total chunks processed: {}", 
              segments.iter().map(|s| s.chunk_index).max().unwrap_or(0));
-    println!("   Total duration: {:.2} minutes", 
+    status!("   Total duration: {:.2} minutes", 
              segments.last().map(|s| s.end_time / 60.0).unwrap_or(0.0));
-    println!("   Total characters: {}", full_text.chars().count());
-    println!("   Total words: {}", full_text.split_whitespace().count());
+    status!("   Total characters: {}", full_text.chars().count());
+    status!("   Total words: {}", full_text.split_whitespace().count());
     
     // Show chunk breakdown
-    println!("\n📊 Chunk Breakdown:");
+    status!("\n📊 Chunk Breakdown:");
     let mut chunks_info: std::collections::HashMap<usize, (usize, f64, f64)> = std::collections::HashMap::new();
     
     for segment in segments {
@@ -416,7 +1388,7 @@ fn display_chunked_transcription_results(
     
     for chunk_idx in 1..=chunks_info.len() {
         if let Some((segment_count, start_time, end_time)) = chunks_info.get(&chunk_idx) {
-            println!("   Chunk {}: {} segments, {:.2}min - {:.2}min", 
+            status!("   Chunk {}: {} segments, {:.2}min - {:.2}min", 
                      chunk_idx, segment_count, start_time / 60.0, end_time / 60.0);
         }
     }
@@ -425,18 +1397,25 @@ fn display_chunked_transcription_results(
 }
 
 #[cfg(feature = "full-audio-support")]
-fn load_audio_file_advanced(path: &str) -> Result<AudioData, Box<dyn std::error::Error>> {
-    println!("🔄 Loading audio with Symphonia support...");
+fn load_audio_file_advanced(path: &str, channel: Option<usize>) -> Result<AudioData, Box<dyn std::error::Error>> {
+    // Headerless PCM can't be probed, so --raw-format bypasses Symphonia entirely
+    if let Some(format) = RAW_FORMAT.get() {
+        return load_raw_pcm(path, format, channel);
+    }
+    
+    status!("🔄 Loading audio with Symphonia support...");
     
     let file = std::fs::File::open(path)?;
     let mss = MediaSourceStream::new(Box::new(file), Default::default());
     
-    // Create a probe hint using the file extension
+    // Create a probe hint from the content-checked extension (needed for MP4/MOV probing)
+    let extension = thai_transcriber::detect_audio_extension(path);
     let mut hint = Hint::new();
-    if let Some(extension) = Path::new(path).extension() {
-        if let Some(extension_str) = extension.to_str() {
-            hint.with_extension(extension_str);
-        }
+    if !extension.is_empty() {
+        hint.with_extension(&extension);
+    }
+    if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+        status!("🎬 Video container detected ({}), extracting the first audio track", extension);
     }
     
     // Use the default options for metadata and format readers
@@ -450,11 +1429,17 @@ fn load_audio_file_advanced(path: &str) -> Result<AudioData, Box<dyn std::error:
     // Get the instantiated format reader
     let mut format = probed.format;
     
-    // Find the first audio track with a known codec
+    // Find the first audio track with a codec we can decode. Video tracks have no
+    // sample rate, so this skips them in MP4/MKV/MOV containers.
+    let codecs = symphonia::default::get_codecs();
     let track = format
         .tracks()
         .iter()
-        .find(|t| t.codec_params.codec != symphonia::core::codecs::CODEC_TYPE_NULL)
+        .find(|t| {
+            t.codec_params.codec != symphonia::core::codecs::CODEC_TYPE_NULL
+                && t.codec_params.sample_rate.is_some()
+                && codecs.get_codec(t.codec_params.codec).is_some()
+        })
         .ok_or("no supported audio tracks")?;
     
     let track_id = track.id;
@@ -463,16 +1448,18 @@ fn load_audio_file_advanced(path: &str) -> Result<AudioData, Box<dyn std::error:
     let original_sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
     let channel_count = track.codec_params.channels.map(|c| c.count()).unwrap_or(1);
     
-    println!("📊 Audio Info (Symphonia):");
-    println!("   Sample Rate: {} Hz", original_sample_rate);
-    println!("   Channels: {}", channel_count);
+    status!("📊 Audio Info (Symphonia):");
+    status!("   Sample Rate: {} Hz", original_sample_rate);
+    status!("   Channels: {}", channel_count);
+    if let Some(channel) = channel {
+        status!("   Using channel {} only", channel);
+    }
     
     // Use the default options for the decoder
     let dec_opts: DecoderOptions = Default::default();
     
     // Create a decoder for the track
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &dec_opts)?;
+    let mut decoder = codecs.make(&track.codec_params, &dec_opts)?;
     
     // Store the audio samples
     let mut audio_samples = Vec::new();
@@ -513,15 +1500,11 @@ fn load_audio_file_advanced(path: &str) -> Result<AudioData, Box<dyn std::error:
                 let mut sample_buf = SampleBuffer::<f32>::new(duration, spec);
                 sample_buf.copy_interleaved_ref(audio_buf);
                 
-                // If stereo, convert to mono by averaging channels
+                // Downmix to mono by averaging channels (video tracks are often 5.1),
+                // or keep just the requested channel
                 let samples = sample_buf.samples();
-                if spec.channels.count() == 2 {
-                    for chunk in samples.chunks_exact(2) {
-                        audio_samples.push((chunk[0] + chunk[1]) / 2.0);
-                    }
-                } else {
-                    audio_samples.extend_from_slice(samples);
-                }
+                let channels = spec.channels.count() as u16;
+                audio_samples.extend(to_mono(samples.to_vec(), channels, channel)?);
             }
             Err(SymphoniaError::IoError(_)) => {
                 continue;
@@ -535,7 +1518,7 @@ fn load_audio_file_advanced(path: &str) -> Result<AudioData, Box<dyn std::error:
         }
     }
     
-    println!("✅ Loaded {} samples with Symphonia", audio_samples.len());
+    status!("✅ Loaded {} samples with Symphonia", audio_samples.len());
     
     Ok(AudioData {
         samples: audio_samples,
@@ -545,106 +1528,327 @@ fn load_audio_file_advanced(path: &str) -> Result<AudioData, Box<dyn std::error:
 }
 
 #[cfg(not(feature = "full-audio-support"))]
-fn load_audio_file_advanced(path: &str) -> Result<AudioData, Box<dyn std::error::Error>> {
-    let extension = Path::new(path)
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("")
-        .to_lowercase();
+fn load_audio_file_advanced(path: &str, channel: Option<usize>) -> Result<AudioData, Box<dyn std::error::Error>> {
+    if let Some(format) = RAW_FORMAT.get() {
+        return load_raw_pcm(path, format, channel);
+    }
+    
+    // Misnamed files (an MP3 saved as .wav) are routed by their content, not their name
+    let extension = thai_transcriber::detect_audio_extension(path);
 
     match extension.as_str() {
         #[cfg(feature = "wav-support")]
-        "wav" => load_wav_file(path),
+        "wav" => load_wav_file(path, channel),
+        ext if VIDEO_EXTENSIONS.contains(&ext) => {
+            Err(format!("Video containers (.{}) require the 'full-audio-support' feature", ext).into())
+        }
         _ => {
-            println!("⚠️  Unsupported format '{}', attempting basic PCM loading...", extension);
-            load_audio_file_basic(path)
+            status!("⚠️  Unsupported format '{}', attempting basic PCM loading...", extension);
+            load_audio_file_basic(path, channel)
+        }
+    }
+}
+
+// Write 16kHz mono f32 samples to a 32-bit float WAV file
+#[cfg(feature = "wav-support")]
+fn dump_audio_wav(samples: &[f32], path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let mut writer = WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+// Write 16kHz mono samples as 16-bit PCM, clamping anything outside [-1, 1]
+#[cfg(feature = "wav-support")]
+fn write_pcm16_wav(samples: &[f32], path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer = WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "wav-support"))]
+fn write_pcm16_wav(_samples: &[f32], _path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err("convert requires the wav-support feature".into())
+}
+
+#[cfg(not(feature = "wav-support"))]
+fn dump_audio_wav(_samples: &[f32], _path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err("--dump-audio requires the wav-support feature".into())
+}
+
+// "debug.wav" -> "debug.chunk03.wav" so chunks don't overwrite each other
+fn chunk_dump_path(path: &str, chunk_number: usize) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("dump");
+    let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("wav");
+    path.with_file_name(format!("{}.chunk{:02}.{}", stem, chunk_number, extension))
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(feature = "wav-support")]
+fn load_wav_file(path: &str, channel: Option<usize>) -> Result<AudioData, Box<dyn std::error::Error>> {
+    let mut reader = WavReader::open(path)?;
+    let spec = reader.spec();
+    
+    status!("📊 Audio Info:");
+    status!("   Sample Rate: {} Hz", spec.sample_rate);
+    status!("   Channels: {}", spec.channels);
+    status!("   Bits per Sample: {}", spec.bits_per_sample);
+    
+    let samples: Result<Vec<f32>, _> = match spec.sample_format {
+        SampleFormat::Int => {
+            // hound returns integer samples unscaled, so normalize by the full range of the bit depth
+            let divisor = match spec.bits_per_sample {
+                8 => i8::MAX as f32,
+                16 => i16::MAX as f32,
+                24 => (1i32 << 23) as f32,
+                32 => i32::MAX as f32,
+                bits => (1i64 << (bits.max(1) - 1)) as f32,
+            };
+            status!("   Integer PCM: {} bits per sample", spec.bits_per_sample);
+            
+            reader.samples::<i32>()
+                .map(|s| s.map(|sample| sample as f32 / divisor))
+                .collect()
+        }
+        SampleFormat::Float => {
+            reader.samples::<f32>().collect()
+        }
+    };
+    
+    let mut audio_samples = samples?;
+    
+    // A header that disagrees with the data would otherwise downmix across frame boundaries
+    check_channel_layout(audio_samples.len(), spec.channels)?;
+    
+    // Keep one channel if asked, otherwise convert to mono
+    if let Some(channel) = channel {
+        status!("🔄 Extracting channel {} of {}...", channel, spec.channels);
+        audio_samples = to_mono(audio_samples, spec.channels, Some(channel))?;
+    } else if channels_identical(&audio_samples, spec.channels) {
+        status!("ℹ️  All {} channels are identical (mono stored as multichannel), using channel 0", spec.channels);
+        audio_samples = to_mono(audio_samples, spec.channels, None)?;
+    } else if spec.channels > 1 {
+        status!("🔄 Converting {} channels to mono...", spec.channels);
+        audio_samples = to_mono(audio_samples, spec.channels, None)?;
+    }
+    
+    status!("✅ Loaded {} samples ({:.2} seconds)", 
+             audio_samples.len(), 
+             audio_samples.len() as f32 / spec.sample_rate as f32);
+    
+    Ok(AudioData {
+        samples: audio_samples,
+        sample_rate: spec.sample_rate,
+        channels: 1, // mono after conversion
+    })
+}
+
+/// Layout of headerless PCM, from `--raw-format <rate>:<bits>:<channels>[:le|be]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawPcmFormat {
+    pub sample_rate: u32,
+    /// 8 (unsigned), 16, 24 or 32 (signed) bit integer samples
+    pub bits_per_sample: u16,
+    pub channels: u16,
+    pub big_endian: bool,
+}
+
+impl Default for RawPcmFormat {
+    // What the basic loader has always assumed for files without a WAV header
+    fn default() -> Self {
+        Self { sample_rate: 16000, bits_per_sample: 16, channels: 1, big_endian: false }
+    }
+}
+
+impl RawPcmFormat {
+    /// Parse `<rate>:<bits>:<channels>[:le|be]`, e.g. `8000:16:1` or `44100:24:2:be`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = spec.split(':').map(|part| part.trim()).collect();
+        if !(3..=4).contains(&parts.len()) {
+            return Err(format!("Invalid raw format '{}', expected <rate>:<bits>:<channels>[:le|be]", spec));
+        }
+        
+        let sample_rate: u32 = parts[0].parse()
+            .ok()
+            .filter(|&rate| rate > 0)
+            .ok_or_else(|| format!("Invalid sample rate '{}' in raw format", parts[0]))?;
+        let bits_per_sample: u16 = parts[1].parse()
+            .ok()
+            .filter(|bits| matches!(bits, 8 | 16 | 24 | 32))
+            .ok_or_else(|| format!("Invalid bit depth '{}' in raw format, expected 8, 16, 24 or 32", parts[1]))?;
+        let channels: u16 = parts[2].parse()
+            .ok()
+            .filter(|&channels| channels > 0)
+            .ok_or_else(|| format!("Invalid channel count '{}' in raw format", parts[2]))?;
+        let big_endian = match parts.get(3).map(|endian| endian.to_lowercase()) {
+            None => false,
+            Some(endian) if endian == "le" => false,
+            Some(endian) if endian == "be" => true,
+            Some(endian) => return Err(format!("Invalid endianness '{}' in raw format, expected le or be", endian)),
+        };
+        
+        Ok(Self { sample_rate, bits_per_sample, channels, big_endian })
+    }
+    
+    fn bytes_per_sample(&self) -> usize {
+        self.bits_per_sample as usize / 8
+    }
+    
+    // Decode interleaved integer samples to f32 in [-1, 1)
+    fn decode(&self, data: &[u8]) -> Vec<f32> {
+        let width = self.bytes_per_sample();
+        if data.len() % width != 0 {
+            status!("⚠️  Ignoring {} trailing bytes that don't make a whole sample", data.len() % width);
+        }
+        
+        data.chunks_exact(width)
+            .map(|bytes| {
+                // Assemble big-endian so the value is the same whatever the stored order
+                let mut value: u32 = 0;
+                for i in 0..width {
+                    let byte = if self.big_endian { bytes[i] } else { bytes[width - 1 - i] };
+                    value = (value << 8) | byte as u32;
+                }
+                match self.bits_per_sample {
+                    8 => (value as f32 - 128.0) / 128.0,
+                    16 => value as u16 as i16 as f32 / 32768.0,
+                    24 => (((value << 8) as i32) >> 8) as f32 / 8_388_608.0,
+                    _ => value as i32 as f32 / 2_147_483_648.0,
+                }
+            })
+            .collect()
+    }
+}
+
+// --raw-format, set once before any audio is loaded
+static RAW_FORMAT: OnceLock<RawPcmFormat> = OnceLock::new();
+
+/// Find the fmt and data chunks of a RIFF/WAVE file. `Ok(None)` when the file has no
+/// WAV header; an error when it has one this loader can't read (e.g. compressed audio).
+fn parse_wav_header(buffer: &[u8]) -> Result<Option<(RawPcmFormat, &[u8])>, String> {
+    if buffer.len() < 12 || &buffer[0..4] != b"RIFF" || &buffer[8..12] != b"WAVE" {
+        return Ok(None);
+    }
+    
+    let mut format = None;
+    let mut position = 12;
+    while position + 8 <= buffer.len() {
+        let chunk_id = &buffer[position..position + 4];
+        let chunk_size = u32::from_le_bytes([buffer[position + 4], buffer[position + 5], buffer[position + 6], buffer[position + 7]]) as usize;
+        let body_start = position + 8;
+        let body_end = body_start.saturating_add(chunk_size).min(buffer.len());
+        let body = &buffer[body_start..body_end];
+        
+        match chunk_id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err("WAV fmt chunk is too short".to_string());
+                }
+                let format_tag = u16::from_le_bytes([body[0], body[1]]);
+                // 1 = integer PCM, 0xFFFE = WAVE_FORMAT_EXTENSIBLE (PCM subformat assumed)
+                if format_tag != 1 && format_tag != 0xFFFE {
+                    return Err(format!("WAV format tag {} is not integer PCM; enable the 'wav-support' feature to read it", format_tag));
+                }
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+                let bits_per_sample = u16::from_le_bytes([body[14], body[15]]);
+                if channels == 0 || sample_rate == 0 || !matches!(bits_per_sample, 8 | 16 | 24 | 32) {
+                    return Err(format!("Unsupported WAV format: {} channels, {}Hz, {} bits", channels, sample_rate, bits_per_sample));
+                }
+                format = Some(RawPcmFormat { sample_rate, bits_per_sample, channels, big_endian: false });
+            }
+            b"data" => {
+                return match format {
+                    Some(format) => Ok(Some((format, body))),
+                    None => Err("WAV data chunk comes before the fmt chunk".to_string()),
+                };
+            }
+            _ => {}
         }
+        
+        // Chunks are padded to an even length
+        position = body_start.saturating_add(chunk_size + chunk_size % 2);
     }
+    
+    Err("WAV file has no data chunk".to_string())
 }
 
-#[cfg(feature = "wav-support")]
-fn load_wav_file(path: &str) -> Result<AudioData, Box<dyn std::error::Error>> {
-    let mut reader = WavReader::open(path)?;
-    let spec = reader.spec();
-    
-    println!("📊 Audio Info:");
-    println!("   Sample Rate: {} Hz", spec.sample_rate);
-    println!("   Channels: {}", spec.channels);
-    println!("   Bits per Sample: {}", spec.bits_per_sample);
-    
-    let samples: Result<Vec<f32>, _> = match spec.sample_format {
-        SampleFormat::Int => {
-            reader.samples::<i32>()
-                .map(|s| s.map(|sample| sample as f32 / i32::MAX as f32))
-                .collect()
-        }
-        SampleFormat::Float => {
-            reader.samples::<f32>().collect()
-        }
-    };
+/// Load headerless PCM laid out as `format`, downmixed (or reduced to `channel`) to mono
+fn load_raw_pcm(path: &str, format: &RawPcmFormat, channel: Option<usize>) -> Result<AudioData, Box<dyn std::error::Error>> {
+    status!("📼 Loading raw PCM: {}Hz, {}-bit, {} channel(s), {}", 
+             format.sample_rate, format.bits_per_sample, format.channels, if format.big_endian { "big-endian" } else { "little-endian" });
     
-    let mut audio_samples = samples?;
-    
-    // Convert stereo to mono if necessary
-    if spec.channels == 2 {
-        println!("🔄 Converting stereo to mono...");
-        audio_samples = audio_samples
-            .chunks_exact(2)
-            .map(|chunk| (chunk[0] + chunk[1]) / 2.0)
-            .collect();
-    }
+    let buffer = std::fs::read(path)?;
+    let samples = to_mono(format.decode(&buffer), format.channels, channel)?;
     
-    println!("✅ Loaded {} samples ({:.2} seconds)", 
-             audio_samples.len(), 
-             audio_samples.len() as f32 / spec.sample_rate as f32);
+    status!("📊 Loaded {} samples ({:.2} seconds)", samples.len(), samples.len() as f64 / format.sample_rate as f64);
     
     Ok(AudioData {
-        samples: audio_samples,
-        sample_rate: spec.sample_rate,
-        channels: if spec.channels == 2 { 1 } else { spec.channels }, // mono after conversion
+        samples,
+        sample_rate: format.sample_rate,
+        channels: 1,
     })
 }
 
-fn load_audio_file_basic(path: &str) -> Result<AudioData, Box<dyn std::error::Error>> {
-    use std::io::Read;
-    
-    println!("⚠️  Using basic PCM loader - assumes 16-bit PCM WAV at 16kHz");
-    println!("   For better audio support, enable 'wav-support' feature");
-    
-    let mut file = File::open(path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+fn load_audio_file_basic(path: &str, channel: Option<usize>) -> Result<AudioData, Box<dyn std::error::Error>> {
+    status!("⚠️  Using basic PCM loader");
+    status!("   For better audio support, enable 'wav-support' feature");
+    
+    let buffer = std::fs::read(path)?;
+    
+    // Read the layout from the WAV header when there is one
+    let (format, data) = match parse_wav_header(&buffer)? {
+        Some((format, data)) => (format, data),
+        None => {
+            status!("⚠️  No WAV header, assuming 16-bit PCM at 16kHz mono (use --raw-format to override)");
+            (RawPcmFormat::default(), &buffer[..])
+        }
+    };
     
-    // Skip WAV header and convert 16-bit PCM to f32
-    let audio_samples: Vec<f32> = buffer
-        .chunks_exact(2)
-        .skip(22) // Skip basic WAV header
-        .map(|chunk| {
-            let sample = i16::from_le_bytes([chunk[0], chunk[1]]);
-            sample as f32 / 32768.0
-        })
-        .collect();
+    let samples = to_mono(format.decode(data), format.channels, channel)?;
     
-    println!("📊 Loaded {} samples (basic PCM)", audio_samples.len());
+    status!("📊 Loaded {} samples at {}Hz (basic PCM)", samples.len(), format.sample_rate);
     
     Ok(AudioData {
-        samples: audio_samples,
-        sample_rate: 16000, // Assumed for basic loader
-        channels: 1, // Assumed mono
+        samples,
+        sample_rate: format.sample_rate,
+        channels: 1,
     })
 }
 
 // Audio resampling function to convert any sample rate to 16kHz
 fn resample_audio(audio_data: Vec<f32>, original_sample_rate: u32, target_sample_rate: u32) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
     if original_sample_rate == target_sample_rate {
-        println!("✅ Audio already at target sample rate ({}Hz)", target_sample_rate);
+        status!("✅ Audio already at target sample rate ({}Hz)", target_sample_rate);
         return Ok(audio_data);
     }
     
-    println!("🔄 Resampling audio: {}Hz → {}Hz", original_sample_rate, target_sample_rate);
+    // Calculate resampling ratio, refusing rates no real recording has
+    let ratio = thai_transcriber::resample_ratio(original_sample_rate, target_sample_rate)?;
+    if audio_data.is_empty() {
+        return Ok(audio_data);
+    }
     
-    // Calculate resampling ratio
-    let ratio = target_sample_rate as f64 / original_sample_rate as f64;
+    status!("🔄 Resampling audio: {}Hz → {}Hz", original_sample_rate, target_sample_rate);
     
     // Create resampler parameters
     let params = SincInterpolationParameters {
@@ -655,93 +1859,144 @@ fn resample_audio(audio_data: Vec<f32>, original_sample_rate: u32, target_sample
         window: WindowFunction::BlackmanHarris2,
     };
     
-    // Create resampler
+    // Resample in one-second blocks rather than one block the size of the file,
+    // which keeps rubato's buffers small for long high-sample-rate recordings
+    let input_len = audio_data.len();
+    let chunk_size = (original_sample_rate as usize).min(input_len);
     let mut resampler = SincFixedIn::<f32>::new(
         ratio,
         2.0, // max_resample_ratio_relative
         params,
-        audio_data.len(),
+        chunk_size,
         1, // mono channel
-    )?;
-    
-    // Prepare input data (rubato expects Vec<Vec<f32>> for multi-channel)
-    let input_channels = vec![audio_data];
-    
-    // Perform resampling
-    let output_channels = resampler.process(&input_channels, None)?;
+    ).map_err(|e| format!("Failed to create resampler for {}Hz → {}Hz: {}", original_sample_rate, target_sample_rate, e))?;
+    
+    let output_len = (input_len as f64 * ratio).ceil() as usize;
+    let mut resampled_data = Vec::with_capacity(output_len + chunk_size);
+    let mut position = 0;
+    while position + chunk_size <= input_len {
+        let output = resampler.process(&[&audio_data[position..position + chunk_size]], None)?;
+        resampled_data.extend_from_slice(&output[0]);
+        position += chunk_size;
+    }
     
-    // Extract mono channel
-    let resampled_data = output_channels.into_iter().next()
-        .ok_or("Failed to get resampled audio channel")?;
+    // Zero-pad the last partial block, then flush the resampler's delay
+    if position < input_len {
+        let output = resampler.process_partial(Some(&[&audio_data[position..]]), None)?;
+        resampled_data.extend_from_slice(&output[0]);
+    }
+    while resampled_data.len() < output_len {
+        let output = resampler.process_partial(None::<&[&[f32]]>, None)?;
+        resampled_data.extend_from_slice(&output[0]);
+    }
+    resampled_data.truncate(output_len);
     
-    println!("✅ Resampling completed: {} samples → {} samples", 
-             input_channels[0].len(), resampled_data.len());
+    status!("✅ Resampling completed: {} samples → {} samples", 
+             input_len, resampled_data.len());
     
     Ok(resampled_data)
 }
 
 // Enhanced audio loading with debugging
-pub fn load_audio_file_with_debug(path: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
-    println!("🔍 DEBUG: Loading audio file: {}", path);
+pub fn load_audio_file_with_debug(
+    path: &str,
+    declip: bool,
+    clip_threshold: f32,
+    agc_target_dbfs: Option<f32>,
+    channel: Option<usize>,
+) -> Result<(Vec<f32>, usize, Option<AgcGain>), Box<dyn std::error::Error>> {
+    status!("🔍 DEBUG: Loading audio file: {}", path);
     
-    let audio_data = load_audio_file_advanced(path)?;
+    let mut audio_data = load_audio_file_advanced(path, channel)?;
     
     // Debug original audio data
-    println!("🔍 DEBUG: Original audio data loaded:");
-    println!("   - Sample count: {}", audio_data.samples.len());
-    println!("   - Sample rate: {} Hz", audio_data.sample_rate);
-    println!("   - Channels: {}", audio_data.channels);
-    println!("   - Duration: {:.2} seconds", audio_data.samples.len() as f32 / audio_data.sample_rate as f32);
+    status!("🔍 DEBUG: Original audio data loaded:");
+    status!("   - Sample count: {}", audio_data.samples.len());
+    status!("   - Sample rate: {} Hz", audio_data.sample_rate);
+    status!("   - Channels: {}", audio_data.channels);
+    status!("   - Duration: {:.2} seconds", audio_data.samples.len() as f32 / audio_data.sample_rate as f32);
+    
+    // Corrupt files can decode to NaN/Inf, which would reach Whisper as garbage
+    let sanitized = sanitize_samples(&mut audio_data.samples);
+    if sanitized > 0 {
+        status!("⚠️  WARNING: Replaced or clamped {} non-finite or out-of-range samples", sanitized);
+    }
+    
+    // Check for clipping before resampling smears the flat tops
+    let declipped = repair_clipping(&mut audio_data.samples, declip, clip_threshold);
     
     // Resample to 16kHz if necessary
-    let final_samples = if audio_data.sample_rate != SAMPLE_RATE {
-        println!("🔄 Resampling required: {}Hz → {}Hz", audio_data.sample_rate, SAMPLE_RATE);
+    let mut final_samples = if audio_data.sample_rate != SAMPLE_RATE {
+        status!("🔄 Resampling required: {}Hz → {}Hz", audio_data.sample_rate, SAMPLE_RATE);
         resample_audio(audio_data.samples, audio_data.sample_rate, SAMPLE_RATE)?
     } else {
-        println!("✅ Audio already at target sample rate ({}Hz)", SAMPLE_RATE);
+        status!("✅ Audio already at target sample rate ({}Hz)", SAMPLE_RATE);
         audio_data.samples
     };
     
     // Debug final audio data
-    println!("🔍 DEBUG: Final audio data:");
-    println!("   - Sample count: {}", final_samples.len());
-    println!("   - Duration: {:.2} seconds", final_samples.len() as f32 / SAMPLE_RATE as f32);
+    status!("🔍 DEBUG: Final audio data:");
+    status!("   - Sample count: {}", final_samples.len());
+    status!("   - Duration: {:.2} seconds", final_samples.len() as f32 / SAMPLE_RATE as f32);
     
     // Check for silence (all zeros or very low amplitude)
     let max_amplitude = final_samples.iter().fold(0.0f32, |max, &x| max.max(x.abs()));
     let rms = (final_samples.iter().map(|&x| x * x).sum::<f32>() / final_samples.len() as f32).sqrt();
     
-    println!("   - Max amplitude: {:.6}", max_amplitude);
-    println!("   - RMS amplitude: {:.6}", rms);
+    status!("   - Max amplitude: {:.6}", max_amplitude);
+    status!("   - RMS amplitude: {:.6}", rms);
     
     if max_amplitude < 0.001 {
-        println!("⚠️  WARNING: Audio appears to be silent or very quiet!");
-        println!("   This could cause transcription to fail.");
+        status!("⚠️  WARNING: Audio appears to be silent or very quiet!");
+        status!("   This could cause transcription to fail.");
     }
     
     if rms < 0.0001 {
-        println!("⚠️  WARNING: Very low RMS - audio might be too quiet for transcription!");
+        status!("⚠️  WARNING: Very low RMS - audio might be too quiet for transcription!");
     }
     
-    // Sample first few values
-    println!("   - First 10 samples: {:?}", &final_samples[..final_samples.len().min(10)]);
+    // RMS-based gain, capped so the loudest transient stays below clipping
+    let agc_gain = agc_target_dbfs.map(|target_dbfs| {
+        let gain = AgcGain::for_levels(rms, max_amplitude, target_dbfs);
+        let factor = gain.factor();
+        for sample in final_samples.iter_mut() {
+            *sample *= factor;
+        }
+        status!("🎚️ AGC: {:+.1} dB towards {:.1} dBFS RMS{}", gain.gain_db, target_dbfs, if gain.peak_limited { " (limited by peak)" } else { "" });
+        gain
+    });
+    
+    // Sample first few values (RUST_LOG=debug)
+    log::debug!("First 10 samples: {:?}", &final_samples[..final_samples.len().min(10)]);
+    
+    Ok((final_samples, declipped, agc_gain))
+}
+
+// Warn about clipped samples and rebuild them when de-clipping is enabled.
+// Returns the number of repaired samples.
+fn repair_clipping(samples: &mut [f32], declip: bool, clip_threshold: f32) -> usize {
+    let clipped_count = samples.iter().filter(|&&x| x.abs() >= clip_threshold).count();
+    if clipped_count == 0 {
+        return 0;
+    }
     
-    // Check for clipping
-    let clipped_count = final_samples.iter().filter(|&&x| x.abs() >= 0.99).count();
-    if clipped_count > 0 {
-        println!("⚠️  WARNING: {} samples appear clipped (>= 0.99)", clipped_count);
+    status!("⚠️  WARNING: {} samples appear clipped (>= {})", clipped_count, clip_threshold);
+    if !declip {
+        return 0;
     }
     
-    Ok(final_samples)
+    let repaired = declip_samples(samples, clip_threshold);
+    status!("🩹 De-clipped {} of {} clipped samples", repaired, clipped_count);
+    repaired
 }
 
 // Enhanced model initialization with debugging
 pub fn initialize_whisper_with_debug(model_path: &str, language: &str, use_gpu: bool, use_coreml: bool) -> Result<WhisperContext, Box<dyn std::error::Error>> {
-    println!("🔍 DEBUG: Initializing Whisper model...");
-    println!("   - Model path: {}", model_path);
-    println!("   - Target language: {}", language);
-    println!("   - GPU acceleration: {}", if use_gpu { "enabled" } else { "disabled" });
-    println!("   - Core ML acceleration: {}", if use_coreml { "enabled" } else { "disabled" });
+    status!("🔍 DEBUG: Initializing Whisper model...");
+    status!("   - Model path: {}", model_path);
+    status!("   - Target language: {}", language);
+    status!("   - GPU acceleration: {}", if use_gpu { "enabled" } else { "disabled" });
+    status!("   - Core ML acceleration: {}", if use_coreml { "enabled" } else { "disabled" });
     
     let ctx = WhisperContext::new_with_params(
         model_path,
@@ -749,9 +2004,9 @@ pub fn initialize_whisper_with_debug(model_path: &str, language: &str, use_gpu:
             use_gpu,
             ..Default::default()
         },
-    ).map_err(|e| format!("Failed to load Whisper model: {}", e))?;
+    ).map_err(|e| thai_transcriber::model_load_error(model_path, e))?;
     
-    println!("✅ Model loaded successfully");
+    status!("✅ Model loaded successfully");
     Ok(ctx)
 }
 
@@ -760,29 +2015,133 @@ pub fn transcribe_with_debug(
     ctx: &WhisperContext,
     audio_data: Vec<f32>,
     language: &str,
+    options: &TranscribeOptions,
+    dump_audio: Option<&str>,
+    progress: Option<ProgressCallback>,
 ) -> Result<Vec<WhisperSegment>, Box<dyn std::error::Error>> {
-    println!("🔍 DEBUG: Starting transcription...");
-    println!("   - Audio samples: {}", audio_data.len());
-    println!("   - Language: {}", language);
+    status!("🔍 DEBUG: Starting transcription...");
+    status!("   - Audio samples: {}", audio_data.len());
+    status!("   - Language: {}", language);
+    
+    let prompt_tokens = match options.initial_prompt.as_deref() {
+        Some(text) if !text.trim().is_empty() => prompt_tokens(ctx, text)?,
+        _ => Vec::new(),
+    };
     
+    // Dump exactly what Whisper is about to receive
+    if let Some(path) = dump_audio {
+        dump_audio_wav(&audio_data, path)?;
+        status!("💾 Dumped {} samples of Whisper input audio to {}", audio_data.len(), path);
+    }
+    
+    // Retry failed decodes at increasing temperatures like the library does. Unlike OpenAI
+    // Whisper, which only re-decodes the failing 30s window, the whole pass is run again.
+    let temperatures: &[f32] = if DETERMINISTIC.load(Ordering::Relaxed) { &[0.0] } else { TEMPERATURE_FALLBACK };
+    let thresholds = options.decode_thresholds;
+    let mut segments = Vec::new();
+    
+    for (attempt, &temperature) in temperatures.iter().enumerate() {
+        // --jsonl lines stream live only from a pass that is kept whatever its quality
+        let last_attempt = attempt + 1 == temperatures.len();
+        let mut jsonl_lines = Vec::new();
+        let jsonl_buffer = if last_attempt { None } else { Some(&mut jsonl_lines) };
+        segments = run_debug_pass(ctx, &audio_data, language, options, &prompt_tokens, temperature, jsonl_buffer, progress.clone())?;
+        
+        let (avg_logprob, compression_ratio) = pass_quality(segments.iter().map(|s| (s.avg_logprob, s.text.as_str())));
+        if avg_logprob >= thresholds.logprob as f64 && compression_ratio <= thresholds.compression_ratio as f64 {
+            for (start, end, text) in &jsonl_lines {
+                emit_jsonl_segment(*start, *end, text);
+            }
+            break;
+        }
+        
+        match temperatures.get(attempt + 1) {
+            Some(next) => status!("⚠️  Decode at temperature {:.1} failed (avg_logprob {:.2}, compression_ratio {:.2}), retrying at {:.1}",
+                                  temperature, avg_logprob, compression_ratio, next),
+            None => status!("⚠️  Decode still failing at temperature {:.1} (avg_logprob {:.2}, compression_ratio {:.2}), keeping last result",
+                            temperature, avg_logprob, compression_ratio),
+        }
+    }
+    
+    Ok(segments)
+}
+
+// One Whisper pass of `transcribe_with_debug` at a fixed temperature. With --jsonl, its
+// segments go into `jsonl_buffer` when given, else straight to stdout.
+#[allow(clippy::too_many_arguments)]
+fn run_debug_pass(
+    ctx: &WhisperContext,
+    audio_data: &[f32],
+    language: &str,
+    options: &TranscribeOptions,
+    prompt_tokens: &[i32],
+    temperature: f32,
+    jsonl_buffer: Option<&mut Vec<(f64, f64, String)>>,
+    progress: Option<ProgressCallback>,
+) -> Result<Vec<WhisperSegment>, Box<dyn std::error::Error>> {
     // Set up transcription parameters
     let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-    params.set_translate(false);
+    params.set_translate(options.translate);
     params.set_language(Some(language));
-    params.set_progress_callback_safe(|progress| {
-        println!("🔄 Transcription progress: {:.1}%", progress as f64 * 100.0);
+    // Fallback is driven by transcribe_with_debug, so disable whisper.cpp's own retries
+    params.set_temperature(temperature);
+    params.set_temperature_inc(0.0);
+    status!("   - Temperature: {:.1}", temperature);
+    if DETERMINISTIC.load(Ordering::Relaxed) {
+        // Greedy best_of=1 at temperature 0 never samples, so whisper.cpp's RNG is never consulted
+        params.set_n_threads(DETERMINISTIC_THREADS);
+        status!("   - Deterministic mode: temperature 0, no fallback, {} threads", DETERMINISTIC_THREADS);
+    }
+    // Suppress blank outputs at the start of a segment, and optionally non-speech tokens,
+    // at decode time rather than filtering them out of the text afterwards
+    params.set_suppress_blank(true);
+    if SUPPRESS_NONSPEECH.load(Ordering::Relaxed) {
+        params.set_suppress_non_speech_tokens(true);
+        status!("   - Suppressing non-speech tokens");
+    }
+    if !prompt_tokens.is_empty() {
+        status!("   - Initial prompt: {} tokens", prompt_tokens.len());
+        params.set_tokens(prompt_tokens);
+    }
+    let limits = options.segment_limits;
+    if limits.max_len.is_some() || limits.max_tokens.is_some() {
+        status!("   - Segment limits: max {:?} chars, max {:?} tokens", limits.max_len, limits.max_tokens);
+        limits.apply(&mut params);
+    }
+    let thresholds = options.decode_thresholds;
+    if thresholds != DecodeThresholds::default() {
+        status!("   - Thresholds: no-speech {}, logprob {}, entropy {}, compression ratio {}", thresholds.no_speech, thresholds.logprob, thresholds.entropy, thresholds.compression_ratio);
+    }
+    thresholds.apply(&mut params);
+    // With --jsonl, hand each segment over as soon as whisper.cpp produces it rather than after the whole file
+    let mut jsonl_pass = JsonlPass { offset: options.offset_seconds, buffered: jsonl_buffer };
+    if JSONL_OUTPUT.load(Ordering::Relaxed) {
+        // SAFETY: the callback only reads segments through the state pointer it is given, and
+        // `jsonl_pass` outlives `state.full` below and isn't touched until it returns
+        unsafe {
+            params.set_new_segment_callback(Some(jsonl_segment_callback));
+            params.set_new_segment_callback_user_data(&mut jsonl_pass as *mut JsonlPass as *mut std::os::raw::c_void);
+        }
+    }
+    params.set_progress_callback_safe(move |percent| {
+        match &progress {
+            Some(callback) => callback(percent as f32),
+            None => status!("🔄 Transcription progress: {:.1}%", percent as f64 * 100.0),
+        }
     });
     
-    println!("   - Parameters configured");
+    status!("   - Parameters configured");
     
     // Create state and run transcription
     let mut state = ctx.create_state().map_err(|e| format!("Failed to create state: {}", e))?;
     
-    println!("   - State created, starting transcription...");
-    state.full(params, &audio_data).map_err(|e| format!("Failed to run model: {}", e))?;
+    status!("   - State created, starting transcription...");
+    state.full(params, audio_data).map_err(|e| format!("Failed to run model: {}", e))?;
     
     let num_segments = state.full_n_segments().map_err(|e| format!("Failed to get segment count: {}", e))?;
-    println!("� DEBUG: Transcription completed with {} segments", num_segments);
+    status!("� DEBUG: Transcription completed with {} segments", num_segments);
+    // A looping decode can produce tens of thousands of segments; don't read past the limit
+    let num_segments = limits.segments_to_collect(num_segments);
     
     let mut segments = Vec::new();
     
@@ -798,33 +2157,63 @@ pub fn transcribe_with_debug(
         let start_time = start_timestamp as f64 / 100.0;
         let end_time = end_timestamp as f64 / 100.0;
         
-        println!("   - Segment {}: [{:.2}s - {:.2}s] '{}'", i, start_time, end_time, segment_text.trim());
+        status!("   - Segment {}: [{:.2}s - {:.2}s] '{}'", i, start_time, end_time, segment_text.trim());
         
         // Get word-level data
         let num_tokens = state.full_n_tokens(i).unwrap_or(0);
         let mut words = Vec::new();
+        let mut usable_tokens = Vec::new();
+        let mut first_token_prob = None;
+        let mut logprob_sum = 0.0;
+        let mut logprob_count = 0;
         
         for j in 0..num_tokens {
+            let token_data = match state.full_get_token_data(i, j) {
+                Ok(token_data) => token_data,
+                Err(_) => continue,
+            };
+            
+            // Timestamps, [_BEG_] and <|endoftext|> are special tokens at or above EOT
+            if token_data.id >= ctx.token_eot() {
+                continue;
+            }
+            first_token_prob.get_or_insert(token_data.p as f64);
+            logprob_sum += token_data.plog as f64;
+            logprob_count += 1;
+            
             if let Ok(token_text) = state.full_get_token_text(i, j) {
-                if let Ok(token_prob) = state.full_get_token_prob(i, j) {
-                    let cleaned_text = token_text.trim();
-                    if !cleaned_text.is_empty() && !cleaned_text.starts_with('<') && !cleaned_text.starts_with('[') {
-                        // Approximate word timestamps
-                        let word_progress = j as f64 / num_tokens.max(1) as f64;
-                        let word_start = start_time + (end_time - start_time) * word_progress;
-                        let word_end = start_time + (end_time - start_time) * ((j + 1) as f64 / num_tokens.max(1) as f64);
-                        
-                        words.push(WhisperWord {
-                            text: cleaned_text.to_string(),
-                            start: word_start,
-                            end: word_end,
-                            confidence: token_prob as f64,
-                        });
-                    }
+                let cleaned_text = token_text.trim();
+                if !cleaned_text.is_empty() {
+                    usable_tokens.push((cleaned_text.to_string(), token_data.p as f64));
                 }
             }
         }
         
+        // Approximate word timestamps by spreading the usable tokens evenly over the segment;
+        // tokens below the word threshold keep their time slot but are left out of `words`
+        let num_words = usable_tokens.len();
+        for (k, (text, prob)) in usable_tokens.into_iter().enumerate() {
+            let word_start = start_time + (end_time - start_time) * (k as f64 / num_words as f64);
+            let word_end = start_time + (end_time - start_time) * ((k + 1) as f64 / num_words as f64);
+            
+            if prob < options.word_confidence_threshold {
+                continue;
+            }
+            
+            words.push(WhisperWord {
+                text,
+                start: word_start,
+                end: word_end,
+                confidence: prob,
+            });
+        }
+        
+        // Segments without text tokens have no meaningful confidence
+        let avg_logprob = if logprob_count > 0 { logprob_sum / logprob_count as f64 } else { 0.0 };
+        let confidence = if logprob_count > 0 { Some(logprob_confidence(avg_logprob)) } else { None };
+        
+        let segment_compression_ratio = compression_ratio(&segment_text);
+        
         // Create segment
         let segment = WhisperSegment {
             id: i as i32,
@@ -833,12 +2222,13 @@ pub fn transcribe_with_debug(
             end: end_time,
             text: segment_text,
             tokens: Vec::new(), // Token IDs not easily accessible
-            temperature: 0.0,
-            avg_logprob: -0.3,
-            compression_ratio: 1.5,
-            no_speech_prob: 0.1,
-            confidence: words.iter().map(|w| w.confidence).sum::<f64>() / words.len().max(1) as f64,
+            temperature: temperature as f64,
+            avg_logprob,
+            compression_ratio: segment_compression_ratio,
+            no_speech_prob: no_speech_prob(first_token_prob),
+            confidence,
             words,
+            speaker: None,
         };
         
         segments.push(segment);
@@ -847,18 +2237,150 @@ pub fn transcribe_with_debug(
     Ok(segments)
 }
 
+/// Merge consecutive segments separated by less than `max_gap_s` of silence,
+/// concatenating text and extending the time range, unless the merged text
+/// would exceed `max_len_chars`. Segment ids are renumbered afterwards.
+fn merge_short_segments(segments: &mut Vec<WhisperSegment>, max_gap_s: f64, max_len_chars: usize) {
+    let original_count = segments.len();
+    let mut merged: Vec<WhisperSegment> = Vec::with_capacity(segments.len());
+    
+    for segment in segments.drain(..) {
+        if let Some(previous) = merged.last_mut() {
+            let gap = segment.start - previous.end;
+            let merged_len = previous.text.trim().chars().count() + segment.text.trim().chars().count();
+            
+            if gap < max_gap_s && merged_len <= max_len_chars {
+                // Weight per-segment scores by their word counts
+                let previous_words = previous.words.len().max(1) as f64;
+                let segment_words = segment.words.len().max(1) as f64;
+                previous.avg_logprob = (previous.avg_logprob * previous_words + segment.avg_logprob * segment_words)
+                    / (previous_words + segment_words);
+                previous.temperature = previous.temperature.max(segment.temperature);
+                previous.no_speech_prob = previous.no_speech_prob.min(segment.no_speech_prob);
+                
+                previous.end = segment.end;
+                previous.text.push_str(&segment.text);
+                previous.tokens.extend(segment.tokens);
+                previous.words.extend(segment.words);
+                previous.compression_ratio = compression_ratio(&previous.text);
+                previous.confidence = previous.confidence.or(segment.confidence)
+                    .map(|_| logprob_confidence(previous.avg_logprob));
+                continue;
+            }
+        }
+        merged.push(segment);
+    }
+    
+    for (id, segment) in merged.iter_mut().enumerate() {
+        segment.id = id as i32;
+    }
+    
+    status!("🔗 Merged {} segments into {}", original_count, merged.len());
+    *segments = merged;
+}
+
+// Shift all segment and word timestamps by `offset` seconds
+fn offset_segments(segments: &mut [WhisperSegment], offset: f64) {
+    if offset == 0.0 {
+        return;
+    }
+    
+    for segment in segments.iter_mut() {
+        segment.start += offset;
+        segment.end += offset;
+        segment.seek = (segment.start * 100.0) as i32;
+        for word in segment.words.iter_mut() {
+            word.start += offset;
+            word.end += offset;
+        }
+    }
+}
+
+/// Remove segments whose confidence is below `min_confidence` and renumber the rest.
+/// Segments without a confidence are kept. Returns how many were removed.
+fn filter_low_confidence(segments: &mut Vec<WhisperSegment>, min_confidence: f64) -> usize {
+    let before = segments.len();
+    segments.retain(|segment| !matches!(segment.confidence, Some(c) if c < min_confidence));
+    
+    for (id, segment) in segments.iter_mut().enumerate() {
+        segment.id = id as i32;
+    }
+    
+    let removed = before - segments.len();
+    status!("🧹 Filtered {} segments below confidence {:.2}", removed, min_confidence);
+    removed
+}
+
+/// Drop repeated-text hallucinations (see `thai_transcriber::find_repetitions`) and
+/// renumber the rest. Returns how many were removed.
+fn suppress_repetitions(segments: &mut Vec<WhisperSegment>, max_repeat: usize) -> usize {
+    let texts: Vec<&str> = segments.iter().map(|s| s.text.as_str()).collect();
+    let drops = thai_transcriber::find_repetitions(&texts, max_repeat);
+    
+    let before = segments.len();
+    let mut index = 0;
+    segments.retain(|_| {
+        let keep = !drops[index];
+        index += 1;
+        keep
+    });
+    
+    for (id, segment) in segments.iter_mut().enumerate() {
+        segment.id = id as i32;
+    }
+    
+    let removed = before - segments.len();
+    if removed > 0 {
+        status!("🔁 Suppressed {} repeated-text segments (max repeat {})", removed, max_repeat);
+    }
+    removed
+}
+
+// Same merge for chunked output; chunk boundaries are never merged across
+fn merge_short_chunked_segments(segments: &mut Vec<TranscriptionSegment>, max_gap_s: f64, max_len_chars: usize) {
+    let original_count = segments.len();
+    let mut merged: Vec<TranscriptionSegment> = Vec::with_capacity(segments.len());
+    
+    for segment in segments.drain(..) {
+        if let Some(previous) = merged.last_mut() {
+            let gap = segment.start_time - previous.end_time;
+            let merged_len = previous.text.trim().chars().count() + segment.text.trim().chars().count();
+            
+            if previous.chunk_index == segment.chunk_index && gap < max_gap_s && merged_len <= max_len_chars {
+                // Weight avg_logprob by text length, as chunked segments keep no words
+                let previous_chars = previous.text.chars().count().max(1) as f64;
+                let segment_chars = segment.text.chars().count().max(1) as f64;
+                previous.avg_logprob = (previous.avg_logprob * previous_chars + segment.avg_logprob * segment_chars)
+                    / (previous_chars + segment_chars);
+                previous.confidence = previous.confidence.or(segment.confidence)
+                    .map(|_| logprob_confidence(previous.avg_logprob));
+                
+                previous.end_time = segment.end_time;
+                previous.text.push_str(&segment.text);
+                previous.temperature = previous.temperature.max(segment.temperature);
+                previous.no_speech_prob = previous.no_speech_prob.min(segment.no_speech_prob);
+                continue;
+            }
+        }
+        merged.push(segment);
+    }
+    
+    status!("🔗 Merged {} segments into {}", original_count, merged.len());
+    *segments = merged;
+}
+
 // Additional debugging: Test audio file manually
 fn test_audio_file_manually(path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔍 Manual audio file test:");
+    status!("🔍 Manual audio file test:");
     
     // Check file existence and size
     let metadata = std::fs::metadata(path)?;
-    println!("   - File size: {} bytes", metadata.len());
+    status!("   - File size: {} bytes", metadata.len());
     
     // Try to open file
     match std::fs::File::open(path) {
-        Ok(_) => println!("   - File can be opened"),
-        Err(e) => println!("   - File open error: {}", e),
+        Ok(_) => status!("   - File can be opened"),
+        Err(e) => status!("   - File open error: {}", e),
     }
     
     // Check file extension
@@ -866,19 +2388,19 @@ fn test_audio_file_manually(path: &str) -> Result<(), Box<dyn std::error::Error>
         .extension()
         .and_then(|ext| ext.to_str())
         .unwrap_or("unknown");
-    println!("   - File extension: {}", extension);
+    status!("   - File extension: {}", extension);
     
     // Try basic audio loading
-    match load_audio_file_advanced(path) {
+    match load_audio_file_advanced(path, None) {
         Ok(data) => {
-            println!("   - Audio loading: SUCCESS");
-            println!("   - Sample count: {}", data.samples.len());
+            status!("   - Audio loading: SUCCESS");
+            status!("   - Sample count: {}", data.samples.len());
             if !data.samples.is_empty() {
-                println!("   - First sample: {}", data.samples[0]);
-                println!("   - Last sample: {}", data.samples[data.samples.len() - 1]);
+                status!("   - First sample: {}", data.samples[0]);
+                status!("   - Last sample: {}", data.samples[data.samples.len() - 1]);
             }
         },
-        Err(e) => println!("   - Audio loading: FAILED - {}", e),
+        Err(e) => status!("   - Audio loading: FAILED - {}", e),
     }
     
     Ok(())
@@ -887,28 +2409,28 @@ fn test_audio_file_manually(path: &str) -> Result<(), Box<dyn std::error::Error>
 // Additional debugging: Test audio file manually
 
 fn display_transcription_results_from_segments(segments: &[WhisperSegment]) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\n\n=== 🇹🇭 Thai Transcription Results ===");
+    status!("\n\n=== 🇹🇭 Thai Transcription Results ===");
     
     if segments.is_empty() {
-        println!("❌ No speech detected in the audio file.");
+        status!("❌ No speech detected in the audio file.");
         return Ok(());
     }
     
     // Display segments with timestamps
-    println!("\n📝 Timestamped Segments:");
-    println!("{}", "─".repeat(60));
+    status!("\n📝 Timestamped Segments:");
+    status!("{}", "─".repeat(60));
     
     for segment in segments {
-        println!("[{:>7.2}s - {:>7.2}s]: {}", 
+        status!("[{:>7.2}s - {:>7.2}s]: {}", 
                  segment.start, 
                  segment.end, 
                  segment.text.trim());
     }
     
     // Display full transcription
-    println!("\n{}", "─".repeat(60));
-    println!("📄 Complete Transcription:");
-    println!("{}", "─".repeat(60));
+    status!("\n{}", "─".repeat(60));
+    status!("📄 Complete Transcription:");
+    status!("{}", "─".repeat(60));
     
     let full_text: String = segments
         .iter()
@@ -917,14 +2439,14 @@ fn display_transcription_results_from_segments(segments: &[WhisperSegment]) -> R
         .collect::<Vec<_>>()
         .join(" ");
     
-    println!("{}", full_text);
+    status!("{}", full_text);
     
     // Display statistics
-    println!("\n{}", "─".repeat(60));
-    println!("📊 Statistics:");
-    println!("   Total segments: {}", segments.len());
-    println!("   Total characters: {}", full_text.chars().count());
-    println!("   Total words: {}", full_text.split_whitespace().count());
+    status!("\n{}", "─".repeat(60));
+    status!("📊 Statistics:");
+    status!("   Total segments: {}", segments.len());
+    status!("   Total characters: {}", full_text.chars().count());
+    status!("   Total words: {}", full_text.split_whitespace().count());
     
     Ok(())
 }
@@ -937,6 +2459,14 @@ struct LogSegment {
     duration: f64,
     text: String,
     chunk_index: Option<usize>,
+    #[serde(default)]
+    temperature: f64,
+    #[serde(default)]
+    no_speech_prob: f64,
+    #[serde(default)]
+    avg_logprob: f64,
+    #[serde(default)]
+    confidence: Option<f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -954,6 +2484,30 @@ struct TranscriptionLog {
     processing_time_seconds: f64,
     segments: Vec<LogSegment>,
     full_transcription: String,
+    #[serde(default)]
+    declip: bool,
+    #[serde(default)]
+    declipped_samples: usize,
+    #[serde(default)]
+    min_confidence: Option<f64>,
+    #[serde(default)]
+    filtered_segments: usize,
+    #[serde(default)]
+    max_repeat: Option<usize>,
+    #[serde(default)]
+    suppressed_hallucinations: usize,
+    #[serde(default)]
+    max_segments: Option<usize>,
+    #[serde(default)]
+    truncated: bool,
+    #[serde(default)]
+    agc_target_dbfs: Option<f32>,
+    #[serde(default)]
+    agc_gain_db: Option<f32>,
+    #[serde(default)]
+    agc_peak_limited: bool,
+    #[serde(default)]
+    model: Option<String>,
 }
 
 // OpenAI Whisper format structures for result.json
@@ -977,8 +2531,12 @@ pub struct WhisperSegment {
     avg_logprob: f64,
     compression_ratio: f64,
     no_speech_prob: f64,
-    confidence: f64,
+    /// `exp(avg_logprob)`, or `None` when the segment has no text tokens
+    confidence: Option<f64>,
     words: Vec<WhisperWord>,
+    /// Gap-based speaker placeholder, only set with --speaker-gap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    speaker: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -986,11 +2544,40 @@ pub struct WhisperResult {
     text: String,
     segments: Vec<WhisperSegment>,
     language: String,
+    /// Segments grouped into paragraphs, only set with --paragraphs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    paragraphs: Option<Vec<Paragraph>>,
+}
+
+// mm:ss, or h:mm:ss once the recording passes an hour
+fn markdown_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (hours, minutes, secs) = (total / 3600, (total % 3600) / 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{:02}:{:02}", minutes, secs)
+    }
+}
+
+// Keep transcript text from being read as Markdown syntax or breaking table cells
+fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if matches!(ch, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
 }
 
 pub struct Logger {
     start_time: std::time::Instant,
     log_data: TranscriptionLog,
+    speaker_gap: Option<f64>,
+    paragraphs: bool,
+    word_confidence_threshold: f64,
 }
 
 impl Logger {
@@ -1011,7 +2598,22 @@ impl Logger {
                 processing_time_seconds: 0.0,
                 segments: Vec::new(),
                 full_transcription: String::new(),
+                declip: false,
+                declipped_samples: 0,
+                min_confidence: None,
+                filtered_segments: 0,
+                max_repeat: None,
+                suppressed_hallucinations: 0,
+                max_segments: None,
+                truncated: false,
+                agc_target_dbfs: None,
+                agc_gain_db: None,
+                agc_peak_limited: false,
+                model: None,
             },
+            speaker_gap: None,
+            paragraphs: false,
+            word_confidence_threshold: 0.0,
         }
     }
 
@@ -1020,6 +2622,48 @@ impl Logger {
         self.log_data.estimated_duration_minutes = duration_minutes;
     }
 
+    fn set_declip_info(&mut self, declip: bool, declipped_samples: usize) {
+        self.log_data.declip = declip;
+        self.log_data.declipped_samples = declipped_samples;
+    }
+
+    fn set_filtered_segments(&mut self, min_confidence: Option<f64>, filtered_segments: usize) {
+        self.log_data.min_confidence = min_confidence;
+        self.log_data.filtered_segments = filtered_segments;
+    }
+
+    fn set_suppressed_hallucinations(&mut self, max_repeat: Option<usize>, suppressed_hallucinations: usize) {
+        self.log_data.max_repeat = max_repeat;
+        self.log_data.suppressed_hallucinations = suppressed_hallucinations;
+    }
+
+    fn set_agc(&mut self, agc_target_dbfs: Option<f32>, agc_gain: Option<AgcGain>) {
+        self.log_data.agc_target_dbfs = agc_target_dbfs;
+        self.log_data.agc_gain_db = agc_gain.map(|gain| gain.gain_db);
+        self.log_data.agc_peak_limited = agc_gain.is_some_and(|gain| gain.peak_limited);
+    }
+
+    fn set_truncated(&mut self, max_segments: Option<usize>, truncated: bool) {
+        self.log_data.max_segments = max_segments;
+        self.log_data.truncated = truncated;
+    }
+
+    fn set_model(&mut self, model_path: &str) {
+        self.log_data.model = Some(model_path.to_string());
+    }
+
+    fn set_speaker_gap(&mut self, speaker_gap: Option<f64>) {
+        self.speaker_gap = speaker_gap;
+    }
+
+    fn set_paragraphs(&mut self, paragraphs: bool) {
+        self.paragraphs = paragraphs;
+    }
+
+    fn set_word_confidence_threshold(&mut self, word_confidence_threshold: f64) {
+        self.word_confidence_threshold = word_confidence_threshold;
+    }
+
     fn set_processing_mode(&mut self, mode: &str, chunks: Option<usize>) {
         self.log_data.processing_mode = mode.to_string();
         self.log_data.total_chunks = chunks;
@@ -1033,6 +2677,10 @@ impl Logger {
                 duration: segment.end - segment.start,
                 text: segment.text.clone(),
                 chunk_index: None,
+                temperature: segment.temperature,
+                no_speech_prob: segment.no_speech_prob,
+                avg_logprob: segment.avg_logprob,
+                confidence: segment.confidence,
             });
         }
         self.finalize_stats();
@@ -1046,6 +2694,10 @@ impl Logger {
                 duration: segment.end_time - segment.start_time,
                 text: segment.text.clone(),
                 chunk_index: Some(segment.chunk_index),
+                temperature: segment.temperature,
+                no_speech_prob: segment.no_speech_prob,
+                avg_logprob: segment.avg_logprob,
+                confidence: segment.confidence,
             });
         }
         self.finalize_stats();
@@ -1070,7 +2722,7 @@ impl Logger {
         let json_data = serde_json::to_string_pretty(&self.log_data)?;
         let mut file = File::create(output_path)?;
         file.write_all(json_data.as_bytes())?;
-        println!("📝 Transcription log saved to: {}", output_path);
+        status!("📝 Transcription log saved to: {}", output_path);
         Ok(())
     }
 
@@ -1114,26 +2766,78 @@ impl Logger {
         writeln!(file, "{}", "─".repeat(80))?;
         writeln!(file, "{}", self.log_data.full_transcription)?;
         
-        println!("📄 Text summary saved to: {}", output_path);
+        status!("📄 Text summary saved to: {}", output_path);
+        Ok(())
+    }
+
+    // Markdown transcript for documentation sites: a metadata table, then one
+    // paragraph per segment with an anchor (#segment-N) to link to
+    fn save_markdown(&self, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = File::create(output_path)?;
+        
+        let file_name = Path::new(&self.log_data.audio_file)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&self.log_data.audio_file);
+        let duration = self.log_data.segments.iter().map(|s| s.end_time).fold(0.0, f64::max);
+        let model = self.log_data.model.as_deref()
+            .and_then(|path| Path::new(path).file_name().and_then(|name| name.to_str()))
+            .unwrap_or("unknown");
+        
+        writeln!(file, "# Transcript: {}", markdown_escape(file_name))?;
+        writeln!(file)?;
+        writeln!(file, "| Field | Value |")?;
+        writeln!(file, "| --- | --- |")?;
+        writeln!(file, "| File | {} |", markdown_escape(&self.log_data.audio_file))?;
+        writeln!(file, "| Language | {} |", markdown_escape(&self.log_data.language))?;
+        writeln!(file, "| Duration | {} |", markdown_timestamp(duration))?;
+        writeln!(file, "| Model | {} |", markdown_escape(model))?;
+        writeln!(file, "| Segments | {} |", self.log_data.total_segments)?;
+        writeln!(file, "| Transcribed | {} |", self.log_data.timestamp.format("%Y-%m-%d %H:%M:%S UTC"))?;
+        writeln!(file)?;
+        
+        writeln!(file, "## Transcript")?;
+        for (i, segment) in self.log_data.segments.iter().enumerate() {
+            let text = segment.text.split_whitespace().collect::<Vec<_>>().join(" ");
+            writeln!(file)?;
+            writeln!(file, "<a id=\"segment-{}\"></a>**[{}]** {}", i + 1, markdown_timestamp(segment.start_time), markdown_escape(&text))?;
+        }
+        
+        status!("📄 Markdown transcript saved to: {}", output_path);
         Ok(())
     }
 
-    fn save_result_json(&self) -> Result<(), Box<dyn std::error::Error>> {
+    fn save_result_json(&self, output_path: &str, pretty: bool) -> Result<(), Box<dyn std::error::Error>> {
         // Create OpenAI Whisper format for result.json
         let whisper_result = self.create_whisper_format();
-        let json_data = serde_json::to_string_pretty(&whisper_result)?;
-        let mut file = File::create("result.json")?;
+        let json_data = if pretty {
+            serde_json::to_string_pretty(&whisper_result)?
+        } else {
+            serde_json::to_string(&whisper_result)?
+        };
+        let mut file = File::create(output_path)?;
         file.write_all(json_data.as_bytes())?;
-        println!("📝 Results saved to result.json (OpenAI Whisper format)");
+        status!("📝 Results saved to {} (OpenAI Whisper format)", output_path);
         Ok(())
     }
 
     pub fn create_whisper_format(&self) -> WhisperResult {
         let mut whisper_segments = Vec::new();
+        let mut speaker = 0;
         
         for (i, segment) in self.log_data.segments.iter().enumerate() {
-            // Better word-level segmentation for Thai text
-            let words = self.create_thai_word_segments(&segment.text, segment.start_time, segment.duration);
+            // Crude turn-taking: a silence longer than the speaker gap starts a new speaker
+            let speaker_label = self.speaker_gap.map(|gap| {
+                if i > 0 && segment.start_time - self.log_data.segments[i - 1].end_time > gap {
+                    speaker += 1;
+                }
+                format!("SPEAKER_{:02}", speaker)
+            });
+            
+            // Dictionary-based word segmentation for Thai text
+            // No token data survives to this point, so words share their segment's confidence
+            let mut words = self.create_thai_word_segments(&segment.text, segment.start_time, segment.duration, segment.confidence.unwrap_or(0.0));
+            words.retain(|word| word.confidence >= self.word_confidence_threshold);
             
             // More realistic token generation (still approximated)
             let tokens = self.approximate_tokens(&segment.text);
@@ -1145,52 +2849,51 @@ impl Logger {
                 end: segment.end_time,
                 text: segment.text.clone(),
                 tokens,
-                temperature: 0.0,
-                avg_logprob: self.calculate_avg_logprob(&segment.text), // More realistic
-                compression_ratio: self.calculate_compression_ratio(&segment.text),
-                no_speech_prob: self.estimate_no_speech_prob(segment.duration),
-                confidence: self.estimate_segment_confidence(&segment.text),
+                temperature: segment.temperature,
+                avg_logprob: segment.avg_logprob,
+                compression_ratio: compression_ratio(&segment.text),
+                no_speech_prob: segment.no_speech_prob,
+                confidence: segment.confidence,
                 words,
+                speaker: speaker_label,
             };
             
             whisper_segments.push(whisper_segment);
         }
 
+        let paragraphs = self.paragraphs.then(|| group_paragraphs(
+            self.log_data.segments.iter().map(|segment| (segment.start_time, segment.end_time, segment.text.as_str())),
+            PARAGRAPH_GAP_SECONDS,
+        ));
+
         WhisperResult {
             text: self.log_data.full_transcription.clone(),
             segments: whisper_segments,
             language: self.log_data.language.clone(),
+            paragraphs,
         }
     }
 
     // Helper methods for better approximation
-    fn create_thai_word_segments(&self, text: &str, start_time: f64, duration: f64) -> Vec<WhisperWord> {
-        // Thai text segmentation is complex - this is a simplified approach
+    fn create_thai_word_segments(&self, text: &str, start_time: f64, duration: f64, confidence: f64) -> Vec<WhisperWord> {
+        // Thai has no spaces between words, so boundaries come from the dictionary word breaker
+        let segmented = thai::segment_words(text);
+        let total_chars: usize = segmented.iter().map(|w| w.chars().count()).sum();
         let mut words = Vec::new();
-        let chars: Vec<char> = text.chars().collect();
-        let mut current_word = String::new();
-        let mut word_start_idx = 0;
+        let mut char_start = 0;
         
-        for (i, &ch) in chars.iter().enumerate() {
-            current_word.push(ch);
+        for word in segmented {
+            let word_chars = word.chars().count();
+            let word_start = start_time + (char_start as f64 / total_chars as f64) * duration;
+            let word_duration = duration * (word_chars as f64 / total_chars as f64);
             
-            // Simple Thai word boundary detection (very basic)
-            if ch.is_whitespace() || i == chars.len() - 1 {
-                if !current_word.trim().is_empty() {
-                    let word_proportion = current_word.len() as f64 / chars.len() as f64;
-                    let word_start = start_time + (word_start_idx as f64 / chars.len() as f64) * duration;
-                    let word_duration = duration * word_proportion;
-                    
-                    words.push(WhisperWord {
-                        text: current_word.trim().to_string(),
-                        start: word_start,
-                        end: word_start + word_duration,
-                        confidence: self.estimate_word_confidence(&current_word),
-                    });
-                }
-                current_word.clear();
-                word_start_idx = i + 1;
-            }
+            words.push(WhisperWord {
+                text: word,
+                start: word_start,
+                end: word_start + word_duration,
+                confidence,
+            });
+            char_start += word_chars;
         }
         
         words
@@ -1203,52 +2906,22 @@ impl Logger {
         let words = text.split_whitespace();
         
         for word in words {
-            // Approximate 1-3 tokens per word depending on length
-            let token_count = match word.len() {
+            // Approximate 1-3 tokens per word depending on length, in characters
+            // rather than bytes so a Thai character doesn't count three times
+            let word_chars = word.chars().count();
+            let token_count = match word_chars {
                 0..=3 => 1,
                 4..=8 => 2,
                 _ => 3,
             };
             
             for j in 0..token_count {
-                tokens.push(50000 + (word.len() * 100 + j) as i32); // Mock token IDs
+                tokens.push(50000 + (word_chars * 100 + j) as i32); // Mock token IDs
             }
         }
         
         tokens
     }
-    
-    fn calculate_avg_logprob(&self, text: &str) -> f64 {
-        // Estimate based on text characteristics
-        let complexity = text.chars().count() as f64 / text.split_whitespace().count().max(1) as f64;
-        -0.2 - (complexity * 0.1) // More complex = lower probability
-    }
-    
-    fn calculate_compression_ratio(&self, text: &str) -> f64 {
-        // Rough estimation
-        let char_count = text.chars().count();
-        let word_count = text.split_whitespace().count();
-        1.5 + (char_count as f64 / word_count.max(1) as f64) * 0.1
-    }
-    
-    fn estimate_no_speech_prob(&self, duration: f64) -> f64 {
-        // Longer segments generally have lower no_speech probability
-        if duration > 3.0 { 0.01 } else { 0.1 }
-    }
-    
-    fn estimate_segment_confidence(&self, text: &str) -> f64 {
-        // Estimate based on text length and content
-        let base_confidence = 0.8;
-        let length_bonus = (text.len().min(100) as f64 / 100.0) * 0.1;
-        (base_confidence + length_bonus).min(0.95)
-    }
-    
-    fn estimate_word_confidence(&self, word: &str) -> f64 {
-        // Simple heuristic - longer words tend to have higher confidence
-        let base = 0.7;
-        let length_factor = (word.len().min(10) as f64 / 10.0) * 0.25;
-        (base + length_factor).min(0.98)
-    }
 }
 
 // Public API functions for the queue system
@@ -1258,8 +2931,15 @@ pub async fn transcribe_audio_file(
     audio_path: &str,
     backend: &str,
     language: Option<&str>,
+    initial_prompt: Option<&str>,
+    translate: bool,
 ) -> Result<serde_json::Value, String> {
     let language = language.unwrap_or("th");
+    let options = TranscribeOptions {
+        initial_prompt: initial_prompt.map(|p| p.to_string()),
+        translate,
+        ..Default::default()
+    };
     
     // Determine backend settings
     let (use_gpu, use_coreml) = match backend {
@@ -1281,8 +2961,9 @@ pub async fn transcribe_audio_file(
     
     if should_chunk {
         // Process with chunking
-        let segments = transcribe_with_chunking(&ctx, audio_path, language)
-            .map_err(|e| format!("Chunked transcription failed: {}", e))?;
+        let segments = transcribe_with_chunking(&ctx, audio_path, language, &options, 0.0, None, None)
+            .map_err(|e| format!("Chunked transcription failed: {}", e))?
+            .segments;
         
         // Convert to WhisperResult format
         let whisper_segments: Vec<_> = segments.iter().enumerate().map(|(i, segment)| {
@@ -1300,10 +2981,10 @@ pub async fn transcribe_audio_file(
         Ok(result)
     } else {
         // Process as single file
-        let audio_data = load_audio_file_with_debug(audio_path)
+        let (audio_data, _, _) = load_audio_file_with_debug(audio_path, false, DEFAULT_CLIP_THRESHOLD, None, None)
             .map_err(|e| format!("Failed to load audio: {}", e))?;
         
-        let segments = transcribe_with_debug(&ctx, audio_data, language)
+        let segments = transcribe_with_debug(&ctx, audio_data, language, &options, None, None)
             .map_err(|e| format!("Transcription failed: {}", e))?;
         
         // Convert to OpenAI format using our existing converter
@@ -1369,17 +3050,13 @@ pub async fn analyze_risk(text: &str) -> Result<serde_json::Value, String> {
         .to_uppercase();
     
     // Determine if risky
-    let is_risky = raw_response.contains("RISKY");
-    let confidence = if raw_response == "RISKY" || raw_response == "SAFE" {
-        0.9
-    } else {
-        0.5 // Lower confidence for unclear responses
-    };
+    let (risk_level, confidence) = thai_transcriber::RiskConfig::default().classify_response(&raw_response);
     
     let result = serde_json::json!({
         "text": text,
         "risk_analysis": {
-            "is_risky": is_risky,
+            "is_risky": risk_level == thai_transcriber::RiskLevel::Risky,
+            "risk_level": risk_level,
             "raw_response": raw_response,
             "confidence": confidence
         },
@@ -1391,4 +3068,228 @@ pub async fn analyze_risk(text: &str) -> Result<serde_json::Value, String> {
     });
     
     Ok(result)
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, feature = "wav-support"))]
+mod wav_tests {
+    use super::*;
+    
+    const TONE_SAMPLES: usize = 1600;
+    
+    // 1kHz at 16kHz puts a sample exactly on every crest, so the tone peaks at full scale
+    fn tone() -> Vec<f32> {
+        (0..TONE_SAMPLES)
+            .map(|i| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+    
+    fn write_tone(bits_per_sample: u16, sample_format: SampleFormat) -> tempfile::TempPath {
+        let path = tempfile::Builder::new().suffix(".wav").tempfile().unwrap().into_temp_path();
+        let spec = WavSpec { channels: 1, sample_rate: SAMPLE_RATE, bits_per_sample, sample_format };
+        let mut writer = WavWriter::create(&path, spec).unwrap();
+        let full_scale = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+        
+        for sample in tone() {
+            let scaled = (sample as f64 * full_scale).round();
+            match (sample_format, bits_per_sample) {
+                (SampleFormat::Float, _) => writer.write_sample(sample).unwrap(),
+                (SampleFormat::Int, 8) => writer.write_sample(scaled as i8).unwrap(),
+                (SampleFormat::Int, 16) => writer.write_sample(scaled as i16).unwrap(),
+                (SampleFormat::Int, _) => writer.write_sample(scaled as i32).unwrap(),
+            }
+        }
+        writer.finalize().unwrap();
+        path
+    }
+    
+    fn assert_loads_tone(bits_per_sample: u16, sample_format: SampleFormat, tolerance: f32) {
+        let path = write_tone(bits_per_sample, sample_format);
+        let audio = load_wav_file(path.to_str().unwrap(), None).unwrap();
+        
+        assert_eq!(audio.sample_rate, SAMPLE_RATE);
+        assert_eq!(audio.samples.len(), TONE_SAMPLES);
+        
+        let peak = audio.samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((peak - 1.0).abs() < tolerance, "{}-bit {:?}: peak {}", bits_per_sample, sample_format, peak);
+        
+        for (i, (loaded, expected)) in audio.samples.iter().zip(tone()).enumerate() {
+            assert!((loaded - expected).abs() < tolerance,
+                    "{}-bit {:?}: sample {} is {}, expected {}", bits_per_sample, sample_format, i, loaded, expected);
+        }
+    }
+    
+    #[test]
+    fn load_wav_file_normalizes_8_bit() {
+        assert_loads_tone(8, SampleFormat::Int, 0.01);
+    }
+    
+    #[test]
+    fn load_wav_file_normalizes_16_bit() {
+        assert_loads_tone(16, SampleFormat::Int, 1e-4);
+    }
+    
+    #[test]
+    fn load_wav_file_normalizes_24_bit() {
+        assert_loads_tone(24, SampleFormat::Int, 1e-4);
+    }
+    
+    #[test]
+    fn load_wav_file_normalizes_32_bit() {
+        assert_loads_tone(32, SampleFormat::Int, 1e-4);
+    }
+    
+    #[test]
+    fn load_wav_file_keeps_32_bit_float() {
+        assert_loads_tone(32, SampleFormat::Float, 1e-4);
+    }
+}
+
+#[cfg(test)]
+mod raw_pcm_tests {
+    use super::*;
+    
+    // A RIFF chunk, padded to an even length like the loader expects
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+    
+    fn fmt_chunk(format_tag: u16, channels: u16, sample_rate: u32, bits_per_sample: u16) -> Vec<u8> {
+        let block_align = channels * bits_per_sample / 8;
+        let mut body = Vec::new();
+        body.extend_from_slice(&format_tag.to_le_bytes());
+        body.extend_from_slice(&channels.to_le_bytes());
+        body.extend_from_slice(&sample_rate.to_le_bytes());
+        body.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        body.extend_from_slice(&block_align.to_le_bytes());
+        body.extend_from_slice(&bits_per_sample.to_le_bytes());
+        chunk(b"fmt ", &body)
+    }
+    
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(&body);
+        bytes
+    }
+    
+    #[test]
+    fn parse_wav_header_skips_padded_and_unknown_chunks() {
+        let wav = riff(&[
+            chunk(b"LIST", &[1, 2, 3]),
+            fmt_chunk(1, 2, 8000, 24),
+            chunk(b"fact", &[0; 4]),
+            chunk(b"data", &[9, 8, 7, 6, 5, 4]),
+        ]);
+        
+        let (format, data) = parse_wav_header(&wav).unwrap().unwrap();
+        assert_eq!(format, RawPcmFormat { sample_rate: 8000, bits_per_sample: 24, channels: 2, big_endian: false });
+        assert_eq!(data, &[9, 8, 7, 6, 5, 4]);
+    }
+    
+    #[test]
+    fn parse_wav_header_rejects_data_before_fmt() {
+        let wav = riff(&[chunk(b"data", &[0; 4]), fmt_chunk(1, 1, 16000, 16)]);
+        assert!(parse_wav_header(&wav).unwrap_err().contains("before the fmt chunk"));
+    }
+    
+    #[test]
+    fn parse_wav_header_rejects_unreadable_wavs() {
+        let float = riff(&[fmt_chunk(3, 1, 16000, 32), chunk(b"data", &[0; 4])]);
+        assert!(parse_wav_header(&float).unwrap_err().contains("not integer PCM"));
+        
+        let no_data = riff(&[fmt_chunk(1, 1, 16000, 16)]);
+        assert!(parse_wav_header(&no_data).unwrap_err().contains("no data chunk"));
+        
+        let odd_depth = riff(&[fmt_chunk(1, 1, 16000, 12), chunk(b"data", &[0; 4])]);
+        assert!(parse_wav_header(&odd_depth).is_err());
+    }
+    
+    #[test]
+    fn parse_wav_header_leaves_headerless_audio_alone() {
+        assert_eq!(parse_wav_header(&[0x12, 0x34, 0x56, 0x78]), Ok(None));
+        assert_eq!(parse_wav_header(&[0; 64]), Ok(None));
+        let mut not_wave = riff(&[fmt_chunk(1, 1, 16000, 16)]);
+        not_wave[8..12].copy_from_slice(b"AVI ");
+        assert_eq!(parse_wav_header(&not_wave), Ok(None));
+    }
+    
+    #[test]
+    fn raw_format_parses_specs() {
+        assert_eq!(RawPcmFormat::parse("8000:16:1"),
+                   Ok(RawPcmFormat { sample_rate: 8000, bits_per_sample: 16, channels: 1, big_endian: false }));
+        assert_eq!(RawPcmFormat::parse("44100 : 24 : 2 : BE"),
+                   Ok(RawPcmFormat { sample_rate: 44100, bits_per_sample: 24, channels: 2, big_endian: true }));
+        assert_eq!(RawPcmFormat::parse("16000:8:1:le").map(|format| format.big_endian), Ok(false));
+    }
+    
+    #[test]
+    fn raw_format_rejects_bad_specs() {
+        for spec in ["8000:16", "8000:16:1:le:x", "0:16:1", "rate:16:1", "8000:12:1", "8000:16:0", "8000:16:1:mixed"] {
+            assert!(RawPcmFormat::parse(spec).is_err(), "{} should not parse", spec);
+        }
+    }
+    
+    fn decode(spec: &str, bytes: &[u8]) -> Vec<f32> {
+        RawPcmFormat::parse(spec).unwrap().decode(bytes)
+    }
+    
+    #[test]
+    fn decode_sign_extends_24_bit_samples() {
+        let samples = decode("16000:24:1", &[0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x80, 0xFF, 0xFF, 0x7F]);
+        assert_eq!(samples, vec![-1.0 / 8_388_608.0, -1.0, 8_388_607.0 / 8_388_608.0]);
+    }
+    
+    #[test]
+    fn decode_centers_unsigned_8_bit_samples() {
+        assert_eq!(decode("8000:8:1", &[0, 128, 255]), vec![-1.0, 0.0, 127.0 / 128.0]);
+    }
+    
+    #[test]
+    fn decode_reads_either_byte_order() {
+        assert_eq!(decode("16000:16:1:be", &[0x80, 0x00, 0x40, 0x00]), vec![-1.0, 0.5]);
+        assert_eq!(decode("16000:16:1:le", &[0x00, 0x80, 0x00, 0x40]), vec![-1.0, 0.5]);
+        assert_eq!(decode("16000:32:1:be", &[0xC0, 0, 0, 0]), vec![-0.5]);
+    }
+    
+    #[test]
+    fn decode_drops_a_partial_trailing_sample() {
+        assert_eq!(decode("16000:16:1", &[0x00, 0x40, 0x12]), vec![0.5]);
+    }
+}
+
+#[cfg(test)]
+mod thai_word_tests {
+    use super::*;
+    
+    #[test]
+    fn word_timings_follow_character_counts() {
+        let logger = Logger::new("test.wav", "th");
+        // Thai characters are 3 bytes in UTF-8 and digits 1, so shares by byte length would differ
+        let text = "ราคาตั๋ว 500 บาท";
+        let words = logger.create_thai_word_segments(text, 10.0, 6.0, 0.9);
+        
+        let total_chars: usize = words.iter().map(|word| word.text.chars().count()).sum();
+        let total_bytes: usize = words.iter().map(|word| word.text.len()).sum();
+        assert!(words.len() > 2, "expected several words, got {:?}", words);
+        assert_ne!(total_chars * 3, total_bytes);
+        
+        assert!((words[0].start - 10.0).abs() < 1e-9);
+        assert!((words[words.len() - 1].end - 16.0).abs() < 1e-9);
+        for word in &words {
+            let expected = 6.0 * word.text.chars().count() as f64 / total_chars as f64;
+            assert!((word.end - word.start - expected).abs() < 1e-9,
+                    "'{}' lasts {}s, expected {}s", word.text, word.end - word.start, expected);
+        }
+        for pair in words.windows(2) {
+            assert!((pair[0].end - pair[1].start).abs() < 1e-9);
+        }
+    }
+}
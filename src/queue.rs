@@ -1,16 +1,18 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 use actix::prelude::*;
 use actix_web_actors::ws;
-use redis::{Client as RedisClient, aio::ConnectionManager, AsyncCommands};
+use redis::{Client as RedisClient, aio::{ConnectionManager, MultiplexedConnection}, AsyncCommands};
 use serde::{Deserialize, Serialize};
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{Mutex, Notify, OwnedSemaphorePermit, RwLock, Semaphore};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
 // Import the transcribe function from lib.rs using crate root
-use crate::transcribe_audio_file;
+use crate::{language_model_path, punctuation_endpoint, restore_punctuation, transcribe_audio_file_with_engine, validate_clip_threshold, ProgressCallback, SegmentLimits, TranscribeOptions, DecodeThresholds, WhisperEngine, AUDIO_LOAD_PROGRESS_SHARE, DEFAULT_MAX_SEGMENTS, MAX_CHUNK_OVERLAP_SECONDS};
 
 // Custom error type that is Send + Sync
 #[derive(Debug)]
@@ -61,6 +63,188 @@ pub struct TaskRequest {
     pub payload: serde_json::Value,
 }
 
+/// Payload of a transcription task, checked when the task is submitted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionPayload {
+    pub file_path: String,
+    #[serde(default)]
+    pub original_filename: Option<String>,
+    #[serde(default = "default_backend")]
+    pub backend: String,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub risk_analysis: bool,
+    #[serde(default)]
+    pub trim_silence: bool,
+    #[serde(default)]
+    pub translate: bool,
+    #[serde(default)]
+    pub declip: bool,
+    #[serde(default)]
+    pub clip_threshold: Option<f32>,
+    #[serde(default)]
+    pub agc_target_dbfs: Option<f32>,
+    #[serde(default)]
+    pub channel: Option<usize>,
+    #[serde(default)]
+    pub chunk_overlap_seconds: Option<f64>,
+    #[serde(default)]
+    pub min_confidence: Option<f64>,
+    #[serde(default)]
+    pub speaker_gap: Option<f64>,
+    #[serde(default)]
+    pub offset_seconds: f64,
+    #[serde(default)]
+    pub initial_prompt: Option<String>,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub file_size_bytes: Option<u64>,
+    #[serde(default)]
+    pub duration_seconds: Option<f64>,
+    #[serde(default)]
+    pub max_total_duration_minutes: Option<f64>,
+    #[serde(default)]
+    pub max_repeat: Option<usize>,
+    #[serde(default)]
+    pub max_segment_len: Option<usize>,
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+    #[serde(default)]
+    pub max_segments: Option<usize>,
+    #[serde(default)]
+    pub word_confidence_threshold: Option<f64>,
+    #[serde(default)]
+    pub keep_audio: bool,
+    #[serde(default)]
+    pub no_speech_threshold: Option<f32>,
+    #[serde(default)]
+    pub logprob_threshold: Option<f32>,
+    #[serde(default)]
+    pub entropy_threshold: Option<f32>,
+    #[serde(default)]
+    pub compression_ratio_threshold: Option<f32>,
+    #[serde(default)]
+    pub restore_punctuation: bool,
+    #[serde(default)]
+    pub paragraphs: bool,
+    /// Overrides the timeout estimated from file size and duration, capped at `MAX_TASK_TIMEOUT_SECS`
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    /// Content hash of the decoded audio, set when the client asked for cache=true
+    #[serde(default)]
+    pub audio_hash: Option<String>,
+}
+
+fn default_backend() -> String {
+    "auto".to_string()
+}
+
+// Payload fields that don't change the transcript: where the upload is, bookkeeping and limits
+const FINGERPRINT_IGNORED_FIELDS: &[&str] = &[
+    "file_path", "original_filename", "request_id", "file_size_bytes", "duration_seconds",
+    "max_total_duration_minutes", "timeout_seconds", "keep_audio", "risk_analysis", "audio_hash",
+];
+
+impl TranscriptionPayload {
+    // Hash of every output-affecting option plus the model that runs them, so a cached
+    // result is only reused for a request that would produce the same transcript
+    fn output_fingerprint(&self, model_path: Option<&str>) -> String {
+        use sha2::{Digest, Sha256};
+        
+        let mut options = serde_json::to_value(self).unwrap_or_default();
+        if let Some(options) = options.as_object_mut() {
+            for field in FINGERPRINT_IGNORED_FIELDS {
+                options.remove(*field);
+            }
+            options.insert("model_path".to_string(), serde_json::json!(model_path));
+        }
+        // serde_json maps are sorted by key, so equal options always hash the same
+        let digest = Sha256::digest(options.to_string().as_bytes());
+        digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl TranscriptionPayload {
+    pub fn from_value(payload: &serde_json::Value) -> Result<Self, String> {
+        let parsed: Self = serde_json::from_value(payload.clone())
+            .map_err(|e| format!("Invalid transcription payload: {}", e))?;
+        
+        if parsed.file_path.trim().is_empty() {
+            return Err("Invalid transcription payload: file_path is empty".to_string());
+        }
+        if let Some(threshold) = parsed.clip_threshold {
+            validate_clip_threshold(threshold)
+                .map_err(|e| format!("Invalid transcription payload: {}", e))?;
+        }
+        if let Some(target) = parsed.agc_target_dbfs {
+            if !(-60.0..=0.0).contains(&target) {
+                return Err(format!("Invalid transcription payload: agc_target_dbfs must be in [-60, 0], got {}", target));
+            }
+        }
+        if let Some(overlap) = parsed.chunk_overlap_seconds {
+            if !(0.0..=MAX_CHUNK_OVERLAP_SECONDS).contains(&overlap) {
+                return Err(format!("Invalid transcription payload: chunk_overlap_seconds must be in [0, {}], got {}", MAX_CHUNK_OVERLAP_SECONDS, overlap));
+            }
+        }
+        if let Some(min_confidence) = parsed.min_confidence {
+            if !(0.0..=1.0).contains(&min_confidence) {
+                return Err(format!("Invalid transcription payload: min_confidence must be in [0, 1], got {}", min_confidence));
+            }
+        }
+        if !parsed.offset_seconds.is_finite() || parsed.offset_seconds < 0.0 {
+            return Err(format!("Invalid transcription payload: offset_seconds must be a non-negative number, got {}", parsed.offset_seconds));
+        }
+        if let Some(threshold) = parsed.word_confidence_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(format!("Invalid transcription payload: word_confidence_threshold must be in [0, 1], got {}", threshold));
+            }
+        }
+        if let Some(gap) = parsed.speaker_gap {
+            if gap.is_nan() || gap <= 0.0 {
+                return Err(format!("Invalid transcription payload: speaker_gap must be positive, got {}", gap));
+            }
+        }
+        if let Some(max_minutes) = parsed.max_total_duration_minutes {
+            if max_minutes.is_nan() || max_minutes <= 0.0 {
+                return Err(format!("Invalid transcription payload: max_total_duration_minutes must be positive, got {}", max_minutes));
+            }
+        }
+        if parsed.max_repeat == Some(0) {
+            return Err("Invalid transcription payload: max_repeat must be at least 1".to_string());
+        }
+        if parsed.max_segment_len == Some(0) {
+            return Err("Invalid transcription payload: max_segment_len must be at least 1".to_string());
+        }
+        if parsed.max_tokens == Some(0) {
+            return Err("Invalid transcription payload: max_tokens must be at least 1".to_string());
+        }
+        if parsed.max_segments == Some(0) {
+            return Err("Invalid transcription payload: max_segments must be at least 1".to_string());
+        }
+        if parsed.timeout_seconds == Some(0) {
+            return Err("Invalid transcription payload: timeout_seconds must be at least 1".to_string());
+        }
+        
+        parsed.decode_thresholds().validate()
+            .map_err(|e| format!("Invalid transcription payload: {}", e))?;
+        
+        Ok(parsed)
+    }
+    
+    /// Upstream Whisper's thresholds with this payload's overrides
+    pub fn decode_thresholds(&self) -> DecodeThresholds {
+        let defaults = DecodeThresholds::default();
+        DecodeThresholds {
+            no_speech: self.no_speech_threshold.unwrap_or(defaults.no_speech),
+            logprob: self.logprob_threshold.unwrap_or(defaults.logprob),
+            entropy: self.entropy_threshold.unwrap_or(defaults.entropy),
+            compression_ratio: self.compression_ratio_threshold.unwrap_or(defaults.compression_ratio),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskResult {
     pub id: String,
@@ -74,6 +258,15 @@ pub struct TaskResult {
     pub progress: f32,
 }
 
+/// A permanently failed task kept in the `dead_letter_queue` Redis list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetterEntry {
+    pub task_id: String,
+    pub error: String,
+    pub failed_at: DateTime<Utc>,
+    pub request: Option<TaskRequest>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueueStats {
     pub pending_count: usize,
@@ -83,18 +276,94 @@ pub struct QueueStats {
     pub total_tasks: usize,
 }
 
+/// Upper bounds, in seconds, of the task duration histogram in `QueueMetrics`
+pub const DURATION_BUCKETS_SECONDS: [f64; 10] = [1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0];
+
+// Running totals since startup; unlike QueueStats they don't shrink when history is purged
+#[derive(Default)]
+struct QueueCounters {
+    submitted: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicU64,
+    dead_lettered: AtomicU64,
+    // Cumulative: each bucket counts the durations at or below its bound
+    duration_buckets: [AtomicU64; DURATION_BUCKETS_SECONDS.len()],
+    duration_sum_millis: AtomicU64,
+    duration_count: AtomicU64,
+}
+
+impl QueueCounters {
+    // Count a task that reached a terminal status, observing its run time if it started
+    fn record_finished(&self, task: &TaskResult) {
+        let counter = match task.status {
+            TaskStatus::Completed => &self.completed,
+            TaskStatus::Failed => &self.failed,
+            TaskStatus::Cancelled => &self.cancelled,
+            TaskStatus::Pending | TaskStatus::Processing => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        
+        let Some(millis) = task.completed_at.zip(task.started_at)
+            .map(|(completed, started)| (completed - started).num_milliseconds().max(0) as u64)
+        else {
+            return;
+        };
+        let seconds = millis as f64 / 1000.0;
+        for (bucket, bound) in self.duration_buckets.iter().zip(DURATION_BUCKETS_SECONDS) {
+            if seconds <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.duration_sum_millis.fetch_add(millis, Ordering::Relaxed);
+        self.duration_count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Monotonic counters since the server started, for the `*_total` metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueMetrics {
+    pub submitted_total: u64,
+    pub completed_total: u64,
+    pub failed_total: u64,
+    pub cancelled_total: u64,
+    pub dead_lettered_total: u64,
+    /// Cumulative counts per `DURATION_BUCKETS_SECONDS` bound
+    pub duration_buckets: Vec<u64>,
+    pub duration_sum_seconds: f64,
+    pub duration_count: u64,
+}
+
 #[derive(Message, Clone)]
 #[rtype(result = "()")]
 pub struct WebSocketMessage {
     pub message: String,
 }
 
+/// A connected WebSocket client and the task it is subscribed to, if any
+#[derive(Clone)]
+pub struct WebSocketSubscriber {
+    pub addr: Recipient<WebSocketMessage>,
+    pub task_filter: Option<String>,
+}
+
 #[derive(Message)]
-#[rtype(result = "Result<String, String>")]
+#[rtype(result = "Result<SubmitOutcome, String>")]
 pub struct SubmitTask {
     pub task_type: TaskType,
     pub payload: serde_json::Value,
     pub priority: Option<i32>,
+    /// Client-supplied key; resubmitting with the same key returns the original task
+    pub idempotency_key: Option<String>,
+}
+
+/// Outcome of a task submission
+#[derive(Debug, Clone)]
+pub enum SubmitOutcome {
+    /// A new task was queued
+    Created(String),
+    /// The idempotency key was already used; nothing new was queued
+    Existing(String),
 }
 
 #[derive(Message)]
@@ -103,153 +372,1416 @@ pub struct GetTaskStatus {
     pub task_id: String,
 }
 
+/// Look up a completed transcription of the same audio run with the same
+/// output-affecting options in `payload` and the same model
+#[derive(Message)]
+#[rtype(result = "Result<Option<TaskResult>, String>")]
+pub struct FindCachedResult {
+    pub audio_hash: String,
+    pub payload: serde_json::Value,
+}
+
 #[derive(Message)]
 #[rtype(result = "Result<QueueStats, String>")]
 pub struct GetQueueStats;
 
+/// Number of tasks waiting in the queue, straight from the store
 #[derive(Message)]
-#[rtype(result = "Result<Vec<TaskResult>, String>")]
+#[rtype(result = "Result<usize, String>")]
+pub struct GetQueueDepth;
+
+/// Read the `QueueMetrics` counters; cheap enough for every metrics scrape
+#[derive(Message)]
+#[rtype(result = "QueueMetrics")]
+pub struct GetQueueMetrics;
+
+/// History newest first by creation time: `offset` tasks are skipped (after `cursor`, when given), then `limit` returned
+#[derive(Message)]
+#[rtype(result = "Result<TaskHistoryPage, String>")]
 pub struct GetTaskHistory {
     pub limit: Option<usize>,
+    pub offset: usize,
+    pub cursor: Option<HistoryCursor>,
     pub status_filter: Option<TaskStatus>,
 }
 
+/// One page of task history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskHistoryPage {
+    pub tasks: Vec<TaskResult>,
+    /// Tasks matching the status filter across all pages
+    pub total: usize,
+    /// Pass back as `cursor` to get the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Where a history page ended: the last task it returned. Unlike an offset it
+/// stays put when new tasks arrive at the top of the history between requests,
+/// and since `created_at` never changes, tasks that progress don't move across it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryCursor {
+    pub created_at: DateTime<Utc>,
+    pub task_id: String,
+}
+
+impl HistoryCursor {
+    fn for_task(task: &TaskResult) -> Self {
+        Self {
+            created_at: task.created_at,
+            task_id: task.id.clone(),
+        }
+    }
+    
+    /// Parse the `<created_at micros>:<task_id>` form handed out as `next_cursor`
+    pub fn parse(cursor: &str) -> Result<Self, String> {
+        let (micros, task_id) = cursor.split_once(':')
+            .ok_or_else(|| format!("Invalid cursor '{}'", cursor))?;
+        let created_at = micros.parse::<i64>().ok()
+            .and_then(DateTime::<Utc>::from_timestamp_micros)
+            .ok_or_else(|| format!("Invalid cursor '{}'", cursor))?;
+        if task_id.is_empty() {
+            return Err(format!("Invalid cursor '{}'", cursor));
+        }
+        Ok(Self { created_at, task_id: task_id.to_string() })
+    }
+    
+    pub fn encode(&self) -> String {
+        format!("{}:{}", self.created_at.timestamp_micros(), self.task_id)
+    }
+    
+    // Whether a task sorts after this cursor in newest-first order
+    fn is_before(&self, task: &TaskResult) -> bool {
+        (task.created_at, task.id.as_str()) < (self.created_at, self.task_id.as_str())
+    }
+}
+
 #[derive(Message)]
 #[rtype(result = "Result<usize, String>")]
 pub struct CleanupStaleTasks;
 
-pub struct TaskQueue {
+#[derive(Message)]
+#[rtype(result = "Result<(), String>")]
+pub struct PingRedis;
+
+#[derive(Message)]
+#[rtype(result = "Result<usize, String>")]
+pub struct PurgeTasks {
+    pub status: TaskStatus,
+    pub older_than_hours: Option<u64>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<SegmentUpdate, String>")]
+pub struct UpdateSegmentText {
+    pub task_id: String,
+    pub segment_id: i64,
+    pub text: String,
+}
+
+/// Outcome of a manual segment correction
+#[derive(Debug, Clone)]
+pub enum SegmentUpdate {
+    /// The corrected segment as stored
+    Updated(serde_json::Value),
+    TaskNotFound,
+    NotCompleted(TaskStatus),
+    /// The task has no segments, or none with the requested id
+    SegmentNotFound,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<CancelOutcome, String>")]
+pub struct CancelTask {
+    pub task_id: String,
+}
+
+/// Outcome of a cancellation request
+#[derive(Debug, Clone)]
+pub enum CancelOutcome {
+    /// Taken off the queue before any work started
+    Cancelled,
+    TaskNotFound,
+    /// A worker already picked the task up; running tasks are not aborted
+    AlreadyRunning,
+    /// Completed, failed or cancelled earlier
+    AlreadyFinished(TaskStatus),
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<TaskAudio, String>")]
+pub struct GetTaskAudio {
+    pub task_id: String,
+}
+
+/// The warm engine for `language` and the task slots, so live sessions decode on the
+/// queue's model and count against its concurrency limit
+#[derive(Message)]
+#[rtype(result = "Result<(WhisperEngine, Arc<Semaphore>), String>")]
+pub struct GetLiveDecoder {
+    pub language: Option<String>,
+}
+
+/// The Whisper input audio kept for a task submitted with `keep_audio`
+#[derive(Debug, Clone)]
+pub enum TaskAudio {
+    /// 16kHz mono 32-bit float WAV file, bit-exact with what Whisper received
+    Available(PathBuf),
+    TaskNotFound,
+    NotCompleted(TaskStatus),
+    /// Not requested at submission, or already expired
+    NotKept,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Vec<DeadLetterEntry>, String>")]
+pub struct GetDeadLetters {
+    pub limit: Option<usize>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<Option<String>, String>")]
+pub struct RequeueDeadLetter {
+    pub task_id: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<bool, String>")]
+pub struct DiscardDeadLetter {
+    pub task_id: String,
+}
+
+// How long a single BZPOPMIN waits before the processor loop comes around again
+const BLOCKING_POP_TIMEOUT_SECS: f64 = 5.0;
+
+// Tasks executed at once unless overridden; each one decodes with its own Whisper state on the shared model
+pub const DEFAULT_MAX_CONCURRENT_TASKS: usize = 2;
+
+// Seconds between queue_stats_update broadcasts; 0 disables them
+pub const DEFAULT_STATS_INTERVAL_SECS: u64 = 30;
+
+// Seconds a processing task may go without a progress update before cleanup fails it
+pub const DEFAULT_STALE_TASK_THRESHOLD_SECS: u64 = 3600;
+
+// Transcripts shorter than this many characters aren't sent to automatic risk analysis
+pub const DEFAULT_MIN_CHARS_FOR_RISK: usize = 10;
+
+// ...nor transcripts with fewer words than this (Thai text split with thai::segment_words)
+pub const DEFAULT_MIN_WORDS_FOR_RISK: usize = 3;
+
+// Longest a transcription may run, whether its timeout was estimated or set with timeout_seconds
+pub const MAX_TASK_TIMEOUT_SECS: u64 = 1800;
+
+// How long the Whisper input audio of a keep_audio task stays on disk after the task completes
+const KEPT_AUDIO_TTL_SECS: u64 = 3600;
+
+// Dead-lettered tasks are dropped, along with the uploaded audio kept for a requeue,
+// this long after they failed
+const DEAD_LETTER_TTL_SECS: u64 = 7 * 24 * 3600;
+
+// How often expired kept audio and dead letters are swept
+const SWEEP_INTERVAL_SECS: u64 = 300;
+
+// Recent broadcasts kept per task for WebSocket clients replaying after a reconnect,
+// and how many tasks keep such a buffer before the least recently active one is dropped
+const REPLAY_EVENTS_PER_TASK: usize = 50;
+const REPLAY_MAX_TASKS: usize = 1000;
+
+/// Whether the server may connect to `ip` on a caller's behalf. Loopback, private,
+/// link-local (cloud metadata at 169.254.169.254), shared and reserved ranges are refused,
+/// as are IPv6 forms that embed or translate to an IPv4 address.
+pub fn is_public_ip(ip: std::net::IpAddr) -> bool {
+    match ip {
+        std::net::IpAddr::V4(v4) => {
+            let [a, b, c, _] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_multicast()
+                || a == 0
+                // Carrier-grade NAT 100.64.0.0/10
+                || (a == 100 && (b & 0xc0) == 64)
+                // IETF protocol assignments 192.0.0.0/24 and the 6to4 relay 192.88.99.0/24
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 192 && b == 88 && c == 99)
+                // Benchmarking 198.18.0.0/15
+                || (a == 198 && (b & 0xfe) == 18)
+                // Reserved 240.0.0.0/4
+                || a >= 240)
+        }
+        std::net::IpAddr::V6(v6) => {
+            // IPv4-mapped ::ffff:a.b.c.d and IPv4-compatible ::a.b.c.d reach the IPv4 address
+            if let Some(v4) = v6.to_ipv4() {
+                return is_public_ip(std::net::IpAddr::V4(v4));
+            }
+            let segments = v6.segments();
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                // NAT64 64:ff9b::/96 and 64:ff9b:1::/48 translate to arbitrary IPv4 addresses
+                || (segments[0] == 0x64 && segments[1] == 0xff9b)
+                // 6to4 2002::/16, Teredo 2001::/32 and documentation 2001:db8::/32
+                || segments[0] == 0x2002
+                || (segments[0] == 0x2001 && (segments[1] == 0 || segments[1] == 0xdb8)))
+        }
+    }
+}
+
+// The URL's host when it is an IP literal, which connects without any DNS lookup
+fn url_ip_literal(url: &reqwest::Url) -> Option<std::net::IpAddr> {
+    url.host_str()?.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+fn check_public_addrs(host: &str, addrs: &[std::net::SocketAddr]) -> Result<(), String> {
+    if addrs.is_empty() {
+        return Err(format!("'{}' did not resolve to any address", host));
+    }
+    match addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        Some(addr) => Err(format!("'{}' resolves to non-public address {}", host, addr.ip())),
+        None => Ok(()),
+    }
+}
+
+/// Refuse `url` unless it is http(s) and its host is a public IP literal. Hostnames pass;
+/// they are checked when resolved, by `PublicResolver`. Cheap enough for redirect policies.
+pub fn check_url_host_literal(url: &reqwest::Url) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("'{}' is not an http(s) URL", url));
+    }
+    if url.host_str().is_none() {
+        return Err(format!("'{}' has no host", url));
+    }
+    match url_ip_literal(url) {
+        Some(ip) if !is_public_ip(ip) => Err(format!("'{}' is a non-public address", ip)),
+        _ => Ok(()),
+    }
+}
+
+/// Resolve `url`'s host and refuse it unless every address is public, to reject bad
+/// URLs up front. Requests must still go through `public_client_builder`, since the
+/// host may resolve differently when the connection is made.
+pub async fn check_public_url(url: &reqwest::Url) -> Result<(), String> {
+    check_url_host_literal(url)?;
+    if url_ip_literal(url).is_some() {
+        return Ok(());
+    }
+    let host = url.host_str().unwrap_or_default();
+    let addrs: Vec<std::net::SocketAddr> = tokio::net::lookup_host((host, 0)).await
+        .map_err(|e| format!("Failed to resolve '{}': {}", host, e))?
+        .collect();
+    check_public_addrs(host, &addrs)
+}
+
+/// DNS resolver for requests made on a caller's behalf: names with any non-public address
+/// fail to resolve. reqwest connects to exactly the addresses returned here, so a DNS
+/// server can't answer a public address for a check and a private one for the request.
+pub struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let host = name.as_str();
+            let addrs: Vec<std::net::SocketAddr> = tokio::net::lookup_host((host, 0)).await?.collect();
+            check_public_addrs(host, &addrs)?;
+            let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// Client builder for fetching caller-supplied URLs: hostnames go through `PublicResolver`
+/// and proxies are off, since a proxy would resolve the host itself. IP literals skip DNS,
+/// so check URLs (and redirect targets) with `check_url_host_literal` as well.
+pub fn public_client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .no_proxy()
+        .dns_resolver(Arc::new(PublicResolver))
+}
+
+// Delivery attempts for a completion callback before giving up
+const CALLBACK_ATTEMPTS: u32 = 3;
+
+// Per-attempt timeout for a completion callback POST
+const CALLBACK_TIMEOUT_SECS: u64 = 10;
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>` when a webhook secret is configured
+pub const CALLBACK_SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+/// The optional `callback_url` of a task payload. Only absolute http(s) URLs are accepted,
+/// and IP literal hosts must be public; see `check_callback_url` for hostnames.
+pub fn callback_url(payload: &serde_json::Value) -> Result<Option<String>, String> {
+    let url = match payload.get("callback_url") {
+        None | Some(serde_json::Value::Null) => return Ok(None),
+        Some(serde_json::Value::String(url)) => url.trim(),
+        Some(other) => return Err(format!("Invalid callback_url: expected a string, got {}", other)),
+    };
+    
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|_| format!("Invalid callback_url: expected an http(s) URL, got {}", url))?;
+    check_url_host_literal(&parsed).map_err(|e| format!("Invalid callback_url: {}", e))?;
+    Ok(Some(url.to_string()))
+}
+
+/// `callback_url` plus resolving the host, so callbacks to internal addresses are refused
+/// when the task is submitted rather than failing silently at delivery
+pub async fn check_callback_url(payload: &serde_json::Value) -> Result<Option<String>, String> {
+    let Some(url) = callback_url(payload)? else {
+        return Ok(None);
+    };
+    let parsed = reqwest::Url::parse(&url).map_err(|e| format!("Invalid callback_url: {}", e))?;
+    check_public_url(&parsed).await.map_err(|e| format!("Invalid callback_url: {}", e))?;
+    Ok(Some(url))
+}
+
+/// Value of `CALLBACK_SIGNATURE_HEADER` for `body` signed with `secret`
+pub fn callback_signature(secret: &str, body: &[u8]) -> String {
+    use hmac::{Hmac, Mac};
+    
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let hex: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+// How long an idempotency key keeps pointing at the task it created
+const IDEMPOTENCY_KEY_TTL_SECS: u64 = 24 * 3600;
+
+// Where the worker thread writes the Whisper input audio; the store only keeps this path
+const KEPT_AUDIO_FILE_PREFIX: &str = "whisper_kept_audio_";
+
+fn kept_audio_path(task_id: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("{}{}.wav", KEPT_AUDIO_FILE_PREFIX, task_id))
+        .to_string_lossy()
+        .to_string()
+}
+
+// The uploaded audio of a transcription request
+fn uploaded_audio_path(request: &TaskRequest) -> Option<&str> {
+    if !matches!(request.task_type, TaskType::Transcription) {
+        return None;
+    }
+    request.payload.get("file_path").and_then(|v| v.as_str())
+}
+
+// Delete an audio file the queue owns: an upload nothing can run again, or kept Whisper input
+fn remove_audio_file(task_id: &str, path: &str) {
+    match std::fs::remove_file(path) {
+        Ok(()) => log::info!("Removed temp file for task {}: {}", task_id, path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => log::warn!("Failed to remove temp file {} for task {}: {}", path, task_id, e),
+    }
+}
+
+/// Storage behind `TaskQueue`: task requests, the pending queue, dead letters and
+/// short-lived keys. The queue logic only talks to this trait, so Redis can be
+/// swapped for `MemoryStore` without touching the actor or its handlers.
+#[async_trait::async_trait]
+pub trait TaskStore: Send + Sync {
+    /// Backend name for logs and health checks
+    fn name(&self) -> &'static str;
+    /// False when everything is lost on restart; task results then live only in the `task_results` cache
+    fn is_persistent(&self) -> bool;
+    async fn get(&self, key: &str) -> Result<Option<String>, QueueError>;
+    async fn set(&self, key: &str, value: &str) -> Result<(), QueueError>;
+    /// Set `key` only if it doesn't exist yet, expiring after `ttl_secs`. True when it was set.
+    async fn set_if_absent(&self, key: &str, value: &str, ttl_secs: u64) -> Result<bool, QueueError>;
+    async fn set_with_ttl(&self, key: &str, value: &str, ttl_secs: u64) -> Result<(), QueueError>;
+    async fn del(&self, key: &str) -> Result<(), QueueError>;
+    /// All keys starting with `prefix`
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, QueueError>;
+    /// Queue a task; lower scores are taken first
+    async fn enqueue(&self, task_id: &str, score: f64) -> Result<(), QueueError>;
+    /// Take the lowest-scored task, waiting up to `BLOCKING_POP_TIMEOUT_SECS` for one
+    async fn pop_next(&self) -> Result<Option<String>, QueueError>;
+    /// Take a task off the queue; false when it was no longer queued
+    async fn remove_queued(&self, task_id: &str) -> Result<bool, QueueError>;
+    async fn queue_len(&self) -> Result<usize, QueueError>;
+    /// Add a serialized `DeadLetterEntry`, newest first
+    async fn push_dead_letter(&self, data: &str) -> Result<(), QueueError>;
+    /// Serialized dead-letter entries, newest first, at most `limit`
+    async fn dead_letters(&self, limit: Option<usize>) -> Result<Vec<String>, QueueError>;
+    async fn remove_dead_letter(&self, data: &str) -> Result<(), QueueError>;
+    async fn ping(&self) -> Result<(), QueueError>;
+}
+
+/// Redis-backed `TaskStore`: a sorted set for the queue, a list for dead letters
+pub struct RedisStore {
+    redis_client: RedisClient,
     redis_manager: ConnectionManager,
+    // Dedicated connection for BZPOPMIN; None falls back to polling
+    blocking_conn: Mutex<Option<MultiplexedConnection>>,
+}
+
+impl RedisStore {
+    pub async fn connect(redis_url: &str) -> Result<Self, QueueError> {
+        let client = RedisClient::open(redis_url)?;
+        let redis_manager = ConnectionManager::new(client.clone()).await?;
+        let blocking_conn = Self::open_blocking_connection(&client).await;
+        
+        Ok(Self {
+            redis_client: client,
+            redis_manager,
+            blocking_conn: Mutex::new(blocking_conn),
+        })
+    }
+    
+    // Dedicated connection for BZPOPMIN, which would otherwise stall every other
+    // command sharing the multiplexed ConnectionManager while it waits
+    async fn open_blocking_connection(client: &RedisClient) -> Option<MultiplexedConnection> {
+        match client.get_multiplexed_async_connection().await {
+            Ok(conn) => Some(conn),
+            Err(e) => {
+                log::warn!("Could not open blocking Redis connection, polling the queue instead: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TaskStore for RedisStore {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+    
+    fn is_persistent(&self) -> bool {
+        true
+    }
+    
+    async fn get(&self, key: &str) -> Result<Option<String>, QueueError> {
+        let mut conn = self.redis_manager.clone();
+        let value: Option<String> = conn.get(key).await?;
+        Ok(value)
+    }
+    
+    async fn set(&self, key: &str, value: &str) -> Result<(), QueueError> {
+        let mut conn = self.redis_manager.clone();
+        conn.set::<_, _, ()>(key, value).await?;
+        Ok(())
+    }
+    
+    async fn set_if_absent(&self, key: &str, value: &str, ttl_secs: u64) -> Result<bool, QueueError> {
+        let mut conn = self.redis_manager.clone();
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async(&mut conn)
+            .await?;
+        Ok(claimed.is_some())
+    }
+    
+    async fn set_with_ttl(&self, key: &str, value: &str, ttl_secs: u64) -> Result<(), QueueError> {
+        let mut conn = self.redis_manager.clone();
+        conn.set_ex::<_, _, ()>(key, value, ttl_secs).await?;
+        Ok(())
+    }
+    
+    async fn del(&self, key: &str) -> Result<(), QueueError> {
+        let mut conn = self.redis_manager.clone();
+        conn.del::<_, ()>(key).await?;
+        Ok(())
+    }
+    
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, QueueError> {
+        let mut conn = self.redis_manager.clone();
+        let keys: Vec<String> = conn.keys(format!("{}*", prefix)).await?;
+        Ok(keys)
+    }
+    
+    async fn enqueue(&self, task_id: &str, score: f64) -> Result<(), QueueError> {
+        let mut conn = self.redis_manager.clone();
+        // Use sorted set for priority queue
+        conn.zadd::<_, _, _, ()>("task_queue", task_id, score).await?;
+        Ok(())
+    }
+    
+    async fn pop_next(&self) -> Result<Option<String>, QueueError> {
+        let mut blocking_conn = self.blocking_conn.lock().await;
+        
+        if let Some(conn) = blocking_conn.as_mut() {
+            // Pops the oldest task atomically, or returns nil once the timeout elapses
+            let result: Result<Option<(String, String, f64)>, redis::RedisError> = conn.bzpopmin("task_queue", BLOCKING_POP_TIMEOUT_SECS).await;
+            return match result {
+                Ok(popped) => Ok(popped.map(|(_, task_id, _)| task_id)),
+                Err(e) if e.kind() == redis::ErrorKind::ResponseError
+                    && e.to_string().to_lowercase().contains("unknown command") => {
+                    // BZPOPMIN needs Redis 5.0+
+                    log::warn!("Redis does not support BZPOPMIN, falling back to polling: {}", e);
+                    *blocking_conn = None;
+                    Ok(None)
+                }
+                Err(e) => {
+                    // Reconnect before the next attempt
+                    *blocking_conn = Self::open_blocking_connection(&self.redis_client).await;
+                    Err(e.into())
+                }
+            };
+        }
+        drop(blocking_conn);
+        
+        let mut conn = self.redis_manager.clone();
+        
+        // Get the oldest task (lowest score)
+        let result: Vec<String> = conn.zrange("task_queue", 0, 0).await?;
+        
+        if let Some(task_id) = result.first() {
+            // ZREM decides the race with cancel_task and other workers
+            let removed: usize = conn.zrem("task_queue", task_id).await?;
+            if removed > 0 {
+                return Ok(Some(task_id.clone()));
+            }
+            return Ok(None);
+        }
+        
+        // No tasks to process, wait a bit
+        tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
+        Ok(None)
+    }
+    
+    async fn remove_queued(&self, task_id: &str) -> Result<bool, QueueError> {
+        let mut conn = self.redis_manager.clone();
+        let removed: usize = conn.zrem("task_queue", task_id).await?;
+        Ok(removed > 0)
+    }
+    
+    async fn queue_len(&self) -> Result<usize, QueueError> {
+        let mut conn = self.redis_manager.clone();
+        let queue_size: usize = conn.zcard("task_queue").await?;
+        Ok(queue_size)
+    }
+    
+    async fn push_dead_letter(&self, data: &str) -> Result<(), QueueError> {
+        let mut conn = self.redis_manager.clone();
+        conn.lpush::<_, _, ()>("dead_letter_queue", data).await?;
+        Ok(())
+    }
+    
+    async fn dead_letters(&self, limit: Option<usize>) -> Result<Vec<String>, QueueError> {
+        if limit == Some(0) {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.redis_manager.clone();
+        let stop = limit.map(|l| l as isize - 1).unwrap_or(-1);
+        let raw_entries: Vec<String> = conn.lrange("dead_letter_queue", 0, stop).await?;
+        Ok(raw_entries)
+    }
+    
+    async fn remove_dead_letter(&self, data: &str) -> Result<(), QueueError> {
+        let mut conn = self.redis_manager.clone();
+        conn.lrem::<_, _, ()>("dead_letter_queue", 1, data).await?;
+        Ok(())
+    }
+    
+    /// Bounded by a timeout because the connection manager keeps retrying while Redis is down
+    async fn ping(&self) -> Result<(), QueueError> {
+        let mut conn = self.redis_manager.clone();
+        let cmd = redis::cmd("PING");
+        let ping = cmd.query_async::<String>(&mut conn);
+        
+        match tokio::time::timeout(tokio::time::Duration::from_secs(2), ping).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => Err(QueueError("Redis PING timed out".to_string())),
+        }
+    }
+}
+
+// How often writes to a MemoryStore also drop every expired value, so TTL'd keys that are
+// never read again (idempotency keys, kept audio) don't stay in memory forever
+const MEMORY_STORE_SWEEP_SECS: u64 = 60;
+
+// Values of a MemoryStore, with their optional expiry
+#[derive(Default)]
+struct MemoryValues {
+    entries: HashMap<String, (Vec<u8>, Option<std::time::Instant>)>,
+    last_sweep: Option<std::time::Instant>,
+}
+
+impl MemoryValues {
+    // The value at `key`, dropping it first if it has expired
+    fn live(&mut self, key: &str, now: std::time::Instant) -> Option<&Vec<u8>> {
+        if matches!(self.entries.get(key), Some((_, Some(expires_at))) if *expires_at <= now) {
+            self.entries.remove(key);
+        }
+        self.entries.get(key).map(|(value, _)| value)
+    }
+    
+    fn insert(&mut self, key: &str, value: Vec<u8>, ttl_secs: Option<u64>, now: std::time::Instant) {
+        let sweep_due = self.last_sweep
+            .is_none_or(|last| now.duration_since(last).as_secs() >= MEMORY_STORE_SWEEP_SECS);
+        if sweep_due {
+            self.entries.retain(|_, (_, expires_at)| expires_at.is_none_or(|expires_at| expires_at > now));
+            self.last_sweep = Some(now);
+        }
+        
+        let expires_at = ttl_secs.map(|ttl| now + std::time::Duration::from_secs(ttl));
+        self.entries.insert(key.to_string(), (value, expires_at));
+    }
+}
+
+/// In-process `TaskStore` for single-node and embedded use without Redis.
+/// Nothing survives a restart.
+#[derive(Default)]
+pub struct MemoryStore {
+    values: Mutex<MemoryValues>,
+    // (score, task id), kept sorted by score
+    queue: Mutex<VecDeque<(f64, String)>>,
+    queue_ready: Notify,
+    dead_letters: Mutex<VecDeque<String>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    async fn live_value(&self, key: &str) -> Option<Vec<u8>> {
+        self.values.lock().await.live(key, std::time::Instant::now()).cloned()
+    }
+    
+    async fn insert_value(&self, key: &str, value: Vec<u8>, ttl_secs: Option<u64>) {
+        self.values.lock().await.insert(key, value, ttl_secs, std::time::Instant::now());
+    }
+}
+
+#[async_trait::async_trait]
+impl TaskStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+    
+    fn is_persistent(&self) -> bool {
+        false
+    }
+    
+    async fn get(&self, key: &str) -> Result<Option<String>, QueueError> {
+        match self.live_value(key).await {
+            Some(value) => String::from_utf8(value)
+                .map(Some)
+                .map_err(|e| QueueError(format!("Value at {} is not UTF-8: {}", key, e))),
+            None => Ok(None),
+        }
+    }
+    
+    async fn set(&self, key: &str, value: &str) -> Result<(), QueueError> {
+        self.insert_value(key, value.as_bytes().to_vec(), None).await;
+        Ok(())
+    }
+    
+    async fn set_if_absent(&self, key: &str, value: &str, ttl_secs: u64) -> Result<bool, QueueError> {
+        // Check and insert under one lock, like SET NX, so concurrent claims can't both win
+        let mut values = self.values.lock().await;
+        let now = std::time::Instant::now();
+        if values.live(key, now).is_some() {
+            return Ok(false);
+        }
+        values.insert(key, value.as_bytes().to_vec(), Some(ttl_secs), now);
+        Ok(true)
+    }
+    
+    async fn set_with_ttl(&self, key: &str, value: &str, ttl_secs: u64) -> Result<(), QueueError> {
+        self.insert_value(key, value.as_bytes().to_vec(), Some(ttl_secs)).await;
+        Ok(())
+    }
+    
+    async fn del(&self, key: &str) -> Result<(), QueueError> {
+        self.values.lock().await.entries.remove(key);
+        Ok(())
+    }
+    
+    async fn keys(&self, prefix: &str) -> Result<Vec<String>, QueueError> {
+        let values = self.values.lock().await;
+        let now = std::time::Instant::now();
+        Ok(values.entries.iter()
+            .filter(|(key, (_, expires_at))| key.starts_with(prefix) && expires_at.is_none_or(|expires_at| expires_at > now))
+            .map(|(key, _)| key.clone())
+            .collect())
+    }
+    
+    async fn enqueue(&self, task_id: &str, score: f64) -> Result<(), QueueError> {
+        let mut queue = self.queue.lock().await;
+        // Re-queuing moves the task like ZADD does
+        queue.retain(|(_, queued)| queued != task_id);
+        let position = queue.iter().position(|(queued_score, _)| *queued_score > score).unwrap_or(queue.len());
+        queue.insert(position, (score, task_id.to_string()));
+        drop(queue);
+        
+        self.queue_ready.notify_one();
+        Ok(())
+    }
+    
+    async fn pop_next(&self) -> Result<Option<String>, QueueError> {
+        let timeout = tokio::time::Duration::from_secs_f64(BLOCKING_POP_TIMEOUT_SECS);
+        let deadline = tokio::time::Instant::now() + timeout;
+        
+        loop {
+            // Register interest before checking so an enqueue in between isn't missed
+            let ready = self.queue_ready.notified();
+            if let Some((_, task_id)) = self.queue.lock().await.pop_front() {
+                return Ok(Some(task_id));
+            }
+            if tokio::time::timeout_at(deadline, ready).await.is_err() {
+                return Ok(None);
+            }
+        }
+    }
+    
+    async fn remove_queued(&self, task_id: &str) -> Result<bool, QueueError> {
+        let mut queue = self.queue.lock().await;
+        let before = queue.len();
+        queue.retain(|(_, queued)| queued != task_id);
+        Ok(queue.len() < before)
+    }
+    
+    async fn queue_len(&self) -> Result<usize, QueueError> {
+        Ok(self.queue.lock().await.len())
+    }
+    
+    async fn push_dead_letter(&self, data: &str) -> Result<(), QueueError> {
+        self.dead_letters.lock().await.push_front(data.to_string());
+        Ok(())
+    }
+    
+    async fn dead_letters(&self, limit: Option<usize>) -> Result<Vec<String>, QueueError> {
+        let dead_letters = self.dead_letters.lock().await;
+        let limit = limit.unwrap_or(dead_letters.len());
+        Ok(dead_letters.iter().take(limit).cloned().collect())
+    }
+    
+    async fn remove_dead_letter(&self, data: &str) -> Result<(), QueueError> {
+        let mut dead_letters = self.dead_letters.lock().await;
+        if let Some(position) = dead_letters.iter().position(|entry| entry == data) {
+            dead_letters.remove(position);
+        }
+        Ok(())
+    }
+    
+    async fn ping(&self) -> Result<(), QueueError> {
+        Ok(())
+    }
+}
+
+pub struct TaskQueue {
+    store: Arc<dyn TaskStore>,
     task_results: Arc<RwLock<HashMap<String, TaskResult>>>,
-    websocket_sessions: Arc<Mutex<HashMap<Uuid, Recipient<WebSocketMessage>>>>,
+    websocket_sessions: Arc<Mutex<HashMap<Uuid, WebSocketSubscriber>>>,
     processing_tasks: Arc<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>>,
+    // Ring buffer of (broadcast time, message) per task, replayed to reconnecting WebSockets
+    recent_events: Arc<Mutex<HashMap<String, VecDeque<(DateTime<Utc>, String)>>>>,
+    // One permit per running task, bounding memory under bursts of uploads
+    task_slots: Arc<Semaphore>,
+    stats_interval_secs: u64,
+    // Processing tasks without a progress update for this long are failed by cleanup_stale_tasks
+    stale_task_threshold_secs: u64,
+    // Signs completion callbacks; unsigned when None
+    webhook_secret: Option<String>,
+    // Shared by every callback delivery so connections are pooled; it only connects to
+    // public addresses and doesn't follow redirects, which could lead anywhere
+    callback_client: reqwest::Client,
+    counters: Arc<QueueCounters>,
+    // Transcripts below either threshold skip automatic risk analysis
+    min_chars_for_risk: usize,
+    min_words_for_risk: usize,
+    // Loaded once (at startup, or by the first task if that failed) and shared by every task
+    whisper_engine: Arc<tokio::sync::OnceCell<WhisperEngine>>,
+    // Models routed to by language (see `set_language_models`), keyed by model path
+    // and loaded by the first task that needs each
+    language_engines: Arc<Mutex<HashMap<String, WhisperEngine>>>,
+    // One lock per task with a segment edit in flight, so concurrent edits of a
+    // transcript apply one after another instead of overwriting each other
+    segment_edit_locks: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>,
 }
 
 impl TaskQueue {
-    pub async fn new(redis_url: &str) -> Result<Self, QueueError> {
-        let client = RedisClient::open(redis_url)?;
-        let redis_manager = ConnectionManager::new(client).await?;
+    pub async fn new(redis_url: &str, max_concurrent_tasks: usize, stats_interval_secs: u64, stale_task_threshold_secs: u64, webhook_secret: Option<String>) -> Result<Self, QueueError> {
+        let store = RedisStore::connect(redis_url).await?;
+        Self::with_store(Arc::new(store), max_concurrent_tasks, stats_interval_secs, stale_task_threshold_secs, webhook_secret).await
+    }
+    
+    /// Same as `new` on any `TaskStore`, e.g. `MemoryStore` to run without Redis
+    pub async fn with_store(store: Arc<dyn TaskStore>, max_concurrent_tasks: usize, stats_interval_secs: u64, stale_task_threshold_secs: u64, webhook_secret: Option<String>) -> Result<Self, QueueError> {
+        let queue = Self {
+            store,
+            task_results: Arc::new(RwLock::new(HashMap::new())),
+            websocket_sessions: Arc::new(Mutex::new(HashMap::new())),
+            processing_tasks: Arc::new(Mutex::new(HashMap::new())),
+            recent_events: Arc::new(Mutex::new(HashMap::new())),
+            task_slots: Arc::new(Semaphore::new(max_concurrent_tasks.max(1))),
+            stats_interval_secs,
+            stale_task_threshold_secs,
+            webhook_secret,
+            callback_client: public_client_builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .map_err(|e| QueueError(format!("Failed to create callback HTTP client: {}", e)))?,
+            counters: Arc::new(QueueCounters::default()),
+            min_chars_for_risk: DEFAULT_MIN_CHARS_FOR_RISK,
+            min_words_for_risk: DEFAULT_MIN_WORDS_FOR_RISK,
+            whisper_engine: Arc::new(tokio::sync::OnceCell::new()),
+            language_engines: Arc::new(Mutex::new(HashMap::new())),
+            segment_edit_locks: Arc::new(Mutex::new(HashMap::new())),
+        };
+        
+        // Warm the model up front so the first task doesn't pay for loading it
+        match WhisperEngine::load_default().await {
+            Ok(engine) => {
+                let _ = queue.whisper_engine.set(engine);
+            }
+            Err(e) => log::warn!("Whisper model not loaded at startup, the first transcription will retry: {}", e),
+        }
+        
+        // Restore state from the store on startup
+        queue.restore_state().await?;
+        
+        Ok(queue)
+    }
+    
+    /// Minimum transcript length, in characters and in words, for automatic risk analysis.
+    /// Call before `start_task_processor`.
+    pub fn set_auto_risk_thresholds(&mut self, min_chars: usize, min_words: usize) {
+        self.min_chars_for_risk = min_chars;
+        self.min_words_for_risk = min_words;
+    }
+    
+    /// Backend name of the underlying `TaskStore` ("redis" or "memory")
+    pub fn store_name(&self) -> &'static str {
+        self.store.name()
+    }
+    
+    async fn restore_state(&self) -> Result<(), QueueError> {
+        // Get all task results from the store
+        let task_keys = self.store.keys("task_result:").await?;
+        let mut task_results = self.task_results.write().await;
+        
+        for key in task_keys {
+            let result_data = self.store.get(&key).await.ok().flatten().unwrap_or_default();
+            if !result_data.is_empty() {
+                if let Ok(task_result) = serde_json::from_str::<TaskResult>(&result_data) {
+                    task_results.insert(task_result.id.clone(), task_result);
+                }
+            }
+        }
+        
+        // Resume processing tasks that were interrupted
+        let processing_tasks: Vec<TaskResult> = task_results
+            .values()
+            .filter(|t| matches!(t.status, TaskStatus::Processing))
+            .cloned()
+            .collect();
+        
+        drop(task_results);
+        
+        for mut task in processing_tasks {
+            log::info!("Resuming interrupted task: {}", task.id);
+            task.status = TaskStatus::Pending;
+            task.updated_at = Utc::now();
+            self.save_task_result(&task).await?;
+            self.enqueue_task_request(&task.id).await?;
+        }
+        
+        Ok(())
+    }
+    
+    async fn save_task_result(&self, task_result: &TaskResult) -> Result<(), QueueError> {
+        // Without persistence the in-memory cache is the only copy worth keeping
+        if self.store.is_persistent() {
+            let key = format!("task_result:{}", task_result.id);
+            let data = serde_json::to_string(task_result)?;
+            self.store.set(&key, &data).await?;
+        }
+        
+        // Also update in-memory cache
+        let mut task_results = self.task_results.write().await;
+        task_results.insert(task_result.id.clone(), task_result.clone());
+        
+        Ok(())
+    }
+    
+    async fn get_task_result(&self, task_id: &str) -> Result<Option<TaskResult>, QueueError> {
+        // First check in-memory cache
+        {
+            let task_results = self.task_results.read().await;
+            if let Some(task_result) = task_results.get(task_id) {
+                return Ok(Some(task_result.clone()));
+            }
+        }
+        
+        // If not in cache, load from the store
+        let key = format!("task_result:{}", task_id);
+        
+        match self.store.get(&key).await {
+            Ok(Some(data)) => {
+                let task_result: TaskResult = serde_json::from_str(&data)?;
+                
+                // Update cache
+                let mut task_results = self.task_results.write().await;
+                task_results.insert(task_id.to_string(), task_result.clone());
+                
+                Ok(Some(task_result))
+            }
+            _ => {
+                // Key doesn't exist or other error
+                Ok(None)
+            }
+        }
+    }
+    
+    // Point idempotency:<key> at task_id unless an earlier submission already claimed it,
+    // in which case that submission's task id is returned
+    async fn claim_idempotency_key(&self, key: &str, task_id: &str) -> Result<Option<String>, QueueError> {
+        let store_key = format!("idempotency:{}", key);
+        
+        if self.store.set_if_absent(&store_key, task_id, IDEMPOTENCY_KEY_TTL_SECS).await? {
+            return Ok(None);
+        }
+        
+        // None here means the key expired in between; the submission then goes ahead unguarded
+        self.store.get(&store_key).await
+    }
+    
+    // Model a transcription in `language` runs on: its language model, else the default
+    // engine's. None before the default engine has loaded.
+    fn resolved_model_path(&self, language: Option<&str>) -> Option<String> {
+        language_model_path(language)
+            .map(str::to_string)
+            .or_else(|| self.whisper_engine.get().map(|engine| engine.model_path().to_string()))
+    }
+    
+    // Cache key of a transcription: the audio plus everything that shapes its transcript
+    fn audio_cache_key(&self, audio_hash: &str, payload: &TranscriptionPayload) -> String {
+        let model_path = self.resolved_model_path(payload.language.as_deref());
+        format!("audio_hash:{}:{}", audio_hash, payload.output_fingerprint(model_path.as_deref()))
+    }
+    
+    // Completed transcription recorded under the cache key of this audio and payload,
+    // if its result is still around
+    async fn find_cached_result(&self, audio_hash: &str, payload: &serde_json::Value) -> Result<Option<TaskResult>, QueueError> {
+        let payload = TranscriptionPayload::from_value(payload).map_err(QueueError)?;
+        let task_id = match self.store.get(&self.audio_cache_key(audio_hash, &payload)).await? {
+            Some(task_id) => task_id,
+            None => return Ok(None),
+        };
+        
+        Ok(self.get_task_result(&task_id).await?
+            .filter(|task_result| task_result.status == TaskStatus::Completed && task_result.result.is_some()))
+    }
+    
+    // Point the cache key of a transcription that just completed at its task
+    async fn record_audio_hash(&self, task_id: &str, payload: &serde_json::Value) {
+        let payload = match TranscriptionPayload::from_value(payload) {
+            Ok(payload) if payload.audio_hash.is_some() => payload,
+            _ => return,
+        };
+        let audio_hash = payload.audio_hash.as_deref().unwrap_or_default();
+        
+        match self.store.set(&self.audio_cache_key(audio_hash, &payload), task_id).await {
+            Ok(()) => log::info!("Cached result of task {} under audio hash {}", task_id, audio_hash),
+            Err(e) => log::warn!("Failed to record audio hash for task {}: {}", task_id, e),
+        }
+    }
+    
+    // Warm engine for `language`: its own model when one is configured, else the default one
+    async fn engine_for_language(&self, language: Option<&str>) -> Result<WhisperEngine, String> {
+        match language_model_path(language) {
+            Some(model_path) => self.language_engine(model_path).await,
+            None => self.whisper_engine
+                .get_or_try_init(WhisperEngine::load_default)
+                .await
+                .cloned()
+                .map_err(|e| e.to_string()),
+        }
+    }
+    
+    // Warm engine for a language-specific model, loading it on first use. The lock is
+    // held while loading so concurrent tasks don't load the same model twice.
+    async fn language_engine(&self, model_path: &str) -> Result<WhisperEngine, String> {
+        let mut engines = self.language_engines.lock().await;
+        if let Some(engine) = engines.get(model_path) {
+            return Ok(engine.clone());
+        }
+        
+        let engine = WhisperEngine::load_async(model_path).await.map_err(|e| e.to_string())?;
+        engines.insert(model_path.to_string(), engine.clone());
+        Ok(engine)
+    }
+    
+    // Free a claimed key when the task behind it never made it into the queue
+    async fn release_idempotency_key(&self, key: &str) {
+        if let Err(e) = self.store.del(&format!("idempotency:{}", key)).await {
+            log::warn!("Failed to release idempotency key {}: {}", key, e);
+        }
+    }
+    
+    // Persist a new task and put it on the queue
+    async fn store_new_task(&self, task_request: &TaskRequest, task_result: &TaskResult) -> Result<(), String> {
+        let request_key = format!("task_request:{}", task_request.id);
+        let request_data = serde_json::to_string(task_request)
+            .map_err(|e| format!("Failed to serialize task request: {}", e))?;
+        
+        self.store.set(&request_key, &request_data).await
+            .map_err(|e| format!("Failed to save task request: {}", e))?;
+        
+        self.save_task_result(task_result).await
+            .map_err(|e| format!("Failed to save task result: {}", e))?;
+        
+        self.enqueue_task_request(&task_request.id).await
+            .map_err(|e| format!("Failed to enqueue task: {}", e))?;
+        self.counters.submitted.fetch_add(1, Ordering::Relaxed);
+        
+        Ok(())
+    }
+    
+    async fn enqueue_task_request(&self, task_id: &str) -> Result<(), QueueError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        
+        self.store.enqueue(task_id, timestamp as f64).await
+    }
+    
+    async fn push_dead_letter(&self, entry: &DeadLetterEntry) -> Result<(), QueueError> {
+        let data = serde_json::to_string(entry)?;
+        
+        // Newest first
+        self.store.push_dead_letter(&data).await?;
+        self.counters.dead_lettered.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+    
+    /// Check that the store answers, so callers can tell a live Redis from a dead one
+    pub async fn ping_redis(&self) -> Result<(), QueueError> {
+        self.store.ping().await
+    }
+    
+    /// Replace one segment's text in a completed transcription, rebuild the
+    /// top-level text, the segment's words and the paragraphs, and persist the result.
+    /// Edits of the same task are applied one at a time.
+    pub async fn update_segment_text(&self, task_id: &str, segment_id: i64, text: &str) -> Result<SegmentUpdate, QueueError> {
+        let edit_lock = {
+            let mut locks = self.segment_edit_locks.lock().await;
+            Arc::clone(locks.entry(task_id.to_string()).or_default())
+        };
         
-        let queue = Self {
-            redis_manager,
-            task_results: Arc::new(RwLock::new(HashMap::new())),
-            websocket_sessions: Arc::new(Mutex::new(HashMap::new())),
-            processing_tasks: Arc::new(Mutex::new(HashMap::new())),
+        let update = {
+            let _edit_guard = edit_lock.lock().await;
+            self.apply_segment_edit(task_id, segment_id, text).await
         };
         
-        // Restore state from Redis on startup
-        queue.restore_state().await?;
+        // Drop the task's lock unless another edit is already waiting on it
+        let mut locks = self.segment_edit_locks.lock().await;
+        if Arc::strong_count(&edit_lock) == 2 {
+            locks.remove(task_id);
+        }
         
-        Ok(queue)
+        update
     }
     
-    async fn restore_state(&self) -> Result<(), QueueError> {
-        let mut conn = self.redis_manager.clone();
-        
-        // Get all task results from Redis
-        let task_keys: Vec<String> = conn.keys("task_result:*").await?;
-        let mut task_results = self.task_results.write().await;
+    async fn apply_segment_edit(&self, task_id: &str, segment_id: i64, text: &str) -> Result<SegmentUpdate, QueueError> {
+        let mut task_result = match self.get_task_result(task_id).await? {
+            Some(task_result) => task_result,
+            None => return Ok(SegmentUpdate::TaskNotFound),
+        };
         
-        for key in task_keys {
-            let result_data: String = conn.get(&key).await.unwrap_or_default();
-            if !result_data.is_empty() {
-                if let Ok(task_result) = serde_json::from_str::<TaskResult>(&result_data) {
-                    task_results.insert(task_result.id.clone(), task_result);
-                }
-            }
+        if task_result.status != TaskStatus::Completed {
+            return Ok(SegmentUpdate::NotCompleted(task_result.status));
         }
         
-        // Resume processing tasks that were interrupted
-        let processing_tasks: Vec<TaskResult> = task_results
-            .values()
-            .filter(|t| matches!(t.status, TaskStatus::Processing))
-            .cloned()
-            .collect();
+        let result = match task_result.result.as_mut() {
+            Some(result) => result,
+            None => return Ok(SegmentUpdate::SegmentNotFound),
+        };
+        let segments = match result.get_mut("segments").and_then(|s| s.as_array_mut()) {
+            Some(segments) => segments,
+            None => return Ok(SegmentUpdate::SegmentNotFound),
+        };
+        let segment = match segments.iter_mut().find(|s| s.get("id").and_then(|id| id.as_i64()) == Some(segment_id)) {
+            Some(segment) => segment,
+            None => return Ok(SegmentUpdate::SegmentNotFound),
+        };
         
-        drop(task_results);
+        // Whisper segment text starts with a space; keep it so the joined text stays readable
+        let old_text = segment.get("text").and_then(|t| t.as_str()).unwrap_or("");
+        let new_text = if old_text.starts_with(' ') && !text.starts_with(' ') {
+            format!(" {}", text)
+        } else {
+            text.to_string()
+        };
         
-        for mut task in processing_tasks {
-            log::info!("Resuming interrupted task: {}", task.id);
-            task.status = TaskStatus::Pending;
-            task.updated_at = Utc::now();
-            self.save_task_result(&task).await?;
-            self.enqueue_task_request(&task.id).await?;
-        }
+        // Old words no longer match the text, so re-segment and spread them over the segment
+        let start = segment.get("start").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let end = segment.get("end").and_then(|v| v.as_f64()).unwrap_or(start);
+        let tokens: Vec<(String, f64)> = segment.get("confidence")
+            .and_then(|c| c.as_f64())
+            .map(|confidence| vec![(new_text.clone(), confidence)])
+            .unwrap_or_default();
+        let words = crate::align_words(&new_text, &tokens, start, end);
+        
+        segment["text"] = serde_json::json!(new_text);
+        segment["words"] = serde_json::to_value(words)?;
+        let updated_segment = segment.clone();
+        
+        let full_text: String = segments.iter()
+            .filter_map(|s| s.get("text").and_then(|t| t.as_str()))
+            .collect();
+        result["text"] = serde_json::json!(full_text.trim());
+        crate::regroup_paragraphs(result);
         
-        Ok(())
+        task_result.updated_at = Utc::now();
+        self.save_task_result(&task_result).await?;
+        
+        log::info!("Segment {} of task {} corrected by hand", segment_id, task_id);
+        Ok(SegmentUpdate::Updated(updated_segment))
     }
     
-    async fn save_task_result(&self, task_result: &TaskResult) -> Result<(), QueueError> {
-        let mut conn = self.redis_manager.clone();
-        let key = format!("task_result:{}", task_result.id);
-        let data = serde_json::to_string(task_result)?;
+    /// Cancel a task that is still waiting in the queue. Removing it from the sorted set
+    /// is what decides the race with the workers: once ZREM succeeds no worker can pop it.
+    pub async fn cancel_task(&self, task_id: &str) -> Result<CancelOutcome, QueueError> {
+        let mut task_result = match self.get_task_result(task_id).await? {
+            Some(task_result) => task_result,
+            None => return Ok(CancelOutcome::TaskNotFound),
+        };
+        
+        match task_result.status {
+            TaskStatus::Processing => return Ok(CancelOutcome::AlreadyRunning),
+            status if is_terminal_status(status) => return Ok(CancelOutcome::AlreadyFinished(status)),
+            _ => {}
+        }
         
-        conn.set::<_, _, ()>(&key, data).await?;
+        // Fast path: a pending task still in the queue never spawns any work
+        if !self.store.remove_queued(task_id).await? {
+            // Popped by a worker between the status check and ZREM
+            return Ok(CancelOutcome::AlreadyRunning);
+        }
         
-        // Also update in-memory cache
-        let mut task_results = self.task_results.write().await;
-        task_results.insert(task_result.id.clone(), task_result.clone());
+        let now = Utc::now();
+        task_result.status = TaskStatus::Cancelled;
+        task_result.completed_at = Some(now);
+        task_result.updated_at = now;
+        self.save_task_result(&task_result).await?;
+        self.counters.record_finished(&task_result);
         
-        Ok(())
-    }
-    
-    async fn get_task_result(&self, task_id: &str) -> Result<Option<TaskResult>, QueueError> {
-        // First check in-memory cache
-        {
-            let task_results = self.task_results.read().await;
-            if let Some(task_result) = task_results.get(task_id) {
-                return Ok(Some(task_result.clone()));
+        // Nothing will run this request, so drop it along with the uploaded audio
+        let request_key = format!("task_request:{}", task_id);
+        let request_data = self.store.get(&request_key).await;
+        let request = request_data.ok().flatten().and_then(|data| serde_json::from_str::<TaskRequest>(&data).ok());
+        if let Some(request) = request {
+            self.spawn_callback(&request.payload, &task_result);
+            if let Some(path) = uploaded_audio_path(&request) {
+                remove_audio_file(task_id, path);
             }
         }
+        self.store.del(&request_key).await?;
         
-        // If not in cache, load from Redis
-        let mut conn = self.redis_manager.clone();
-        let key = format!("task_result:{}", task_id);
-        let data: Result<String, redis::RedisError> = conn.get(&key).await;
+        log::info!("Cancelled pending task: {}", task_id);
         
-        match data {
-            Ok(data) => {
-                let task_result: TaskResult = serde_json::from_str(&data)?;
-                
-                // Update cache
-                let mut task_results = self.task_results.write().await;
-                task_results.insert(task_id.to_string(), task_result.clone());
-                
-                Ok(Some(task_result))
+        let status_msg = serde_json::json!({
+            "type": "task_completed",
+            "task_id": task_id,
+            "status": task_result.status,
+            "result": null,
+            "error": null,
+            "timestamp": now
+        });
+        self.broadcast_to_websockets(&status_msg.to_string()).await;
+        
+        Ok(CancelOutcome::Cancelled)
+    }
+    
+    /// Whisper input audio of a completed task, if it was kept and hasn't expired
+    pub async fn get_task_audio(&self, task_id: &str) -> Result<TaskAudio, QueueError> {
+        let task_result = match self.get_task_result(task_id).await? {
+            Some(task_result) => task_result,
+            None => return Ok(TaskAudio::TaskNotFound),
+        };
+        if task_result.status != TaskStatus::Completed {
+            return Ok(TaskAudio::NotCompleted(task_result.status));
+        }
+        
+        let path = self.store.get(&format!("task_audio:{}", task_id)).await?.map(PathBuf::from);
+        Ok(match path {
+            Some(path) if path.is_file() => TaskAudio::Available(path),
+            _ => TaskAudio::NotKept,
+        })
+    }
+    
+    // Record where the worker thread wrote the WAV. The file stays on disk, with its
+    // mtime reset so sweep_kept_audio expires it KEPT_AUDIO_TTL_SECS from now.
+    async fn store_kept_audio(&self, task_id: &str, path: &str) -> Result<(), QueueError> {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .map_err(|e| QueueError(format!("Failed to keep audio {}: {}", path, e)))?;
+        
+        self.store.set_with_ttl(&format!("task_audio:{}", task_id), path, KEPT_AUDIO_TTL_SECS).await
+    }
+    
+    /// Delete kept audio files older than `KEPT_AUDIO_TTL_SECS`, including ones left
+    /// behind by a restart
+    pub async fn sweep_kept_audio(&self) -> usize {
+        let entries = match std::fs::read_dir(std::env::temp_dir()) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Failed to list kept audio files: {}", e);
+                return 0;
             }
-            Err(_) => {
-                // Key doesn't exist or other error
-                Ok(None)
+        };
+        let ttl = std::time::Duration::from_secs(KEPT_AUDIO_TTL_SECS);
+        let mut swept = 0;
+        
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let task_id = match name.strip_prefix(KEPT_AUDIO_FILE_PREFIX).and_then(|rest| rest.strip_suffix(".wav")) {
+                Some(task_id) => task_id.to_string(),
+                None => continue,
+            };
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > ttl);
+            if expired {
+                remove_audio_file(&task_id, &entry.path().to_string_lossy());
+                swept += 1;
             }
         }
+        
+        swept
     }
     
-    async fn enqueue_task_request(&self, task_id: &str) -> Result<(), QueueError> {
-        let mut conn = self.redis_manager.clone();
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    pub async fn get_dead_letters(&self, limit: Option<usize>) -> Result<Vec<DeadLetterEntry>, QueueError> {
+        let raw_entries = self.store.dead_letters(limit).await?;
         
-        // Use sorted set for priority queue
-        conn.zadd::<_, _, _, ()>("task_queue", task_id, timestamp as f64).await?;
+        Ok(raw_entries
+            .iter()
+            .filter_map(|data| serde_json::from_str::<DeadLetterEntry>(data).ok())
+            .collect())
+    }
+    
+    // The serialized dead-letter entry of a task along with its parsed form
+    async fn find_dead_letter(&self, task_id: &str) -> Result<Option<(String, DeadLetterEntry)>, QueueError> {
+        let raw_entries = self.store.dead_letters(None).await?;
+        
+        Ok(raw_entries.into_iter().find_map(|data| {
+            serde_json::from_str::<DeadLetterEntry>(&data)
+                .ok()
+                .filter(|entry| entry.task_id == task_id)
+                .map(|entry| (data, entry))
+        }))
+    }
+    
+    /// Move a dead-lettered task back onto the main queue. Returns `None` if it isn't in the list.
+    pub async fn requeue_dead_letter(&self, task_id: &str) -> Result<Option<String>, QueueError> {
+        let (raw, entry) = match self.find_dead_letter(task_id).await? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
         
-        Ok(())
+        let request = entry.request.ok_or_else(|| {
+            QueueError(format!("Dead-letter entry for task {} has no stored request", task_id))
+        })?;
+        
+        // Restore the request and reset the result before putting the task back on the queue
+        let request_key = format!("task_request:{}", task_id);
+        self.store.set(&request_key, &serde_json::to_string(&request)?).await?;
+        
+        let now = Utc::now();
+        let task_result = match self.get_task_result(task_id).await? {
+            Some(mut task_result) => {
+                task_result.status = TaskStatus::Pending;
+                task_result.updated_at = now;
+                task_result.started_at = None;
+                task_result.completed_at = None;
+                task_result.result = None;
+                task_result.error = None;
+                task_result.progress = 0.0;
+                task_result
+            }
+            None => TaskResult {
+                id: task_id.to_string(),
+                status: TaskStatus::Pending,
+                created_at: request.created_at,
+                updated_at: now,
+                started_at: None,
+                completed_at: None,
+                result: None,
+                error: None,
+                progress: 0.0,
+            },
+        };
+        self.save_task_result(&task_result).await?;
+        self.enqueue_task_request(task_id).await?;
+        
+        self.store.remove_dead_letter(&raw).await?;
+        
+        log::info!("Requeued dead-lettered task: {}", task_id);
+        
+        let status_msg = serde_json::json!({
+            "type": "task_requeued",
+            "task_id": task_id,
+            "status": task_result.status,
+            "timestamp": now
+        });
+        self.broadcast_to_websockets(&status_msg.to_string()).await;
+        
+        Ok(Some(task_id.to_string()))
     }
     
-    async fn dequeue_task_request(&self) -> Result<Option<String>, QueueError> {
-        let mut conn = self.redis_manager.clone();
+    /// Drop a dead-lettered task for good, deleting the uploaded audio it kept for a
+    /// requeue. Returns `false` if it isn't in the list.
+    pub async fn discard_dead_letter(&self, task_id: &str) -> Result<bool, QueueError> {
+        let (raw, entry) = match self.find_dead_letter(task_id).await? {
+            Some(found) => found,
+            None => return Ok(false),
+        };
         
-        // Get the oldest task (lowest score)
-        let result: Vec<String> = conn.zrange("task_queue", 0, 0).await?;
+        self.remove_dead_letter(&raw, &entry).await?;
         
-        if let Some(task_id) = result.first() {
-            // Remove from queue
-            conn.zrem::<_, _, ()>("task_queue", task_id).await?;
-            Ok(Some(task_id.clone()))
-        } else {
-            Ok(None)
+        log::info!("Discarded dead-lettered task: {}", task_id);
+        
+        Ok(true)
+    }
+    
+    // Remove a dead-letter entry along with the uploaded audio it kept for a requeue
+    async fn remove_dead_letter(&self, raw: &str, entry: &DeadLetterEntry) -> Result<(), QueueError> {
+        self.store.remove_dead_letter(raw).await?;
+        if let Some(path) = entry.request.as_ref().and_then(uploaded_audio_path) {
+            remove_audio_file(&entry.task_id, path);
+        }
+        Ok(())
+    }
+    
+    /// Discard dead-lettered tasks that failed more than `DEAD_LETTER_TTL_SECS` ago
+    pub async fn sweep_dead_letters(&self) -> Result<usize, QueueError> {
+        let cutoff = Utc::now() - chrono::Duration::seconds(DEAD_LETTER_TTL_SECS as i64);
+        let mut swept = 0;
+        
+        for raw in self.store.dead_letters(None).await? {
+            let entry = match serde_json::from_str::<DeadLetterEntry>(&raw) {
+                Ok(entry) if entry.failed_at < cutoff => entry,
+                _ => continue,
+            };
+            self.remove_dead_letter(&raw, &entry).await?;
+            swept += 1;
+        }
+        
+        if swept > 0 {
+            log::info!("Dropped {} dead-lettered tasks older than {}s", swept, DEAD_LETTER_TTL_SECS);
         }
+        
+        Ok(swept)
     }
     
     async fn broadcast_to_websockets(&self, message: &str) {
@@ -258,14 +1790,85 @@ impl TaskQueue {
             message: message.to_string(),
         };
         
-        for (_, recipient) in sessions.iter() {
-            let _ = recipient.do_send(msg.clone());
+        // Messages without a task_id (e.g. queue stats) go to every session
+        let task_id = serde_json::from_str::<serde_json::Value>(message)
+            .ok()
+            .and_then(|v| v.get("task_id").and_then(|id| id.as_str()).map(|id| id.to_string()));
+        
+        if let Some(task_id) = &task_id {
+            self.record_event(task_id, message).await;
+        }
+        
+        for (_, subscriber) in sessions.iter() {
+            if let (Some(filter), Some(task_id)) = (&subscriber.task_filter, &task_id) {
+                if filter != task_id {
+                    continue;
+                }
+            }
+            let _ = subscriber.addr.do_send(msg.clone());
+        }
+    }
+    
+    // Keep the message in the task's replay buffer
+    async fn record_event(&self, task_id: &str, message: &str) {
+        let mut recent_events = self.recent_events.lock().await;
+        
+        if !recent_events.contains_key(task_id) && recent_events.len() >= REPLAY_MAX_TASKS {
+            let least_recent = recent_events.iter()
+                .min_by_key(|(_, events)| events.back().map(|(at, _)| *at))
+                .map(|(id, _)| id.clone());
+            if let Some(least_recent) = least_recent {
+                recent_events.remove(&least_recent);
+            }
+        }
+        
+        let events = recent_events.entry(task_id.to_string()).or_default();
+        if events.len() >= REPLAY_EVENTS_PER_TASK {
+            events.pop_front();
+        }
+        events.push_back((Utc::now(), message.to_string()));
+    }
+    
+    /// Messages for a WebSocket client catching up after a reconnect: the buffered
+    /// broadcasts after `since` for `task_id` (or for every task updated after `since`),
+    /// each task followed by a `task_snapshot` of its current state.
+    pub async fn replay_events(&self, since: DateTime<Utc>, task_id: Option<&str>) -> Vec<String> {
+        let task_results = self.task_results.read().await;
+        let recent_events = self.recent_events.lock().await;
+        
+        let task_ids: Vec<String> = match task_id {
+            Some(task_id) => vec![task_id.to_string()],
+            None => {
+                let mut updated: Vec<&TaskResult> = task_results.values()
+                    .filter(|task| task.updated_at > since)
+                    .collect();
+                updated.sort_by_key(|task| task.updated_at);
+                updated.into_iter().map(|task| task.id.clone()).collect()
+            }
+        };
+        
+        let mut messages = Vec::new();
+        for task_id in &task_ids {
+            if let Some(events) = recent_events.get(task_id) {
+                messages.extend(events.iter().filter(|(at, _)| *at > since).map(|(_, message)| message.clone()));
+            }
+            messages.push(task_snapshot_message(task_id, task_results.get(task_id)).to_string());
         }
+        
+        messages
     }
     
     pub async fn add_websocket_session(&self, session_id: Uuid, addr: Recipient<WebSocketMessage>) {
         let mut sessions = self.websocket_sessions.lock().await;
-        sessions.insert(session_id, addr);
+        sessions.insert(session_id, WebSocketSubscriber { addr, task_filter: None });
+    }
+    
+    /// Limit the broadcasts a WebSocket session receives to `task_id` (all tasks when `None`)
+    pub async fn set_websocket_filter(&self, session_id: &Uuid, task_id: Option<String>) {
+        let mut sessions = self.websocket_sessions.lock().await;
+        if let Some(subscriber) = sessions.get_mut(session_id) {
+            subscriber.task_filter = task_id;
+        }
     }
     
     pub async fn remove_websocket_session(&self, session_id: &Uuid) {
@@ -279,13 +1882,15 @@ impl TaskQueue {
         // Start main task processor
         tokio::spawn(async move {
             loop {
-                match queue_clone.process_next_task().await {
-                    Ok(processed) => {
-                        if !processed {
-                            // No tasks to process, wait a bit
-                            tokio::time::sleep(tokio::time::Duration::from_millis(1000)).await;
-                        }
-                    }
+                // Wait for a free slot before taking anything off the queue
+                let permit = match Arc::clone(&queue_clone.task_slots).acquire_owned().await {
+                    Ok(permit) => permit,
+                    Err(_) => break,
+                };
+                
+                // The store waits for work itself, so an empty queue doesn't spin
+                match queue_clone.process_next_task(permit).await {
+                    Ok(_) => {}
                     Err(e) => {
                         log::error!("Error processing task: {}", e);
                         tokio::time::sleep(tokio::time::Duration::from_millis(5000)).await;
@@ -294,10 +1899,28 @@ impl TaskQueue {
             }
         });
         
+        // Delete expired kept audio, and dead letters (with their uploads) too old to requeue
+        let sweep_queue_clone = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(SWEEP_INTERVAL_SECS));
+            loop {
+                interval.tick().await;
+                sweep_queue_clone.sweep_kept_audio().await;
+                if let Err(e) = sweep_queue_clone.sweep_dead_letters().await {
+                    log::warn!("Failed to sweep dead letters: {}", e);
+                }
+            }
+        });
+        
         // Start periodic stats broadcaster
+        if self.stats_interval_secs == 0 {
+            log::info!("Periodic queue stats broadcasts disabled");
+            return;
+        }
         let stats_queue_clone = self.clone();
+        let stats_interval_secs = self.stats_interval_secs;
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+            let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(stats_interval_secs));
             loop {
                 interval.tick().await;
                 if let Ok(Ok(stats)) = stats_queue_clone.get_queue_stats_internal().await {
@@ -312,8 +1935,10 @@ impl TaskQueue {
         });
     }
     
-    async fn process_next_task(&self) -> Result<bool, QueueError> {
-        if let Some(task_id) = self.dequeue_task_request().await? {
+    async fn process_next_task(&self, permit: OwnedSemaphorePermit) -> Result<bool, QueueError> {
+        let next_task = self.store.pop_next().await?;
+        
+        if let Some(task_id) = next_task {
             let task_results = self.task_results.read().await;
             
             if let Some(mut task_result) = task_results.get(&task_id).cloned() {
@@ -340,6 +1965,8 @@ impl TaskQueue {
                 let queue_clone = self.clone();
                 let handle = tokio::spawn(async move {
                     queue_clone.execute_task(task_result).await;
+                    // Free the slot for the next task (also released if the task is aborted)
+                    drop(permit);
                 });
                 
                 // Store the handle for potential cancellation
@@ -360,11 +1987,10 @@ impl TaskQueue {
         let task_id = task_result.id.clone();
         
         // Get the original request
-        let mut conn = self.redis_manager.clone();
         let request_key = format!("task_request:{}", task_id);
-        let request_data: Result<String, redis::RedisError> = conn.get(&request_key).await;
+        let request_data = self.store.get(&request_key).await;
         
-        let (result, original_request) = if let Ok(request_data) = request_data {
+        let (result, original_request) = if let Ok(Some(request_data)) = request_data {
             if let Ok(request) = serde_json::from_str::<TaskRequest>(&request_data) {
                 let process_result = self.process_task(&request, &mut task_result).await;
                 (process_result, Some(request))
@@ -376,6 +2002,7 @@ impl TaskQueue {
         };
         
         // Update final status
+        let mut dead_lettered = false;
         match result {
             Ok(result_data) => {
                 task_result.status = TaskStatus::Completed;
@@ -385,6 +2012,8 @@ impl TaskQueue {
                 // Auto-trigger risk analysis for completed transcription tasks
                 if let Some(request) = &original_request {
                     if matches!(request.task_type, TaskType::Transcription) {
+                        self.record_audio_hash(&task_id, &request.payload).await;
+                        
                         log::info!("Transcription completed, auto-triggering risk analysis for task: {}", task_id);
                         
                         // Submit risk analysis in the background (don't block completion)
@@ -395,7 +2024,8 @@ impl TaskQueue {
                         
                         tokio::spawn(async move {
                             match queue_clone.auto_submit_risk_analysis(&result_clone, &payload_clone).await {
-                                Ok(risk_task_id) => {
+                                Ok(None) => {}
+                                Ok(Some(risk_task_id)) => {
                                     log::info!("Successfully auto-submitted risk analysis {} for transcription {}", risk_task_id, task_id_clone);
                                     
                                     // Broadcast that risk analysis was auto-triggered
@@ -427,12 +2057,25 @@ impl TaskQueue {
             }
             Err(error) => {
                 task_result.status = TaskStatus::Failed;
-                task_result.error = Some(error);
+                task_result.error = Some(error.clone());
+                
+                // Keep the request so operators can inspect and requeue the task
+                let entry = DeadLetterEntry {
+                    task_id: task_id.clone(),
+                    error,
+                    failed_at: Utc::now(),
+                    request: original_request.clone(),
+                };
+                match self.push_dead_letter(&entry).await {
+                    Ok(()) => dead_lettered = true,
+                    Err(e) => log::error!("Failed to push task {} to dead-letter queue: {}", task_id, e),
+                }
             }
         }
         
         task_result.completed_at = Some(Utc::now());
         task_result.updated_at = Utc::now();
+        self.counters.record_finished(&task_result);
         
         // Save final result
         if let Err(e) = self.save_task_result(&task_result).await {
@@ -440,7 +2083,15 @@ impl TaskQueue {
         }
         
         // Clean up request data
-        let _: Result<(), redis::RedisError> = conn.del(&request_key).await;
+        let _ = self.store.del(&request_key).await;
+        
+        // A dead-lettered task keeps its uploaded audio so it can be requeued; the file goes
+        // when the task succeeds or its dead-letter entry is discarded
+        if !dead_lettered {
+            if let Some(path) = original_request.as_ref().and_then(uploaded_audio_path) {
+                remove_audio_file(&task_id, path);
+            }
+        }
         
         // Remove from processing tasks
         let mut processing_tasks = self.processing_tasks.lock().await;
@@ -459,6 +2110,71 @@ impl TaskQueue {
             "timestamp": Utc::now()
         });
         self.broadcast_to_websockets(&status_msg.to_string()).await;
+        
+        if let Some(request) = &original_request {
+            self.spawn_callback(&request.payload, &task_result);
+        }
+    }
+    
+    // POST the final TaskResult to the payload's callback_url in the background
+    fn spawn_callback(&self, payload: &serde_json::Value, task_result: &TaskResult) {
+        let url = match callback_url(payload) {
+            Ok(Some(url)) => url,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!("Not sending callback for task {}: {}", task_result.id, e);
+                return;
+            }
+        };
+        
+        let queue_clone = self.clone();
+        let task_result = task_result.clone();
+        tokio::spawn(async move {
+            queue_clone.deliver_callback(&url, &task_result).await;
+        });
+    }
+    
+    // Retries with a growing delay; only a 2xx response counts as delivered
+    async fn deliver_callback(&self, url: &str, task_result: &TaskResult) {
+        let body = match serde_json::to_vec(task_result) {
+            Ok(body) => body,
+            Err(e) => {
+                log::error!("Failed to serialize callback body for task {}: {}", task_result.id, e);
+                return;
+            }
+        };
+        let signature = self.webhook_secret.as_deref().map(|secret| callback_signature(secret, &body));
+        
+        for attempt in 1..=CALLBACK_ATTEMPTS {
+            let mut request = self.callback_client
+                .post(url)
+                .timeout(std::time::Duration::from_secs(CALLBACK_TIMEOUT_SECS))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header("X-Task-Id", task_result.id.as_str())
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header(CALLBACK_SIGNATURE_HEADER, signature.as_str());
+            }
+            
+            match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    log::info!("Delivered callback for task {} to {}", task_result.id, url);
+                    return;
+                }
+                Ok(response) => {
+                    log::warn!("Callback for task {} to {} returned {} (attempt {}/{})", task_result.id, url, response.status(), attempt, CALLBACK_ATTEMPTS);
+                }
+                Err(e) => {
+                    log::warn!("Callback for task {} to {} failed: {} (attempt {}/{})", task_result.id, url, e, attempt, CALLBACK_ATTEMPTS);
+                }
+            }
+            
+            if attempt < CALLBACK_ATTEMPTS {
+                tokio::time::sleep(tokio::time::Duration::from_secs(2 * attempt as u64)).await;
+            }
+        }
+        
+        log::error!("Giving up on callback for task {} to {} after {} attempts", task_result.id, url, CALLBACK_ATTEMPTS);
     }
     
     async fn process_task(&self, request: &TaskRequest, task_result: &mut TaskResult) -> Result<serde_json::Value, String> {
@@ -473,96 +2189,85 @@ impl TaskQueue {
     }
     
     async fn process_transcription_task(&self, payload: &serde_json::Value, task_result: &mut TaskResult) -> Result<serde_json::Value, String> {
-        // Extract parameters from payload
-        let file_path = payload.get("file_path")
-            .and_then(|v| v.as_str())
-            .ok_or("Missing file_path in payload")?;
-        
-        let backend = payload.get("backend")
-            .and_then(|v| v.as_str())
-            .unwrap_or("auto");
-        
-        let language = payload.get("language")
-            .and_then(|v| v.as_str());
+        // Validated again here since requests restored from Redis never went through submit
+        let payload = TranscriptionPayload::from_value(payload)?;
+        
+        let options = TranscribeOptions {
+            trim_silence: payload.trim_silence,
+            initial_prompt: payload.initial_prompt.clone(),
+            translate: payload.translate,
+            declip: payload.declip,
+            clip_threshold: payload.clip_threshold,
+            min_confidence: payload.min_confidence,
+            speaker_gap: payload.speaker_gap,
+            offset_seconds: payload.offset_seconds,
+            task_id: Some(task_result.id.clone()),
+            max_total_duration_minutes: payload.max_total_duration_minutes,
+            max_repeat: payload.max_repeat,
+            segment_limits: SegmentLimits {
+                max_len: payload.max_segment_len,
+                max_tokens: payload.max_tokens,
+                // Always bounded so one runaway task can't bloat task_results and the broadcasts
+                max_segments: Some(payload.max_segments.unwrap_or(DEFAULT_MAX_SEGMENTS)),
+            },
+            decode_thresholds: payload.decode_thresholds(),
+            word_confidence_threshold: payload.word_confidence_threshold.unwrap_or(0.0),
+            agc_target_dbfs: payload.agc_target_dbfs,
+            channel: payload.channel,
+            chunk_overlap_seconds: payload.chunk_overlap_seconds,
+            keep_audio_path: payload.keep_audio.then(|| kept_audio_path(&task_result.id)),
+            paragraphs: payload.paragraphs,
+            ..Default::default()
+        };
+        let keep_audio_path = options.keep_audio_path.clone();
         
-        // Update progress and broadcast - Audio file loaded
+        // Update progress and broadcast - real progress follows from audio loading and Whisper
         task_result.progress = 5.0;
+        task_result.updated_at = Utc::now();
         let _ = self.save_task_result(task_result).await;
         let progress_msg = serde_json::json!({
             "type": "task_progress",
             "task_id": task_result.id,
             "progress": task_result.progress,
-            "message": "Audio file loaded and validated"
-        });
-        self.broadcast_to_websockets(&progress_msg.to_string()).await;
-        
-        // Update progress - Preprocessing audio
-        task_result.progress = 10.0;
-        let _ = self.save_task_result(task_result).await;
-        let progress_msg = serde_json::json!({
-            "type": "task_progress",
-            "task_id": task_result.id,
-            "progress": task_result.progress,
-            "message": "Preprocessing audio file"
-        });
-        self.broadcast_to_websockets(&progress_msg.to_string()).await;
-
-        // Update progress - Initializing transcription model
-        task_result.progress = 20.0;
-        let _ = self.save_task_result(task_result).await;
-        let progress_msg = serde_json::json!({
-            "type": "task_progress", 
-            "task_id": task_result.id,
-            "progress": task_result.progress,
-            "message": "Initializing transcription model"
+            "message": "Preparing transcription"
         });
         self.broadcast_to_websockets(&progress_msg.to_string()).await;
         
         // Create a channel for communication
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
         
+        // Progress reported by the transcription thread (0-100% covering audio loading and Whisper)
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel::<f32>();
+        let progress_callback: ProgressCallback = Arc::new(move |percent| {
+            let _ = progress_tx.send(percent);
+        });
+        
+        // Reuse the warm model; only loads here if startup loading failed
+        let engine = self.engine_for_language(payload.language.as_deref()).await?;
+        
         // Clone necessary data for the thread
-        let file_path_owned = file_path.to_string();
-        let backend_owned = backend.to_string();
-        let language_owned = language.map(|s| s.to_string());
-        let _queue_clone = self.clone(); // Prefix with underscore to suppress warning
-        let task_id = task_result.id.clone();
+        let file_path_owned = payload.file_path.clone();
+        let backend_owned = payload.backend.clone();
+        let language_owned = payload.language.clone();
         
         // Run transcription in a separate thread to avoid blocking the actor
         std::thread::spawn(move || {
-            // Create a new Tokio runtime for this thread
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            let result = rt.block_on(async {
-                transcribe_audio_file(&file_path_owned, &backend_owned, language_owned.as_deref()).await
-            });
+            let result = transcribe_audio_file_with_engine(
+                &engine,
+                &file_path_owned,
+                &backend_owned,
+                language_owned.as_deref(),
+                &options,
+                Some(progress_callback),
+            );
             
             // Send result back
-            rt.block_on(async {
-                let _ = tx.send(result).await;
-            });
-        });
-        
-        // Update progress - Starting transcription
-        task_result.progress = 30.0;
-        let _ = self.save_task_result(task_result).await;
-        let progress_msg = serde_json::json!({
-            "type": "task_progress",
-            "task_id": task_result.id,
-            "progress": task_result.progress,
-            "message": "Starting transcription process"
+            let _ = tx.blocking_send(result);
         });
-        self.broadcast_to_websockets(&progress_msg.to_string()).await;
-
-        // Wait for result while periodically updating progress and allowing other tasks to run
-        let mut progress = 35.0f64; // Initial progress value
         
         // Dynamic timeout based on file size and estimated duration
-        let file_size = payload.get("file_size_bytes")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(0);
-        let duration_seconds = payload.get("duration_seconds")
-            .and_then(|v| v.as_f64())
-            .unwrap_or(0.0);
+        let file_size = payload.file_size_bytes.unwrap_or(0);
+        let duration_seconds = payload.duration_seconds.unwrap_or(0.0);
             
         let file_size_mb = file_size as f64 / (1024.0 * 1024.0);
         let estimated_duration_minutes = duration_seconds / 60.0;
@@ -580,23 +2285,62 @@ impl TaskQueue {
             max_wait_time += ((estimated_duration_minutes / 30.0) * 120.0) as u64;
         }
         
-        // Cap maximum timeout at 30 minutes for safety
-        max_wait_time = max_wait_time.min(1800);
+        // A client-chosen timeout replaces the estimate; either way it's capped at 30 minutes for safety
+        if let Some(timeout_seconds) = payload.timeout_seconds {
+            max_wait_time = timeout_seconds;
+        }
+        max_wait_time = max_wait_time.min(MAX_TASK_TIMEOUT_SECS);
         
-        println!("Processing file: {:.1}MB, {:.1}min duration, timeout: {}s", 
-                file_size_mb, estimated_duration_minutes, max_wait_time);
+        println!("Processing file: {:.1}MB, {:.1}min duration, timeout: {}s{}", 
+                file_size_mb, estimated_duration_minutes, max_wait_time,
+                if payload.timeout_seconds.is_some() { " (timeout_seconds)" } else { "" });
         
-        let mut elapsed_seconds = 0;
+        let started = tokio::time::Instant::now();
+        let deadline = started + tokio::time::Duration::from_secs(max_wait_time);
         
         loop {
-            // Check if we have a result (non-blocking)
-            match tokio::time::timeout(tokio::time::Duration::from_secs(2), rx.recv()).await {
-                Ok(Some(result)) => {
-                    // Got the result
+            tokio::select! {
+                result = rx.recv() => {
                     match result {
-                        Ok(transcription_result) => {
+                        Some(Ok(mut transcription_result)) => {
+                            // Results reference the uploaded name rather than the temp file path
+                            if let Some(original_filename) = &payload.original_filename {
+                                if let Some(metadata) = transcription_result.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+                                    metadata.insert("original_filename".to_string(), serde_json::json!(original_filename));
+                                }
+                            }
+                            
+                            // A failed restoration leaves Whisper's own text in place. The select's
+                            // deadline branch can't fire while this arm runs, so bound it here.
+                            if payload.restore_punctuation {
+                                let endpoint = punctuation_endpoint();
+                                let restoring = restore_punctuation(&mut transcription_result, &endpoint);
+                                let outcome = match tokio::time::timeout_at(deadline, restoring).await {
+                                    Ok(outcome) => outcome,
+                                    Err(_) => Err("timed out at the task deadline, keeping the original".to_string()),
+                                };
+                                if let Err(e) = &outcome {
+                                    log::warn!("Punctuation restoration failed for task {}: {}", task_result.id, e);
+                                }
+                                if let Some(metadata) = transcription_result.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+                                    metadata.insert("punctuation_restored".to_string(), serde_json::json!(outcome.is_ok()));
+                                    metadata.insert("punctuation_endpoint".to_string(), serde_json::json!(endpoint));
+                                    if let Err(e) = outcome {
+                                        metadata.insert("punctuation_error".to_string(), serde_json::json!(e));
+                                    }
+                                }
+                            }
+                            
+                            if let Some(path) = &keep_audio_path {
+                                match self.store_kept_audio(&task_result.id, path).await {
+                                    Ok(()) => log::info!("Kept Whisper input audio for task {} ({}s TTL)", task_result.id, KEPT_AUDIO_TTL_SECS),
+                                    Err(e) => log::warn!("Failed to keep Whisper input audio for task {}: {}", task_result.id, e),
+                                }
+                            }
+                            
                             // Final progress update
                             task_result.progress = 95.0;
+                            task_result.updated_at = Utc::now();
                             let _ = self.save_task_result(task_result).await;
                             let progress_msg = serde_json::json!({
                                 "type": "task_progress",
@@ -606,68 +2350,60 @@ impl TaskQueue {
                             });
                             self.broadcast_to_websockets(&progress_msg.to_string()).await;
                             
-            task_result.progress = 100.0;
+                            task_result.progress = 100.0;
                             return Ok(transcription_result);
                         }
-                        Err(e) => {
+                        Some(Err(e)) => {
+                            if let Some(path) = &keep_audio_path {
+                                remove_audio_file(&task_result.id, path);
+                            }
                             return Err(format!("Transcription failed: {}", e));
                         }
-                    }
-                }
-                Ok(None) => {
-                    // Channel closed without result - error
-                    return Err("Transcription task failed unexpectedly".to_string());
-                }
-                Err(_) => {
-                    // Timeout - continue waiting but update progress
-                    elapsed_seconds += 2;
-                    
-                    // Check if we've exceeded max wait time
-                    if elapsed_seconds > max_wait_time {
-                        let timeout_msg = if file_size_mb > 100.0 || estimated_duration_minutes > 60.0 {
-                            format!("Large file processing timed out after {} minutes. File: {:.1}MB, {:.1}min duration. Consider splitting the file into smaller segments.", 
-                                    max_wait_time / 60, file_size_mb, estimated_duration_minutes)
-                        } else {
-                            format!("Transcription timed out after {} minutes", max_wait_time / 60)
-                        };
-                        return Err(timeout_msg);
-                    }
-                    
-                    // Calculate progress based on time elapsed (smoother progression)
-                    let time_progress = (elapsed_seconds as f64 / max_wait_time as f64) * 50.0; // 50% for time-based progress
-                    progress = (progress + time_progress).min(90.0); // Start from initial progress
-                    
-                    // Update progress every 10 seconds or at major milestones
-                    if elapsed_seconds % 10 == 0 || progress as i32 % 15 == 0 {
-                        // Update progress in Redis
-                        if let Ok(mut current_task) = self.get_task_result(&task_id).await {
-                            if let Some(ref mut task) = current_task {
-                                task.progress = progress as f32;
-                                let _ = self.save_task_result(task).await;
-                                
-                                // Broadcast progress update with contextual message
-                                let message = match progress as i32 {
-                                    35..=50 => "Processing audio segments",
-                                    51..=70 => "Running speech recognition",
-                                    71..=85 => "Post-processing results",
-                                    86..=90 => "Preparing final output",
-                                    _ => "Transcribing audio"
-                                };
-                                
-                                let progress_msg = serde_json::json!({
-                                    "type": "task_progress",
-                                    "task_id": task_id,
-                                    "progress": progress as f32,
-                                    "message": message,
-                                    "elapsed_seconds": elapsed_seconds
-                                });
-                                self.broadcast_to_websockets(&progress_msg.to_string()).await;
+                        None => {
+                            if let Some(path) = &keep_audio_path {
+                                remove_audio_file(&task_result.id, path);
                             }
+                            // Channel closed without result - error
+                            return Err("Transcription task failed unexpectedly".to_string());
                         }
                     }
+                }
+                Some(percent) = progress_rx.recv() => {
+                    // Map loading + Whisper progress onto the 5-90% band of the task
+                    let percent = percent.clamp(0.0, 100.0);
+                    let progress = 5.0 + percent * 0.85;
+                    let phase = if percent < AUDIO_LOAD_PROGRESS_SHARE {
+                        "Loading and resampling audio"
+                    } else {
+                        "Running speech recognition"
+                    };
                     
-                    // Yield control to allow other tasks to process
-                    tokio::task::yield_now().await;
+                    // Only persist and broadcast whole-percent changes
+                    if progress.floor() > task_result.progress.floor() {
+                        task_result.progress = progress;
+                        task_result.updated_at = Utc::now();
+                        let _ = self.save_task_result(task_result).await;
+                        
+                        let progress_msg = serde_json::json!({
+                            "type": "task_progress",
+                            "task_id": task_result.id,
+                            "progress": task_result.progress,
+                            "message": phase,
+                            "elapsed_seconds": started.elapsed().as_secs()
+                        });
+                        self.broadcast_to_websockets(&progress_msg.to_string()).await;
+                    }
+                }
+                _ = tokio::time::sleep_until(deadline) => {
+                    let timeout_msg = if payload.timeout_seconds.is_some() {
+                        format!("Transcription timed out after {}s (timeout_seconds)", max_wait_time)
+                    } else if file_size_mb > 100.0 || estimated_duration_minutes > 60.0 {
+                        format!("Large file processing timed out after {} minutes. File: {:.1}MB, {:.1}min duration. Consider splitting the file into smaller segments.", 
+                                max_wait_time / 60, file_size_mb, estimated_duration_minutes)
+                    } else {
+                        format!("Transcription timed out after {} minutes", max_wait_time / 60)
+                    };
+                    return Err(timeout_msg);
                 }
             }
         }
@@ -692,8 +2428,7 @@ impl TaskQueue {
         }
         
         // Also count queued tasks
-        let mut conn = self.redis_manager.clone();
-        let queue_size: usize = conn.zcard("task_queue").await.unwrap_or(0);
+        let queue_size = self.store.queue_len().await.unwrap_or(0);
         pending_count += queue_size;
         
         let total_tasks = task_results.len();
@@ -707,49 +2442,108 @@ impl TaskQueue {
         }))
     }
     
-    pub async fn cleanup_stale_tasks(&self) -> Result<usize, QueueError> {
+    /// Delete tasks with `status` that finished more than `older_than_hours` ago
+    /// (all of them when `None`) from the store and the in-memory cache, along with
+    /// the dead-letter entries and kept uploads of purged failed tasks.
+    /// Pending and processing tasks are never purged.
+    pub async fn purge_tasks(&self, status: TaskStatus, older_than_hours: Option<u64>) -> Result<usize, QueueError> {
+        if matches!(status, TaskStatus::Pending | TaskStatus::Processing) {
+            return Err(QueueError(format!("Refusing to purge {:?} tasks", status)));
+        }
+        
         let now = Utc::now();
-        let stale_threshold = chrono::Duration::hours(1); // Consider tasks stale after 1 hour
+        let older_than = older_than_hours.map(|hours| chrono::Duration::hours(hours as i64));
         
         let mut task_results = self.task_results.write().await;
-        let mut cleaned_count = 0;
         
-        let stale_tasks: Vec<String> = task_results
+        let purge_ids: Vec<String> = task_results
             .values()
-            .filter(|task| {
-                matches!(task.status, TaskStatus::Processing) &&
-                task.started_at.map_or(false, |started| now - started > stale_threshold)
+            .filter(|task| task.status == status)
+            .filter(|task| match older_than {
+                Some(age) => now - task.completed_at.unwrap_or(task.updated_at) > age,
+                None => true,
             })
             .map(|task| task.id.clone())
             .collect();
         
-        for task_id in stale_tasks {
-            if let Some(mut task) = task_results.get(&task_id).cloned() {
-                log::warn!("Cleaning up stale task: {}", task_id);
-                
-                task.status = TaskStatus::Failed;
-                task.error = Some("Task timed out and was cleaned up".to_string());
-                task.completed_at = Some(now);
-                task.updated_at = now;
-                
-                // Save to Redis
-                let _ = self.save_task_result(&task).await;
-                
-                // Update in-memory cache
-                task_results.insert(task_id.clone(), task.clone());
-                
-                // Broadcast task failure
-                let status_msg = serde_json::json!({
-                    "type": "task_completed",
-                    "task_id": task_id,
-                    "status": "failed",
-                    "error": "Task timed out and was cleaned up",
-                    "timestamp": now
-                });
-                self.broadcast_to_websockets(&status_msg.to_string()).await;
-                
-                cleaned_count += 1;
+        let dead_letters = if status == TaskStatus::Failed {
+            self.store.dead_letters(None).await?
+        } else {
+            Vec::new()
+        };
+        
+        for task_id in &purge_ids {
+            self.store.del(&format!("task_result:{}", task_id)).await?;
+            task_results.remove(task_id);
+            self.recent_events.lock().await.remove(task_id);
+            
+            for raw in &dead_letters {
+                if let Some(entry) = serde_json::from_str::<DeadLetterEntry>(raw).ok().filter(|entry| &entry.task_id == task_id) {
+                    self.remove_dead_letter(raw, &entry).await?;
+                }
+            }
+        }
+        
+        log::info!("Purged {} {:?} tasks", purge_ids.len(), status);
+        
+        Ok(purge_ids.len())
+    }
+    
+    /// Fail processing tasks that haven't reported progress for `stale_task_threshold_secs`.
+    /// Staleness is measured from `updated_at`, which every progress update bumps, so long
+    /// jobs that are still moving are left alone.
+    pub async fn cleanup_stale_tasks(&self) -> Result<usize, QueueError> {
+        let now = Utc::now();
+        let stale_threshold = chrono::Duration::seconds(self.stale_task_threshold_secs as i64);
+        let is_stale = |task: &TaskResult| {
+            matches!(task.status, TaskStatus::Processing) && now - task.updated_at > stale_threshold
+        };
+        
+        // save_task_result takes the write lock itself, so only hold the read lock while scanning
+        let stale_tasks: Vec<TaskResult> = {
+            let task_results = self.task_results.read().await;
+            task_results
+                .values()
+                .filter(|task| is_stale(task))
+                .cloned()
+                .collect()
+        };
+        let mut cleaned_count = 0;
+        
+        for mut task in stale_tasks {
+            let task_id = task.id.clone();
+            
+            // The task may have finished or reported progress since the scan
+            let still_stale = {
+                let task_results = self.task_results.read().await;
+                task_results.get(&task_id).is_some_and(is_stale)
+            };
+            if !still_stale {
+                continue;
             }
+            
+            log::warn!("Cleaning up stale task: {} (no progress since {})", task_id, task.updated_at);
+            
+            task.status = TaskStatus::Failed;
+            task.error = Some("Task timed out and was cleaned up".to_string());
+            task.completed_at = Some(now);
+            task.updated_at = now;
+            
+            // Save to Redis and the in-memory cache
+            let _ = self.save_task_result(&task).await;
+            self.counters.record_finished(&task);
+            
+            // Broadcast task failure
+            let status_msg = serde_json::json!({
+                "type": "task_completed",
+                "task_id": task_id,
+                "status": "failed",
+                "error": "Task timed out and was cleaned up",
+                "timestamp": now
+            });
+            self.broadcast_to_websockets(&status_msg.to_string()).await;
+            
+            cleaned_count += 1;
         }
         
         Ok(cleaned_count)
@@ -762,6 +2556,7 @@ impl TaskQueue {
         
         // Update progress
         task_result.progress = 20.0;
+        task_result.updated_at = Utc::now();
         let _ = self.save_task_result(task_result).await;
         
         // Broadcast progress update
@@ -773,7 +2568,9 @@ impl TaskQueue {
         self.broadcast_to_websockets(&progress_msg.to_string()).await;
         
         // Call the actual risk analysis function
-        match crate::analyze_risk(text).await {
+        let risk_config = crate::RiskConfig::from_payload(payload);
+        
+        match crate::analyze_risk(text, &risk_config).await {
             Ok(result) => {
                 task_result.progress = 100.0;
                 
@@ -809,7 +2606,13 @@ impl TaskQueue {
             let risk_analysis = &risk_result["risk_analysis"];
             serde_json::json!({
                 "riskDetectionStatus": "completed",
-                "riskDetectionResult": if risk_analysis["is_risky"].as_bool().unwrap_or(false) { "risky" } else { "safe" },
+                "riskDetectionResult": match risk_analysis["risk_level"].as_str() {
+                    Some("Uncertain") => "uncertain",
+                    Some("Risky") => "risky",
+                    Some(_) => "safe",
+                    // Results stored before risk levels existed only carry is_risky
+                    None => if risk_analysis["is_risky"].as_bool().unwrap_or(false) { "risky" } else { "safe" },
+                },
                 "riskDetectionResponse": risk_result,
                 "riskConfidence": risk_analysis["confidence"].as_f64().unwrap_or(0.0),
                 "original_file": original_payload.get("original_file"),
@@ -845,17 +2648,31 @@ impl TaskQueue {
     }
 
     // Auto-submit risk analysis after transcription completion
-    async fn auto_submit_risk_analysis(&self, transcription_result: &serde_json::Value, original_payload: &serde_json::Value) -> Result<String, String> {
+    // Queue risk analysis of a finished transcript. Ok(None) when the transcript is
+    // too short to be worth analyzing.
+    async fn auto_submit_risk_analysis(&self, transcription_result: &serde_json::Value, original_payload: &serde_json::Value) -> Result<Option<String>, String> {
         // Extract the transcription text
         let text = transcription_result
             .get("text")
             .and_then(|v| v.as_str())
             .ok_or("No text found in transcription result")?;
         
-        // Skip risk analysis if text is empty or too short
-        if text.trim().is_empty() || text.trim().len() < 10 {
-            log::info!("Skipping risk analysis for short/empty text");
-            return Ok("skipped".to_string());
+        // Characters, not bytes: each Thai character is three bytes of UTF-8
+        let trimmed = text.trim();
+        let char_count = trimmed.chars().count();
+        let word_count = crate::thai::segment_words(trimmed).len();
+        
+        if char_count == 0 {
+            log::info!("Skipping risk analysis: transcript is empty");
+            return Ok(None);
+        }
+        if char_count < self.min_chars_for_risk {
+            log::info!("Skipping risk analysis: {} characters, fewer than the minimum of {}", char_count, self.min_chars_for_risk);
+            return Ok(None);
+        }
+        if word_count < self.min_words_for_risk {
+            log::info!("Skipping risk analysis: {} words, fewer than the minimum of {}", word_count, self.min_words_for_risk);
+            return Ok(None);
         }
         
         log::info!("Auto-submitting risk analysis for transcription text (length: {})", text.len());
@@ -865,13 +2682,15 @@ impl TaskQueue {
             "text": text,
             "auto_triggered": true,
             "source_type": "transcription",
-            "original_file": original_payload.get("file_path"),
+            "original_file": original_payload.get("original_filename")
+                .filter(|v| !v.is_null())
+                .or_else(|| original_payload.get("file_path")),
             "transcription_backend": original_payload.get("backend"),
             "language": original_payload.get("language")
         });
         
         // Create task directly (internal method)
-        self.submit_task_internal(TaskType::RiskAnalysis, risk_payload, Some(2)).await
+        self.submit_task_internal(TaskType::RiskAnalysis, risk_payload, Some(2)).await.map(Some)
     }
 
     // Internal method to submit tasks without going through the actor system
@@ -901,12 +2720,11 @@ impl TaskQueue {
         };
         
         // Save task request and result
-        let mut conn = self.redis_manager.clone();
         let request_key = format!("task_request:{}", task_id);
         let request_data = serde_json::to_string(&task_request)
             .map_err(|e| format!("Failed to serialize task request: {}", e))?;
         
-        conn.set::<_, _, ()>(&request_key, request_data).await
+        self.store.set(&request_key, &request_data).await
             .map_err(|e| format!("Failed to save task request: {}", e))?;
         
         self.save_task_result(&task_result).await
@@ -915,6 +2733,7 @@ impl TaskQueue {
         // Add to queue
         self.enqueue_task_request(&task_id).await
             .map_err(|e| format!("Failed to enqueue task: {}", e))?;
+        self.counters.submitted.fetch_add(1, Ordering::Relaxed);
         
         // Broadcast new task
         let new_task_msg = serde_json::json!({
@@ -934,10 +2753,22 @@ impl TaskQueue {
 impl Clone for TaskQueue {
     fn clone(&self) -> Self {
         Self {
-            redis_manager: self.redis_manager.clone(),
+            store: Arc::clone(&self.store),
             task_results: Arc::clone(&self.task_results),
             websocket_sessions: Arc::clone(&self.websocket_sessions),
             processing_tasks: Arc::clone(&self.processing_tasks),
+            recent_events: Arc::clone(&self.recent_events),
+            task_slots: Arc::clone(&self.task_slots),
+            stats_interval_secs: self.stats_interval_secs,
+            stale_task_threshold_secs: self.stale_task_threshold_secs,
+            webhook_secret: self.webhook_secret.clone(),
+            callback_client: self.callback_client.clone(),
+            counters: Arc::clone(&self.counters),
+            min_chars_for_risk: self.min_chars_for_risk,
+            min_words_for_risk: self.min_words_for_risk,
+            whisper_engine: Arc::clone(&self.whisper_engine),
+            language_engines: Arc::clone(&self.language_engines),
+            segment_edit_locks: Arc::clone(&self.segment_edit_locks),
         }
     }
 }
@@ -951,9 +2782,16 @@ impl Actor for TaskQueue {
 }
 
 impl Handler<SubmitTask> for TaskQueue {
-    type Result = ResponseActFuture<Self, Result<String, String>>;
+    type Result = ResponseActFuture<Self, Result<SubmitOutcome, String>>;
     
     fn handle(&mut self, msg: SubmitTask, _ctx: &mut Self::Context) -> Self::Result {
+        // Reject malformed transcription submissions before anything is queued
+        if matches!(msg.task_type, TaskType::Transcription) {
+            if let Err(e) = TranscriptionPayload::from_value(&msg.payload) {
+                return Box::pin(async move { Err(e) }.into_actor(self));
+            }
+        }
+        
         let task_id = Uuid::new_v4().to_string();
         let now = Utc::now();
         
@@ -978,24 +2816,29 @@ impl Handler<SubmitTask> for TaskQueue {
             progress: 0.0,
         };
         
+        let idempotency_key = msg.idempotency_key;
         let queue_clone = self.clone();
         
         Box::pin(async move {
-            // Save task request and result
-            let mut conn = queue_clone.redis_manager.clone();
-            let request_key = format!("task_request:{}", task_id);
-            let request_data = serde_json::to_string(&task_request)
-                .map_err(|e| format!("Failed to serialize task request: {}", e))?;
-            
-            conn.set::<_, _, ()>(&request_key, request_data).await
-                .map_err(|e| format!("Failed to save task request: {}", e))?;
+            check_callback_url(&task_request.payload).await?;
             
-            queue_clone.save_task_result(&task_result).await
-                .map_err(|e| format!("Failed to save task result: {}", e))?;
+            // Claim the key before anything is written so concurrent retries can't both queue
+            if let Some(key) = &idempotency_key {
+                let existing = queue_clone.claim_idempotency_key(key, &task_id).await
+                    .map_err(|e| format!("Failed to check idempotency key: {}", e))?;
+                if let Some(existing_id) = existing {
+                    log::info!("Idempotency key {} already used by task {}, not queueing again", key, existing_id);
+                    return Ok(SubmitOutcome::Existing(existing_id));
+                }
+            }
             
-            // Add to queue
-            queue_clone.enqueue_task_request(&task_id).await
-                .map_err(|e| format!("Failed to enqueue task: {}", e))?;
+            // Save task request and result, then add to queue
+            if let Err(e) = queue_clone.store_new_task(&task_request, &task_result).await {
+                if let Some(key) = &idempotency_key {
+                    queue_clone.release_idempotency_key(key).await;
+                }
+                return Err(e);
+            }
             
             // Broadcast new task
             let new_task_msg = serde_json::json!({
@@ -1008,7 +2851,7 @@ impl Handler<SubmitTask> for TaskQueue {
             });
             queue_clone.broadcast_to_websockets(&new_task_msg.to_string()).await;
             
-            Ok(task_id)
+            Ok(SubmitOutcome::Created(task_id))
         }.into_actor(self))
     }
 }
@@ -1026,12 +2869,55 @@ impl Handler<GetTaskStatus> for TaskQueue {
     }
 }
 
+impl Handler<FindCachedResult> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<Option<TaskResult>, String>>;
+    
+    fn handle(&mut self, msg: FindCachedResult, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.find_cached_result(&msg.audio_hash, &msg.payload).await
+                .map_err(|e| format!("Failed to look up cached result: {}", e))
+        }.into_actor(self))
+    }
+}
+
+impl Handler<GetQueueDepth> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<usize, String>>;
+    
+    fn handle(&mut self, _msg: GetQueueDepth, _ctx: &mut Self::Context) -> Self::Result {
+        let store = Arc::clone(&self.store);
+        
+        Box::pin(async move {
+            store.queue_len().await.map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
+impl Handler<GetQueueMetrics> for TaskQueue {
+    type Result = MessageResult<GetQueueMetrics>;
+    
+    fn handle(&mut self, _msg: GetQueueMetrics, _ctx: &mut Self::Context) -> Self::Result {
+        let counters = &self.counters;
+        MessageResult(QueueMetrics {
+            submitted_total: counters.submitted.load(Ordering::Relaxed),
+            completed_total: counters.completed.load(Ordering::Relaxed),
+            failed_total: counters.failed.load(Ordering::Relaxed),
+            cancelled_total: counters.cancelled.load(Ordering::Relaxed),
+            dead_lettered_total: counters.dead_lettered.load(Ordering::Relaxed),
+            duration_buckets: counters.duration_buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect(),
+            duration_sum_seconds: counters.duration_sum_millis.load(Ordering::Relaxed) as f64 / 1000.0,
+            duration_count: counters.duration_count.load(Ordering::Relaxed),
+        })
+    }
+}
+
 impl Handler<GetQueueStats> for TaskQueue {
     type Result = ResponseActFuture<Self, Result<QueueStats, String>>;
     
     fn handle(&mut self, _msg: GetQueueStats, _ctx: &mut Self::Context) -> Self::Result {
         let task_results = Arc::clone(&self.task_results);
-        let redis_manager = self.redis_manager.clone();
+        let store = Arc::clone(&self.store);
         
         Box::pin(async move {
             let task_results = task_results.read().await;
@@ -1052,8 +2938,7 @@ impl Handler<GetQueueStats> for TaskQueue {
             }
             
             // Also count queued tasks
-            let mut conn = redis_manager.clone();
-            let queue_size: usize = conn.zcard("task_queue").await.unwrap_or(0);
+            let queue_size = store.queue_len().await.unwrap_or(0);
             pending_count += queue_size;
             
             let total_tasks = task_results.len();
@@ -1070,7 +2955,7 @@ impl Handler<GetQueueStats> for TaskQueue {
 }
 
 impl Handler<GetTaskHistory> for TaskQueue {
-    type Result = ResponseActFuture<Self, Result<Vec<TaskResult>, String>>;
+    type Result = ResponseActFuture<Self, Result<TaskHistoryPage, String>>;
     
     fn handle(&mut self, msg: GetTaskHistory, _ctx: &mut Self::Context) -> Self::Result {
         let task_results = Arc::clone(&self.task_results);
@@ -1084,35 +2969,86 @@ impl Handler<GetTaskHistory> for TaskQueue {
                 tasks.retain(|t| t.status == status_filter);
             }
             
-            // Sort by updated_at desc
-            tasks.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+            // Sort by created_at desc, ties by id; both are fixed, so pages don't shuffle as tasks progress
+            tasks.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.id.cmp(&a.id)));
+            let total = tasks.len();
+            
+            if let Some(cursor) = &msg.cursor {
+                tasks.retain(|t| cursor.is_before(t));
+            }
+            tasks.drain(..msg.offset.min(tasks.len()));
             
             // Apply limit if specified
+            let mut next_cursor = None;
             if let Some(limit) = msg.limit {
+                if limit > 0 && tasks.len() > limit {
+                    next_cursor = Some(HistoryCursor::for_task(&tasks[limit - 1]).encode());
+                }
                 tasks.truncate(limit);
             }
             
-            Ok(tasks)
+            Ok(TaskHistoryPage { tasks, total, next_cursor })
         }.into_actor(self))
     }
 }
 
+fn is_terminal_status(status: TaskStatus) -> bool {
+    matches!(status, TaskStatus::Completed | TaskStatus::Failed | TaskStatus::Cancelled)
+}
+
 // WebSocket session actor
+// Current state of one task as sent to WebSocket clients; unknown tasks count as terminal
+fn task_snapshot_message(task_id: &str, task_result: Option<&TaskResult>) -> serde_json::Value {
+    match task_result {
+        Some(task_result) => serde_json::json!({
+            "type": "task_snapshot",
+            "task_id": task_id,
+            "task": task_result,
+            "terminal": is_terminal_status(task_result.status),
+            "timestamp": Utc::now()
+        }),
+        None => serde_json::json!({
+            "type": "task_snapshot",
+            "task_id": task_id,
+            "task": null,
+            "terminal": true,
+            "error": "Task not found",
+            "timestamp": Utc::now()
+        }),
+    }
+}
+
 pub struct WebSocketSession {
     pub id: Uuid,
     pub queue_addr: Addr<TaskQueue>,
+    /// Only forward messages for this task
+    pub task_filter: Option<String>,
+    /// Close the socket once the `task_filter` task finishes (`/ws/task/{id}` sessions)
+    pub close_on_finish: bool,
 }
 
 impl Actor for WebSocketSession {
     type Context = ws::WebsocketContext<Self>;
     
     fn started(&mut self, ctx: &mut Self::Context) {
-        let addr = ctx.address().recipient();
+        let session_addr = ctx.address();
+        let addr = session_addr.clone().recipient();
         let queue_addr = self.queue_addr.clone();
         let session_id = self.id;
+        let task_filter = self.task_filter.clone();
         
         tokio::spawn(async move {
-            let _ = queue_addr.send(AddWebSocketSession { session_id, addr }).await;
+            let _ = queue_addr.send(AddWebSocketSession { session_id, addr, task_filter: task_filter.clone() }).await;
+            
+            // Task-scoped sessions start with the current state of their task
+            if let Some(task_id) = task_filter {
+                let task_result = match queue_addr.send(GetTaskStatus { task_id: task_id.clone() }).await {
+                    Ok(Ok(task_result)) => task_result,
+                    _ => None,
+                };
+                let snapshot = task_snapshot_message(&task_id, task_result.as_ref());
+                session_addr.do_send(WebSocketMessage { message: snapshot.to_string() });
+            }
         });
     }
     
@@ -1131,8 +3067,68 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebSocketSession
         match msg {
             Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
             Ok(ws::Message::Text(text)) => {
-                // Handle incoming WebSocket messages if needed
                 log::debug!("WebSocket message received: {}", text);
+                
+                // {"subscribe": "<task_id>"} limits updates to one task, {"subscribe": null} clears it
+                let request = match serde_json::from_str::<serde_json::Value>(&text) {
+                    Ok(value) => value,
+                    Err(_) => return,
+                };
+                
+                if let Some(subscribe) = request.get("subscribe") {
+                    let task_id = subscribe.as_str().map(|id| id.to_string());
+                    self.task_filter = task_id.clone();
+                    let queue_addr = self.queue_addr.clone();
+                    let session_id = self.id;
+                    
+                    let ack = serde_json::json!({
+                        "type": "subscribed",
+                        "subscribe": task_id
+                    });
+                    
+                    tokio::spawn(async move {
+                        let _ = queue_addr.send(SetWebSocketFilter { session_id, task_id }).await;
+                    });
+                    
+                    ctx.text(ack.to_string());
+                }
+                
+                // {"replay_since": "<RFC 3339 timestamp>", "task_id": "<optional>"} resends what a
+                // dropped connection missed, for the given task, the subscribed one, or all tasks
+                if let Some(replay_since) = request.get("replay_since") {
+                    let since = match replay_since.as_str().and_then(|ts| DateTime::parse_from_rfc3339(ts).ok()) {
+                        Some(since) => since.with_timezone(&Utc),
+                        None => {
+                            let error = serde_json::json!({
+                                "type": "replay_error",
+                                "error": "replay_since must be an RFC 3339 timestamp"
+                            });
+                            ctx.text(error.to_string());
+                            return;
+                        }
+                    };
+                    let task_id = request.get("task_id")
+                        .and_then(|id| id.as_str())
+                        .map(|id| id.to_string())
+                        .or_else(|| self.task_filter.clone());
+                    let queue_addr = self.queue_addr.clone();
+                    let session_addr = ctx.address();
+                    
+                    tokio::spawn(async move {
+                        let messages = queue_addr.send(ReplayEvents { since, task_id: task_id.clone() }).await.unwrap_or_default();
+                        let done = serde_json::json!({
+                            "type": "replay_complete",
+                            "replay_since": since,
+                            "task_id": task_id,
+                            "messages": messages.len(),
+                            "timestamp": Utc::now()
+                        });
+                        for message in messages {
+                            session_addr.do_send(WebSocketMessage { message });
+                        }
+                        session_addr.do_send(WebSocketMessage { message: done.to_string() });
+                    });
+                }
             }
             Ok(ws::Message::Close(reason)) => {
                 ctx.close(reason);
@@ -1147,7 +3143,24 @@ impl Handler<WebSocketMessage> for WebSocketSession {
     type Result = ();
     
     fn handle(&mut self, msg: WebSocketMessage, ctx: &mut Self::Context) {
+        // Close task-scoped sockets after the final message for their task
+        let finished = self.close_on_finish && self.task_filter.as_ref().is_some_and(|task_id| {
+            serde_json::from_str::<serde_json::Value>(&msg.message)
+                .ok()
+                .filter(|value| value.get("task_id").and_then(|id| id.as_str()) == Some(task_id.as_str()))
+                .map_or(false, |value| match value.get("type").and_then(|t| t.as_str()) {
+                    Some("task_completed") => true,
+                    Some("task_snapshot") => value.get("terminal").and_then(|t| t.as_bool()).unwrap_or(false),
+                    _ => false,
+                })
+        });
+        
         ctx.text(msg.message);
+        
+        if finished {
+            ctx.close(Some(ws::CloseReason::from(ws::CloseCode::Normal)));
+            ctx.stop();
+        }
     }
 }
 
@@ -1156,6 +3169,7 @@ impl Handler<WebSocketMessage> for WebSocketSession {
 struct AddWebSocketSession {
     session_id: Uuid,
     addr: Recipient<WebSocketMessage>,
+    task_filter: Option<String>,
 }
 
 #[derive(Message)]
@@ -1164,6 +3178,20 @@ struct RemoveWebSocketSession {
     session_id: Uuid,
 }
 
+#[derive(Message)]
+#[rtype(result = "()")]
+struct SetWebSocketFilter {
+    session_id: Uuid,
+    task_id: Option<String>,
+}
+
+#[derive(Message)]
+#[rtype(result = "Vec<String>")]
+struct ReplayEvents {
+    since: DateTime<Utc>,
+    task_id: Option<String>,
+}
+
 impl Handler<AddWebSocketSession> for TaskQueue {
     type Result = ResponseActFuture<Self, ()>;
     
@@ -1172,7 +3200,7 @@ impl Handler<AddWebSocketSession> for TaskQueue {
         
         Box::pin(async move {
             let mut sessions = websocket_sessions.lock().await;
-            sessions.insert(msg.session_id, msg.addr);
+            sessions.insert(msg.session_id, WebSocketSubscriber { addr: msg.addr, task_filter: msg.task_filter });
         }.into_actor(self))
     }
 }
@@ -1190,6 +3218,147 @@ impl Handler<RemoveWebSocketSession> for TaskQueue {
     }
 }
 
+impl Handler<ReplayEvents> for TaskQueue {
+    type Result = ResponseActFuture<Self, Vec<String>>;
+    
+    fn handle(&mut self, msg: ReplayEvents, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.replay_events(msg.since, msg.task_id.as_deref()).await
+        }.into_actor(self))
+    }
+}
+
+impl Handler<SetWebSocketFilter> for TaskQueue {
+    type Result = ResponseActFuture<Self, ()>;
+    
+    fn handle(&mut self, msg: SetWebSocketFilter, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.set_websocket_filter(&msg.session_id, msg.task_id).await
+        }.into_actor(self))
+    }
+}
+
+impl Handler<GetDeadLetters> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<Vec<DeadLetterEntry>, String>>;
+    
+    fn handle(&mut self, msg: GetDeadLetters, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.get_dead_letters(msg.limit).await
+                .map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
+impl Handler<PingRedis> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<(), String>>;
+    
+    fn handle(&mut self, _msg: PingRedis, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.ping_redis().await
+                .map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
+impl Handler<PurgeTasks> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<usize, String>>;
+    
+    fn handle(&mut self, msg: PurgeTasks, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.purge_tasks(msg.status, msg.older_than_hours).await
+                .map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
+impl Handler<UpdateSegmentText> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<SegmentUpdate, String>>;
+    
+    fn handle(&mut self, msg: UpdateSegmentText, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.update_segment_text(&msg.task_id, msg.segment_id, &msg.text).await
+                .map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
+impl Handler<CancelTask> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<CancelOutcome, String>>;
+    
+    fn handle(&mut self, msg: CancelTask, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.cancel_task(&msg.task_id).await
+                .map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
+impl Handler<GetLiveDecoder> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<(WhisperEngine, Arc<Semaphore>), String>>;
+    
+    fn handle(&mut self, msg: GetLiveDecoder, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            let engine = queue_clone.engine_for_language(msg.language.as_deref()).await?;
+            Ok((engine, Arc::clone(&queue_clone.task_slots)))
+        }.into_actor(self))
+    }
+}
+
+impl Handler<GetTaskAudio> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<TaskAudio, String>>;
+    
+    fn handle(&mut self, msg: GetTaskAudio, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.get_task_audio(&msg.task_id).await
+                .map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
+impl Handler<RequeueDeadLetter> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<Option<String>, String>>;
+    
+    fn handle(&mut self, msg: RequeueDeadLetter, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.requeue_dead_letter(&msg.task_id).await
+                .map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
+impl Handler<DiscardDeadLetter> for TaskQueue {
+    type Result = ResponseActFuture<Self, Result<bool, String>>;
+    
+    fn handle(&mut self, msg: DiscardDeadLetter, _ctx: &mut Self::Context) -> Self::Result {
+        let queue_clone = self.clone();
+        
+        Box::pin(async move {
+            queue_clone.discard_dead_letter(&msg.task_id).await
+                .map_err(|e| e.to_string())
+        }.into_actor(self))
+    }
+}
+
 impl Handler<CleanupStaleTasks> for TaskQueue {
     type Result = ResponseActFuture<Self, Result<usize, String>>;
     
@@ -1202,3 +3371,109 @@ impl Handler<CleanupStaleTasks> for TaskQueue {
         }.into_actor(self))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback_signature_is_prefixed_hex() {
+        assert_eq!(
+            callback_signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn is_public_ip_refuses_internal_and_embedded_ranges() {
+        let refused = [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "0.0.0.0",
+            "100.64.0.1", "192.0.0.8", "198.18.0.1", "198.19.255.255", "240.0.0.1", "255.255.255.255",
+            "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1", "::ffff:169.254.169.254",
+            "::127.0.0.1", "64:ff9b::7f00:1", "64:ff9b:1::1", "2002:7f00:1::1", "2001::1", "2001:db8::1",
+        ];
+        for ip in refused {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} should be refused", ip);
+        }
+        for ip in ["93.184.216.34", "8.8.8.8", "198.20.0.1", "2606:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} should be allowed", ip);
+        }
+    }
+
+    #[test]
+    fn check_url_host_literal_only_rejects_bad_literals() {
+        let check = |url: &str| check_url_host_literal(&reqwest::Url::parse(url).unwrap());
+        assert!(check("http://127.0.0.1/a.wav").is_err());
+        assert!(check("http://[::ffff:7f00:1]/a.wav").is_err());
+        assert!(check("ftp://example.com/a.wav").is_err());
+        assert!(check("https://8.8.8.8/a.wav").is_ok());
+        // Hostnames are left to PublicResolver
+        assert!(check("https://example.com/a.wav").is_ok());
+    }
+
+    #[test]
+    fn callback_url_refuses_internal_ip_literals() {
+        let payload = |url: &str| serde_json::json!({ "callback_url": url });
+        assert!(callback_url(&payload("http://169.254.169.254/latest/meta-data")).is_err());
+        assert!(callback_url(&payload("http://[::1]:8080/hook")).is_err());
+        assert_eq!(callback_url(&payload(" https://example.com/hook ")).unwrap().as_deref(), Some("https://example.com/hook"));
+        assert_eq!(callback_url(&serde_json::json!({})).unwrap(), None);
+    }
+
+    #[test]
+    fn output_fingerprint_ignores_bookkeeping_only() {
+        let fingerprint = |payload: serde_json::Value, model: Option<&str>| {
+            TranscriptionPayload::from_value(&payload).unwrap().output_fingerprint(model)
+        };
+        let base = fingerprint(serde_json::json!({ "file_path": "/tmp/a.wav", "language": "th" }), Some("model/a.bin"));
+        
+        let bookkeeping = serde_json::json!({
+            "file_path": "/tmp/b.wav", "language": "th", "request_id": "r2", "timeout_seconds": 60,
+            "callback_url": "https://example.com/hook", "audio_hash": "abc"
+        });
+        assert_eq!(fingerprint(bookkeeping, Some("model/a.bin")), base);
+        
+        for option in [
+            serde_json::json!({ "translate": true }),
+            serde_json::json!({ "initial_prompt": "สวัสดี" }),
+            serde_json::json!({ "channel": 1 }),
+            serde_json::json!({ "max_segments": 10 }),
+            serde_json::json!({ "paragraphs": true }),
+        ] {
+            let mut payload = serde_json::json!({ "file_path": "/tmp/a.wav", "language": "th" });
+            payload.as_object_mut().unwrap().extend(option.as_object().unwrap().clone());
+            assert_ne!(fingerprint(payload, Some("model/a.bin")), base, "{}", option);
+        }
+        assert_ne!(fingerprint(serde_json::json!({ "file_path": "/tmp/a.wav", "language": "th" }), Some("model/b.bin")), base);
+    }
+
+    #[tokio::test]
+    async fn memory_store_set_if_absent_has_one_winner() {
+        let store = Arc::new(MemoryStore::new());
+        let claims: Vec<_> = (0..16)
+            .map(|i| {
+                let store = Arc::clone(&store);
+                tokio::spawn(async move { store.set_if_absent("idempotency:k", &i.to_string(), 60).await.unwrap() })
+            })
+            .collect();
+        let mut winners = 0;
+        for claim in claims {
+            winners += claim.await.unwrap() as usize;
+        }
+        assert_eq!(winners, 1);
+    }
+
+    #[test]
+    fn memory_values_sweep_expired_entries_on_write() {
+        let start = std::time::Instant::now();
+        let mut values = MemoryValues::default();
+        values.insert("idempotency:a", b"1".to_vec(), Some(5), start);
+        values.insert("task_result:b", b"2".to_vec(), None, start);
+        
+        let later = start + std::time::Duration::from_secs(MEMORY_STORE_SWEEP_SECS + 1);
+        values.insert("idempotency:c", b"3".to_vec(), Some(5), later);
+        assert!(!values.entries.contains_key("idempotency:a"));
+        assert!(values.entries.contains_key("task_result:b"));
+        assert!(values.entries.contains_key("idempotency:c"));
+    }
+}
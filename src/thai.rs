@@ -0,0 +1,193 @@
+// Thai word breaking: Thai is written without spaces between words, so splitting
+// on whitespace yields whole phrases. Thai runs are split by dictionary-based
+// maximal matching over the bundled word list; everything else splits on whitespace.
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+// Bundled word list, one word per line ('#' lines are comments): the project's own
+// entries plus PyThaiNLP's words_th.txt (Apache-2.0, see data/LICENSE-thai_words)
+const THAI_WORD_LIST: &str = include_str!("../data/thai_words.txt");
+
+struct Dictionary {
+    words: HashSet<&'static str>,
+    // Longest entry in chars, bounds the candidate lengths tried at each position
+    max_word_chars: usize,
+}
+
+fn dictionary() -> &'static Dictionary {
+    static DICTIONARY: OnceLock<Dictionary> = OnceLock::new();
+    DICTIONARY.get_or_init(|| {
+        let words: HashSet<&'static str> = THAI_WORD_LIST
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        let max_word_chars = words.iter().map(|w| w.chars().count()).max().unwrap_or(1);
+        Dictionary { words, max_word_chars }
+    })
+}
+
+/// True for characters in the Thai Unicode block
+pub fn is_thai(ch: char) -> bool {
+    ('\u{0E00}'..='\u{0E7F}').contains(&ch)
+}
+
+// Vowels and tone marks that attach to the preceding consonant, so a word can't start with them
+fn is_following_mark(ch: char) -> bool {
+    matches!(ch, '\u{0E30}'..='\u{0E3A}' | '\u{0E45}' | '\u{0E47}'..='\u{0E4E}')
+}
+
+// Vowels written before the consonant they belong to, so a word can't end with them
+fn is_leading_vowel(ch: char) -> bool {
+    ('\u{0E40}'..='\u{0E44}').contains(&ch)
+}
+
+/// Split text into words. Whitespace and changes between Thai and non-Thai
+/// script always separate words; Thai runs are broken with the dictionary.
+pub fn segment_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    
+    for chunk in text.split_whitespace() {
+        let chars: Vec<char> = chunk.chars().collect();
+        let mut start = 0;
+        
+        while start < chars.len() {
+            let thai = is_thai(chars[start]);
+            let mut end = start + 1;
+            while end < chars.len() && is_thai(chars[end]) == thai {
+                end += 1;
+            }
+            
+            if thai {
+                words.extend(segment_thai_run(&chars[start..end]));
+            } else {
+                words.push(chars[start..end].iter().collect());
+            }
+            start = end;
+        }
+    }
+    
+    words
+}
+
+/// Maximal matching over one run of Thai characters: pick the split with the
+/// fewest characters outside the dictionary, then the fewest words. Unknown
+/// stretches are kept together as a single word.
+fn segment_thai_run(chars: &[char]) -> Vec<String> {
+    let dict = dictionary();
+    let n = chars.len();
+    
+    // A word may only start/end where it doesn't cut a vowel or tone mark off its consonant
+    let boundary = |i: usize| -> bool {
+        i == 0 || i == n || (!is_following_mark(chars[i]) && !is_leading_vowel(chars[i - 1]))
+    };
+    
+    // best[i] = (unknown chars, words, previous boundary, matched a dictionary word)
+    let mut best: Vec<Option<(usize, usize, usize, bool)>> = vec![None; n + 1];
+    best[0] = Some((0, 0, 0, true));
+    
+    for i in 0..n {
+        let (unknown, count, _, _) = match best[i] {
+            Some(state) if boundary(i) => state,
+            _ => continue,
+        };
+        
+        let mut relax = |j: usize, cost: (usize, usize), known: bool| {
+            let better = match best[j] {
+                Some((u, c, _, _)) => cost < (u, c),
+                None => true,
+            };
+            if better {
+                best[j] = Some((cost.0, cost.1, i, known));
+            }
+        };
+        
+        let mut candidate = String::new();
+        for j in (i + 1)..=n.min(i + dict.max_word_chars) {
+            candidate.push(chars[j - 1]);
+            if boundary(j) && dict.words.contains(candidate.as_str()) {
+                relax(j, (unknown, count + 1), true);
+            }
+        }
+        
+        // Fall back to the next character cluster as an unknown piece
+        let mut next = i + 1;
+        while !boundary(next) {
+            next += 1;
+        }
+        relax(next, (unknown + (next - i), count + 1), false);
+    }
+    
+    // Walk back through the chosen boundaries
+    let mut pieces = Vec::new();
+    let mut end = n;
+    while end > 0 {
+        let (_, _, start, known) = best[end].expect("every boundary is reachable");
+        pieces.push((start, end, known));
+        end = start;
+    }
+    pieces.reverse();
+    
+    // Merge consecutive unknown clusters so unrecognised words stay whole
+    let mut words: Vec<String> = Vec::new();
+    let mut previous_known = true;
+    for (start, end, known) in pieces {
+        let piece: String = chars[start..end].iter().collect();
+        match words.last_mut() {
+            Some(last) if !known && !previous_known => last.push_str(&piece),
+            _ => words.push(piece),
+        }
+        previous_known = known;
+    }
+    
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn words(text: &str) -> Vec<String> {
+        segment_words(text)
+    }
+    
+    #[test]
+    fn splits_a_thai_run_into_dictionary_words() {
+        assert_eq!(words("เขาไปโรงเรียน"), ["เขา", "ไป", "โรงเรียน"]);
+    }
+    
+    #[test]
+    fn prefers_the_longest_dictionary_entry() {
+        assert_eq!(words("ประเทศไทย"), ["ประเทศไทย"]);
+    }
+    
+    #[test]
+    fn keeps_unknown_stretches_whole() {
+        assert_eq!(words("ผมชื่อฬฬฬครับ"), ["ผม", "ชื่อ", "ฬฬฬ", "ครับ"]);
+    }
+    
+    #[test]
+    fn splits_on_whitespace_and_script_changes() {
+        assert_eq!(words(" ราคา 500บาท  hello "), ["ราคา", "500", "บาท", "hello"]);
+        assert!(words("").is_empty());
+        assert!(words(" \n ").is_empty());
+    }
+    
+    #[test]
+    fn never_cuts_a_mark_off_its_consonant() {
+        for word in words("ฬี่ฬ้าเฬาะกินข้าวที่ร้านแฬ็ก") {
+            let first = word.chars().next().unwrap();
+            let last = word.chars().last().unwrap();
+            assert!(!is_following_mark(first), "'{}' starts with a following mark", word);
+            assert!(!is_leading_vowel(last), "'{}' ends with a leading vowel", word);
+        }
+    }
+    
+    #[test]
+    fn bundled_lexicon_is_loaded() {
+        let dict = dictionary();
+        assert!(dict.words.len() > 50_000, "only {} words", dict.words.len());
+        assert!(dict.words.iter().all(|word| word.chars().all(is_thai)));
+    }
+}
@@ -0,0 +1,58 @@
+#!/bin/bash
+
+# Regression check for --deterministic: transcribe the same clip twice and
+# require byte-identical segment text in result.json
+# Usage: ./test_deterministic.sh [audio_file] [model_file]
+
+AUDIO_FILE="${1:-audio/C.mp3}"
+MODEL_FILE="${2:-model/ggml-large-v3.bin}"
+BINARY="target/release/transcribe"
+WORK_DIR=$(mktemp -d)
+trap 'rm -rf "$WORK_DIR"' EXIT
+
+echo "=== Thai Audio Transcriber - Deterministic Output Test ==="
+echo ""
+
+# Check if required files exist
+if [ ! -f "$AUDIO_FILE" ]; then
+    echo "❌ Error: $AUDIO_FILE not found"
+    exit 1
+fi
+
+if [ ! -f "$MODEL_FILE" ]; then
+    echo "❌ Error: $MODEL_FILE not found"
+    exit 1
+fi
+
+if [ ! -f "$BINARY" ]; then
+    echo "❌ Error: Release binary not found. Run 'cargo build --release' first."
+    exit 1
+fi
+
+if ! command -v jq &> /dev/null; then
+    echo "❌ Error: jq is required to compare result.json"
+    exit 1
+fi
+
+echo "✅ All required files found"
+echo ""
+
+for run in 1 2; do
+    echo "$run. Transcribing $AUDIO_FILE with --deterministic..."
+    if ! ./$BINARY "$AUDIO_FILE" "$MODEL_FILE" --cpu --deterministic > "$WORK_DIR/run$run.log" 2>&1; then
+        echo "❌ Run $run failed, see output below:"
+        tail -20 "$WORK_DIR/run$run.log"
+        exit 1
+    fi
+    jq -r '.segments[].text' result.json > "$WORK_DIR/run$run.txt"
+    echo "   ✅ $(wc -l < "$WORK_DIR/run$run.txt" | tr -d ' ') segments"
+done
+
+echo ""
+if cmp -s "$WORK_DIR/run1.txt" "$WORK_DIR/run2.txt"; then
+    echo "✅ PASS: segment text is byte-identical across runs"
+else
+    echo "❌ FAIL: segment text differs between runs"
+    diff "$WORK_DIR/run1.txt" "$WORK_DIR/run2.txt" | head -20
+    exit 1
+fi